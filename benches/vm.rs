@@ -0,0 +1,80 @@
+//! Micro-benchmarks for the interpreter internals, run with `cargo bench`. Each benchmark
+//! reports the median time of one iteration, so runs can be compared before and after a
+//! change to the parser or the dispatch loop.
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use toylang::{parse, Interpreter, RunOptions};
+
+/// How long each benchmark is run for after warming up
+const MEASUREMENT_TIME: Duration = Duration::from_secs(2);
+
+fn main() {
+    let countdown = include_str!("workloads/countdown.tyl");
+    let strings = include_str!("workloads/strings.tyl");
+
+    // Five lines per section
+    let large = generate(200_000);
+    bench("parse: 1M line program", || {
+        parse(&large, &RunOptions::default())
+    });
+
+    let sections = parse(&generate(10_000), &RunOptions::default());
+    bench("label resolution: 10k sections", || {
+        Interpreter::new(sections.clone(), RunOptions::default())
+    });
+
+    bench("dispatch: countdown", || run(countdown));
+    bench("push: strings", || run(strings));
+}
+
+/// Generates a program of `sections` sections that each jump to the next
+fn generate(sections: usize) -> String {
+    let mut source = String::new();
+
+    for i in 0..sections {
+        source.push_str(&format!(
+            "::section{i}:\npush {i}\npush \"label {i}\"\ndrop\njump section{}\n",
+            i + 1
+        ));
+    }
+
+    source
+}
+
+/// Parses and runs a workload to completion
+fn run(source: &str) {
+    let program = parse(source, &RunOptions::default());
+    Interpreter::new(program, RunOptions::default())
+        .run()
+        .unwrap();
+}
+
+/// Runs `f` repeatedly and prints the median duration of a call
+fn bench<T>(name: &str, mut f: impl FnMut() -> T) {
+    let warm_up = Instant::now();
+    while warm_up.elapsed() < MEASUREMENT_TIME / 4 {
+        black_box(f());
+    }
+
+    let mut samples = Vec::new();
+    let start = Instant::now();
+    while start.elapsed() < MEASUREMENT_TIME || samples.len() < 10 {
+        let iteration = Instant::now();
+        black_box(f());
+        samples.push(iteration.elapsed());
+    }
+
+    samples.sort();
+    let median = samples[samples.len() / 2];
+
+    println!(
+        "{name:<32} {median:>12.2?}  ({} iterations, {:.2?} to {:.2?})",
+        samples.len(),
+        samples[0],
+        samples[samples.len() - 1]
+    );
+}
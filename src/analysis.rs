@@ -0,0 +1,560 @@
+//! Static analysis of the values on the stack. Every section is followed from its start
+//! with the types of the values it pushes, and `main` is followed with the stack known to
+//! start empty, running the sections it jumps to in place of the jump just like the
+//! interpreter does. The analysis stops at the first conditional, past which the stack is
+//! no longer known.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{parser::Program, Instructions};
+
+/// How many sections deep `main` is followed through jumps, and how many instructions,
+/// before the analysis gives up on a program that loops
+const MAX_DEPTH: usize = 64;
+const MAX_STEPS: usize = 100_000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Severity {
+    Error,
+    Warning,
+}
+
+/// An instruction of a section, or the section itself when there is no index
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Place {
+    pub section: String,
+    pub index: Option<usize>,
+}
+
+/// Something the analysis is sure will go wrong or is pointless
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Finding {
+    pub severity: Severity,
+    pub place: Place,
+    pub message: String,
+    /// Other places that explain the finding, like where an offending value was pushed
+    pub related: Vec<(Place, String)>,
+}
+
+/// A value on the stack, as far as it is known
+#[derive(Debug, Clone)]
+struct Value {
+    type_name: Option<&'static str>,
+    /// Where it was pushed, unless it was already on the stack when the analysis started
+    origin: Option<Place>,
+}
+
+/// Runs every check over a program
+pub(crate) fn analyze(program: &[Program]) -> Vec<Finding> {
+    let sections: HashMap<&str, &[Instructions]> = program
+        .iter()
+        .filter_map(|item| match item {
+            Program::Section(name, instructions) => Some((name.0.as_str(), &instructions[..])),
+            _ => None,
+        })
+        .collect();
+
+    let mut findings = Vec::new();
+
+    for item in program {
+        let Program::Section(name, instructions) = item else {
+            continue;
+        };
+
+        if name.0 == "main" {
+            follow_main(&sections, &mut findings);
+        } else {
+            follow_section(&name.0, instructions, &mut findings);
+        }
+
+        // Jumps never come back, so nothing after one runs either
+        let leaves = instructions.iter().position(|instruction| {
+            matches!(
+                instruction,
+                Instructions::Exit
+                    | Instructions::Jump(_)
+                    | Instructions::Switch(_)
+                    | Instructions::Ret
+            )
+        });
+
+        if let Some(leave) = leaves {
+            if leave + 1 < instructions.len() {
+                let (message, note) = match instructions[leave] {
+                    Instructions::Exit => ("exits", "exits here"),
+                    Instructions::Ret => ("returns", "returns here"),
+                    _ => ("jumps away", "jumps here"),
+                };
+
+                findings.push(Finding {
+                    severity: Severity::Warning,
+                    place: Place {
+                        section: name.0.clone(),
+                        index: Some(leave + 1),
+                    },
+                    message: format!("unreachable: the section {message} before this"),
+                    related: vec![(
+                        Place {
+                            section: name.0.clone(),
+                            index: Some(leave),
+                        },
+                        note.to_string(),
+                    )],
+                });
+            }
+        }
+    }
+
+    findings.extend(unreachable_sections(program));
+
+    // Sections followed from `main` are also followed on their own
+    let mut seen = HashSet::new();
+    findings.retain(|finding| seen.insert((finding.place.clone(), finding.message.clone())));
+    findings
+}
+
+/// Follows `main` from an empty stack through every jump, until the first conditional
+fn follow_main(sections: &HashMap<&str, &[Instructions]>, findings: &mut Vec<Finding>) {
+    let Some(main) = sections.get("main") else {
+        return;
+    };
+
+    let mut stack = Stack {
+        values: Vec::new(),
+        depth_known: true,
+    };
+
+    // The sections being run, innermost last, with the index of their next instruction
+    let mut frames: Vec<(&str, &[Instructions], usize)> = vec![("main", main, 0)];
+    let mut steps = 0;
+
+    while let Some((section, instructions, index)) = frames.last_mut() {
+        let Some(instruction) = instructions.get(*index) else {
+            frames.pop();
+            continue;
+        };
+
+        let place = Place {
+            section: section.to_string(),
+            index: Some(*index),
+        };
+        *index += 1;
+
+        steps += 1;
+        if steps > MAX_STEPS {
+            return;
+        }
+
+        match instruction {
+            Instructions::Jump(label) => {
+                let Some(target) = sections.get(label.as_str()) else {
+                    return;
+                };
+
+                // A jump never comes back, so the section it leaves is done
+                frames.pop();
+                frames.push((label, target, 0));
+            }
+            Instructions::Call(label) => {
+                let Some(target) = sections.get(label.as_str()) else {
+                    return;
+                };
+
+                if frames.len() >= MAX_DEPTH {
+                    return;
+                }
+
+                frames.push((label, target, 0));
+            }
+            Instructions::Exit => {
+                frames.pop();
+
+                // Whatever follows the calls that led here never runs
+                for (section, instructions, index) in frames.iter().rev() {
+                    if *index < instructions.len() {
+                        findings.push(Finding {
+                            severity: Severity::Warning,
+                            place: Place {
+                                section: section.to_string(),
+                                index: Some(*index),
+                            },
+                            message: "unreachable: the section called before this always exits"
+                                .to_string(),
+                            related: vec![(place.clone(), "exits here".to_string())],
+                        });
+                        break;
+                    }
+                }
+
+                return;
+            }
+            Instructions::Ret => {
+                frames.pop();
+            }
+            Instructions::IfJmp(_) | Instructions::Switch(_) => {
+                stack.check(instruction, &place, findings);
+                return;
+            }
+            _ => stack.check(instruction, &place, findings),
+        }
+    }
+}
+
+/// Follows a section that may be entered with anything on the stack, until it transfers
+/// control
+fn follow_section(name: &str, instructions: &[Instructions], findings: &mut Vec<Finding>) {
+    let mut stack = Stack {
+        values: Vec::new(),
+        depth_known: false,
+    };
+
+    for (index, instruction) in instructions.iter().enumerate() {
+        let place = Place {
+            section: name.to_string(),
+            index: Some(index),
+        };
+
+        stack.check(instruction, &place, findings);
+
+        if instruction.stack_effect().is_none() {
+            return;
+        }
+    }
+}
+
+/// Sections that no jump, `ifjmp` or `switch` can reach from `main`
+fn unreachable_sections(program: &[Program]) -> Vec<Finding> {
+    let targets: HashMap<&str, Vec<&str>> = program
+        .iter()
+        .filter_map(|item| match item {
+            Program::Section(name, instructions) => Some((
+                name.0.as_str(),
+                instructions
+                    .iter()
+                    .flat_map(|instruction| match instruction {
+                        Instructions::Jump(label)
+                        | Instructions::IfJmp(label)
+                        | Instructions::Call(label) => vec![label.as_str()],
+                        Instructions::Switch(group) => program
+                            .iter()
+                            .find_map(|item| match item {
+                                Program::States(other, states) if other == group => {
+                                    Some(states.iter().map(String::as_str).collect())
+                                }
+                                _ => None,
+                            })
+                            .unwrap_or_default(),
+                        _ => Vec::new(),
+                    })
+                    .collect(),
+            )),
+            _ => None,
+        })
+        .collect();
+
+    if !targets.contains_key("main") {
+        return Vec::new();
+    }
+
+    let mut reached = HashSet::from(["main"]);
+    let mut pending = vec!["main"];
+
+    while let Some(section) = pending.pop() {
+        for &target in targets.get(section).into_iter().flatten() {
+            if reached.insert(target) {
+                pending.push(target);
+            }
+        }
+    }
+
+    program
+        .iter()
+        .filter_map(|item| match item {
+            Program::Section(name, _) if !reached.contains(name.0.as_str()) => Some(Finding {
+                severity: Severity::Warning,
+                place: Place {
+                    section: name.0.clone(),
+                    index: None,
+                },
+                message: format!("unreachable: nothing jumps to {}", name.0),
+                related: Vec::new(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+struct Stack {
+    /// The values pushed since the analysis started, or all of them if `depth_known`
+    values: Vec<Value>,
+    /// Whether the stack started out empty, so taking more than `values` is an underflow
+    depth_known: bool,
+}
+
+impl Stack {
+    /// Reports what is sure to go wrong when `instruction` runs, then applies its effect
+    fn check(&mut self, instruction: &Instructions, place: &Place, findings: &mut Vec<Finding>) {
+        let pops = instruction.operands();
+
+        // `drop` does nothing on an empty stack
+        let needed = match instruction {
+            Instructions::Drop => 0,
+            _ => pops,
+        };
+
+        if self.depth_known && needed > self.values.len() {
+            findings.push(Finding {
+                severity: Severity::Error,
+                place: place.clone(),
+                message: format!(
+                    "stack underflow: {instruction} needs {needed} value{} but the stack holds {}",
+                    if needed == 1 { "" } else { "s" },
+                    self.values.len()
+                ),
+                related: Vec::new(),
+            });
+
+            // Nothing after this runs
+            self.depth_known = false;
+            self.values.clear();
+            return;
+        }
+
+        // Operands, top first. Values from before the analysis started are unknown
+        let operands: Vec<Value> = (0..pops)
+            .map(|_| {
+                self.values.pop().unwrap_or(Value {
+                    type_name: None,
+                    origin: None,
+                })
+            })
+            .collect();
+        let types: Vec<Option<&str>> = operands.iter().map(|value| value.type_name).collect();
+
+        if let Some(expected) = mismatch(instruction, &types) {
+            findings.push(Finding {
+                severity: Severity::Error,
+                place: place.clone(),
+                message: format!("type mismatch: {instruction} expects {expected}"),
+                related: operands
+                    .iter()
+                    .filter_map(|value| {
+                        let origin = value.origin.clone()?;
+                        Some((origin, format!("{} pushed here", value.type_name?)))
+                    })
+                    .collect(),
+            });
+        }
+
+        let pushed = |type_name: Option<&'static str>| Value {
+            type_name,
+            origin: Some(place.clone()),
+        };
+
+        match instruction {
+            // Moving values keeps where they came from
+            Instructions::Dup => self
+                .values
+                .extend([operands[0].clone(), operands[0].clone()]),
+            Instructions::Swap => self
+                .values
+                .extend([operands[0].clone(), operands[1].clone()]),
+            Instructions::Over => self.values.extend([
+                operands[1].clone(),
+                operands[0].clone(),
+                operands[1].clone(),
+            ]),
+            Instructions::Rot => self.values.extend([
+                operands[1].clone(),
+                operands[0].clone(),
+                operands[2].clone(),
+            ]),
+            Instructions::Add
+            | Instructions::Sub
+            | Instructions::Mul
+            | Instructions::Div
+            | Instructions::LT
+            | Instructions::GT
+            | Instructions::LE
+            | Instructions::GE => {
+                let same = types[0].filter(|_| types[0] == types[1]);
+                self.values.push(pushed(same));
+            }
+            _ => {
+                let (_, pushes) = instruction.stack_effect().unwrap_or((0, 0));
+                let results = result_types(instruction);
+
+                for position in 0..pushes {
+                    self.values
+                        .push(pushed(results.get(position).copied().flatten()));
+                }
+            }
+        }
+    }
+}
+
+/// What an instruction expects of its operands if the known types do not fit it
+fn mismatch(instruction: &Instructions, types: &[Option<&str>]) -> Option<&'static str> {
+    // Required types of the operands, top first
+    let (required, description): (&[&[&str]], &str) = match instruction {
+        Instructions::Add | Instructions::Sub | Instructions::Mul | Instructions::Div
+            if types.contains(&Some("Matrix")) =>
+        {
+            let numeric = types
+                .iter()
+                .flatten()
+                .all(|t| matches!(*t, "Int" | "Float" | "Matrix"));
+
+            return (!numeric).then_some("Matrices or a Matrix and a number");
+        }
+        Instructions::Add | Instructions::Sub | Instructions::Mul | Instructions::Div
+            if types.contains(&Some("Complex")) =>
+        {
+            let numeric = types
+                .iter()
+                .flatten()
+                .all(|t| matches!(*t, "Int" | "Float" | "Complex"));
+
+            return (!numeric).then_some("Complex numbers or a Complex and a number");
+        }
+        Instructions::Add if types.contains(&Some("String")) => {
+            let strings = types.iter().flatten().all(|t| *t == "String");
+
+            return (!strings).then_some("two Strings");
+        }
+        Instructions::Add
+        | Instructions::Sub
+        | Instructions::Mul
+        | Instructions::Div
+        | Instructions::LT
+        | Instructions::GT
+        | Instructions::LE
+        | Instructions::GE => {
+            let numeric = types
+                .iter()
+                .flatten()
+                .all(|t| matches!(*t, "Int" | "Float"));
+            let same = !matches!((types[0], types[1]), (Some(a), Some(b)) if a != b);
+
+            return (!numeric || !same).then_some("two Ints or two Floats");
+        }
+        Instructions::Mod | Instructions::Tone | Instructions::CNew => {
+            (&[&["Int"], &["Int"]], "two Ints")
+        }
+        Instructions::CSet => (&[&["Int"], &["Int"], &["Int"]], "three Ints"),
+        Instructions::MatMul => (&[&["Matrix"], &["Matrix"]], "two Matrices"),
+        Instructions::Transpose => (&[&["Matrix"]], "a Matrix"),
+        Instructions::Complex => (
+            &[&["Int", "Float"], &["Int", "Float"]],
+            "a real and an imaginary part",
+        ),
+        Instructions::Re | Instructions::Im | Instructions::CAbs => (&[&["Complex"]], "a Complex"),
+        Instructions::MGet => (
+            &[&["Int"], &["Int"], &["Matrix"]],
+            "a Matrix and Int row and column indices",
+        ),
+        Instructions::CSave | Instructions::TSave => (&[&["String"]], "a String path"),
+        Instructions::Forward => (&[&["Int"]], "an Int distance"),
+        Instructions::Turn => (&[&["Int"]], "an Int number of degrees"),
+        Instructions::And | Instructions::Or => (&[&["Bool"], &["Bool"]], "two Bools"),
+        Instructions::Not => (&[&["Bool"]], "a Bool"),
+        Instructions::PrintTable => (&[&["List"]], "a List of rows"),
+        Instructions::IfJmp(_) => (&[&["Bool", "Int"]], "a Bool or an Int"),
+        Instructions::Switch(_) => (&[&["Int"]], "an Int state"),
+        Instructions::ByteLen | Instructions::FromBytes => (&[&["Bytes"]], "Bytes"),
+        Instructions::ToBytes | Instructions::SetFmt => (&[&["String"]], "a String"),
+        Instructions::ByteAt => (&[&["Int"], &["Bytes"]], "Bytes and an Int index"),
+        Instructions::ByteSlice => (
+            &[&["Int"], &["Int"], &["Bytes"]],
+            "Bytes and two Int indices",
+        ),
+        Instructions::Len => (&[&["String", "List"]], "a String or a List"),
+        Instructions::Substr => (
+            &[&["Int"], &["Int"], &["String"]],
+            "a String and two Int indices",
+        ),
+        Instructions::StrIndex => (&[&["String"], &["String"]], "two Strings"),
+        // Any value can go in a List
+        Instructions::Append => {
+            let list = types.get(1).copied().flatten().is_none_or(|t| t == "List");
+
+            return (!list).then_some("a List and a value");
+        }
+        Instructions::Set => {
+            let index = types.get(1).copied().flatten().is_none_or(|t| t == "Int");
+            let list = types.get(2).copied().flatten().is_none_or(|t| t == "List");
+
+            return (!index || !list).then_some("a List, an Int index and a value");
+        }
+        Instructions::Get => (&[&["Int"], &["List"]], "a List and an Int index"),
+        Instructions::Pop => (&[&["List"]], "a List"),
+        Instructions::ToInt | Instructions::ToFloat | Instructions::ToBool => (
+            &[&["Int", "Float", "Bool", "String"]],
+            "an Int, Float, Bool or String",
+        ),
+        _ => return None,
+    };
+
+    let fits = required
+        .iter()
+        .zip(types)
+        .all(|(allowed, actual)| actual.is_none_or(|actual| allowed.contains(&actual)));
+
+    (!fits).then_some(description)
+}
+
+/// The types an instruction pushes, bottom first, where they are known
+fn result_types(instruction: &Instructions) -> Vec<Option<&'static str>> {
+    let known = |type_name: &'static str| vec![Some(type_name)];
+
+    match instruction {
+        Instructions::Push(value) => known(value.type_name()),
+        Instructions::EQ
+        | Instructions::NE
+        | Instructions::LT
+        | Instructions::GT
+        | Instructions::LE
+        | Instructions::GE
+        | Instructions::And
+        | Instructions::Or
+        | Instructions::Not
+        | Instructions::ToBool => known("Bool"),
+        Instructions::Mod
+        | Instructions::ByteLen
+        | Instructions::ByteAt
+        | Instructions::Len
+        | Instructions::StrIndex
+        | Instructions::ReadInt
+        | Instructions::ToInt
+        | Instructions::Argc
+        | Instructions::Tock => known("Int"),
+        Instructions::ToBytes | Instructions::ByteSlice | Instructions::FReadN => known("Bytes"),
+        Instructions::FromBytes
+        | Instructions::Substr
+        | Instructions::ReadAll
+        | Instructions::Read
+        | Instructions::ToString
+        | Instructions::CsvEmit
+        | Instructions::Argv(_)
+        | Instructions::PushData(_) => known("String"),
+        Instructions::CsvParse | Instructions::List | Instructions::Append | Instructions::Set => {
+            known("List")
+        }
+        Instructions::Pop | Instructions::Get => vec![Some("List"), None],
+        Instructions::Vector(_)
+        | Instructions::Matrix(..)
+        | Instructions::MatMul
+        | Instructions::Transpose => known("Matrix"),
+        Instructions::MGet
+        | Instructions::Re
+        | Instructions::Im
+        | Instructions::CAbs
+        | Instructions::ReadFloat
+        | Instructions::ToFloat => known("Float"),
+        Instructions::Complex => known("Complex"),
+        Instructions::FOpen => known("Handle"),
+        Instructions::ReadLine => vec![Some("String"), Some("Bool")],
+        Instructions::MemInfo => vec![Some("Int"); 3],
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,637 @@
+//! A compact binary form of parsed programs, written by `toylang compile` so programs can be
+//! run without parsing their source again.
+//!
+//! A file starts with [`MAGIC`] and a little endian `u16` format version. Next is the
+//! constant pool: a count followed by every string the program uses, each a length and its
+//! UTF-8 bytes, which the rest of the file refers to by index. Last is the section table: a
+//! count followed by the program's sections and declarations, each a tag and its contents.
+//! Instructions are an opcode, their position in [`OPCODES`], followed by their operands.
+//!
+//! Counts, lengths, indices and other unsigned numbers are LEB128 varints. Ints are
+//! 8 bytes and Floats the 8 bytes of their bits, both little endian.
+
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+
+use crate::{
+    complex::Complex,
+    contract::{Comparison, Contract, ContractKind},
+    format::PrintFormat,
+    instructions::Instructions,
+    interpreter::RunOptions,
+    log::LogLevel,
+    matrix::Matrix,
+    parser::{parse_instruction, Program, SectionName},
+    value::DataType,
+};
+
+/// The first bytes of every bytecode file. Source files never start with a zero byte
+pub const MAGIC: &[u8; 4] = b"\0toy";
+
+/// Bumped whenever the layout changes, so old files are rejected instead of misread
+const FORMAT_VERSION: u16 = 1;
+
+/// How deeply Lists may be nested in a constant, so a crafted file cannot overflow the
+/// stack of the decoder
+const MAX_NESTING: usize = 64;
+
+/// Every instruction by opcode. New instructions are only ever added to the end, so
+/// existing opcodes keep their meaning
+const OPCODES: &[&str] = &[
+    "push",
+    "jump",
+    "call",
+    "ret",
+    "ifjmp",
+    "switch",
+    "eq",
+    "ne",
+    "lt",
+    "gt",
+    "le",
+    "ge",
+    "and",
+    "or",
+    "not",
+    "add",
+    "sub",
+    "mul",
+    "div",
+    "mod",
+    "vector",
+    "matrix",
+    "mget",
+    "matmul",
+    "transpose",
+    "complex",
+    "re",
+    "im",
+    "cabs",
+    "dup",
+    "swap",
+    "over",
+    "rot",
+    "drop",
+    "store",
+    "load",
+    "host",
+    "print",
+    "setfmt",
+    "printtable",
+    "trace",
+    "break",
+    "log",
+    "tick",
+    "tock",
+    "frame",
+    "tone",
+    "meminfo",
+    "flush",
+    "byteat",
+    "byteslice",
+    "bytelen",
+    "tobytes",
+    "frombytes",
+    "fopen",
+    "freadn",
+    "fwriteh",
+    "fseek",
+    "fclose",
+    "cnew",
+    "cset",
+    "csave",
+    "forward",
+    "turn",
+    "penup",
+    "pendown",
+    "tsave",
+    "readall",
+    "readline",
+    "csvparse",
+    "csvemit",
+    "pushdata",
+    "getconst",
+    "getglobal",
+    "setglobal",
+    "exit",
+    "endsection",
+    "len",
+    "substr",
+    "strindex",
+    "read",
+    "readint",
+    "readfloat",
+    "toint",
+    "tofloat",
+    "tostring",
+    "tobool",
+    "list",
+    "append",
+    "get",
+    "set",
+    "pop",
+    "argc",
+    "argv",
+];
+
+const COMPARISONS: [Comparison; 6] = [
+    Comparison::Eq,
+    Comparison::Ne,
+    Comparison::Lt,
+    Comparison::Le,
+    Comparison::Gt,
+    Comparison::Ge,
+];
+
+/// Whether `bytes` start like a bytecode file rather than source
+pub fn is_bytecode(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Encodes a parsed program as bytecode
+pub fn compile(program: &[Program]) -> Vec<u8> {
+    let mut writer = Writer::default();
+
+    writer.varint(program.len() as u64);
+    for item in program {
+        writer.item(item);
+    }
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+
+    let mut pool = Writer::default();
+    pool.varint(writer.strings.len() as u64);
+    for string in &writer.strings {
+        pool.varint(string.len() as u64);
+        pool.bytes.extend_from_slice(string.as_bytes());
+    }
+
+    bytes.extend(pool.bytes);
+    bytes.extend(writer.bytes);
+    bytes
+}
+
+/// Decodes a program from bytecode written by [`compile`]
+pub fn load(bytes: &[u8]) -> Result<Vec<Program>, String> {
+    let Some(rest) = bytes.strip_prefix(MAGIC) else {
+        return Err("Not a toylang bytecode file".to_string());
+    };
+
+    let mut reader = Reader {
+        bytes: rest,
+        position: 0,
+        strings: Vec::new(),
+        options: RunOptions::default(),
+        depth: 0,
+    };
+
+    let version = u16::from_le_bytes(reader.array()?);
+    if version != FORMAT_VERSION {
+        return Err(format!(
+            "Bytecode format {version} is not supported, recompile the program"
+        ));
+    }
+
+    for _ in 0..reader.varint()? {
+        let length = reader.length()?;
+        let string = String::from_utf8(reader.take(length)?.to_vec())
+            .map_err(|_| "Invalid UTF-8 in the constant pool".to_string())?;
+        reader.strings.push(string);
+    }
+
+    let count = reader.length()?;
+    let mut program = Vec::new();
+    for _ in 0..count {
+        program.push(reader.item()?);
+    }
+
+    if reader.position != reader.bytes.len() {
+        return Err("Unexpected bytes after the section table".to_string());
+    }
+
+    Ok(program)
+}
+
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+    strings: Vec<String>,
+    /// The index of each string in `strings`
+    pool: HashMap<String, usize>,
+}
+
+impl Writer {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    fn string(&mut self, string: &str) {
+        let index = match self.pool.get(string) {
+            Some(&index) => index,
+            None => {
+                self.strings.push(string.to_string());
+                self.pool.insert(string.to_string(), self.strings.len() - 1);
+                self.strings.len() - 1
+            }
+        };
+
+        self.varint(index as u64);
+    }
+
+    /// Writes 0 for `None`, or one more than the value
+    fn optional(&mut self, value: Option<u64>) {
+        self.varint(value.map_or(0, |value| value + 1));
+    }
+
+    fn item(&mut self, item: &Program) {
+        match item {
+            Program::Section(name, instructions) => {
+                self.bytes.push(0);
+                self.string(&name.0);
+                self.varint(instructions.len() as u64);
+                for instruction in instructions {
+                    self.instruction(instruction);
+                }
+            }
+            Program::Data(name, text) => {
+                self.bytes.push(1);
+                self.string(&name.0);
+                self.string(text);
+            }
+            Program::Const(name, values) => {
+                self.bytes.push(2);
+                self.string(name);
+                self.varint(values.len() as u64);
+                for value in values {
+                    self.value(value);
+                }
+            }
+            Program::Contract(section, contract) => {
+                self.bytes.push(3);
+                self.string(&section.0);
+                self.bytes.push(match contract.kind {
+                    ContractKind::Pre => 0,
+                    ContractKind::Post => 1,
+                });
+                let comparison = COMPARISONS.iter().position(|c| *c == contract.comparison);
+                self.bytes.push(comparison.unwrap_or_default() as u8);
+                self.varint(contract.depth as u64);
+            }
+            Program::States(group, states) => {
+                self.bytes.push(4);
+                self.string(group);
+                self.varint(states.len() as u64);
+                for state in states {
+                    self.string(state);
+                }
+            }
+            Program::Budget(section, steps) => {
+                self.bytes.push(5);
+                self.string(&section.0);
+                self.varint(*steps as u64);
+            }
+        }
+    }
+
+    fn instruction(&mut self, instruction: &Instructions) {
+        let mnemonic = match instruction {
+            Instructions::Host { .. } => "host",
+            instruction => instruction.mnemonic(),
+        };
+
+        let Some(opcode) = OPCODES.iter().position(|other| *other == mnemonic) else {
+            unreachable!("{mnemonic} has no opcode");
+        };
+        self.bytes.push(opcode as u8);
+
+        match instruction {
+            Instructions::Push(value) => self.value(value),
+            Instructions::Jump(name)
+            | Instructions::Call(name)
+            | Instructions::IfJmp(name)
+            | Instructions::Switch(name)
+            | Instructions::Store(name)
+            | Instructions::Load(name)
+            | Instructions::Trace(name)
+            | Instructions::PushData(name)
+            | Instructions::GetGlobal(name)
+            | Instructions::SetGlobal(name)
+            | Instructions::EndSection(name) => self.string(name),
+            Instructions::Vector(length) => self.varint(*length as u64),
+            Instructions::Matrix(rows, cols) => {
+                self.varint(*rows as u64);
+                self.varint(*cols as u64);
+            }
+            Instructions::Frame(fps) => self.varint(u64::from(*fps)),
+            Instructions::Print(None) => self.optional(None),
+            Instructions::Print(Some(format)) => {
+                self.bytes.push(1);
+                self.string(&format.to_string());
+            }
+            Instructions::Log(level) => self.string(&format!("{level:?}").to_lowercase()),
+            Instructions::GetConst(name, index) => {
+                self.string(name);
+                self.optional(index.map(|index| index as u64));
+            }
+            Instructions::Argv(index) => self.optional(index.map(|index| index as u64)),
+            Instructions::Host { name, pops, pushes } => {
+                self.string(name);
+                self.varint(*pops as u64);
+                self.varint(*pushes as u64);
+            }
+            _ => {}
+        }
+    }
+
+    fn value(&mut self, value: &DataType) {
+        match value {
+            DataType::Bool(value) => self.bytes.extend([0, *value as u8]),
+            DataType::Int(value) => {
+                self.bytes.push(1);
+                self.bytes.extend(value.to_le_bytes());
+            }
+            DataType::Float(value) => {
+                self.bytes.push(2);
+                self.bytes.extend(value.to_bits().to_le_bytes());
+            }
+            DataType::String(value) => {
+                self.bytes.push(3);
+                self.string(value);
+            }
+            DataType::Bytes(value) => {
+                self.bytes.push(4);
+                self.varint(value.len() as u64);
+                self.bytes.extend_from_slice(value);
+            }
+            DataType::Handle(value) => {
+                self.bytes.push(5);
+                self.varint(*value as u64);
+            }
+            DataType::List(values) => {
+                self.bytes.push(6);
+                self.varint(values.len() as u64);
+                for value in values {
+                    self.value(value);
+                }
+            }
+            DataType::Matrix(matrix) => {
+                self.bytes.push(7);
+                self.varint(matrix.rows() as u64);
+                self.varint(matrix.cols() as u64);
+                for value in matrix.values() {
+                    self.bytes.extend(value.to_bits().to_le_bytes());
+                }
+            }
+            DataType::Complex(value) => {
+                self.bytes.push(8);
+                self.bytes.extend(value.re.to_bits().to_le_bytes());
+                self.bytes.extend(value.im.to_bits().to_le_bytes());
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    strings: Vec<String>,
+    /// For decoding instructions without operands with the parser
+    options: RunOptions,
+    /// How many Lists the value being decoded is inside of
+    depth: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, length: usize) -> Result<&[u8], String> {
+        let end = self
+            .position
+            .checked_add(length)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or("Bytecode ends unexpectedly")?;
+
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err("Invalid varint in bytecode".to_string())
+    }
+
+    /// A count or length, which cannot be longer than the rest of the file
+    fn length(&mut self) -> Result<usize, String> {
+        let length = self.varint()?;
+
+        usize::try_from(length)
+            .ok()
+            .filter(|length| *length <= self.bytes.len() - self.position)
+            .ok_or_else(|| format!("Invalid length {length} in bytecode"))
+    }
+
+    fn usize(&mut self) -> Result<usize, String> {
+        let value = self.varint()?;
+        usize::try_from(value).map_err(|_| format!("{value} is too large for this machine"))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let index = self.varint()?;
+
+        usize::try_from(index)
+            .ok()
+            .and_then(|index| self.strings.get(index))
+            .cloned()
+            .ok_or_else(|| format!("Invalid constant pool index {index}"))
+    }
+
+    fn optional(&mut self) -> Result<Option<u64>, String> {
+        Ok(self.varint()?.checked_sub(1))
+    }
+
+    fn f64(&mut self) -> Result<f64, String> {
+        Ok(f64::from_bits(u64::from_le_bytes(self.array()?)))
+    }
+
+    fn item(&mut self) -> Result<Program, String> {
+        Ok(match self.byte()? {
+            0 => {
+                let name = SectionName(self.string()?);
+                let count = self.length()?;
+                let mut instructions = Vec::with_capacity(count);
+                for _ in 0..count {
+                    instructions.push(self.instruction()?);
+                }
+                Program::Section(name, instructions)
+            }
+            1 => Program::Data(SectionName(self.string()?), self.string()?),
+            2 => {
+                let name = self.string()?;
+                let count = self.length()?;
+                let mut values = Vec::with_capacity(count);
+                for _ in 0..count {
+                    values.push(self.value()?);
+                }
+                Program::Const(name, values)
+            }
+            3 => {
+                let section = SectionName(self.string()?);
+                let kind = match self.byte()? {
+                    0 => ContractKind::Pre,
+                    1 => ContractKind::Post,
+                    kind => return Err(format!("Invalid contract kind {kind}")),
+                };
+                let comparison = self.byte()?;
+                let Some(&comparison) = COMPARISONS.get(comparison as usize) else {
+                    return Err(format!("Invalid contract comparison {comparison}"));
+                };
+
+                Program::Contract(
+                    section,
+                    Contract {
+                        kind,
+                        comparison,
+                        depth: self.usize()?,
+                    },
+                )
+            }
+            4 => {
+                let group = self.string()?;
+                let count = self.length()?;
+                let mut states = Vec::with_capacity(count);
+                for _ in 0..count {
+                    states.push(self.string()?);
+                }
+                Program::States(group, states)
+            }
+            5 => Program::Budget(SectionName(self.string()?), self.usize()?),
+            tag => return Err(format!("Invalid section tag {tag}")),
+        })
+    }
+
+    fn instruction(&mut self) -> Result<Instructions, String> {
+        let opcode = self.byte()?;
+        let Some(&mnemonic) = OPCODES.get(opcode as usize) else {
+            return Err(format!("Invalid opcode {opcode}"));
+        };
+
+        Ok(match mnemonic {
+            "push" => Instructions::Push(self.value()?),
+            "jump" => Instructions::Jump(self.string()?),
+            "call" => Instructions::Call(self.string()?),
+            "ifjmp" => Instructions::IfJmp(self.string()?),
+            "switch" => Instructions::Switch(self.string()?),
+            "store" => Instructions::Store(self.string()?),
+            "load" => Instructions::Load(self.string()?),
+            "trace" => Instructions::Trace(self.string()?),
+            "pushdata" => Instructions::PushData(self.string()?),
+            "getglobal" => Instructions::GetGlobal(self.string()?),
+            "setglobal" => Instructions::SetGlobal(self.string()?),
+            "endsection" => Instructions::EndSection(self.string()?),
+            "vector" | "frame" => {
+                let operand = self.varint()?;
+                self.reparse(&format!("{mnemonic} {operand}"))?
+            }
+            "matrix" => {
+                let (rows, cols) = (self.varint()?, self.varint()?);
+                self.reparse(&format!("matrix {rows} {cols}"))?
+            }
+            "print" => match self.optional()? {
+                None => Instructions::Print(None),
+                Some(_) => Instructions::Print(Some(PrintFormat::parse(&self.string()?)?)),
+            },
+            "log" => {
+                let level = self.string()?;
+                Instructions::Log(LogLevel::from_str(&level, true)?)
+            }
+            "getconst" => {
+                let name = self.string()?;
+                let index = self.optional()?.map(usize::try_from).transpose();
+                Instructions::GetConst(name, index.map_err(|_| "Invalid getconst index")?)
+            }
+            "argv" => {
+                let index = self.optional()?.map(usize::try_from).transpose();
+                Instructions::Argv(index.map_err(|_| "Invalid argv index")?)
+            }
+            "host" => Instructions::Host {
+                name: self.string()?,
+                pops: self.usize()?,
+                pushes: self.usize()?,
+            },
+            mnemonic => match parse_instruction(mnemonic, &self.options)? {
+                Some(instruction) => instruction,
+                None => return Err(format!("Cannot decode {mnemonic}")),
+            },
+        })
+    }
+
+    /// Decodes an instruction through the parser, so its operands are checked the same way
+    /// as in source
+    fn reparse(&self, line: &str) -> Result<Instructions, String> {
+        parse_instruction(line, &self.options)?.ok_or_else(|| format!("Cannot decode {line}"))
+    }
+
+    fn value(&mut self) -> Result<DataType, String> {
+        Ok(match self.byte()? {
+            0 => DataType::Bool(self.byte()? != 0),
+            1 => DataType::Int(i64::from_le_bytes(self.array()?)),
+            2 => DataType::Float(self.f64()?),
+            3 => DataType::String(self.string()?),
+            4 => {
+                let length = self.length()?;
+                DataType::Bytes(self.take(length)?.to_vec())
+            }
+            5 => DataType::Handle(self.usize()?),
+            6 => {
+                if self.depth == MAX_NESTING {
+                    return Err(format!(
+                        "Lists nested more than {MAX_NESTING} deep in bytecode"
+                    ));
+                }
+
+                let count = self.length()?;
+                let mut values = Vec::with_capacity(count);
+                self.depth += 1;
+                for _ in 0..count {
+                    values.push(self.value()?);
+                }
+                self.depth -= 1;
+                DataType::List(values)
+            }
+            7 => {
+                let (rows, cols) = (self.usize()?, self.usize()?);
+                let count = rows.checked_mul(cols).ok_or("Invalid matrix size")?;
+                let mut values = Vec::with_capacity(count.min(self.bytes.len() / 8));
+                for _ in 0..count {
+                    values.push(self.f64()?);
+                }
+                DataType::Matrix(Matrix::new(rows, cols, values).ok_or("Invalid matrix size")?)
+            }
+            8 => DataType::Complex(Complex::new(self.f64()?, self.f64()?)),
+            tag => return Err(format!("Invalid value tag {tag}")),
+        })
+    }
+}
@@ -0,0 +1,25 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// A handle for stopping a running [`Interpreter`](crate::Interpreter) from another thread.
+/// The interpreter checks it before every instruction and stops with
+/// [`RuntimeError::Cancelled`](crate::RuntimeError::Cancelled) once it has been triggered.
+/// Cancellation is permanent, every later run stops immediately as well
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
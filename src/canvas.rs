@@ -0,0 +1,168 @@
+/// The largest width or height of a canvas, which keeps a typo from allocating gigabytes
+pub(crate) const MAX_SIDE: usize = 16384;
+
+/// An RGB image programs draw on with `cset` and save with `csave`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Canvas {
+    width: usize,
+    height: usize,
+    /// Rows of 3 bytes per pixel, top to bottom
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    /// A black canvas
+    pub(crate) fn new(width: usize, height: usize) -> Canvas {
+        Canvas {
+            width,
+            height,
+            pixels: vec![0; width * height * 3],
+        }
+    }
+
+    /// Colours a pixel with a `0xRRGGBB` colour, returning whether it is on the canvas
+    pub(crate) fn set(&mut self, x: usize, y: usize, color: u32) -> bool {
+        if x >= self.width || y >= self.height {
+            return false;
+        }
+
+        let offset = (y * self.width + x) * 3;
+        self.pixels[offset..offset + 3].copy_from_slice(&color.to_be_bytes()[1..]);
+        true
+    }
+
+    /// Colours the pixels on a straight line between two points, leaving out the part of it
+    /// that is off the canvas
+    pub(crate) fn line(&mut self, from: (f64, f64), to: (f64, f64), color: u32) {
+        let point = |t: f64| (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t);
+
+        // Clip the line to the canvas first, so a long one only walks the pixels it covers
+        let (mut start, mut end) = (0.0f64, 1.0f64);
+        for (a, b, side) in [(from.0, to.0, self.width), (from.1, to.1, self.height)] {
+            let (low, high) = (-0.5, side as f64 - 0.5);
+
+            if a == b {
+                if a < low || a >= high {
+                    return;
+                }
+                continue;
+            }
+
+            let (t0, t1) = ((low - a) / (b - a), (high - a) / (b - a));
+            start = start.max(t0.min(t1));
+            end = end.min(t0.max(t1));
+        }
+
+        if start > end {
+            return;
+        }
+
+        let (a, b) = (point(start), point(end));
+        let steps = (b.0 - a.0).abs().max((b.1 - a.1).abs()).ceil() as usize;
+
+        for step in 0..=steps {
+            let t = if steps == 0 {
+                0.0
+            } else {
+                step as f64 / steps as f64
+            };
+            let (x, y) = (
+                (a.0 + (b.0 - a.0) * t).round(),
+                (a.1 + (b.1 - a.1) * t).round(),
+            );
+
+            if x >= 0.0 && y >= 0.0 {
+                self.set(x as usize, y as usize, color);
+            }
+        }
+    }
+
+    /// Approximate number of bytes the canvas holds
+    pub(crate) fn bytes(&self) -> usize {
+        self.pixels.len()
+    }
+
+    /// Encodes the canvas as a PNG. The image data is stored uncompressed, which keeps the
+    /// encoder small at the cost of larger files
+    pub(crate) fn to_png(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(self.width as u32).to_be_bytes());
+        header.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // 8 bits per channel, RGB, default compression, filtering and no interlacing
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        // Each row starts with the filter it uses, none
+        let mut scanlines = Vec::with_capacity(self.pixels.len() + self.height);
+        for row in self.pixels.chunks(self.width * 3) {
+            scanlines.push(0);
+            scanlines.extend_from_slice(row);
+        }
+
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        chunk(&mut png, b"IHDR", &header);
+        chunk(&mut png, b"IDAT", &zlib_stored(&scanlines));
+        chunk(&mut png, b"IEND", &[]);
+        png
+    }
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wraps data in a zlib stream of uncompressed blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let length = block.len() as u16;
+
+        stream.push(last as u8);
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
@@ -0,0 +1,57 @@
+//! Static checks for `toylang check`, which finds every problem that would keep a program
+//! from running without running it.
+//!
+//! Parsing normally stops at the first error. Here the parser is fed the rest of the source
+//! after one, so unknown instructions, malformed literals and duplicate sections are all
+//! reported with their lines. What did parse is then checked as a whole for a `main`
+//! section and for jumps, calls and other references to things that are not defined.
+
+use crate::{
+    error::ParseError,
+    interpreter::RunOptions,
+    parser::{place, unknown_references, verify_contracts, Parser, Program},
+};
+
+/// Every problem with `source`, in the order they appear. An empty list means the program
+/// is ready to run
+pub fn check(source: &str, options: &RunOptions) -> Vec<ParseError> {
+    let mut parser = Parser::new(options);
+    let mut program = Vec::new();
+    let mut problems = Vec::new();
+
+    for line in source.lines() {
+        match parser.feed(line) {
+            Ok(Some(item)) => place(&mut program, parser.replaced(), item),
+            Ok(None) => {}
+            Err(error) => problems.push(error),
+        }
+    }
+
+    match parser.finish() {
+        Ok(Some(item)) => place(&mut program, parser.replaced(), item),
+        Ok(None) => {}
+        Err(error) => problems.push(error),
+    }
+
+    // Duplicate sections are only found once they end, after the lines inside them
+    problems.sort_by_key(|problem| problem.line);
+
+    let has_main = program
+        .iter()
+        .any(|item| matches!(item, Program::Section(name, _) if name.0 == "main"));
+
+    if !has_main {
+        problems.push(ParseError::program(
+            "The program has no main section to start from",
+        ));
+    }
+
+    problems.extend(
+        unknown_references(&program)
+            .into_iter()
+            .chain(verify_contracts(&program))
+            .map(ParseError::program),
+    );
+
+    problems
+}
@@ -0,0 +1,276 @@
+//! Snapshots of a running program, written periodically so the state of a long run that
+//! crashed or was killed can be loaded into the debugger afterwards.
+//!
+//! A checkpoint is a JSON object holding the program as source, the stream of instructions
+//! being executed and the position in it, the streams of the `call`s it will return to, the
+//! stack, the globals and the variables. Open files are not
+//! part of it, so Handles on a restored stack no longer refer to anything.
+
+use std::path::Path;
+
+use crate::{
+    complex::Complex,
+    instructions::Instructions,
+    interpreter::RunOptions,
+    json::Json,
+    matrix::Matrix,
+    parser::{parse, parse_instruction, Program},
+    Value,
+};
+
+/// Bumped whenever the layout changes, so old checkpoints are rejected instead of misread
+const FORMAT_VERSION: usize = 3;
+
+/// The state of a program between two instructions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    /// How many instructions had run when the checkpoint was taken
+    pub step: usize,
+    /// The program's sections and declarations, as source
+    pub source: String,
+    /// The program laid out section by section, or with `--compat splice` the stream of
+    /// instructions being executed including any sections spliced in by jumps
+    pub instructions: Vec<Instructions>,
+    /// The position of the next instruction to run
+    pub ic: usize,
+    /// The streams and positions `ret` returns to, outermost call first
+    pub calls: Vec<(Vec<Instructions>, usize)>,
+    pub stack: Vec<Value>,
+    /// Globals sorted by name
+    pub globals: Vec<(String, Value)>,
+    /// Variables set with `store`, sorted by name
+    pub variables: Vec<(String, Value)>,
+}
+
+impl Checkpoint {
+    /// Reads a checkpoint written by a run with `checkpoint_every` set
+    pub fn load(path: &Path) -> Result<Checkpoint, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| format!("Cannot read {}: {error}", path.display()))?;
+
+        Checkpoint::from_json(&Json::parse(&contents)?)
+            .map_err(|error| format!("Invalid checkpoint {}: {error}", path.display()))
+    }
+
+    /// Parses the program the checkpoint was taken from
+    pub(crate) fn program(&self, options: &RunOptions) -> Vec<Program> {
+        parse(&self.source, options)
+    }
+
+    pub fn to_json(&self) -> Json {
+        Json::object([
+            ("version", Json::from(FORMAT_VERSION)),
+            ("step", Json::from(self.step)),
+            ("source", Json::from(self.source.as_str())),
+            ("instructions", stream_to_json(&self.instructions)),
+            ("ic", Json::from(self.ic)),
+            (
+                "calls",
+                Json::Array(
+                    self.calls
+                        .iter()
+                        .map(|(instructions, ic)| {
+                            Json::object([
+                                ("instructions", stream_to_json(instructions)),
+                                ("ic", Json::from(*ic)),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+            (
+                "stack",
+                Json::Array(self.stack.iter().map(value_to_json).collect()),
+            ),
+            (
+                "globals",
+                Json::object(
+                    self.globals
+                        .iter()
+                        .map(|(name, value)| (name.as_str(), value_to_json(value))),
+                ),
+            ),
+            (
+                "variables",
+                Json::object(
+                    self.variables
+                        .iter()
+                        .map(|(name, value)| (name.as_str(), value_to_json(value))),
+                ),
+            ),
+        ])
+    }
+
+    pub fn from_json(json: &Json) -> Result<Checkpoint, String> {
+        let field = |name: &str| json.get(name).ok_or(format!("missing `{name}`"));
+
+        let version = field("version")?.as_usize();
+        if version != Some(FORMAT_VERSION) {
+            return Err(format!("unsupported version {}", field("version")?));
+        }
+
+        let instructions = stream_from_json(field("instructions")?)?;
+
+        let calls = field("calls")?
+            .as_array()
+            .ok_or("`calls` must be an array")?
+            .iter()
+            .map(|call| {
+                let instructions = call.get("instructions").ok_or("missing `instructions`")?;
+                let ic = call.get("ic").and_then(Json::as_usize);
+
+                Ok((
+                    stream_from_json(instructions)?,
+                    ic.ok_or("`ic` must be a position")?,
+                ))
+            })
+            .collect::<Result<_, String>>()?;
+
+        let stack = field("stack")?
+            .as_array()
+            .ok_or("`stack` must be an array")?
+            .iter()
+            .map(value_from_json)
+            .collect::<Result<_, _>>()?;
+
+        let globals = field("globals")?
+            .as_object()
+            .ok_or("`globals` must be an object")?
+            .iter()
+            .map(|(name, value)| Ok((name.clone(), value_from_json(value)?)))
+            .collect::<Result<_, String>>()?;
+
+        let variables = field("variables")?
+            .as_object()
+            .ok_or("`variables` must be an object")?
+            .iter()
+            .map(|(name, value)| Ok((name.clone(), value_from_json(value)?)))
+            .collect::<Result<_, String>>()?;
+
+        Ok(Checkpoint {
+            step: field("step")?.as_usize().ok_or("`step` must be a count")?,
+            source: field("source")?
+                .as_str()
+                .ok_or("`source` must be a string")?
+                .to_string(),
+            instructions,
+            ic: field("ic")?.as_usize().ok_or("`ic` must be a position")?,
+            calls,
+            stack,
+            globals,
+            variables,
+        })
+    }
+}
+
+fn stream_to_json(instructions: &[Instructions]) -> Json {
+    Json::Array(
+        instructions
+            .iter()
+            .map(|instruction| Json::from(instruction.to_string()))
+            .collect(),
+    )
+}
+
+fn stream_from_json(json: &Json) -> Result<Vec<Instructions>, String> {
+    // Traces are only kept in the stream of debug runs, so a trace in a checkpoint was
+    // running and must not be stripped again
+    let options = RunOptions {
+        debug: true,
+        ..RunOptions::default()
+    };
+
+    json.as_array()
+        .ok_or("`instructions` must be an array")?
+        .iter()
+        .map(|line| {
+            let line = line.as_str().ok_or("instructions must be strings")?;
+
+            // The marker debug runs add at the end of every section
+            if let Some(section) = line.strip_prefix("// end of ") {
+                return Ok(Instructions::EndSection(section.to_string()));
+            }
+
+            parse_instruction(line, &options)
+                .ok()
+                .flatten()
+                .ok_or(format!("cannot restore `{line}`"))
+        })
+        .collect()
+}
+
+/// Encodes a value as an object naming its type, e.g. `{"Int":"3"}`. Ints and Handles are
+/// written as strings since JSON numbers cannot hold every 64-bit integer exactly
+fn value_to_json(value: &Value) -> Json {
+    let encoded = match value {
+        Value::Bool(value) => Json::Bool(*value),
+        Value::Int(value) => Json::from(value.to_string()),
+        Value::Handle(value) => Json::from(value.to_string()),
+        Value::Float(value) => Json::Number(*value),
+        Value::String(value) => Json::from(value.as_str()),
+        Value::Bytes(_) => Json::from(value.to_string()),
+        Value::List(values) => Json::Array(values.iter().map(value_to_json).collect()),
+        Value::Complex(complex) => {
+            Json::Array(vec![Json::from(complex.re), Json::from(complex.im)])
+        }
+        Value::Matrix(matrix) => Json::object([
+            ("rows", Json::from(matrix.rows())),
+            ("cols", Json::from(matrix.cols())),
+            (
+                "values",
+                Json::Array(matrix.values().iter().copied().map(Json::from).collect()),
+            ),
+        ]),
+    };
+
+    Json::object([(value.type_name(), encoded)])
+}
+
+fn value_from_json(json: &Json) -> Result<Value, String> {
+    let invalid = || format!("invalid value {json}");
+
+    let [(type_name, encoded)] = json.as_object().ok_or_else(invalid)? else {
+        return Err(invalid());
+    };
+
+    let value = match (type_name.as_str(), encoded) {
+        ("Bool", Json::Bool(value)) => Value::Bool(*value),
+        ("Int", Json::String(value)) => Value::Int(value.parse().map_err(|_| invalid())?),
+        ("Handle", Json::String(value)) => Value::Handle(value.parse().map_err(|_| invalid())?),
+        ("Float", Json::Number(value)) => Value::Float(*value),
+        ("String", Json::String(value)) => Value::String(value.clone()),
+        ("Bytes", Json::String(digits)) if digits.is_ascii() && digits.len().is_multiple_of(2) => {
+            Value::Bytes(
+                (0..digits.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| invalid()))
+                    .collect::<Result<_, _>>()?,
+            )
+        }
+        ("List", Json::Array(values)) => Value::List(
+            values
+                .iter()
+                .map(value_from_json)
+                .collect::<Result<_, _>>()?,
+        ),
+        ("Complex", Json::Array(parts)) => match parts.as_slice() {
+            [Json::Number(re), Json::Number(im)] => Value::Complex(Complex::new(*re, *im)),
+            _ => return Err(invalid()),
+        },
+        ("Matrix", matrix) => {
+            let size = |key| matrix.get(key).and_then(Json::as_usize).ok_or_else(invalid);
+            let values = matrix
+                .get("values")
+                .and_then(Json::as_array)
+                .ok_or_else(invalid)?
+                .iter()
+                .map(|value| value.as_f64().ok_or_else(invalid))
+                .collect::<Result<_, _>>()?;
+
+            Value::Matrix(Matrix::new(size("rows")?, size("cols")?, values).ok_or_else(invalid)?)
+        }
+        _ => return Err(invalid()),
+    };
+
+    Ok(value)
+}
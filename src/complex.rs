@@ -0,0 +1,100 @@
+use std::{
+    fmt,
+    ops::{Add, Div, Mul, Sub},
+};
+
+use crate::value::DataType;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Complex {
+        Complex { re, im }
+    }
+
+    /// The distance from zero
+    pub fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    /// Combines two values of which at least one is Complex, treating Ints and Floats as
+    /// having no imaginary part. `op` returns `None` for a division by zero
+    pub(crate) fn combine(
+        a: &DataType,
+        b: &DataType,
+        verb: &str,
+        op: impl Fn(Complex, Complex) -> Option<Complex>,
+    ) -> Result<Complex, String> {
+        let complex = |value: &DataType| match value {
+            DataType::Int(a) => Some(Complex::new(*a as f64, 0.0)),
+            DataType::Float(a) => Some(Complex::new(*a, 0.0)),
+            DataType::Complex(a) => Some(*a),
+            _ => None,
+        };
+
+        let (Some(x), Some(y)) = (complex(a), complex(b)) else {
+            return Err(format!(
+                "Cannot {verb} {} and {}",
+                a.type_name(),
+                b.type_name()
+            ));
+        };
+
+        op(x, y).ok_or_else(|| "Cannot divide by zero".to_string())
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+
+    fn div(self, other: Complex) -> Complex {
+        let divisor = other.re * other.re + other.im * other.im;
+
+        Complex::new(
+            (self.re * other.re + self.im * other.im) / divisor,
+            (self.im * other.re - self.re * other.im) / divisor,
+        )
+    }
+}
+
+impl fmt::Display for Complex {
+    /// Writes the number the way it is written in source, e.g. `1.5-2i`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.im.is_sign_negative() {
+            write!(f, "{}-{}i", self.re, -self.im)
+        } else {
+            write!(f, "{}+{}i", self.re, self.im)
+        }
+    }
+}
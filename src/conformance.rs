@@ -0,0 +1,515 @@
+//! A backend independent format for describing how programs must behave, so every way of
+//! running toylang can be checked against the same suite.
+//!
+//! Each test case is a `.tyt` file holding the program, followed by optional sections
+//! introduced by a `--- name` line:
+//!
+//! ```text
+//! push "hello"
+//! print
+//! --- stdin
+//! (input given to readall and readline)
+//! --- stdout
+//! hello
+//! --- error
+//! (part of the error message the program must stop with)
+//! --- exit
+//! 0
+//! ```
+//!
+//! The line break ending a section is not part of it, so output that ends in a newline is
+//! written with an empty line after it. A program is expected to print nothing and exit
+//! with 0 unless stated otherwise. Naming an `error` makes the expected exit code default
+//! to 1, the code for any failure.
+//!
+//! A plain program can describe the same expectations with directives, which the parser
+//! treats as comments:
+//!
+//! ```text
+//! #stdin: 42
+//! #stdout: Enter a number:
+//! #exit: 0
+//! ```
+//!
+//! Every `#stdin:` directive is a line of input. `#stdout:` directives are lines of output,
+//! joined by line breaks without one at the end, and `#error:` works like the section.
+
+use std::{
+    io::Cursor,
+    panic::AssertUnwindSafe,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "threads")]
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    mpsc,
+};
+
+use crate::{
+    error::RuntimeError,
+    interpreter::{Interpreter, RunOptions},
+    parser::{try_parse, try_validate},
+    sandbox::SharedBuffer,
+    test_report::{diff_lines, Status, TestResult},
+};
+
+/// How long a test that stopped responding gets past its time limit before it is abandoned
+#[cfg(feature = "threads")]
+const GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+/// A program and the behaviour expected from it
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestCase {
+    pub name: String,
+    pub program: String,
+    pub stdin: String,
+    pub stdout: String,
+    pub exit_code: i32,
+    /// Text the error message must contain, if the program is expected to fail
+    pub error: Option<String>,
+}
+
+/// What happened when a backend ran a test case
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub stdout: String,
+    pub exit_code: i32,
+    pub error: Option<String>,
+    /// Whether the program was stopped for running past its time limit
+    pub timed_out: bool,
+}
+
+/// Resources each test case may use when run in isolation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub timeout: Duration,
+    /// Maximum approximate number of bytes the program's values may occupy
+    pub max_memory: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            timeout: Duration::from_secs(10),
+            max_memory: None,
+        }
+    }
+}
+
+impl TestCase {
+    /// Parses a test case from the contents of a `.tyt` file
+    pub fn parse(name: impl Into<String>, contents: &str) -> Result<TestCase, String> {
+        let name = name.into();
+        let mut sections: Vec<(&str, String)> = vec![("program", String::new())];
+
+        for line in contents.lines() {
+            if let Some(section) = line.strip_prefix("--- ") {
+                let section = section.trim();
+
+                if !["stdin", "stdout", "error", "exit"].contains(&section) {
+                    return Err(format!("{name}: unknown section `{section}`"));
+                }
+
+                if sections.iter().any(|(existing, _)| *existing == section) {
+                    return Err(format!("{name}: section `{section}` given twice"));
+                }
+
+                sections.push((section, String::new()));
+                continue;
+            }
+
+            let (_, body) = sections.last_mut().unwrap();
+            body.push_str(line);
+            body.push('\n');
+        }
+
+        let mut take = |section: &str| {
+            sections
+                .iter_mut()
+                .find(|(name, _)| *name == section)
+                .map(|(_, body)| {
+                    body.pop();
+                    std::mem::take(body)
+                })
+        };
+
+        let program = take("program").unwrap_or_default();
+        let stdin = take("stdin").unwrap_or_default();
+        let stdout = take("stdout").unwrap_or_default();
+        let error = take("error").map(|error| error.trim().to_string());
+
+        let exit_code = match take("exit") {
+            Some(code) => code
+                .trim()
+                .parse()
+                .map_err(|_| format!("{name}: invalid exit code `{}`", code.trim()))?,
+            None if error.is_some() => 1,
+            None => 0,
+        };
+
+        Ok(TestCase {
+            name,
+            program,
+            stdin,
+            stdout,
+            exit_code,
+            error,
+        })
+    }
+
+    /// Reads a test case from a program annotated with `#stdin:`, `#stdout:`, `#error:`
+    /// and `#exit:` directives
+    pub fn from_program(name: impl Into<String>, contents: &str) -> Result<TestCase, String> {
+        let name = name.into();
+        let mut stdin = String::new();
+        let mut stdout: Vec<&str> = Vec::new();
+        let mut error = None;
+        let mut exit_code = None;
+
+        for line in contents.lines() {
+            let Some((directive, value)) = line
+                .strip_prefix('#')
+                .and_then(|directive| directive.split_once(':'))
+            else {
+                continue;
+            };
+
+            let value = value.strip_prefix(' ').unwrap_or(value);
+
+            match directive {
+                "stdin" => {
+                    stdin.push_str(value);
+                    stdin.push('\n');
+                }
+                "stdout" => stdout.push(value),
+                "error" => error = Some(value.trim().to_string()),
+                "exit" => {
+                    exit_code = Some(
+                        value
+                            .trim()
+                            .parse()
+                            .map_err(|_| format!("{name}: invalid exit code `{}`", value.trim()))?,
+                    )
+                }
+                _ => {}
+            }
+        }
+
+        Ok(TestCase {
+            exit_code: exit_code.unwrap_or(if error.is_some() { 1 } else { 0 }),
+            name,
+            program: contents.to_string(),
+            stdin,
+            stdout: stdout.join("\n"),
+            error,
+        })
+    }
+
+    /// Reads a `.tyt` test case, or a program with directives from any other file
+    pub fn load(path: &Path) -> Result<TestCase, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| format!("Cannot read {}: {error}", path.display()))?;
+        let name = path.display().to_string();
+
+        if path.extension().is_some_and(|ext| ext == "tyt") {
+            TestCase::parse(name, &contents)
+        } else {
+            TestCase::from_program(name, &contents)
+        }
+    }
+
+    /// Loads the given test files, and every `.tyl` and `.tyt` file in the given
+    /// directories, sorted by path
+    pub fn discover(paths: &[PathBuf]) -> Result<Vec<TestCase>, String> {
+        let mut files = Vec::new();
+
+        for path in paths {
+            if !path.is_dir() {
+                files.push(path.clone());
+                continue;
+            }
+
+            let entries = std::fs::read_dir(path)
+                .map_err(|error| format!("Cannot read {}: {error}", path.display()))?;
+
+            for entry in entries {
+                let file = entry.map_err(|error| error.to_string())?.path();
+
+                if file
+                    .extension()
+                    .is_some_and(|ext| ext == "tyl" || ext == "tyt")
+                {
+                    // Name tests in the current directory without a leading `./`
+                    files.push(
+                        file.strip_prefix(".")
+                            .map_or(file.clone(), Path::to_path_buf),
+                    );
+                }
+            }
+        }
+
+        files.sort();
+        files.iter().map(|file| TestCase::load(file)).collect()
+    }
+
+    /// Loads every `.tyt` file in `dir`, sorted by name
+    pub fn load_dir(dir: &Path) -> Result<Vec<TestCase>, String> {
+        let entries = std::fs::read_dir(dir)
+            .map_err(|error| format!("Cannot read {}: {error}", dir.display()))?;
+
+        let mut paths = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|error| error.to_string())?.path();
+
+            if path.extension().is_some_and(|ext| ext == "tyt") {
+                paths.push(path);
+            }
+        }
+
+        paths.sort();
+
+        paths
+            .iter()
+            .map(|path| {
+                let contents = std::fs::read_to_string(path)
+                    .map_err(|error| format!("Cannot read {}: {error}", path.display()))?;
+                let name = path.file_stem().unwrap().to_string_lossy();
+
+                TestCase::parse(name, &contents)
+            })
+            .collect()
+    }
+
+    /// Checks an outcome against the expected behaviour, describing every difference
+    pub fn check(&self, outcome: &Outcome) -> Result<(), String> {
+        let mut problems = Vec::new();
+
+        if outcome.stdout != self.stdout {
+            problems.push(format!(
+                "expected output {:?} but got {:?}",
+                self.stdout, outcome.stdout
+            ));
+        }
+
+        if outcome.exit_code != self.exit_code {
+            problems.push(format!(
+                "expected exit code {} but got {}",
+                self.exit_code, outcome.exit_code
+            ));
+        }
+
+        match (&self.error, &outcome.error) {
+            (Some(expected), Some(actual)) if !actual.contains(expected.as_str()) => {
+                problems.push(format!("expected error containing {expected:?} but got {actual:?}"))
+            }
+            (Some(expected), None) => {
+                problems.push(format!("expected error containing {expected:?} but got none"))
+            }
+            (None, Some(actual)) => problems.push(format!("unexpected error: {actual}")),
+            _ => {}
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("\n"))
+        }
+    }
+
+    /// Runs the test case in isolation and checks how it went
+    pub fn evaluate(&self, limits: Limits) -> TestResult {
+        let start = Instant::now();
+        let outcome = self.run_isolated(limits);
+        let duration = start.elapsed();
+
+        // A test that ran out of time says nothing about its output
+        let status = if outcome.timed_out {
+            Status::TimedOut(outcome.error.clone().unwrap_or_default())
+        } else {
+            match self.check(&outcome) {
+                Ok(()) => Status::Passed,
+                Err(problems) => Status::Failed(problems),
+            }
+        };
+
+        let diff = matches!(status, Status::Failed(_))
+            .then(|| {
+                (outcome.stdout != self.stdout).then(|| diff_lines(&self.stdout, &outcome.stdout))
+            })
+            .flatten();
+
+        TestResult {
+            name: self.name.clone(),
+            status,
+            duration,
+            diff,
+        }
+    }
+
+    /// Runs the test case on the reference interpreter
+    pub fn run(&self) -> Outcome {
+        self.run_with(RunOptions::default())
+    }
+
+    /// Runs the test case on the reference interpreter in a thread of its own, so a test
+    /// that loops forever or exhausts its memory cannot take the rest of the suite with it
+    #[cfg(feature = "threads")]
+    pub fn run_isolated(&self, limits: Limits) -> Outcome {
+        let case = self.clone();
+        let (sender, receiver) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let outcome = case.run_with(RunOptions {
+                timeout: Some(limits.timeout),
+                max_memory: limits.max_memory,
+                budgets: true,
+                ..RunOptions::default()
+            });
+
+            // The runner may have given up on this test already
+            let _ = sender.send(outcome);
+        });
+
+        // The interpreter stops itself once it runs past the time limit, but an instruction
+        // can block for longer (e.g. opening a pipe), in which case the thread is abandoned
+        receiver
+            .recv_timeout(limits.timeout + GRACE_PERIOD)
+            .unwrap_or_else(|_| Outcome {
+                stdout: String::new(),
+                exit_code: 1,
+                error: Some(format!("Did not finish within {:?}", limits.timeout)),
+                timed_out: true,
+            })
+    }
+
+    /// Runs the test case on the reference interpreter within `limits`. Without threads an
+    /// instruction that blocks past the time limit holds up the rest of the suite
+    #[cfg(not(feature = "threads"))]
+    pub fn run_isolated(&self, limits: Limits) -> Outcome {
+        self.run_with(RunOptions {
+            timeout: Some(limits.timeout),
+            max_memory: limits.max_memory,
+            budgets: true,
+            ..RunOptions::default()
+        })
+    }
+
+    fn run_with(&self, options: RunOptions) -> Outcome {
+        let output = SharedBuffer::default();
+
+        let program = try_parse(&self.program, &options)
+            .and_then(|program| try_validate(&program).map(|_| program));
+
+        let program = match program {
+            Ok(program) => program,
+            Err(error) => {
+                return Outcome {
+                    stdout: String::new(),
+                    exit_code: 1,
+                    error: Some(error.to_string()),
+                    timed_out: false,
+                }
+            }
+        };
+
+        // Instructions that fail by panicking are turned into errors too
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut interpreter = Interpreter::new(program, options);
+            interpreter.set_output(output.clone());
+            interpreter.set_input(Cursor::new(self.stdin.clone().into_bytes()));
+            interpreter.run()
+        }));
+
+        let timed_out = matches!(result, Ok(Err(RuntimeError::Timeout { .. })));
+
+        let error = match result {
+            Ok(Ok(())) => None,
+            Ok(Err(error)) => Some(error.to_string()),
+            Err(payload) => Some(
+                payload
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+                    .unwrap_or_else(|| "unknown error".to_string()),
+            ),
+        };
+
+        let stdout = String::from_utf8_lossy(&output.0.borrow()).into_owned();
+
+        Outcome {
+            stdout,
+            exit_code: if error.is_some() { 1 } else { 0 },
+            error,
+            timed_out,
+        }
+    }
+}
+
+/// Evaluates test cases on `jobs` threads. `report` sees every result in the order of
+/// `cases` as soon as it and all the ones before it are done, so output stays the same
+/// however the work was scheduled
+#[cfg(feature = "threads")]
+pub fn evaluate_all(
+    cases: &[TestCase],
+    limits: Limits,
+    jobs: usize,
+    mut report: impl FnMut(&TestResult),
+) -> Vec<TestResult> {
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    let mut results: Vec<Option<TestResult>> = vec![None; cases.len()];
+    let mut reported = 0;
+
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.max(1).min(cases.len()) {
+            let sender = sender.clone();
+            let next = &next;
+
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(case) = cases.get(index) else {
+                    break;
+                };
+
+                if sender.send((index, case.evaluate(limits))).is_err() {
+                    break;
+                }
+            });
+        }
+
+        // Only the workers hold senders now, so the loop ends once they are all done
+        drop(sender);
+
+        for (index, result) in receiver {
+            results[index] = Some(result);
+
+            while let Some(Some(result)) = results.get(reported) {
+                report(result);
+                reported += 1;
+            }
+        }
+    });
+
+    results.into_iter().flatten().collect()
+}
+
+/// Evaluates test cases one at a time, as `jobs` needs threads. `report` sees every result
+/// as soon as it is done
+#[cfg(not(feature = "threads"))]
+pub fn evaluate_all(
+    cases: &[TestCase],
+    limits: Limits,
+    _jobs: usize,
+    mut report: impl FnMut(&TestResult),
+) -> Vec<TestResult> {
+    cases
+        .iter()
+        .map(|case| {
+            let result = case.evaluate(limits);
+            report(&result);
+            result
+        })
+        .collect()
+}
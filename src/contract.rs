@@ -0,0 +1,109 @@
+use std::fmt;
+
+/// When a contract is checked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContractKind {
+    /// `#pre`, checked as the section is entered
+    Pre,
+    /// `#post`, checked once the section has run to its end
+    Post,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A `#pre` or `#post` annotation constraining the stack depth around a section, such as
+/// `#pre depth>=2`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contract {
+    pub kind: ContractKind,
+    pub comparison: Comparison,
+    pub depth: usize,
+}
+
+impl Contract {
+    /// Parses the condition of a contract, e.g. `depth>=2`
+    pub(crate) fn parse(kind: ContractKind, condition: &str) -> Option<Contract> {
+        let condition: String = condition.split_whitespace().collect();
+        let rest = condition.strip_prefix("depth")?;
+
+        // Two character operators first so `>=` is not read as `>`
+        let (comparison, value) = [
+            ("==", Comparison::Eq),
+            ("!=", Comparison::Ne),
+            ("<=", Comparison::Le),
+            (">=", Comparison::Ge),
+            ("<", Comparison::Lt),
+            (">", Comparison::Gt),
+        ]
+        .into_iter()
+        .find_map(|(operator, comparison)| {
+            rest.strip_prefix(operator).map(|value| (comparison, value))
+        })?;
+
+        Some(Contract {
+            kind,
+            comparison,
+            depth: value.parse().ok()?,
+        })
+    }
+
+    /// Whether a stack of the given depth satisfies the contract
+    pub fn holds(&self, depth: usize) -> bool {
+        match self.comparison {
+            Comparison::Eq => depth == self.depth,
+            Comparison::Ne => depth != self.depth,
+            Comparison::Lt => depth < self.depth,
+            Comparison::Le => depth <= self.depth,
+            Comparison::Gt => depth > self.depth,
+            Comparison::Ge => depth >= self.depth,
+        }
+    }
+
+    /// Whether the contract fails for every depth of at least `minimum`
+    pub(crate) fn fails_from(&self, minimum: usize) -> bool {
+        match self.comparison {
+            Comparison::Eq | Comparison::Le => self.depth < minimum,
+            Comparison::Lt => self.depth <= minimum,
+            Comparison::Ne | Comparison::Gt | Comparison::Ge => false,
+        }
+    }
+
+    /// The smallest depth a `#pre` contract guarantees, and whether it is exact
+    pub(crate) fn guarantees(&self) -> (usize, bool) {
+        match self.comparison {
+            Comparison::Eq => (self.depth, true),
+            Comparison::Gt => (self.depth + 1, false),
+            Comparison::Ge => (self.depth, false),
+            Comparison::Ne | Comparison::Lt | Comparison::Le => (0, false),
+        }
+    }
+}
+
+impl fmt::Display for Contract {
+    /// Renders the contract back into source form
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            ContractKind::Pre => "pre",
+            ContractKind::Post => "post",
+        };
+
+        let operator = match self.comparison {
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        };
+
+        write!(f, "#{kind} depth{operator}{}", self.depth)
+    }
+}
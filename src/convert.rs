@@ -0,0 +1,209 @@
+//! Conversions between [`Value`](crate::Value)s and Rust types, so embedders can work with
+//! typed values instead of matching on every variant. Rust types become values with `From`
+//! and values become Rust types with `TryFrom`, which fails with a [`RuntimeError`] naming
+//! the type that was expected. A `HashMap` is a List of `[key, value]` pairs ordered by key.
+//!
+//! [`FromStack`] and [`ToStack`] do the same for whole argument lists, so a host function can
+//! take `(i64, String)` off the stack and push its result back in one step.
+
+use std::collections::HashMap;
+
+use crate::{complex::Complex, error::RuntimeError, matrix::Matrix, stack::Stack, value::DataType};
+
+fn mismatch(expected: &str, found: &DataType) -> RuntimeError {
+    RuntimeError::Instruction(format!("Expected {expected}, found {}", found.type_name()))
+}
+
+/// Types that can be taken off the top of the stack. Tuples take one value per element, the
+/// last element from the top of the stack
+pub trait FromStack: Sized {
+    fn from_stack(stack: &mut Stack) -> Result<Self, RuntimeError>;
+}
+
+/// Types that can be pushed onto the stack. Tuples push one value per element, leaving the
+/// last element on top
+pub trait ToStack {
+    fn to_stack(self, stack: &mut Stack);
+}
+
+macro_rules! convert {
+    ($($type:ty => $variant:ident),* $(,)?) => {$(
+        impl From<$type> for DataType {
+            fn from(value: $type) -> Self {
+                DataType::$variant(value)
+            }
+        }
+
+        impl TryFrom<DataType> for $type {
+            type Error = RuntimeError;
+
+            fn try_from(value: DataType) -> Result<Self, RuntimeError> {
+                match value {
+                    DataType::$variant(value) => Ok(value),
+                    value => Err(mismatch(stringify!($variant), &value)),
+                }
+            }
+        }
+    )*};
+}
+
+convert! {
+    bool => Bool,
+    i64 => Int,
+    f64 => Float,
+    String => String,
+    Vec<u8> => Bytes,
+    Vec<DataType> => List,
+    Matrix => Matrix,
+    Complex => Complex,
+}
+
+impl From<&str> for DataType {
+    fn from(value: &str) -> Self {
+        DataType::String(value.to_string())
+    }
+}
+
+impl From<HashMap<String, DataType>> for DataType {
+    fn from(map: HashMap<String, DataType>) -> Self {
+        let mut pairs: Vec<(String, DataType)> = map.into_iter().collect();
+        pairs.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        DataType::List(
+            pairs
+                .into_iter()
+                .map(|(key, value)| DataType::List(vec![DataType::String(key), value]))
+                .collect(),
+        )
+    }
+}
+
+impl TryFrom<DataType> for HashMap<String, DataType> {
+    type Error = RuntimeError;
+
+    fn try_from(value: DataType) -> Result<Self, RuntimeError> {
+        let expected = "a List of [key, value] pairs";
+
+        let DataType::List(pairs) = value else {
+            return Err(mismatch(expected, &value));
+        };
+
+        pairs
+            .into_iter()
+            .map(|pair| match pair {
+                DataType::List(pair) => match <[DataType; 2]>::try_from(pair) {
+                    Ok([DataType::String(key), value]) => Ok((key, value)),
+                    Ok([key, _]) => Err(mismatch("a String key", &key)),
+                    Err(pair) => Err(RuntimeError::Instruction(format!(
+                        "Expected {expected}, found a List of {} values",
+                        pair.len()
+                    ))),
+                },
+                value => Err(mismatch(expected, &value)),
+            })
+            .collect()
+    }
+}
+
+macro_rules! stack_value {
+    ($($type:ty),* $(,)?) => {$(
+        impl FromStack for $type {
+            fn from_stack(stack: &mut Stack) -> Result<Self, RuntimeError> {
+                DataType::from_stack(stack)?.try_into()
+            }
+        }
+
+        impl ToStack for $type {
+            fn to_stack(self, stack: &mut Stack) {
+                stack.push(self.into());
+            }
+        }
+    )*};
+}
+
+stack_value!(
+    bool,
+    i64,
+    f64,
+    String,
+    Vec<u8>,
+    Vec<DataType>,
+    Matrix,
+    Complex,
+    HashMap<String, DataType>,
+);
+
+impl FromStack for DataType {
+    fn from_stack(stack: &mut Stack) -> Result<Self, RuntimeError> {
+        stack
+            .pop()
+            .ok_or_else(|| RuntimeError::Instruction("Not enough values on the stack".to_string()))
+    }
+}
+
+impl ToStack for DataType {
+    fn to_stack(self, stack: &mut Stack) {
+        stack.push(self);
+    }
+}
+
+impl ToStack for &str {
+    fn to_stack(self, stack: &mut Stack) {
+        stack.push(self.into());
+    }
+}
+
+impl FromStack for () {
+    fn from_stack(_: &mut Stack) -> Result<Self, RuntimeError> {
+        Ok(())
+    }
+}
+
+impl ToStack for () {
+    fn to_stack(self, _: &mut Stack) {}
+}
+
+impl<A: FromStack, B: FromStack> FromStack for (A, B) {
+    fn from_stack(stack: &mut Stack) -> Result<Self, RuntimeError> {
+        let b = B::from_stack(stack)?;
+        let a = A::from_stack(stack)?;
+        Ok((a, b))
+    }
+}
+
+impl<A: ToStack, B: ToStack> ToStack for (A, B) {
+    fn to_stack(self, stack: &mut Stack) {
+        self.0.to_stack(stack);
+        self.1.to_stack(stack);
+    }
+}
+
+impl<A: FromStack, B: FromStack, C: FromStack> FromStack for (A, B, C) {
+    fn from_stack(stack: &mut Stack) -> Result<Self, RuntimeError> {
+        let c = C::from_stack(stack)?;
+        let (a, b) = <(A, B)>::from_stack(stack)?;
+        Ok((a, b, c))
+    }
+}
+
+impl<A: ToStack, B: ToStack, C: ToStack> ToStack for (A, B, C) {
+    fn to_stack(self, stack: &mut Stack) {
+        (self.0, self.1).to_stack(stack);
+        self.2.to_stack(stack);
+    }
+}
+
+impl<A: FromStack, B: FromStack, C: FromStack, D: FromStack> FromStack for (A, B, C, D) {
+    fn from_stack(stack: &mut Stack) -> Result<Self, RuntimeError> {
+        let d = D::from_stack(stack)?;
+        let (a, b, c) = <(A, B, C)>::from_stack(stack)?;
+        Ok((a, b, c, d))
+    }
+}
+
+impl<A: ToStack, B: ToStack, C: ToStack, D: ToStack> ToStack for (A, B, C, D) {
+    fn to_stack(self, stack: &mut Stack) {
+        (self.0, self.1, self.2).to_stack(stack);
+        self.3.to_stack(stack);
+    }
+}
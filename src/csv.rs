@@ -0,0 +1,71 @@
+use crate::value::DataType;
+
+/// Splits CSV text into rows of fields, honouring quoted fields with `""` escapes
+pub(crate) fn parse_csv(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) if chars.peek() == Some(&'\n') => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (c, _) => field.push(c),
+        }
+    }
+
+    if quoted {
+        return Err("Unterminated quoted field in CSV".to_string());
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
+
+/// Renders rows as CSV text, quoting fields that contain separators or quotes
+pub(crate) fn emit_csv(rows: &[DataType]) -> Result<String, String> {
+    let mut text = String::new();
+
+    for row in rows {
+        let DataType::List(fields) = row else {
+            return Err(format!(
+                "csvemit rows must be Lists, found {}",
+                row.type_name()
+            ));
+        };
+
+        let fields: Vec<String> = fields
+            .iter()
+            .map(|field| {
+                let field = field.to_string();
+
+                if field.contains([',', '"', '\n', '\r']) {
+                    format!("\"{}\"", field.replace('"', "\"\""))
+                } else {
+                    field
+                }
+            })
+            .collect();
+
+        text.push_str(&fields.join(","));
+        text.push('\n');
+    }
+
+    Ok(text)
+}
@@ -0,0 +1,200 @@
+//! The interactive debugger behind `run --debug` and `toylang debug`. It pauses at
+//! breakpoints, set by line or by section, and before every `break` instruction, then reads
+//! commands until one of them resumes the program:
+//!
+//! ```text
+//! step, s (or an empty line)  run the next instruction and pause again
+//! continue, c                 run until the next breakpoint or `break`
+//! stack                       show the stack
+//! vars                        show the variables and globals
+//! where                       show the position of the next instruction
+//! calls                       show the calls in progress, innermost first
+//! break <line|section>        pause when reaching a line or the start of a section
+//! delete <line|section>       remove a breakpoint
+//! quit, q                     stop the program
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{BufRead, BufReader, Write},
+    str::FromStr,
+};
+
+use crate::{interpreter::RunOptions, parser::instruction_lines};
+
+/// Where the debugger pauses
+#[derive(Debug, Clone, PartialEq)]
+pub enum Breakpoint {
+    /// Before the instruction read from this line of the source, counting from 1
+    Line(usize),
+    /// Before the first instruction of the section
+    Section(String),
+}
+
+impl FromStr for Breakpoint {
+    type Err = String;
+
+    fn from_str(at: &str) -> Result<Breakpoint, String> {
+        let at = at.trim();
+
+        if at.is_empty() {
+            return Err("Expected a line number or a section name".to_string());
+        }
+
+        Ok(match at.parse() {
+            Ok(line) => Breakpoint::Line(line),
+            Err(_) => Breakpoint::Section(at.trim_matches(':').to_string()),
+        })
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Line(line) => write!(f, "line {line}"),
+            Breakpoint::Section(section) => write!(f, "section {section}"),
+        }
+    }
+}
+
+const HELP: &str = "\
+step, s (or an empty line)  run the next instruction and pause again
+continue, c                 run until the next breakpoint or `break`
+stack                       show the stack
+vars                        show the variables and globals
+where                       show the position of the next instruction
+calls                       show the calls in progress, innermost first
+break <line|section>        pause when reaching a line or the start of a section
+delete <line|section>       remove a breakpoint
+quit, q                     stop the program";
+
+/// A debugger command read at a pause
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Command {
+    Step,
+    Continue,
+    Stack,
+    Vars,
+    Where,
+    Calls,
+    Break(Breakpoint),
+    Delete(Breakpoint),
+    Quit,
+    Help,
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Command, String> {
+        let line = line.trim();
+        let (name, argument) = line.split_once(' ').unwrap_or((line, ""));
+
+        Ok(match name {
+            "" | "step" | "s" => Command::Step,
+            "continue" | "c" => Command::Continue,
+            "stack" => Command::Stack,
+            "vars" => Command::Vars,
+            "where" => Command::Where,
+            "calls" => Command::Calls,
+            "break" | "b" => Command::Break(argument.parse()?),
+            "delete" | "d" => Command::Delete(argument.parse()?),
+            "quit" | "q" => Command::Quit,
+            "help" | "h" => Command::Help,
+            _ => return Err(format!("Unknown command `{line}`, try `help`")),
+        })
+    }
+}
+
+/// What the debugger knows between pauses
+pub(crate) struct Debugger {
+    pub(crate) breakpoints: Vec<Breakpoint>,
+    /// Whether to pause before the next instruction whatever the breakpoints
+    pub(crate) stepping: bool,
+    /// The line each instruction was read from, by section and position in it, once the
+    /// host has provided the source
+    lines: HashMap<(String, usize), usize>,
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+}
+
+impl Debugger {
+    pub(crate) fn new(breakpoints: Vec<Breakpoint>) -> Debugger {
+        Debugger {
+            stepping: false,
+            breakpoints,
+            lines: HashMap::new(),
+            input: Box::new(BufReader::new(std::io::stdin())),
+            output: Box::new(std::io::stderr()),
+        }
+    }
+
+    pub(crate) fn set_source(&mut self, source: &str, options: &RunOptions) {
+        let lines: Vec<&str> = source.lines().collect();
+
+        self.lines = instruction_lines(&lines, options)
+            .into_iter()
+            .flat_map(|(section, _, numbers)| {
+                numbers
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(index, number)| ((section.clone(), index), number + 1))
+            })
+            .collect();
+    }
+
+    pub(crate) fn set_io(&mut self, input: Box<dyn BufRead>, output: Box<dyn Write>) {
+        self.input = input;
+        self.output = output;
+    }
+
+    /// Whether to pause at a section and position in it
+    pub(crate) fn should_pause(&self, at: Option<&(String, usize)>) -> bool {
+        if self.stepping {
+            return true;
+        }
+
+        let Some(at) = at else {
+            return false;
+        };
+
+        self.breakpoints.iter().any(|breakpoint| match breakpoint {
+            Breakpoint::Line(line) => self.lines.get(at) == Some(line),
+            Breakpoint::Section(section) => at.0 == *section && at.1 == 0,
+        })
+    }
+
+    /// A section and position in it, with the line it was read from if that is known, or
+    /// the position in the running stream for instructions outside the program's sections
+    pub(crate) fn describe(&self, at: Option<&(String, usize)>, index: usize) -> String {
+        let Some(at) = at else {
+            return format!("instruction {index}");
+        };
+
+        match self.lines.get(at) {
+            Some(line) => format!("{}+{} (line {line})", at.0, at.1),
+            None => format!("{}+{}", at.0, at.1),
+        }
+    }
+
+    pub(crate) fn say(&mut self, message: &str) {
+        writeln!(self.output, "{message}").unwrap();
+    }
+
+    pub(crate) fn help(&mut self) {
+        self.say(HELP);
+    }
+
+    /// Prompts for the next command. `None` once the input is closed
+    pub(crate) fn read_command(&mut self) -> Option<Result<Command, String>> {
+        write!(self.output, "(debug) ").unwrap();
+        self.output.flush().unwrap();
+
+        let mut line = String::new();
+        match self.input.read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.parse()),
+        }
+    }
+}
@@ -0,0 +1,345 @@
+//! Shared libraries for the programs of a project.
+//!
+//! The [manifest](crate::manifest) lists the libraries a project depends on. `toylang fetch`
+//! copies each of them to `vendor/NAME.toy` and records where it came from in `toy.lock`, so
+//! programs run against the same code until the dependencies are fetched again with
+//! `--update`. A git dependency is pinned to the commit it was fetched at.
+//!
+//! Programs use a library with an `#import NAME` directive, which [`link`] resolves through
+//! the vendored copies before the program runs. The library's sections, data sections and
+//! declarations are added to the program, except for a `main` section, which libraries can
+//! keep for trying them out. Libraries can `#import` the project's other dependencies in
+//! turn.
+
+use std::{
+    collections::HashSet,
+    fmt,
+    io::BufRead,
+    path::{Component, Path, PathBuf},
+    process::Command,
+};
+
+use crate::{
+    error::ParseError,
+    interpreter::RunOptions,
+    manifest::{Manifest, Source},
+    parser::{try_parse, Program},
+};
+
+/// Where fetched libraries are kept, relative to the project root
+pub const VENDOR_DIR: &str = "vendor";
+
+/// Where `fetch` records what it fetched, relative to the project root
+pub const LOCK_FILE: &str = "toy.lock";
+
+/// A fetched library and where it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locked {
+    pub name: String,
+    /// The path or git URL the library was fetched from
+    pub source: String,
+    /// The commit a git library was fetched at
+    pub rev: Option<String>,
+}
+
+/// The contents of a `toy.lock` file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Lock {
+    pub dependencies: Vec<Locked>,
+}
+
+impl Lock {
+    /// Reads a `toy.lock` file, or an empty lock if there is none yet
+    pub fn load(path: &Path) -> Result<Lock, String> {
+        if !path.exists() {
+            return Ok(Lock::default());
+        }
+
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| format!("cannot read {}: {error}", path.display()))?;
+
+        Lock::parse(&contents).map_err(|error| format!("{}: {error}", path.display()))
+    }
+
+    /// Parses the `[[dependency]]` tables written by [`fetch`]
+    pub fn parse(contents: &str) -> Result<Lock, String> {
+        let mut lock = Lock::default();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            let error = |message: &str| format!("line {}: {message}", number + 1);
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if line == "[[dependency]]" {
+                lock.dependencies.push(Locked {
+                    name: String::new(),
+                    source: String::new(),
+                    rev: None,
+                });
+                continue;
+            }
+
+            let Some(locked) = lock.dependencies.last_mut() else {
+                return Err(error("expected [[dependency]]"));
+            };
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(error("expected `key = value`"));
+            };
+
+            let Some(value) = value
+                .trim()
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+            else {
+                return Err(error(&format!("{} must be a quoted string", key.trim())));
+            };
+
+            match key.trim() {
+                "name" => locked.name = value.to_string(),
+                "source" => locked.source = value.to_string(),
+                "rev" => locked.rev = Some(value.to_string()),
+                key => return Err(error(&format!("unknown setting: {key}"))),
+            }
+        }
+
+        Ok(lock)
+    }
+
+    /// The library called `name`
+    pub fn dependency(&self, name: &str) -> Option<&Locked> {
+        self.dependencies.iter().find(|locked| locked.name == name)
+    }
+}
+
+impl fmt::Display for Lock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# Written by `toylang fetch`, do not edit")?;
+
+        for locked in &self.dependencies {
+            writeln!(f, "\n[[dependency]]")?;
+            writeln!(f, "name = \"{}\"", locked.name)?;
+            writeln!(f, "source = \"{}\"", locked.source)?;
+            if let Some(rev) = &locked.rev {
+                writeln!(f, "rev = \"{rev}\"")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Vendors every dependency of the project in `root` and writes its `toy.lock`. Git
+/// libraries already in the lock are fetched at the commit it records unless `update` is
+/// set, in which case they are fetched at the `rev` the manifest asks for
+pub fn fetch(root: &Path, manifest: &Manifest, update: bool) -> Result<Lock, String> {
+    let previous = Lock::load(&root.join(LOCK_FILE))?;
+    let vendor = root.join(VENDOR_DIR);
+    std::fs::create_dir_all(&vendor)
+        .map_err(|error| format!("cannot create {}: {error}", vendor.display()))?;
+
+    let mut lock = Lock::default();
+
+    for dependency in &manifest.dependencies {
+        let destination = vendor.join(format!("{}.toy", dependency.name));
+
+        let locked = match &dependency.source {
+            Source::Path(path) => {
+                std::fs::copy(path, &destination).map_err(|error| {
+                    format!(
+                        "cannot copy dependency {} from {}: {error}",
+                        dependency.name,
+                        path.display()
+                    )
+                })?;
+
+                Locked {
+                    name: dependency.name.clone(),
+                    source: path.display().to_string(),
+                    rev: None,
+                }
+            }
+            Source::Git { url, file, rev } => {
+                let pinned = previous
+                    .dependency(&dependency.name)
+                    .filter(|locked| !update && locked.source == *url)
+                    .and_then(|locked| locked.rev.as_ref());
+
+                let commit = fetch_git(url, pinned.or(rev.as_ref()), file, &destination).map_err(
+                    |error| format!("cannot fetch dependency {}: {error}", dependency.name),
+                )?;
+
+                Locked {
+                    name: dependency.name.clone(),
+                    source: url.clone(),
+                    rev: Some(commit),
+                }
+            }
+        };
+
+        lock.dependencies.push(locked);
+    }
+
+    let path = root.join(LOCK_FILE);
+    std::fs::write(&path, lock.to_string())
+        .map_err(|error| format!("cannot write {}: {error}", path.display()))?;
+
+    Ok(lock)
+}
+
+/// Clones `url` at `rev`, copies its `file` to `destination` and returns the commit it
+/// was at
+fn fetch_git(
+    url: &str,
+    rev: Option<&String>,
+    file: &str,
+    destination: &Path,
+) -> Result<String, String> {
+    let checkout = std::env::temp_dir().join(format!(
+        "toylang-fetch-{}-{}",
+        std::process::id(),
+        destination
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+    ));
+    let _ = std::fs::remove_dir_all(&checkout);
+
+    let git = |args: &[&str]| -> Result<String, String> {
+        let output = Command::new("git")
+            .args(args)
+            .output()
+            .map_err(|error| format!("cannot run git: {error}"))?;
+
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+
+    // The URL is passed after `--` so git cannot take it for an option, but a rev has to
+    // come before it and is checked instead
+    if let Some(rev) = rev.filter(|rev| rev.starts_with('-')) {
+        return Err(format!("invalid rev: {rev}"));
+    }
+
+    if !is_relative_inside(file) {
+        return Err(format!("file must be a path inside the repository: {file}"));
+    }
+
+    let result = (|| {
+        let directory = checkout.to_string_lossy();
+        git(&["clone", "--quiet", "--", url, &directory])?;
+
+        if let Some(rev) = rev {
+            git(&["-C", &directory, "checkout", "--quiet", rev, "--"])?;
+        }
+
+        let commit = git(&["-C", &directory, "rev-parse", "HEAD"])?;
+
+        std::fs::copy(checkout.join(file), destination)
+            .map_err(|error| format!("cannot copy {file} out of {url}: {error}"))?;
+
+        Ok(commit)
+    })();
+
+    let _ = std::fs::remove_dir_all(&checkout);
+    result
+}
+
+/// Whether `path` is relative and stays inside the directory it is relative to
+pub(crate) fn is_relative_inside(path: &str) -> bool {
+    Path::new(path)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+/// The libraries a program `#import`s, with the line each import is on
+pub fn imports(source: impl BufRead) -> Vec<(usize, String)> {
+    source
+        .lines()
+        .map_while(Result::ok)
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let name = line.trim().strip_prefix("#import ")?.trim();
+            Some((index + 1, name.to_string()))
+        })
+        .collect()
+}
+
+/// Adds the libraries in `imports`, and those they import, to `program` from the vendored
+/// copies in the project in `root`
+pub fn link(
+    program: &mut Vec<Program>,
+    imports: Vec<(usize, String)>,
+    root: &Path,
+    manifest: &Manifest,
+    options: &RunOptions,
+) -> Result<(), ParseError> {
+    let mut pending: Vec<(Option<PathBuf>, usize, String)> = imports
+        .into_iter()
+        .map(|(line, name)| (None, line, name))
+        .collect();
+    let mut linked = HashSet::new();
+
+    while let Some((importer, line, name)) = pending.pop() {
+        let at = |message: String| {
+            let error = ParseError::at(line, &format!("#import {name}"), message);
+            match &importer {
+                Some(path) => error.in_file(path),
+                None => error,
+            }
+        };
+
+        if !linked.insert(name.clone()) {
+            continue;
+        }
+
+        if manifest.dependency(&name).is_none() {
+            return Err(at(format!("{name} is not a dependency of the project")));
+        }
+
+        let path = root.join(VENDOR_DIR).join(format!("{name}.toy"));
+        let Ok(source) = std::fs::read_to_string(&path) else {
+            return Err(at(format!(
+                "dependency {name} has not been fetched, run `toylang fetch`"
+            )));
+        };
+
+        let library = try_parse(&source, options).map_err(|error| error.in_file(&path))?;
+
+        for item in library {
+            if let Program::Section(section, _) = &item {
+                if section.0 == "main" {
+                    continue;
+                }
+            }
+
+            if let Some(section) = item.name() {
+                if program
+                    .iter()
+                    .any(|other| other.name().is_some_and(|other| other.0 == section.0))
+                {
+                    return Err(at(format!(
+                        "{name} defines {}, which is already defined",
+                        section.0
+                    )));
+                }
+            }
+
+            program.push(item);
+        }
+
+        pending.extend(
+            self::imports(source.as_bytes())
+                .into_iter()
+                .map(|(line, name)| (Some(path.clone()), line, name)),
+        );
+    }
+
+    Ok(())
+}
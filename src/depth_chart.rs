@@ -0,0 +1,93 @@
+//! Pictures of how deep the stack gets as a program runs, so code that leaves values behind
+//! or takes too many off shows up as a slope instead of having to be read out of a dump.
+//! `--debug-dump` draws a sparkline of the latest steps beside the stack, and
+//! `run --depth-chart` writes an SVG chart of the whole run once it ends.
+
+use std::io::Write;
+
+use crate::{hooks::InterpreterHooks, instructions::Instructions, value::DataType};
+
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Draws depths as a line of bars, scaled so the deepest one is a full bar
+pub fn sparkline(depths: &[usize]) -> String {
+    let peak = depths.iter().copied().max().unwrap_or(0).max(1);
+
+    depths
+        .iter()
+        .map(|depth| BARS[depth * (BARS.len() - 1) / peak])
+        .collect()
+}
+
+const WIDTH: usize = 640;
+const HEIGHT: usize = 240;
+const PADDING: usize = 40;
+
+/// Draws the depth of the stack before each step as an SVG line chart. Runs with more steps
+/// than the chart is wide show the deepest step of each stretch, so no peak is lost
+pub fn svg(depths: &[usize]) -> String {
+    let plot_width = (WIDTH - 2 * PADDING) as f64;
+    let plot_height = (HEIGHT - 2 * PADDING) as f64;
+    let peak = depths.iter().copied().max().unwrap_or(0);
+
+    let buckets = depths.len().clamp(1, WIDTH - 2 * PADDING);
+    let points: Vec<String> = (0..buckets)
+        .map(|bucket| {
+            let start = bucket * depths.len() / buckets;
+            let end = ((bucket + 1) * depths.len() / buckets).max(start + 1);
+            let depth = depths
+                .get(start..end)
+                .and_then(|d| d.iter().max())
+                .unwrap_or(&0);
+
+            let x = PADDING as f64 + plot_width * bucket as f64 / (buckets - 1).max(1) as f64;
+            let y = PADDING as f64 + plot_height * (1.0 - *depth as f64 / peak.max(1) as f64);
+            format!("{x:.1},{y:.1}")
+        })
+        .collect();
+
+    let (left, right, top, bottom) = (PADDING, WIDTH - PADDING, PADDING, HEIGHT - PADDING);
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{HEIGHT}\" viewBox=\"0 0 {WIDTH} {HEIGHT}\">\n\
+         <title>Stack depth over {steps} steps (peak {peak})</title>\n\
+         <rect width=\"{WIDTH}\" height=\"{HEIGHT}\" fill=\"white\"/>\n\
+         <polyline points=\"{left},{top} {left},{bottom} {right},{bottom}\" fill=\"none\" stroke=\"black\"/>\n\
+         <polyline points=\"{points}\" fill=\"none\" stroke=\"steelblue\" stroke-width=\"1.5\"/>\n\
+         <text x=\"{left}\" y=\"{label_top}\" font-family=\"monospace\" font-size=\"12\">depth {peak}</text>\n\
+         <text x=\"{right}\" y=\"{label_bottom}\" font-family=\"monospace\" font-size=\"12\" text-anchor=\"end\">{steps} steps</text>\n\
+         </svg>\n",
+        steps = depths.len(),
+        points = points.join(" "),
+        label_top = top - 8,
+        label_bottom = bottom + 20,
+    )
+}
+
+/// Records the depth of the stack before every instruction and writes the [`svg`] chart
+/// of them once the interpreter is dropped, used for `--depth-chart`
+pub(crate) struct DepthChart {
+    out: Box<dyn Write>,
+    depths: Vec<usize>,
+}
+
+impl DepthChart {
+    pub(crate) fn new(out: Box<dyn Write>) -> DepthChart {
+        DepthChart {
+            out,
+            depths: Vec::new(),
+        }
+    }
+}
+
+impl InterpreterHooks for DepthChart {
+    fn on_instruction(&mut self, _index: usize, _instruction: &Instructions, stack: &[DataType]) {
+        self.depths.push(stack.len());
+    }
+}
+
+impl Drop for DepthChart {
+    fn drop(&mut self) {
+        let _ = self.out.write_all(svg(&self.depths).as_bytes());
+        let _ = self.out.flush();
+    }
+}
@@ -0,0 +1,166 @@
+use std::{fmt, path::PathBuf, time::Duration};
+
+use crate::{instructions::Instructions, stack::render, value::DataType};
+
+/// An error that stops a program while it is running
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    /// An instruction could not be executed, e.g. because there were too few values on
+    /// the stack or they had the wrong type
+    Instruction(String),
+    /// A jump targeted a section that does not exist
+    UnknownSection(String),
+    /// The host stopped the program through its [`CancellationToken`](crate::CancellationToken)
+    Cancelled,
+    /// The program's values took up more memory than its limit allows
+    OutOfMemory { used: usize, limit: usize },
+    /// The program printed more than its output limit allows. Records how many bytes of
+    /// the print that went over the limit were dropped
+    OutputLimit { limit: usize, truncated: usize },
+    /// The program ran for longer than its time limit. Records the instruction it was about
+    /// to execute and the stack at that point
+    Timeout {
+        limit: Duration,
+        instruction: Instructions,
+        stack: Vec<DataType>,
+    },
+    /// The program spent its [`Metering`](crate::Metering) budget. Records the instruction
+    /// that would have gone over it
+    BudgetExhausted {
+        budget: u64,
+        spent: u64,
+        instruction: Instructions,
+    },
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::Instruction(message) => write!(f, "{message}"),
+            RuntimeError::UnknownSection(label) => write!(f, "Unknown label: {label}"),
+            RuntimeError::Cancelled => write!(f, "Execution was cancelled"),
+            RuntimeError::OutOfMemory { used, limit } => write!(
+                f,
+                "Out of memory: the program is holding about {used} bytes, over its limit of {limit} bytes"
+            ),
+            RuntimeError::OutputLimit { limit, truncated } => write!(
+                f,
+                "Output limit reached: the program printed more than {limit} bytes, the last {truncated} bytes of its final print were dropped"
+            ),
+            RuntimeError::BudgetExhausted {
+                budget,
+                spent,
+                instruction,
+            } => write!(
+                f,
+                "Budget exhausted: spent {spent} of {budget}, not enough to run `{instruction}`"
+            ),
+            RuntimeError::Timeout {
+                limit,
+                instruction,
+                stack,
+            } => {
+                // Only the top of the stack is shown, long running programs tend to have deep ones
+                let shown = &stack[stack.len().saturating_sub(8)..];
+
+                write!(
+                    f,
+                    "Timed out after {:?} before running `{instruction}`\nStack ({} values, top {} shown): {}",
+                    limit,
+                    stack.len(),
+                    shown.len(),
+                    render(shown)
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// Lets host functions fail with a message, see [`toy_fn`](crate::toy_fn)
+impl From<String> for RuntimeError {
+    fn from(message: String) -> Self {
+        RuntimeError::Instruction(message)
+    }
+}
+
+/// Where in a program's source something happened
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLocation {
+    /// The file the source was read from, if it was read from one
+    pub file: Option<PathBuf>,
+    /// Counting from 1
+    pub line: usize,
+    /// The line as written, without its indentation
+    pub instruction: String,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{}:{}", file.display(), self.line),
+            None => write!(f, "line {}", self.line),
+        }
+    }
+}
+
+/// An error in a program's source that keeps it from running
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// The file the source was read from, if it was read from one
+    pub file: Option<PathBuf>,
+    /// The line the error is on, counting from 1, or `None` for errors about the program
+    /// as a whole, such as a jump to a section that does not exist
+    pub line: Option<usize>,
+    /// The offending line as written, without its indentation
+    pub instruction: Option<String>,
+    pub message: String,
+}
+
+impl ParseError {
+    pub(crate) fn at(line: usize, instruction: &str, message: impl Into<String>) -> Self {
+        ParseError {
+            file: None,
+            line: Some(line),
+            instruction: Some(instruction.trim().to_string()),
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn program(message: impl Into<String>) -> Self {
+        ParseError {
+            file: None,
+            line: None,
+            instruction: None,
+            message: message.into(),
+        }
+    }
+
+    /// Records the file the source was read from
+    pub fn in_file(mut self, file: impl Into<PathBuf>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => write!(f, "{}:{line}: ", file.display())?,
+            (Some(file), None) => write!(f, "{}: ", file.display())?,
+            (None, Some(line)) => write!(f, "line {line}: ")?,
+            (None, None) => {}
+        }
+
+        write!(f, "{}", self.message)?;
+
+        if let (Some(line), Some(instruction)) = (self.line, &self.instruction) {
+            write!(f, "\n    {line} | {instruction}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
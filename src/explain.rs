@@ -0,0 +1,133 @@
+//! Narrated dry runs for `toylang explain-run`, meant for showing a class what a program
+//! does one instruction at a time.
+//!
+//! The program runs for a given number of steps without touching the machine: it cannot
+//! open files or make sounds, has no input to read and what it prints is described instead
+//! of written. Each step is told as the instruction, the stack after it and what it
+//! printed, followed by what the instruction does in the words of the
+//! [instruction reference](crate::reference).
+
+use std::{cell::RefCell, fmt::Write, rc::Rc};
+
+use crate::{
+    error::RuntimeError,
+    hooks::InterpreterHooks,
+    instructions::Instructions,
+    interpreter::{Interpreter, RunOptions, RunStatus},
+    parser::Program,
+    reference::instruction_info,
+    stack::{render, StackDiff},
+    value::DataType,
+};
+
+/// Runs `program` for at most `steps` instructions, describing each of them
+pub fn explain_run(program: Vec<Program>, steps: usize) -> String {
+    let options = RunOptions {
+        files: false,
+        buffered: false,
+        ..RunOptions::default()
+    };
+
+    let narration = Rc::new(RefCell::new(Narration::default()));
+    let mut interpreter = Interpreter::new(program, options);
+    interpreter.set_output(std::io::sink());
+    interpreter.set_input(std::io::empty());
+    interpreter.add_hooks(Narrator(narration.clone()));
+
+    let status = interpreter.run_for(steps);
+
+    let mut narration = narration.borrow_mut();
+    narration.finish_step();
+
+    let ran = narration.steps;
+    match status {
+        Ok(RunStatus::Finished) => {
+            narration.say(&format!("The program finished after {ran} steps"))
+        }
+        Ok(RunStatus::Yielded) => narration.say(&format!("Stopped after {ran} steps")),
+        Err(_) if ran > 0 => narration.say(&format!("The program failed at step {ran}")),
+        Err(error) => narration.say(&format!("The program cannot run: {error}")),
+    }
+
+    std::mem::take(&mut narration.text)
+}
+
+/// The step being told and everything told so far
+#[derive(Default)]
+struct Narration {
+    text: String,
+    steps: usize,
+    /// The instruction running and the stack before it, told once it is known what the
+    /// instruction did
+    pending: Option<(Instructions, Vec<DataType>)>,
+    /// What the running instruction printed
+    printed: Vec<String>,
+}
+
+impl Narration {
+    fn say(&mut self, line: &str) {
+        writeln!(self.text, "{line}").unwrap();
+    }
+
+    /// Tells the pending step, with the stack it left behind or the error it failed with
+    fn tell(&mut self, outcome: Result<Vec<DataType>, &RuntimeError>) {
+        let Some((instruction, _)) = self.pending.take() else {
+            return;
+        };
+
+        self.steps += 1;
+        let mut line = format!("{}. {instruction} → ", self.steps);
+
+        match outcome {
+            Ok(stack) => {
+                for printed in self.printed.drain(..) {
+                    write!(line, "prints {printed}, ").unwrap();
+                }
+                write!(line, "stack is {}", render(&stack)).unwrap();
+            }
+            Err(error) => write!(line, "fails: {error}").unwrap(),
+        }
+
+        self.say(&line);
+        if let Some(info) = instruction_info(instruction.mnemonic()) {
+            self.say(&format!("   {}", info.description));
+        }
+    }
+
+    /// Tells a step that ended the program, which never reports how it changed the stack
+    fn finish_step(&mut self) {
+        if let Some((_, stack)) = &self.pending {
+            let stack = stack.clone();
+            self.tell(Ok(stack));
+        }
+    }
+}
+
+struct Narrator(Rc<RefCell<Narration>>);
+
+impl InterpreterHooks for Narrator {
+    fn on_instruction(&mut self, _index: usize, instruction: &Instructions, stack: &[DataType]) {
+        let mut narration = self.0.borrow_mut();
+        narration.finish_step();
+        narration.pending = Some((instruction.clone(), stack.to_vec()));
+    }
+
+    fn on_stack_change(&mut self, _index: usize, _instruction: &Instructions, diff: &StackDiff) {
+        let mut narration = self.0.borrow_mut();
+        let Some((_, before)) = &narration.pending else {
+            return;
+        };
+
+        let mut after = before[..before.len() - diff.popped].to_vec();
+        after.extend(diff.pushed.iter().cloned());
+        narration.tell(Ok(after));
+    }
+
+    fn on_error(&mut self, error: &RuntimeError, _stack: &[DataType]) {
+        self.0.borrow_mut().tell(Err(error));
+    }
+
+    fn on_print(&mut self, value: &DataType) {
+        self.0.borrow_mut().printed.push(value.display());
+    }
+}
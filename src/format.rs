@@ -0,0 +1,265 @@
+//! How `print` lays out values, set for the rest of a run with `setfmt` or for a single
+//! value with `print <format>`.
+//!
+//! A format is written `[[fill]align][0][width][,][.precision]`, much like Rust's:
+//! `>8` right aligns in 8 characters, `*^10` centres with `*` on either side, `08` pads
+//! numbers with zeros after their sign, `,` groups the digits of numbers in thousands and
+//! `.2` shows numbers with 2 decimal places. The empty format prints values as they are.
+
+use std::fmt;
+
+use crate::value::DataType;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrintFormat {
+    /// Where values narrower than `width` go, by default numbers are aligned right and
+    /// everything else left
+    pub align: Option<Align>,
+    pub fill: char,
+    /// Pad numbers with zeros between their sign and their digits
+    pub zero: bool,
+    /// The least number of characters a value takes up
+    pub width: usize,
+    pub separators: bool,
+    /// Decimal places of numbers
+    pub precision: Option<usize>,
+}
+
+impl Default for PrintFormat {
+    fn default() -> Self {
+        PrintFormat {
+            align: None,
+            fill: ' ',
+            zero: false,
+            width: 0,
+            separators: false,
+            precision: None,
+        }
+    }
+}
+
+impl PrintFormat {
+    pub fn parse(spec: &str) -> Result<PrintFormat, String> {
+        let mut format = PrintFormat::default();
+
+        let align = |c: char| match c {
+            '<' => Some(Align::Left),
+            '^' => Some(Align::Center),
+            '>' => Some(Align::Right),
+            _ => None,
+        };
+
+        let mut rest = spec;
+        let mut chars = spec.chars();
+        let (first, second) = (chars.next(), chars.next());
+
+        if let (Some(fill), Some(second)) = (first, second.and_then(align)) {
+            format.fill = fill;
+            format.align = Some(second);
+            rest = &spec[fill.len_utf8() + 1..];
+        } else if let Some(first) = first.and_then(align) {
+            format.align = Some(first);
+            rest = &spec[1..];
+        }
+
+        if let Some(after) = rest.strip_prefix('0') {
+            format.zero = true;
+            rest = after;
+        }
+
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        if digits > 0 {
+            format.width = rest[..digits]
+                .parse()
+                .map_err(|_| format!("width {} is too large", &rest[..digits]))?;
+            rest = &rest[digits..];
+        }
+
+        if let Some(after) = rest.strip_prefix(',') {
+            format.separators = true;
+            rest = after;
+        }
+
+        if let Some(after) = rest.strip_prefix('.') {
+            let digits = after.bytes().take_while(u8::is_ascii_digit).count();
+            if digits == 0 {
+                return Err("`.` must be followed by a number of decimal places".to_string());
+            }
+
+            format.precision = Some(
+                after[..digits]
+                    .parse()
+                    .map_err(|_| format!("precision {} is too large", &after[..digits]))?,
+            );
+            rest = &after[digits..];
+        }
+
+        if !rest.is_empty() {
+            return Err(format!("unexpected `{rest}`"));
+        }
+
+        Ok(format)
+    }
+
+    /// Renders a value the way `print` writes it
+    pub fn apply(&self, value: &DataType) -> String {
+        if *self == PrintFormat::default() {
+            return value.to_string();
+        }
+
+        let (negative, digits) = match value {
+            DataType::Int(a) => (*a < 0, self.number(&a.unsigned_abs().to_string())),
+            DataType::Float(a) => (
+                a.is_sign_negative() && *a != 0.0,
+                self.number(&match self.precision {
+                    Some(precision) => format!("{:.precision$}", a.abs()),
+                    None => a.abs().to_string(),
+                }),
+            ),
+            value => return self.pad(value.to_string(), Align::Left),
+        };
+
+        let sign = if negative { "-" } else { "" };
+
+        if self.zero {
+            let zeros = self
+                .width
+                .saturating_sub(sign.len() + digits.chars().count());
+            return format!("{sign}{}{digits}", "0".repeat(zeros));
+        }
+
+        self.pad(format!("{sign}{digits}"), Align::Right)
+    }
+
+    /// Applies the precision and separators to the digits of a number without its sign
+    fn number(&self, digits: &str) -> String {
+        let (whole, fraction) = match digits.split_once('.') {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (digits, None),
+        };
+
+        // Ints are given decimal places too, so a column of amounts lines up
+        let fraction = match (fraction, self.precision) {
+            (None, Some(precision)) if precision > 0 => Some("0".repeat(precision)),
+            (fraction, _) => fraction.map(str::to_string),
+        };
+
+        let mut grouped = String::new();
+        for (index, digit) in whole.chars().enumerate() {
+            if self.separators && index > 0 && (whole.len() - index) % 3 == 0 {
+                grouped.push(',');
+            }
+            grouped.push(digit);
+        }
+
+        match fraction {
+            Some(fraction) => format!("{grouped}.{fraction}"),
+            None => grouped,
+        }
+    }
+
+    fn pad(&self, text: String, default: Align) -> String {
+        let padding = self.width.saturating_sub(text.chars().count());
+        let fill = |count: usize| self.fill.to_string().repeat(count);
+
+        match self.align.unwrap_or(default) {
+            Align::Left => format!("{text}{}", fill(padding)),
+            Align::Right => format!("{}{text}", fill(padding)),
+            Align::Center => format!("{}{text}{}", fill(padding / 2), fill(padding - padding / 2)),
+        }
+    }
+}
+
+/// Renders rows of values as a table with a border, separating the first row as a header.
+/// Numbers are aligned right and everything else left, and short rows are padded with
+/// empty cells
+pub(crate) fn render_table(rows: &[&[DataType]]) -> String {
+    let columns = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    if columns == 0 {
+        return String::new();
+    }
+
+    let text: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(DataType::to_string).collect())
+        .collect();
+
+    let mut widths = vec![0; columns];
+    for row in &text {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let rule: String = widths
+        .iter()
+        .map(|width| format!("+{}", "-".repeat(width + 2)))
+        .chain(["+\n".to_string()])
+        .collect();
+
+    let mut table = rule.clone();
+    for (index, row) in rows.iter().enumerate() {
+        for (column, width) in widths.iter().enumerate() {
+            let cell = text[index].get(column).map_or("", String::as_str);
+            let padding = " ".repeat(width - cell.chars().count());
+
+            match row.get(column) {
+                Some(DataType::Int(_) | DataType::Float(_)) => {
+                    table.push_str(&format!("| {padding}{cell} "))
+                }
+                _ => table.push_str(&format!("| {cell}{padding} ")),
+            }
+        }
+        table.push_str("|\n");
+
+        if index == 0 && rows.len() > 1 {
+            table.push_str(&rule);
+        }
+    }
+    table.push_str(&rule);
+
+    table
+}
+
+impl fmt::Display for PrintFormat {
+    /// Writes the format back in the form it is parsed from
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(align) = self.align {
+            if self.fill != ' ' {
+                write!(f, "{}", self.fill)?;
+            }
+
+            let align = match align {
+                Align::Left => '<',
+                Align::Center => '^',
+                Align::Right => '>',
+            };
+            write!(f, "{align}")?;
+        }
+
+        if self.zero {
+            write!(f, "0")?;
+        }
+
+        if self.width > 0 {
+            write!(f, "{}", self.width)?;
+        }
+
+        if self.separators {
+            write!(f, ",")?;
+        }
+
+        if let Some(precision) = self.precision {
+            write!(f, ".{precision}")?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,279 @@
+//! Syntax highlighting for toylang source, shared by the [language server](crate::lsp) and
+//! `toylang highlight`. Source is split into tokens the way the parser reads it, so
+//! directives, data sections and operands are told apart the same way they are when the
+//! program runs, and the tokens can be rendered as HTML or with ANSI colours.
+
+use crate::parser::{is_section_header, split_instruction};
+
+/// A kind of token
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Token {
+    /// Instruction names and other words of the language
+    Keyword,
+    /// Section names
+    Label,
+    /// Consts and globals
+    Variable,
+    /// States and log levels
+    EnumMember,
+    Number,
+    String,
+    Comment,
+    Directive,
+}
+
+/// Directives the parser understands, every other line starting with `#` is a comment
+const DIRECTIVES: &[&str] = &[
+    "lang", "states", "pre", "post", "budget", "import", "if", "else", "endif",
+];
+
+impl Token {
+    /// The class of the `<span>` holding the token in HTML
+    pub fn class(self) -> &'static str {
+        match self {
+            Token::Keyword => "toy-keyword",
+            Token::Label => "toy-label",
+            Token::Variable => "toy-variable",
+            Token::EnumMember => "toy-enum-member",
+            Token::Number => "toy-number",
+            Token::String => "toy-string",
+            Token::Comment => "toy-comment",
+            Token::Directive => "toy-directive",
+        }
+    }
+
+    /// The SGR parameters of the colour the token is written in on a terminal
+    fn ansi(self) -> &'static str {
+        match self {
+            Token::Keyword => "34",
+            Token::Label => "33",
+            Token::Variable => "36",
+            Token::EnumMember => "35",
+            Token::Number => "31",
+            Token::String => "32",
+            Token::Comment => "90",
+            Token::Directive => "1;35",
+        }
+    }
+}
+
+/// Every token worth highlighting as a line, a byte range within it and its kind, in order
+pub fn tokens(source: &str) -> Vec<(usize, (usize, usize), Token)> {
+    let mut tokens = Vec::new();
+    let mut in_data = false;
+
+    for (number, line) in source.lines().enumerate() {
+        let mut words = words(line);
+        let mut push = |(start, word): (usize, &str), token| {
+            tokens.push((number, (start, start + word.len()), token));
+        };
+
+        let header = line.strip_prefix("override ").map_or(line, str::trim_start);
+
+        if is_section_header(header) {
+            in_data = header.starts_with("::data ");
+
+            if header.len() != line.len() {
+                push((0, "override"), Token::Keyword);
+            }
+
+            let offset = line.len() - header.len();
+            let name = header.trim_matches(':');
+            let (start, name) = match name.strip_prefix("data ") {
+                Some(data) => {
+                    push((offset + 2, "data"), Token::Keyword);
+                    (offset + 2 + name.len() - data.len(), data.trim())
+                }
+                None => (offset + 2, name),
+            };
+
+            push((start, name), Token::Label);
+            continue;
+        }
+
+        if in_data {
+            if !line.is_empty() {
+                push((0, line), Token::String);
+            }
+            continue;
+        }
+
+        let Some(first) = words.next() else {
+            continue;
+        };
+
+        if first.1.starts_with('/') {
+            push((0, line), Token::Comment);
+            continue;
+        }
+
+        if let Some(directive) = first.1.strip_prefix('#') {
+            if !DIRECTIVES.contains(&directive) {
+                push((0, line), Token::Comment);
+                continue;
+            }
+
+            push(first, Token::Directive);
+
+            let rest = match directive {
+                "states" => {
+                    if let Some(group) = words.next() {
+                        push(group, Token::Label);
+                    }
+                    Token::EnumMember
+                }
+                "if" => Token::Directive,
+                "pre" | "post" | "budget" => Token::Number,
+                "import" => Token::Label,
+                _ => Token::Keyword,
+            };
+
+            for word in words {
+                push(word, rest);
+            }
+            continue;
+        }
+
+        if first.1 == "const" {
+            push(first, Token::Keyword);
+
+            if let Some(name) = words.next() {
+                push(name, Token::Variable);
+            }
+
+            for (start, word) in words {
+                let word = word.trim_matches([',', '[', ']']);
+
+                if !word.is_empty() && word != "=" {
+                    let start = start + line[start..].find(word).unwrap_or(0);
+                    push((start, word), literal(word));
+                }
+            }
+            continue;
+        }
+
+        push(first, Token::Keyword);
+
+        let (_, operand) = split_instruction(line);
+        if operand.is_empty() {
+            continue;
+        }
+
+        let start = line.len() - operand.len();
+        match first.1.to_ascii_lowercase().as_str() {
+            "push" => push((start, operand), literal(operand)),
+            "jump" | "ifjmp" | "call" | "switch" | "pushdata" => {
+                push((start, operand), Token::Label)
+            }
+            "getglobal" | "setglobal" => push((start, operand), Token::Variable),
+            "log" => push((start, operand), Token::EnumMember),
+            "trace" => push((start, operand), Token::String),
+            "getconst" => {
+                for (index, word) in words.enumerate() {
+                    push(
+                        word,
+                        if index == 0 {
+                            Token::Variable
+                        } else {
+                            Token::Number
+                        },
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+
+    tokens
+}
+
+/// Renders source as a `<pre>` block, with every token in a `<span>` of its
+/// [class](Token::class)
+pub fn to_html(source: &str) -> String {
+    let mut html = String::from("<pre class=\"toylang\"><code>");
+    render(
+        source,
+        &mut html,
+        |html, token, text| {
+            html.push_str(&format!(
+                "<span class=\"{}\">{}</span>",
+                token.class(),
+                escape_html(text)
+            ));
+        },
+        |html, text| html.push_str(&escape_html(text)),
+    );
+    html.push_str("</code></pre>\n");
+    html
+}
+
+/// Renders source for a terminal, colouring tokens with ANSI escape codes
+pub fn to_ansi(source: &str) -> String {
+    let mut output = String::new();
+    render(
+        source,
+        &mut output,
+        |output, token, text| {
+            output.push_str(&format!("\x1b[{}m{text}\x1b[0m", token.ansi()));
+        },
+        |output, text| output.push_str(text),
+    );
+    output
+}
+
+/// Walks the source line by line, handing tokens and the text between them to the
+/// callbacks
+fn render(
+    source: &str,
+    output: &mut String,
+    mut token: impl FnMut(&mut String, Token, &str),
+    mut text: impl FnMut(&mut String, &str),
+) {
+    let tokens = tokens(source);
+    let mut tokens = tokens.iter().peekable();
+
+    for (number, line) in source.lines().enumerate() {
+        let mut end = 0;
+
+        while let Some(&(_, (start, stop), kind)) = tokens.next_if(|(line, ..)| *line == number) {
+            // Skip a token overlapping the one before it rather than slicing backwards
+            if start < end {
+                continue;
+            }
+
+            text(output, &line[end..start]);
+            token(output, kind, &line[start..stop]);
+            end = stop;
+        }
+
+        text(output, &line[end..]);
+        output.push('\n');
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// How a literal operand is highlighted
+fn literal(value: &str) -> Token {
+    if value.starts_with('"') || value.starts_with("x\"") {
+        Token::String
+    } else if value == "true" || value == "false" {
+        Token::Keyword
+    } else if value.starts_with(|c: char| c.is_ascii_digit() || c == '.' || c == '-') {
+        Token::Number
+    } else {
+        // Anything else pushed by name is a state
+        Token::EnumMember
+    }
+}
+
+/// The words of a line separated by whitespace, with their byte offsets
+fn words(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    line.split_whitespace()
+        .map(move |word| (word.as_ptr() as usize - line.as_ptr() as usize, word))
+}
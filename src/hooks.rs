@@ -0,0 +1,97 @@
+use std::{collections::VecDeque, io::Write};
+
+use crate::{
+    depth_chart::sparkline,
+    error::RuntimeError,
+    instructions::Instructions,
+    stack::{render, StackDiff},
+    value::DataType,
+};
+
+/// Callbacks into the interpreter's run loop, letting embedders and tools such as tracers
+/// and profilers observe a program without the interpreter knowing about them. Every
+/// method does nothing by default, so implementations only override what they need
+pub trait InterpreterHooks {
+    /// Called before each instruction runs, with its index in the running instruction stream
+    fn on_instruction(&mut self, _index: usize, _instruction: &Instructions, _stack: &[DataType]) {}
+
+    /// Called after each instruction completes with how it changed the stack
+    fn on_stack_change(&mut self, _index: usize, _instruction: &Instructions, _diff: &StackDiff) {}
+
+    /// Called whenever execution enters a section, either from the host or through a jump
+    fn on_call(&mut self, _section: &str, _stack: &[DataType]) {}
+
+    /// Called when execution stops because of an error
+    fn on_error(&mut self, _error: &RuntimeError, _stack: &[DataType]) {}
+
+    /// Called with every value the program prints
+    fn on_print(&mut self, _value: &DataType) {}
+}
+
+/// Prints the stack and each instruction before it runs, used for `--debug`
+pub(crate) struct DebugPrinter {
+    /// How many values from the top of the stack to show, or all of them
+    pub(crate) depth: Option<usize>,
+    /// Only every nth instruction is shown
+    pub(crate) every: usize,
+    pub(crate) out: Box<dyn Write>,
+    pub(crate) steps: usize,
+    /// Show how each instruction changed the stack rather than the whole stack
+    pub(crate) diff: bool,
+    /// The depth of the stack before each of the latest instructions, oldest first
+    pub(crate) recent: VecDeque<usize>,
+}
+
+/// How many instructions the depth gauge in debug output looks back over
+const GAUGE_STEPS: usize = 32;
+
+impl DebugPrinter {
+    fn sampled(&self) -> bool {
+        self.steps.is_multiple_of(self.every.max(1))
+    }
+}
+
+impl InterpreterHooks for DebugPrinter {
+    fn on_instruction(&mut self, _index: usize, instruction: &Instructions, stack: &[DataType]) {
+        if self.recent.len() == GAUGE_STEPS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(stack.len());
+
+        if !self.sampled() {
+            return;
+        }
+
+        let recent: Vec<usize> = self.recent.iter().copied().collect();
+        writeln!(self.out, "Depth: {} {}", sparkline(&recent), stack.len()).unwrap();
+
+        if self.diff {
+            writeln!(self.out, "Running Instruction: {:?}", instruction).unwrap();
+            return;
+        }
+
+        match self.depth {
+            Some(depth) if depth < stack.len() => writeln!(
+                self.out,
+                "Stack (top {depth} of {}): [.., {}]",
+                stack.len(),
+                stack[stack.len() - depth..]
+                    .iter()
+                    .map(DataType::display)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            _ => writeln!(self.out, "Stack: {}", render(stack)),
+        }
+        .and_then(|_| writeln!(self.out, "Running Instruction: {:?}", instruction))
+        .unwrap();
+    }
+
+    fn on_stack_change(&mut self, _index: usize, _instruction: &Instructions, diff: &StackDiff) {
+        if self.diff && self.sampled() {
+            writeln!(self.out, "  {diff}").unwrap();
+        }
+
+        self.steps += 1;
+    }
+}
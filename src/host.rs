@@ -0,0 +1,14 @@
+use crate::{error::RuntimeError, stack::Stack};
+
+/// A Rust function programs can use as an instruction, registered through
+/// [`RunOptions::host_functions`](crate::RunOptions::host_functions). Usually written with
+/// [`toy_fn`](crate::toy_fn) rather than by hand
+#[derive(Debug, Clone, Copy)]
+pub struct HostFunction {
+    /// The instruction programs write to call it. Built-in instructions take precedence
+    pub name: &'static str,
+    /// How many values it pops and then pushes, for the stack analysis
+    pub pops: usize,
+    pub pushes: usize,
+    pub call: fn(&mut Stack) -> Result<(), RuntimeError>,
+}
@@ -0,0 +1,311 @@
+//! An on-disk index of everything a project defines, so tools can find where a name is
+//! defined without parsing every file of the project each time. Entries remember a hash of
+//! the file they were read from and only files whose hash changed are parsed again.
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    error::ParseError,
+    json::Json,
+    parser::{is_section_header, Parser},
+    Program, RunOptions,
+};
+
+/// Bumped whenever the layout changes, so old indexes are rebuilt instead of misread
+const FORMAT_VERSION: usize = 1;
+
+/// What a name is defined as
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    Section,
+    Data,
+    Const,
+    /// A `#states` group
+    States,
+}
+
+impl SymbolKind {
+    fn name(self) -> &'static str {
+        match self {
+            SymbolKind::Section => "section",
+            SymbolKind::Data => "data",
+            SymbolKind::Const => "const",
+            SymbolKind::States => "states",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<SymbolKind> {
+        [
+            SymbolKind::Section,
+            SymbolKind::Data,
+            SymbolKind::Const,
+            SymbolKind::States,
+        ]
+        .into_iter()
+        .find(|kind| kind.name() == name)
+    }
+}
+
+impl std::fmt::Display for SymbolKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A definition of a name
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The line it is defined on, counted from 1
+    pub line: usize,
+}
+
+/// The definitions in one file
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedFile {
+    pub path: PathBuf,
+    /// Hash of the contents the symbols were read from
+    pub hash: u64,
+    pub symbols: Vec<Symbol>,
+}
+
+/// The definitions of every file in a project
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SymbolIndex {
+    pub files: Vec<IndexedFile>,
+}
+
+impl SymbolIndex {
+    /// Reads an index saved by [`SymbolIndex::save`]. A missing or outdated index is
+    /// treated as empty, so it is rebuilt
+    pub fn load(path: &Path) -> Result<SymbolIndex, String> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(SymbolIndex::default())
+            }
+            Err(error) => return Err(format!("Cannot read {}: {error}", path.display())),
+        };
+
+        let json = Json::parse(&contents)
+            .map_err(|error| format!("Invalid index {}: {error}", path.display()))?;
+
+        if json.get("version").and_then(Json::as_usize) != Some(FORMAT_VERSION) {
+            return Ok(SymbolIndex::default());
+        }
+
+        SymbolIndex::from_json(&json)
+            .map_err(|error| format!("Invalid index {}: {error}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_json().to_string())
+    }
+
+    /// Brings the index up to date with `files`, parsing only the ones that changed since
+    /// they were indexed and forgetting files that are no longer part of the project.
+    /// Returns how many files were parsed
+    pub fn update(&mut self, files: &[PathBuf]) -> Result<usize, String> {
+        let mut parsed = 0;
+        let mut updated = Vec::with_capacity(files.len());
+
+        for path in files {
+            let contents = std::fs::read_to_string(path)
+                .map_err(|error| format!("Cannot read {}: {error}", path.display()))?;
+            let hash = hash(&contents);
+
+            match self
+                .files
+                .iter()
+                .position(|file| file.path == *path && file.hash == hash)
+            {
+                Some(position) => updated.push(self.files.swap_remove(position)),
+                None => {
+                    parsed += 1;
+                    updated.push(IndexedFile {
+                        path: path.clone(),
+                        hash,
+                        symbols: symbols(&contents)
+                            .map_err(|error| error.in_file(path).to_string())?,
+                    });
+                }
+            }
+        }
+
+        updated.sort_by(|a, b| a.path.cmp(&b.path));
+        self.files = updated;
+        Ok(parsed)
+    }
+
+    /// Every definition of `name`, with the file it is in
+    pub fn find<'a>(&'a self, name: &'a str) -> impl Iterator<Item = (&'a Path, &'a Symbol)> {
+        self.files.iter().flat_map(move |file| {
+            file.symbols
+                .iter()
+                .filter(move |symbol| symbol.name == name)
+                .map(|symbol| (file.path.as_path(), symbol))
+        })
+    }
+
+    pub fn to_json(&self) -> Json {
+        let files = self
+            .files
+            .iter()
+            .map(|file| {
+                let symbols = file
+                    .symbols
+                    .iter()
+                    .map(|symbol| {
+                        Json::object([
+                            ("name", Json::from(symbol.name.as_str())),
+                            ("kind", Json::from(symbol.kind.name())),
+                            ("line", Json::from(symbol.line)),
+                        ])
+                    })
+                    .collect();
+
+                Json::object([
+                    ("path", Json::from(file.path.to_string_lossy().as_ref())),
+                    // Written as a string since JSON numbers cannot hold every u64 exactly
+                    ("hash", Json::from(format!("{:016x}", file.hash))),
+                    ("symbols", Json::Array(symbols)),
+                ])
+            })
+            .collect();
+
+        Json::object([
+            ("version", Json::from(FORMAT_VERSION)),
+            ("files", Json::Array(files)),
+        ])
+    }
+
+    pub fn from_json(json: &Json) -> Result<SymbolIndex, String> {
+        let files = json
+            .get("files")
+            .and_then(Json::as_array)
+            .ok_or("`files` must be an array")?
+            .iter()
+            .map(|file| {
+                let symbols = file
+                    .get("symbols")
+                    .and_then(Json::as_array)
+                    .ok_or("`symbols` must be an array")?
+                    .iter()
+                    .map(|symbol| {
+                        let name = symbol.get("name").and_then(Json::as_str);
+                        let kind = symbol
+                            .get("kind")
+                            .and_then(Json::as_str)
+                            .and_then(SymbolKind::from_name);
+                        let line = symbol.get("line").and_then(Json::as_usize);
+
+                        match (name, kind, line) {
+                            (Some(name), Some(kind), Some(line)) => Ok(Symbol {
+                                name: name.to_string(),
+                                kind,
+                                line,
+                            }),
+                            _ => Err(format!("invalid symbol {symbol}")),
+                        }
+                    })
+                    .collect::<Result<_, String>>()?;
+
+                let path = file.get("path").and_then(Json::as_str);
+                let hash = file
+                    .get("hash")
+                    .and_then(Json::as_str)
+                    .and_then(|hash| u64::from_str_radix(hash, 16).ok());
+
+                match (path, hash) {
+                    (Some(path), Some(hash)) => Ok(IndexedFile {
+                        path: PathBuf::from(path),
+                        hash,
+                        symbols,
+                    }),
+                    _ => Err(format!("invalid file {file}")),
+                }
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(SymbolIndex { files })
+    }
+}
+
+/// Every `.tyl` file in `dir` and the directories below it, skipping hidden directories
+/// and build output
+pub fn project_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let entries = std::fs::read_dir(&dir)
+            .map_err(|error| format!("Cannot read {}: {error}", dir.display()))?;
+
+        for entry in entries {
+            let path = entry.map_err(|error| error.to_string())?.path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+            if path.is_dir() {
+                if !name.starts_with('.') && name != "target" {
+                    pending.push(path);
+                }
+            } else if path.extension().is_some_and(|ext| ext == "tyl") {
+                files.push(
+                    path.strip_prefix(".")
+                        .map_or(path.clone(), Path::to_path_buf),
+                );
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// The definitions in a file, read with the parser so sections excluded by `#if` and the
+/// contents of data sections are not mistaken for definitions
+pub fn symbols(source: &str) -> Result<Vec<Symbol>, ParseError> {
+    let options = RunOptions::default();
+    let mut parser = Parser::new(&options);
+    let mut symbols = Vec::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let header = line.strip_prefix("override ").map_or(line, str::trim_start);
+
+        if is_section_header(header) && !parser.skipping() {
+            let name = header.trim_matches(':');
+            let (name, kind) = match name.strip_prefix("data ") {
+                Some(name) => (name.trim(), SymbolKind::Data),
+                None => (name, SymbolKind::Section),
+            };
+
+            symbols.push(Symbol {
+                name: name.to_string(),
+                kind,
+                line: number + 1,
+            });
+        }
+
+        let (name, kind) = match parser.feed(line)? {
+            Some(Program::Const(name, _)) => (name, SymbolKind::Const),
+            Some(Program::States(group, _)) => (group, SymbolKind::States),
+            _ => continue,
+        };
+
+        symbols.push(Symbol {
+            name,
+            kind,
+            line: number + 1,
+        });
+    }
+
+    Ok(symbols)
+}
+
+/// FNV-1a, which unlike the standard library's hasher is the same on every build
+fn hash(contents: &str) -> u64 {
+    contents.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,438 @@
+use crate::{format::PrintFormat, log::LogLevel, value::DataType};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instructions {
+    Push(DataType),
+    Jump(String),
+    /// Runs a section as a subroutine, continuing after the call once it returns
+    Call(String),
+    /// Returns from the section that was called
+    Ret,
+    IfJmp(String),
+    /// Pops a state of a `#states` group and jumps to the section named after it
+    Switch(String),
+    EQ,
+    NE,
+    /// Pops two Ints or two Floats and pushes whether the top one is less than the other
+    LT,
+    GT,
+    LE,
+    GE,
+    And,
+    Or,
+    Not,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    /// Pops the given number of numbers, pushing them as a vector
+    Vector(usize),
+    /// Pops enough numbers to fill the given number of rows and columns, pushing them as a
+    /// matrix filled row by row
+    Matrix(usize, usize),
+    /// Pops a column, a row and a matrix, pushing the element there as a Float
+    MGet,
+    /// Pops two matrices and pushes their matrix product
+    MatMul,
+    Transpose,
+    /// Pops an imaginary and a real part, pushing the Complex number made of them
+    Complex,
+    /// Pops a Complex and pushes its real part
+    Re,
+    /// Pops a Complex and pushes its imaginary part
+    Im,
+    /// Pops a Complex and pushes its distance from zero
+    CAbs,
+    Dup,
+    Swap,
+    Over,
+    Rot,
+    Drop,
+    /// Pops a value into the named variable
+    Store(String),
+    /// Pushes the value of the named variable
+    Load(String),
+    /// Calls the [`HostFunction`](crate::HostFunction) registered under the name
+    Host {
+        name: String,
+        pops: usize,
+        pushes: usize,
+    },
+    /// Writes the top value to the output, in the given format instead of the one set by
+    /// `setfmt` if there is one
+    Print(Option<PrintFormat>),
+    /// Pops a format String that `print` uses for the rest of the run
+    SetFmt,
+    /// Pops a List of rows, each a List, and prints them as a table
+    PrintTable,
+    Trace(String),
+    Break,
+    Log(LogLevel),
+    Tick,
+    Tock,
+    /// Sleeps until a frame of the given number of frames per second has passed since the
+    /// previous `frame`
+    Frame(u32),
+    /// Pops a duration in milliseconds and a frequency in hertz and plays a tone
+    Tone,
+    MemInfo,
+    Flush,
+    /// Pops an index and Bytes, pushing the byte at that index as an Int
+    ByteAt,
+    /// Pops an end index, a start index and Bytes, pushing the bytes in between
+    ByteSlice,
+    ByteLen,
+    /// Converts a String to its UTF-8 Bytes
+    ToBytes,
+    /// Converts UTF-8 Bytes back into a String
+    FromBytes,
+    /// Pushes the number of characters in a String or items in a List
+    Len,
+    /// Pops an end index, a start index and a String, pushing the characters in between
+    Substr,
+    /// Pops a String to look for and a String to search, pushing the character index of
+    /// its first occurrence or -1
+    StrIndex,
+    /// Converts a Float (rounding toward zero), Bool or String to an Int
+    ToInt,
+    /// Converts an Int, Bool or String to a Float
+    ToFloat,
+    /// Converts any value to the String `print` would show for it
+    ToString,
+    /// Converts an Int or Float (true unless zero) or a String (`true` or `false`) to a Bool
+    ToBool,
+    /// Pushes an empty List
+    List,
+    /// Pops a value and a List, pushing the List with the value added at its end
+    Append,
+    /// Pops an index and a List, pushing the item at that index
+    Get,
+    /// Pops a value, an index and a List, pushing the List with the item at that index
+    /// replaced by the value
+    Set,
+    /// Pops a List, pushing it without its last item and then that item
+    Pop,
+    /// Pops a mode (any of `r`, `w`, `a`, `+`) and a path, pushing a Handle to the opened file
+    FOpen,
+    /// Pops a byte count and a Handle, pushing up to that many Bytes read from the file
+    FReadN,
+    /// Pops a String or Bytes and a Handle, writing the data to the file
+    FWriteH,
+    /// Pops an offset and a Handle, moving the file position to that offset from the start
+    FSeek,
+    FClose,
+    /// Pops a height and a width, replacing the canvas with a black one of that size
+    CNew,
+    /// Pops a `0xRRGGBB` color and y and x coordinates, colouring that pixel of the canvas
+    CSet,
+    /// Pops a path and saves the canvas there as a PNG
+    CSave,
+    /// Pops a distance and moves the turtle that far, drawing on the canvas if its pen is down
+    Forward,
+    /// Pops a number of degrees and turns the turtle clockwise by them
+    Turn,
+    PenUp,
+    PenDown,
+    /// Pops a path and saves what the turtle drew there as a PNG, like `csave`
+    TSave,
+    /// Reads the rest of stdin into a String
+    ReadAll,
+    /// Reads the next line of stdin, pushing the line and then whether one was read
+    ReadLine,
+    /// Reads the next line of stdin as a String, failing at the end of the input
+    Read,
+    /// Reads the next line of stdin as an Int
+    ReadInt,
+    /// Reads the next line of stdin as a Float
+    ReadFloat,
+    /// Parses a CSV String into a List of rows, each a List of String fields
+    CsvParse,
+    /// Renders a List of rows as a CSV String
+    CsvEmit,
+    /// Pushes the text of a `::data` section as a String
+    PushData(String),
+    /// Pushes an element of a `const` table, at the given index or one popped off the stack
+    GetConst(String, Option<usize>),
+    /// Pushes the value of a global shared with the host
+    GetGlobal(String),
+    /// Pops a value into a global shared with the host
+    SetGlobal(String),
+    /// Pushes how many arguments the program was run with
+    Argc,
+    /// Pushes one of the arguments the program was run with as a String, at the given
+    /// index or one popped off the stack
+    Argv(Option<usize>),
+    Exit,
+    /// Marks where a section's instructions end so its `#post` contracts can be checked.
+    /// Only inserted by the interpreter during debug runs, never written in source
+    EndSection(String),
+}
+
+impl Instructions {
+    /// Whether the instruction only compares the top two values
+    pub(crate) fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Instructions::EQ
+                | Instructions::NE
+                | Instructions::LT
+                | Instructions::GT
+                | Instructions::LE
+                | Instructions::GE
+        )
+    }
+
+    /// How many values the instruction takes off the stack
+    pub(crate) fn operands(&self) -> usize {
+        match self.stack_effect() {
+            Some((pops, _)) => pops,
+            None if matches!(self, Instructions::IfJmp(_) | Instructions::Switch(_)) => 1,
+            None => 0,
+        }
+    }
+
+    /// How many values the instruction pops and then pushes, or `None` if it transfers
+    /// control elsewhere
+    pub(crate) fn stack_effect(&self) -> Option<(usize, usize)> {
+        Some(match self {
+            Instructions::Jump(_)
+            | Instructions::Call(_)
+            | Instructions::Ret
+            | Instructions::IfJmp(_)
+            | Instructions::Switch(_)
+            | Instructions::Exit => return None,
+            Instructions::Push(_)
+            | Instructions::PushData(_)
+            | Instructions::GetConst(_, Some(_))
+            | Instructions::GetGlobal(_)
+            | Instructions::Argc
+            | Instructions::Argv(Some(_))
+            | Instructions::Load(_)
+            | Instructions::Tock
+            | Instructions::ReadAll
+            | Instructions::Read
+            | Instructions::ReadInt
+            | Instructions::ReadFloat
+            | Instructions::List => (0, 1),
+            Instructions::ReadLine => (0, 2),
+            Instructions::MemInfo => (0, 3),
+            Instructions::Trace(_)
+            | Instructions::Break
+            | Instructions::Tick
+            | Instructions::Frame(_)
+            | Instructions::Flush
+            | Instructions::PenUp
+            | Instructions::PenDown
+            | Instructions::EndSection(_) => (0, 0),
+            Instructions::Drop
+            | Instructions::Print(_)
+            | Instructions::SetFmt
+            | Instructions::PrintTable
+            | Instructions::Log(_)
+            | Instructions::FClose
+            | Instructions::CSave
+            | Instructions::Forward
+            | Instructions::Turn
+            | Instructions::TSave
+            | Instructions::SetGlobal(_)
+            | Instructions::Store(_) => (1, 0),
+            Instructions::Not
+            | Instructions::ByteLen
+            | Instructions::Len
+            | Instructions::ToInt
+            | Instructions::ToFloat
+            | Instructions::ToString
+            | Instructions::ToBool
+            | Instructions::ToBytes
+            | Instructions::FromBytes
+            | Instructions::Transpose
+            | Instructions::Re
+            | Instructions::Im
+            | Instructions::CAbs
+            | Instructions::CsvParse
+            | Instructions::CsvEmit
+            | Instructions::GetConst(_, None)
+            | Instructions::Argv(None) => (1, 1),
+            Instructions::Dup | Instructions::Pop => (1, 2),
+            Instructions::FWriteH
+            | Instructions::FSeek
+            | Instructions::Tone
+            | Instructions::CNew => (2, 0),
+            Instructions::CSet => (3, 0),
+            Instructions::EQ
+            | Instructions::NE
+            | Instructions::LT
+            | Instructions::GT
+            | Instructions::LE
+            | Instructions::GE
+            | Instructions::And
+            | Instructions::Or
+            | Instructions::Add
+            | Instructions::Sub
+            | Instructions::Mul
+            | Instructions::Div
+            | Instructions::Mod
+            | Instructions::ByteAt
+            | Instructions::StrIndex
+            | Instructions::Append
+            | Instructions::MatMul
+            | Instructions::Complex
+            | Instructions::FOpen
+            | Instructions::FReadN => (2, 1),
+            Instructions::Swap | Instructions::Get => (2, 2),
+            Instructions::Over => (2, 3),
+            Instructions::ByteSlice
+            | Instructions::Substr
+            | Instructions::Set
+            | Instructions::MGet => (3, 1),
+            Instructions::Vector(length) => (*length, 1),
+            Instructions::Matrix(rows, cols) => (rows * cols, 1),
+            Instructions::Rot => (3, 3),
+            Instructions::Host { pops, pushes, .. } => (*pops, *pushes),
+        })
+    }
+
+    /// The name the instruction is written with, without its operand
+    pub fn mnemonic(&self) -> &str {
+        match self {
+            Instructions::Push(..) => "push",
+            Instructions::Jump(..) => "jump",
+            Instructions::Call(..) => "call",
+            Instructions::Ret => "ret",
+            Instructions::IfJmp(..) => "ifjmp",
+            Instructions::Switch(..) => "switch",
+            Instructions::EQ => "eq",
+            Instructions::NE => "ne",
+            Instructions::LT => "lt",
+            Instructions::GT => "gt",
+            Instructions::LE => "le",
+            Instructions::GE => "ge",
+            Instructions::And => "and",
+            Instructions::Or => "or",
+            Instructions::Not => "not",
+            Instructions::Add => "add",
+            Instructions::Sub => "sub",
+            Instructions::Mul => "mul",
+            Instructions::Div => "div",
+            Instructions::Mod => "mod",
+            Instructions::Vector(..) => "vector",
+            Instructions::Matrix(..) => "matrix",
+            Instructions::MGet => "mget",
+            Instructions::MatMul => "matmul",
+            Instructions::Transpose => "transpose",
+            Instructions::Complex => "complex",
+            Instructions::Re => "re",
+            Instructions::Im => "im",
+            Instructions::CAbs => "cabs",
+            Instructions::Dup => "dup",
+            Instructions::Swap => "swap",
+            Instructions::Over => "over",
+            Instructions::Rot => "rot",
+            Instructions::Drop => "drop",
+            Instructions::Store(..) => "store",
+            Instructions::Load(..) => "load",
+            Instructions::Print(..) => "print",
+            Instructions::SetFmt => "setfmt",
+            Instructions::PrintTable => "printtable",
+            Instructions::Trace(..) => "trace",
+            Instructions::Break => "break",
+            Instructions::Log(..) => "log",
+            Instructions::Tick => "tick",
+            Instructions::Tock => "tock",
+            Instructions::Frame(..) => "frame",
+            Instructions::Tone => "tone",
+            Instructions::MemInfo => "meminfo",
+            Instructions::Flush => "flush",
+            Instructions::ByteAt => "byteat",
+            Instructions::ByteSlice => "byteslice",
+            Instructions::ByteLen => "bytelen",
+            Instructions::ToBytes => "tobytes",
+            Instructions::FromBytes => "frombytes",
+            Instructions::Len => "len",
+            Instructions::Substr => "substr",
+            Instructions::StrIndex => "strindex",
+            Instructions::ToInt => "toint",
+            Instructions::ToFloat => "tofloat",
+            Instructions::ToString => "tostring",
+            Instructions::ToBool => "tobool",
+            Instructions::List => "list",
+            Instructions::Append => "append",
+            Instructions::Get => "get",
+            Instructions::Set => "set",
+            Instructions::Pop => "pop",
+            Instructions::FOpen => "fopen",
+            Instructions::FReadN => "freadn",
+            Instructions::FWriteH => "fwriteh",
+            Instructions::FSeek => "fseek",
+            Instructions::FClose => "fclose",
+            Instructions::CNew => "cnew",
+            Instructions::CSet => "cset",
+            Instructions::CSave => "csave",
+            Instructions::Forward => "forward",
+            Instructions::Turn => "turn",
+            Instructions::PenUp => "penup",
+            Instructions::PenDown => "pendown",
+            Instructions::TSave => "tsave",
+            Instructions::ReadAll => "readall",
+            Instructions::ReadLine => "readline",
+            Instructions::Read => "read",
+            Instructions::ReadInt => "readint",
+            Instructions::ReadFloat => "readfloat",
+            Instructions::CsvParse => "csvparse",
+            Instructions::CsvEmit => "csvemit",
+            Instructions::PushData(..) => "pushdata",
+            Instructions::GetConst(..) => "getconst",
+            Instructions::GetGlobal(..) => "getglobal",
+            Instructions::SetGlobal(..) => "setglobal",
+            Instructions::Argc => "argc",
+            Instructions::Argv(..) => "argv",
+            Instructions::Exit => "exit",
+            Instructions::EndSection(..) => "endsection",
+            Instructions::Host { name, .. } => name,
+        }
+    }
+}
+
+impl std::fmt::Display for Instructions {
+    /// Renders the instruction back into source form
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instructions::Push(DataType::String(a)) => {
+                write!(
+                    f,
+                    "push \"{}\"",
+                    a.replace('\n', "\\n").replace('\r', "\\r")
+                )
+            }
+            Instructions::Push(DataType::Float(a)) => write!(f, "push {:?}", a),
+            Instructions::Push(DataType::Bytes(a)) => {
+                write!(f, "push x\"{}\"", DataType::Bytes(a.clone()))
+            }
+            Instructions::Push(a) => write!(f, "push {}", a),
+            Instructions::Jump(label) => write!(f, "jump {label}"),
+            Instructions::Call(label) => write!(f, "call {label}"),
+            Instructions::IfJmp(label) => write!(f, "ifjmp {label}"),
+            Instructions::Switch(group) => write!(f, "switch {group}"),
+            Instructions::PushData(name) => write!(f, "pushdata {name}"),
+            Instructions::GetConst(name, Some(index)) => write!(f, "getconst {name} {index}"),
+            Instructions::GetConst(name, None) => write!(f, "getconst {name}"),
+            Instructions::GetGlobal(name) => write!(f, "getglobal {name}"),
+            Instructions::SetGlobal(name) => write!(f, "setglobal {name}"),
+            Instructions::Argv(Some(index)) => write!(f, "argv {index}"),
+            Instructions::Argv(None) => write!(f, "argv"),
+            Instructions::Store(name) => write!(f, "store {name}"),
+            Instructions::Load(name) => write!(f, "load {name}"),
+            Instructions::Host { name, .. } => write!(f, "{name}"),
+            Instructions::Print(None) => write!(f, "print"),
+            Instructions::Print(Some(format)) => write!(f, "print {format}"),
+            Instructions::Frame(fps) => write!(f, "frame {fps}"),
+            Instructions::Vector(length) => write!(f, "vector {length}"),
+            Instructions::Matrix(rows, cols) => write!(f, "matrix {rows} {cols}"),
+            Instructions::Trace(message) => write!(f, "trace {message}"),
+            Instructions::EndSection(section) => write!(f, "// end of {section}"),
+            Instructions::Log(level) => write!(f, "log {}", format!("{:?}", level).to_lowercase()),
+            instruction => write!(f, "{}", format!("{:?}", instruction).to_lowercase()),
+        }
+    }
+}
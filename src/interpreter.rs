@@ -0,0 +1,2638 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufWriter, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use clap::ValueEnum;
+
+use crate::{
+    cancellation::CancellationToken,
+    canvas::{Canvas, MAX_SIDE},
+    checkpoint::Checkpoint,
+    complex::Complex,
+    contract::{Contract, ContractKind},
+    csv::{emit_csv, parse_csv},
+    debugger::{Breakpoint, Command, Debugger},
+    depth_chart::DepthChart,
+    error::{RuntimeError, SourceLocation},
+    format::{render_table, PrintFormat},
+    hooks::{DebugPrinter, InterpreterHooks},
+    host::HostFunction,
+    instructions::Instructions,
+    log::{LogLevel, Logger, StderrLogger},
+    matrix::Matrix,
+    metering::Metering,
+    parser::{
+        budget, contracts, instruction_lines, resolve_labels, to_source, Program, SectionName,
+    },
+    replay::{Replay, ReplayEvent},
+    shadow::ShadowStack,
+    sound::Speaker,
+    stack::{Stack, StackDiff},
+    trace::TraceWriter,
+    turtle::Turtle,
+    value::DataType,
+    Value,
+};
+
+pub struct RunOptions {
+    pub debug: bool,
+    pub trap_fallthrough: bool,
+    pub defines: Vec<String>,
+    /// Whether to run under the interactive [debugger](crate::debugger), which pauses at
+    /// `break` instructions and `breakpoints`. Replaces the `debug` output
+    pub debugger: bool,
+    /// Where the debugger pauses besides `break` instructions
+    pub breakpoints: Vec<Breakpoint>,
+    /// Where messages from the `log` instruction are sent
+    pub logger: Box<dyn Logger>,
+    /// Whether output is buffered until a `flush`, an input read or the program exits
+    pub buffered: bool,
+    /// Maximum approximate number of bytes the program's values may occupy
+    pub max_memory: Option<usize>,
+    /// Maximum number of bytes the program may print in a run
+    pub max_output_bytes: Option<usize>,
+    /// Maximum time a run of the program may take
+    pub timeout: Option<Duration>,
+    /// Whether `fopen` may open files, off for programs that must not touch the machine
+    pub files: bool,
+    /// Whether `frame` may wait for the next frame, off for runs that must not be held up
+    /// by the clock
+    pub frames: bool,
+    /// Plays the program's `tone`s. Without one `tone` fails, so programs only make noise
+    /// when allowed to
+    pub speaker: Option<Box<dyn Speaker>>,
+    /// Older behaviour to reproduce for programs that depend on it
+    pub compat: Option<Compat>,
+    /// How many values from the top of the stack `debug` output shows, or all of them
+    pub debug_depth: Option<usize>,
+    /// Only show every nth instruction in `debug` output
+    pub debug_every: usize,
+    /// Where `debug` output is written instead of stdout
+    pub debug_to: Option<Box<dyn Write>>,
+    /// Whether `debug` output shows how each instruction changed the stack instead of
+    /// the whole stack
+    pub debug_diff: bool,
+    /// Where to write a [trace](crate::trace) of every instruction run
+    pub trace_to: Option<Box<dyn Write>>,
+    /// Where to write an SVG [chart](crate::depth_chart) of the stack depth once the
+    /// interpreter is dropped
+    pub depth_chart_to: Option<Box<dyn Write>>,
+    /// Whether to pause after every instruction until Enter is pressed
+    pub step: bool,
+    /// Write a [`Checkpoint`] every this many instructions
+    pub checkpoint_every: Option<usize>,
+    /// Where checkpoints are written, as `checkpoint.json`
+    pub checkpoint_dir: PathBuf,
+    /// Save the input and clock readings of the run to this file once it ends
+    pub record: Option<PathBuf>,
+    /// Recorded input and clock readings to feed the program instead of the real ones
+    pub replay: Option<Replay>,
+    /// Rust functions programs can use as instructions
+    pub host_functions: Vec<HostFunction>,
+    /// Charges each instruction against a budget, stopping the program once it is spent
+    pub metering: Option<Metering>,
+    /// Whether a section fails once it runs more steps than its `#budget` allows, on for
+    /// `toylang test`
+    pub budgets: bool,
+    /// The arguments the program was run with, read with `argc` and `argv`
+    pub args: Vec<String>,
+    /// The section [`Interpreter::run`] starts from, `main` unless an entry point of the
+    /// [manifest](crate::manifest) names another
+    pub entry: String,
+}
+
+/// Behaviours kept around so existing programs keep running while they are migrated
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Compat {
+    /// `jump`, `ifjmp` and `switch` copy the target section into the running stream, so
+    /// execution continues after the jump once the section ends. Without it they go to the
+    /// target section for good, and only `call` comes back
+    Splice,
+}
+
+/// Stops execution with a [`RuntimeError::Instruction`] built from a format string
+macro_rules! bail {
+    ($($arg:tt)*) => {
+        return Err(RuntimeError::Instruction(format!($($arg)*)))
+    };
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            debug: false,
+            trap_fallthrough: false,
+            defines: Vec::new(),
+            debugger: false,
+            breakpoints: Vec::new(),
+            logger: Box::new(StderrLogger {
+                level: LogLevel::Info,
+            }),
+            buffered: true,
+            max_memory: None,
+            max_output_bytes: None,
+            timeout: None,
+            files: true,
+            frames: true,
+            speaker: None,
+            compat: None,
+            debug_depth: None,
+            debug_every: 1,
+            debug_to: None,
+            trace_to: None,
+            depth_chart_to: None,
+            debug_diff: false,
+            step: false,
+            checkpoint_every: None,
+            checkpoint_dir: PathBuf::from("."),
+            record: None,
+            replay: None,
+            host_functions: Vec::new(),
+            metering: None,
+            budgets: false,
+            args: Vec::new(),
+            entry: "main".to_string(),
+        }
+    }
+}
+
+/// The running state of a program, which can outlive a single run so sections can be
+/// (re)defined and executed incrementally
+pub struct Interpreter {
+    pub(crate) options: RunOptions,
+    pub(crate) program: Vec<Program>,
+    labels: HashMap<String, usize>,
+    pub(crate) stack: Stack,
+    /// Types and origins of the values on the stack, only kept up to date in debug runs
+    shadow: ShadowStack,
+    pub(crate) out: Box<dyn Write>,
+    /// Where `readall` and `readline` read from, stdin unless the host provided input
+    input: Option<Box<dyn BufRead>>,
+    /// Files opened by the program, keyed by the id stored in their Handle
+    files: HashMap<usize, (String, File)>,
+    next_handle: usize,
+    /// Start times of the currently open `tick`s, so stopwatches can be nested
+    stopwatches: Vec<Instant>,
+    /// When the current frame started, once a `frame` has run
+    frame_start: Option<Instant>,
+    /// What the program is drawing, once it has run `cnew`
+    canvas: Option<Canvas>,
+    /// The turtle drawing on `canvas`, which `cnew` puts back in the middle
+    turtle: Option<Turtle>,
+    /// Named values shared between the program and the host
+    globals: HashMap<String, Value>,
+    /// Approximate number of bytes held by `globals`
+    globals_bytes: usize,
+    /// Values the program stored by name with `store`
+    variables: HashMap<String, Value>,
+    /// Approximate number of bytes held by `variables`
+    variables_bytes: usize,
+    hooks: Vec<Box<dyn InterpreterHooks>>,
+    cancellation: CancellationToken,
+    /// The run of the main section that is in progress, if it was paused by `run_for`
+    execution: Option<Execution>,
+    /// When the current run exceeds its timeout
+    deadline: Option<Instant>,
+    /// The instructions around the one that failed most recently, and the stack at the time
+    error_context: Option<String>,
+    /// The section and position within it of the instruction that failed most recently,
+    /// unknown with [`Compat::Splice`] and for instructions the host ran directly
+    error_at: Option<(String, usize)>,
+    /// How many instructions have run since the main section was started
+    steps: usize,
+    /// How much of the metering budget has been spent since the main section was started
+    spent: u64,
+    /// How many bytes have been printed since the main section was started
+    printed: usize,
+    /// The most values the stack has held since the main section was started
+    peak_depth: usize,
+    /// How `print` lays out values, as last set by `setfmt`
+    print_format: PrintFormat,
+    /// Input and clock readings taken so far, kept when the run is recorded
+    recorded: Vec<ReplayEvent>,
+    /// Recorded readings still to be fed back, when the run is a replay
+    replaying: Option<VecDeque<ReplayEvent>>,
+    /// Breakpoints and where the debugger talks to the user, when running under it
+    debugger: Debugger,
+    /// The sections with a `#budget` that are running, outermost first
+    budgets: Vec<RunningBudget>,
+}
+
+/// Whether a call to [`Interpreter::run_for`] finished the program
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunStatus {
+    /// The step limit was reached, call `run_for` again to continue
+    Yielded,
+    Finished,
+}
+
+/// A stream of instructions being executed and the position in it
+struct Execution {
+    /// The whole program laid out by [`flatten`], followed by any instructions the host ran
+    /// directly. With [`Compat::Splice`], the section being run with the sections it
+    /// jumped to spliced in
+    instructions: Vec<Instructions>,
+    ic: usize,
+    /// Where each section starts in `instructions`, empty with [`Compat::Splice`]
+    starts: HashMap<String, usize>,
+    /// Where each `call` in progress returns to, innermost last
+    calls: Vec<Frame>,
+}
+
+/// The stream a `call` was made from and the position to continue at once it returns. The
+/// stream is only kept with [`Compat::Splice`], otherwise it never changes and is left empty
+type Frame = (Vec<Instructions>, usize);
+
+/// A section with a `#budget` that has been entered and not yet returned from
+struct RunningBudget {
+    section: String,
+    steps: usize,
+    /// The step count at which the section has used up its budget
+    deadline: usize,
+    /// How deeply calls were nested while the section ran, known once it has started
+    depth: Option<usize>,
+}
+
+/// How deeply calls may nest, so runaway recursion fails instead of exhausting memory
+const MAX_CALL_DEPTH: usize = 10_000;
+
+impl Execution {
+    fn new(instructions: Vec<Instructions>) -> Self {
+        Execution {
+            instructions,
+            ic: 0,
+            starts: HashMap::new(),
+            calls: Vec::new(),
+        }
+    }
+}
+
+impl Interpreter {
+    pub fn new(program: Vec<Program>, options: RunOptions) -> Self {
+        // Debug output is printed by a hook as the program runs, so program output must
+        // not be held back in a buffer or the two would be interleaved out of order
+        let out: Box<dyn Write> = if options.buffered && !options.debug {
+            Box::new(BufWriter::new(std::io::stdout()))
+        } else {
+            Box::new(std::io::stdout())
+        };
+
+        let options_breakpoints = options.breakpoints.clone();
+        let mut interpreter = Interpreter {
+            labels: resolve_labels(&program),
+            program,
+            options,
+            stack: Stack::new(),
+            shadow: ShadowStack::default(),
+            out,
+            input: None,
+            files: HashMap::new(),
+            next_handle: 0,
+            stopwatches: Vec::new(),
+            frame_start: None,
+            canvas: None,
+            turtle: None,
+            globals: HashMap::new(),
+            globals_bytes: 0,
+            variables: HashMap::new(),
+            variables_bytes: 0,
+            hooks: Vec::new(),
+            cancellation: CancellationToken::new(),
+            execution: None,
+            deadline: None,
+            error_context: None,
+            error_at: None,
+            steps: 0,
+            spent: 0,
+            printed: 0,
+            peak_depth: 0,
+            print_format: PrintFormat::default(),
+            recorded: Vec::new(),
+            replaying: None,
+            debugger: Debugger::new(options_breakpoints),
+            budgets: Vec::new(),
+        };
+
+        interpreter.replaying = interpreter
+            .options
+            .replay
+            .take()
+            .map(|replay| replay.events.into());
+
+        if interpreter.options.debug && !interpreter.options.debugger {
+            let out = interpreter
+                .options
+                .debug_to
+                .take()
+                .unwrap_or_else(|| Box::new(std::io::stdout()));
+
+            interpreter.add_hooks(DebugPrinter {
+                depth: interpreter.options.debug_depth,
+                every: interpreter.options.debug_every,
+                out,
+                steps: 0,
+                diff: interpreter.options.debug_diff,
+                recent: VecDeque::new(),
+            });
+        }
+
+        if let Some(out) = interpreter.options.trace_to.take() {
+            interpreter.add_hooks(TraceWriter::new(out));
+        }
+
+        if let Some(out) = interpreter.options.depth_chart_to.take() {
+            interpreter.add_hooks(DepthChart::new(out));
+        }
+
+        interpreter
+    }
+
+    /// Recreates an interpreter from a checkpoint, so the next call to `run` or `run_for`
+    /// continues from where the checkpoint was taken
+    pub fn restore(checkpoint: Checkpoint, options: RunOptions) -> Self {
+        let program = checkpoint.program(&options);
+        let mut interpreter = Interpreter::new(program, options);
+
+        interpreter.stack = Stack::from(checkpoint.stack);
+        for (name, value) in checkpoint.globals {
+            interpreter.set_global(name, value);
+        }
+        for (name, value) in checkpoint.variables {
+            interpreter.store(name, value);
+        }
+
+        interpreter.steps = checkpoint.step;
+        let starts = if interpreter.splices() {
+            HashMap::new()
+        } else {
+            flatten(&interpreter.program).1
+        };
+
+        interpreter.execution = Some(Execution {
+            instructions: checkpoint.instructions,
+            ic: checkpoint.ic,
+            starts,
+            calls: checkpoint.calls,
+        });
+
+        interpreter
+    }
+
+    /// Defines a section, replacing any existing section of the same name in place so
+    /// jumps to it pick up the new body. Returns whether a section was replaced
+    pub fn define_section(&mut self, name: SectionName, instructions: Vec<Instructions>) -> bool {
+        match self.labels.get(&name.0) {
+            Some(&index) => {
+                self.program[index] = Program::Section(name, instructions);
+                true
+            }
+            None => {
+                self.labels.insert(name.0.clone(), self.program.len());
+                self.program.push(Program::Section(name, instructions));
+                false
+            }
+        }
+    }
+
+    /// Defines a data section for `pushdata`, replacing any existing one of the same name.
+    /// Returns whether a data section was replaced
+    pub fn define_data(&mut self, name: SectionName, text: String) -> bool {
+        self.replace_or_push(
+            |section| matches!(section, Program::Data(other, _) if other.0 == name.0),
+            Program::Data(name.clone(), text),
+        )
+    }
+
+    /// Defines a `const` table, replacing any existing one of the same name. Returns
+    /// whether a table was replaced
+    pub fn define_const(&mut self, name: String, values: Vec<Value>) -> bool {
+        self.replace_or_push(
+            |constant| matches!(constant, Program::Const(other, _) if *other == name),
+            Program::Const(name.clone(), values),
+        )
+    }
+
+    /// Defines a `#states` group for `switch`, replacing any existing group of the same
+    /// name. Returns whether a group was replaced
+    pub fn define_states(&mut self, group: String, states: Vec<String>) -> bool {
+        self.replace_or_push(
+            |existing| matches!(existing, Program::States(other, _) if *other == group),
+            Program::States(group.clone(), states),
+        )
+    }
+
+    /// Sets the `#budget` of a section, replacing any it already had. Returns whether it
+    /// already had one
+    pub fn define_budget(&mut self, section: SectionName, steps: usize) -> bool {
+        self.replace_or_push(
+            |existing| matches!(existing, Program::Budget(name, _) if name.0 == section.0),
+            Program::Budget(section.clone(), steps),
+        )
+    }
+
+    /// Adds a `#pre` or `#post` contract to a section unless it already has it. Returns
+    /// whether the section already had the contract
+    pub fn define_contract(&mut self, section: SectionName, contract: Contract) -> bool {
+        self.replace_or_push(
+            |existing| matches!(existing, Program::Contract(name, other) if name.0 == section.0 && *other == contract),
+            Program::Contract(section.clone(), contract),
+        )
+    }
+
+    /// Replaces the first declaration matching `existing` with `item`, or adds `item` if
+    /// there is none. Returns whether one was replaced
+    fn replace_or_push(&mut self, existing: impl Fn(&Program) -> bool, item: Program) -> bool {
+        match self.program.iter().position(existing) {
+            Some(index) => {
+                self.program[index] = item;
+                true
+            }
+            None => {
+                self.program.push(item);
+                false
+            }
+        }
+    }
+
+    /// Sends everything the program prints to `out` instead of stdout
+    pub fn set_output(&mut self, out: impl Write + 'static) {
+        self.out = Box::new(out);
+    }
+
+    /// Makes `readall` and `readline` read from `input` instead of stdin
+    pub fn set_input(&mut self, input: impl BufRead + 'static) {
+        self.input = Some(Box::new(input));
+    }
+
+    /// Lets the debugger show which line of `source` each instruction was read from and
+    /// pause at breakpoints on lines. Only needed when running under the debugger
+    pub fn set_source(&mut self, source: &str) {
+        self.debugger.set_source(source, &self.options);
+    }
+
+    /// Makes the debugger read commands from `input` and answer on `output` instead of
+    /// stdin and stderr
+    pub fn set_debugger_io(&mut self, input: impl BufRead + 'static, output: impl Write + 'static) {
+        self.debugger.set_io(Box::new(input), Box::new(output));
+    }
+
+    /// Registers callbacks that observe the program as it runs
+    pub fn add_hooks(&mut self, hooks: impl InterpreterHooks + 'static) {
+        self.hooks.push(Box::new(hooks));
+    }
+
+    /// Returns a token that stops this interpreter at the next instruction boundary when
+    /// cancelled, which can be sent to and triggered from another thread
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
+    /// Sets a global the program can read with `getglobal`
+    pub fn set_global(&mut self, name: impl Into<String>, value: Value) {
+        self.globals_bytes += value.size();
+
+        if let Some(previous) = self.globals.insert(name.into(), value) {
+            self.globals_bytes -= previous.size();
+        }
+    }
+
+    fn store(&mut self, name: String, value: Value) {
+        self.variables_bytes += value.size();
+
+        if let Some(previous) = self.variables.insert(name, value) {
+            self.variables_bytes -= previous.size();
+        }
+    }
+
+    /// Approximate number of bytes held by the values on the stack, in globals and in
+    /// variables, and by
+    /// the canvas
+    pub fn memory_used(&self) -> usize {
+        self.stack.bytes()
+            + self.globals_bytes
+            + self.variables_bytes
+            + self.canvas.as_ref().map_or(0, Canvas::bytes)
+    }
+
+    /// How many instructions the current or most recent run of the program executed
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// How much of its [`Metering`] budget the current or most recent run spent
+    pub fn spent(&self) -> u64 {
+        self.spent
+    }
+
+    /// The most values the stack held during the current or most recent run of the program
+    pub fn peak_depth(&self) -> usize {
+        self.peak_depth
+    }
+
+    /// Reads back a global, including any the program set with `setglobal`
+    pub fn global(&self, name: &str) -> Option<&Value> {
+        self.globals.get(name)
+    }
+
+    pub fn globals(&self) -> &HashMap<String, Value> {
+        &self.globals
+    }
+
+    /// Looks up the instructions of the section with the given name
+    fn section(&self, label: &str) -> Result<&[Instructions], RuntimeError> {
+        let Some(&index) = self.labels.get(label) else {
+            return Err(RuntimeError::UnknownSection(label.to_string()));
+        };
+
+        let Program::Section(_, instructions) = &self.program[index] else {
+            unreachable!("labels only point at sections of instructions");
+        };
+
+        Ok(instructions)
+    }
+
+    /// Describes where the most recent runtime error happened: a disassembly of the
+    /// instructions around the one that failed, followed by the stack
+    pub fn error_context(&self) -> Option<&str> {
+        self.error_context.as_deref()
+    }
+
+    /// Where the instruction the most recent runtime error happened at is written in
+    /// `source`, the source the program was parsed from
+    pub fn error_location(&self, source: &str) -> Option<SourceLocation> {
+        let (section, index) = self.error_at.as_ref()?;
+        let lines: Vec<&str> = source.lines().collect();
+
+        let number = instruction_lines(&lines, &self.options)
+            .into_iter()
+            .find(|(name, ..)| name == section)
+            .and_then(|(_, _, numbers)| numbers.get(*index).copied())?;
+
+        Some(SourceLocation {
+            file: None,
+            line: number + 1,
+            instruction: lines[number].trim().to_string(),
+        })
+    }
+
+    /// Runs the program from its entry section, `main` by default
+    pub fn run(&mut self) -> Result<(), RuntimeError> {
+        while self.run_for(usize::MAX)? == RunStatus::Yielded {}
+
+        Ok(())
+    }
+
+    /// Runs the program from its entry section for at most `steps` instructions, so a host
+    /// can interleave execution with other work (e.g. yielding to an async runtime between
+    /// calls). Each call continues where the previous one left off until the program
+    /// finishes, after which the next call starts it again from the beginning
+    pub fn run_for(&mut self, steps: usize) -> Result<RunStatus, RuntimeError> {
+        let mut execution = match self.execution.take() {
+            Some(execution) => execution,
+            None => {
+                if !self.labels.contains_key(&self.options.entry) {
+                    match self.options.entry.as_str() {
+                        "main" => bail!("No main section found"),
+                        entry => bail!("No section named {entry} to start from"),
+                    }
+                }
+
+                self.deadline = match self.options.timeout {
+                    Some(timeout) => Some(now("A timeout")? + timeout),
+                    None => None,
+                };
+                self.steps = 0;
+                self.spent = 0;
+                self.printed = 0;
+                self.frame_start = None;
+                self.canvas = None;
+                self.turtle = None;
+                self.variables.clear();
+                self.variables_bytes = 0;
+                self.peak_depth = self.stack.len();
+                self.print_format = PrintFormat::default();
+
+                let entry = self.options.entry.clone();
+                self.start(&entry)?
+            }
+        };
+
+        let result = self.execute_for(&mut execution, steps);
+
+        if let Ok(None) = result {
+            self.execution = Some(execution);
+            return Ok(RunStatus::Yielded);
+        }
+
+        self.deadline = None;
+
+        self.finish();
+
+        // Running out of instructions is an implicit exit unless fallthrough is trapped
+        if result? == Some(false) && self.options.trap_fallthrough {
+            bail!("Execution reached the end of the program without an exit");
+        }
+
+        Ok(RunStatus::Finished)
+    }
+
+    /// Runs a single section as if it were a function: `args` become the initial stack
+    /// (last argument on top) and whatever the section leaves on the stack is returned.
+    /// The interpreter's own stack is left untouched
+    pub fn call_section(
+        &mut self,
+        name: &str,
+        args: Vec<Value>,
+    ) -> Result<Vec<Value>, RuntimeError> {
+        let saved = std::mem::replace(&mut self.stack, Stack::from(args));
+        let result = self
+            .start(name)
+            .and_then(|mut execution| self.execute_for(&mut execution, usize::MAX));
+        let values = std::mem::replace(&mut self.stack, saved);
+
+        self.out.flush().unwrap();
+
+        result.map(|_| values.into_vec())
+    }
+
+    /// Flushes output, saves the recording and reports resources the program never released
+    pub(crate) fn finish(&mut self) {
+        self.out.flush().unwrap();
+
+        if let Some(path) = &self.options.record {
+            let replay = Replay {
+                events: std::mem::take(&mut self.recorded),
+            };
+
+            if let Err(error) = replay.save(path) {
+                eprintln!(
+                    "warning: cannot write recording to {}: {error}",
+                    path.display()
+                );
+            }
+        }
+
+        if !self.files.is_empty() {
+            let mut leaked: Vec<_> = self.files.iter().collect();
+            leaked.sort_by_key(|(handle, _)| **handle);
+
+            for (handle, (path, _)) in leaked {
+                eprintln!("warning: file handle {handle} ({path}) was never closed");
+            }
+        }
+    }
+
+    /// Pops the Ints and Floats that make up a vector or matrix, which were pushed row by row
+    fn pop_matrix(&mut self, rows: usize, cols: usize, name: &str) -> Result<Matrix, RuntimeError> {
+        let count = rows * cols;
+        if self.stack.len() < count {
+            bail!(
+                "{name} requires {count} numbers on the stack, found {}",
+                self.stack.len()
+            );
+        }
+
+        let mut values = Vec::with_capacity(count);
+        for _ in 0..count {
+            values.push(match self.stack.pop() {
+                Some(DataType::Int(value)) => value as f64,
+                Some(DataType::Float(value)) => value,
+                Some(value) => bail!("{name} requires numbers, found {}", value.type_name()),
+                None => unreachable!("the stack holds at least {count} values"),
+            });
+        }
+
+        values.reverse();
+        match Matrix::new(rows, cols, values) {
+            Some(matrix) => Ok(matrix),
+            None => unreachable!("the parser only accepts matrices with elements"),
+        }
+    }
+
+    /// Pops a path and writes the canvas there as a PNG, for `csave` and `tsave`
+    fn save_canvas(&mut self, name: &str) -> Result<(), RuntimeError> {
+        let Some(DataType::String(path)) = self.stack.pop() else {
+            bail!("{name} requires a String path on the stack");
+        };
+
+        let Some(canvas) = &self.canvas else {
+            bail!("{name} requires a canvas, create one with cnew first");
+        };
+
+        if !self.options.files {
+            bail!("Cannot write file {path}: file access is disabled");
+        }
+
+        if let Err(error) = std::fs::write(&path, canvas.to_png()) {
+            bail!("Cannot write file {path}: {error}");
+        }
+
+        Ok(())
+    }
+
+    /// The turtle, for an instruction that needs one
+    fn turtle(&mut self, name: &str) -> Result<&mut Turtle, RuntimeError> {
+        match &mut self.turtle {
+            Some(turtle) => Ok(turtle),
+            None => bail!("{name} requires a canvas, create one with cnew first"),
+        }
+    }
+
+    /// Whether jumps splice their target into the running stream, see [`Compat::Splice`]
+    fn splices(&self) -> bool {
+        self.options.compat == Some(Compat::Splice)
+    }
+
+    /// Prepares to execute the named section
+    fn start(&mut self, name: &str) -> Result<Execution, RuntimeError> {
+        self.budgets.clear();
+
+        let result = if self.splices() {
+            self.enter(name).map(Execution::new)
+        } else {
+            let mut execution = self.execution_of(Vec::new());
+            self.enter_at(name, &execution.starts).map(|ic| {
+                execution.ic = ic;
+                execution
+            })
+        };
+
+        if let Err(error) = &result {
+            self.report(error);
+        }
+
+        result
+    }
+
+    /// Prepares to execute instructions the host provided, which may jump into the program
+    fn execution_of(&self, instructions: Vec<Instructions>) -> Execution {
+        if self.splices() {
+            return Execution::new(instructions);
+        }
+
+        let (mut code, starts) = flatten(&self.program);
+        let ic = code.len();
+        code.extend(instructions);
+
+        Execution {
+            instructions: code,
+            ic,
+            starts,
+            calls: Vec::new(),
+        }
+    }
+
+    /// Returns the instructions of a section that is being jumped into, for splicing. Debug
+    /// runs mark its end so its `#post` contracts are checked there
+    fn enter(&mut self, name: &str) -> Result<Vec<Instructions>, RuntimeError> {
+        let mut instructions = self.section(name)?.to_vec();
+
+        if self.options.debug {
+            instructions.push(Instructions::EndSection(name.to_string()));
+        }
+
+        self.entered(name)?;
+        Ok(instructions)
+    }
+
+    /// Returns where a section that is being jumped into starts in the flattened program
+    fn enter_at(
+        &mut self,
+        name: &str,
+        starts: &HashMap<String, usize>,
+    ) -> Result<usize, RuntimeError> {
+        let Some(&start) = starts.get(name) else {
+            return Err(RuntimeError::UnknownSection(name.to_string()));
+        };
+
+        self.entered(name)?;
+        Ok(start)
+    }
+
+    /// Checks the `#pre` contracts of a section that is being entered in debug runs, starts
+    /// its `#budget` if budgets are enforced, and tells hooks about it
+    fn entered(&mut self, name: &str) -> Result<(), RuntimeError> {
+        if self.options.debug {
+            self.check_contracts(name, ContractKind::Pre)?;
+        }
+
+        // A section that is already running counts the steps of every way back into it,
+        // so loops and recursion cannot restart its budget
+        if let (true, Some(steps)) = (self.options.budgets, budget(&self.program, name)) {
+            if !self.budgets.iter().any(|running| running.section == name) {
+                self.budgets.push(RunningBudget {
+                    section: name.to_string(),
+                    steps,
+                    deadline: self.steps + steps,
+                    depth: None,
+                });
+            }
+        }
+
+        for hook in &mut self.hooks {
+            hook.on_call(name, &self.stack);
+        }
+
+        Ok(())
+    }
+
+    /// Continues execution at the start of a section, or with [`Compat::Splice`] replaces
+    /// the jump at `ic` with the section
+    fn jump(
+        &mut self,
+        label: &str,
+        instructions: &mut Vec<Instructions>,
+        ic: &mut usize,
+        starts: &HashMap<String, usize>,
+    ) -> Result<(), RuntimeError> {
+        if self.splices() {
+            let section = self.enter(label)?;
+            instructions.splice(*ic..*ic + 1, section);
+        } else {
+            *ic = self.enter_at(label, starts)?;
+        }
+
+        Ok(())
+    }
+
+    /// Forgets the budgets of sections that have returned, then fails if any section still
+    /// running has used up its budget. `depth` is how deeply calls are nested right now
+    fn check_budgets(&mut self, depth: usize) -> Result<(), RuntimeError> {
+        for running in &mut self.budgets {
+            running.depth.get_or_insert(depth);
+        }
+        self.budgets
+            .retain(|running| running.depth.is_some_and(|started| started <= depth));
+
+        if let Some(running) = self
+            .budgets
+            .iter()
+            .find(|running| self.steps >= running.deadline)
+        {
+            bail!(
+                "Section {} went over its budget of {} steps",
+                running.section,
+                running.steps
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Fails if the stack does not satisfy the section's contracts of the given kind
+    fn check_contracts(&self, section: &str, kind: ContractKind) -> Result<(), RuntimeError> {
+        let depth = self.stack.len();
+
+        for contract in contracts(&self.program, section) {
+            if contract.kind == kind && !contract.holds(depth) {
+                bail!(
+                    "Contract {contract} of section {section} failed with a stack depth of {depth}"
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Shows `message` and the stack, then waits for Enter. Returns whether the user chose
+    /// to quit instead
+    fn pause(&mut self, message: &str) -> bool {
+        self.out.flush().unwrap();
+
+        eprintln!("{message}");
+        eprintln!("Stack: {}", self.stack.render());
+        eprint!("Press Enter to continue or q to quit: ");
+
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input).unwrap();
+
+        input.trim() == "q"
+    }
+
+    /// Reads debugger commands until one resumes the program, about to run
+    /// `instructions[ic]`. Returns whether the user chose to quit instead
+    fn debug_prompt(
+        &mut self,
+        instructions: &[Instructions],
+        ic: usize,
+        starts: &HashMap<String, usize>,
+        calls: &[Frame],
+    ) -> bool {
+        self.out.flush().unwrap();
+
+        let at = section_position(instructions, starts, ic);
+        let message = format!(
+            "Paused before `{}` at {}",
+            instructions[ic],
+            self.debugger.describe(at.as_ref(), ic)
+        );
+        self.debugger.say(&message);
+
+        loop {
+            let command = match self.debugger.read_command() {
+                Some(Ok(command)) => command,
+                Some(Err(error)) => {
+                    self.debugger.say(&error);
+                    continue;
+                }
+                // Nobody is left to answer, so the program runs to the end
+                None => {
+                    self.debugger.stepping = false;
+                    self.debugger.breakpoints.clear();
+                    return false;
+                }
+            };
+
+            let answer = match command {
+                Command::Step => {
+                    self.debugger.stepping = true;
+                    return false;
+                }
+                Command::Continue => {
+                    self.debugger.stepping = false;
+                    return false;
+                }
+                Command::Quit => return true,
+                Command::Stack => self.stack.render(),
+                Command::Vars => self.render_variables(),
+                Command::Where => disassemble_around(instructions, ic, 2)
+                    .trim_end()
+                    .to_string(),
+                Command::Calls => {
+                    let mut lines = vec![format!("#0 {}", self.debugger.describe(at.as_ref(), ic))];
+
+                    // Each call returns to just after the instruction that made it
+                    for (depth, (caller, return_to)) in calls.iter().rev().enumerate() {
+                        let stream = if caller.is_empty() {
+                            instructions
+                        } else {
+                            caller
+                        };
+                        let call = return_to - 1;
+                        let at = section_position(stream, starts, call);
+                        lines.push(format!(
+                            "#{} {}",
+                            depth + 1,
+                            self.debugger.describe(at.as_ref(), call)
+                        ));
+                    }
+
+                    lines.join("\n")
+                }
+                Command::Break(breakpoint) => {
+                    let answer = format!("Breakpoint set at {breakpoint}");
+                    if !self.debugger.breakpoints.contains(&breakpoint) {
+                        self.debugger.breakpoints.push(breakpoint);
+                    }
+                    answer
+                }
+                Command::Delete(breakpoint) => {
+                    let before = self.debugger.breakpoints.len();
+                    self.debugger
+                        .breakpoints
+                        .retain(|other| *other != breakpoint);
+
+                    if self.debugger.breakpoints.len() < before {
+                        format!("Breakpoint at {breakpoint} deleted")
+                    } else {
+                        format!("No breakpoint at {breakpoint}")
+                    }
+                }
+                Command::Help => {
+                    self.debugger.help();
+                    continue;
+                }
+            };
+
+            self.debugger.say(&answer);
+        }
+    }
+
+    /// Lists the variables and then the globals by name, one per line
+    fn render_variables(&self) -> String {
+        let mut lines: Vec<String> = self
+            .variables
+            .iter()
+            .map(|(name, value)| format!("{name} = {}", value.display()))
+            .collect();
+        lines.sort();
+
+        let mut globals: Vec<String> = self
+            .globals
+            .iter()
+            .map(|(name, value)| format!("global {name} = {}", value.display()))
+            .collect();
+        globals.sort();
+        lines.extend(globals);
+
+        if lines.is_empty() {
+            "No variables".to_string()
+        } else {
+            lines.join("\n")
+        }
+    }
+
+    /// Captures the state of the program, about to run `instructions[ic]`
+    fn checkpoint(&self, instructions: &[Instructions], ic: usize, calls: &[Frame]) -> Checkpoint {
+        let mut globals: Vec<_> = self
+            .globals
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        globals.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut variables: Vec<_> = self
+            .variables
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        variables.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        Checkpoint {
+            step: self.steps,
+            source: to_source(&self.program),
+            instructions: instructions.to_vec(),
+            ic,
+            calls: calls.to_vec(),
+            stack: self.stack.to_vec(),
+            globals,
+            variables,
+        }
+    }
+
+    /// Replaces the checkpoint in the checkpoint directory. The file is written under a
+    /// temporary name first so a run killed halfway through never leaves a torn checkpoint
+    fn write_checkpoint(&mut self, instructions: &[Instructions], ic: usize, calls: &[Frame]) {
+        let dir = &self.options.checkpoint_dir;
+        let json = self
+            .checkpoint(instructions, ic, calls)
+            .to_json()
+            .to_string();
+        let partial = dir.join("checkpoint.json.partial");
+
+        let result = std::fs::create_dir_all(dir)
+            .and_then(|_| std::fs::write(&partial, json))
+            .and_then(|_| std::fs::rename(&partial, dir.join("checkpoint.json")));
+
+        if let Err(error) = result {
+            eprintln!(
+                "warning: cannot write checkpoint to {}, no more will be taken: {error}",
+                dir.display()
+            );
+            self.options.checkpoint_every = None;
+        }
+    }
+
+    /// Reads the next line of input without its line break, failing at the end of the input
+    fn read_line(&mut self, instruction: &str) -> Result<String, RuntimeError> {
+        let mut line = self.read_input(false)?;
+        if line.is_empty() {
+            bail!("{instruction}: no more input to read");
+        }
+
+        let trimmed = line.trim_end_matches(['\n', '\r']).len();
+        line.truncate(trimmed);
+        Ok(line)
+    }
+
+    /// Reads all of the input, or a line of it including the line break, unless the run is
+    /// a replay in which case the recorded text is returned instead
+    fn read_input(&mut self, whole: bool) -> Result<String, RuntimeError> {
+        self.out.flush().unwrap();
+
+        let text = match &mut self.replaying {
+            Some(events) => match events.pop_front() {
+                Some(ReplayEvent::Input(text)) => text,
+                other => bail!("The run no longer matches the replay: expected input but the next recorded event is {other:?}"),
+            },
+            None => {
+                let mut text = String::new();
+                let read = match (&mut self.input, whole) {
+                    (Some(source), true) => source.read_to_string(&mut text),
+                    (Some(source), false) => source.read_line(&mut text),
+                    (None, true) => std::io::stdin().read_to_string(&mut text),
+                    (None, false) => std::io::stdin().read_line(&mut text),
+                };
+
+                if let Err(error) = read {
+                    bail!("Cannot read from stdin: {error}");
+                }
+
+                text
+            }
+        };
+
+        if self.options.record.is_some() {
+            self.recorded.push(ReplayEvent::Input(text.clone()));
+        }
+
+        Ok(text)
+    }
+
+    /// Writes printed text to the output, stopping the program once it has printed more
+    /// than its output limit
+    fn write_output(&mut self, text: &str) -> Result<(), RuntimeError> {
+        let room = self
+            .options
+            .max_output_bytes
+            .map_or(text.len(), |limit| limit.saturating_sub(self.printed));
+
+        // Whatever fits is still written so the output shows where the program was when
+        // it was stopped
+        let kept = floor_char_boundary(text, room);
+        self.out.write_all(&text.as_bytes()[..kept]).unwrap();
+        self.printed += kept;
+
+        if kept < text.len() {
+            return Err(RuntimeError::OutputLimit {
+                limit: self.options.max_output_bytes.unwrap_or_default(),
+                truncated: text.len() - kept,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Nanoseconds since `start`, or the recorded reading when the run is a replay
+    fn elapsed(&mut self, start: Instant) -> Result<usize, RuntimeError> {
+        let nanos = match &mut self.replaying {
+            Some(events) => match events.pop_front() {
+                Some(ReplayEvent::Elapsed(nanos)) => nanos,
+                other => bail!("The run no longer matches the replay: expected a clock reading but the next recorded event is {other:?}"),
+            },
+            None => start.elapsed().as_nanos() as usize,
+        };
+
+        if self.options.record.is_some() {
+            self.recorded.push(ReplayEvent::Elapsed(nanos));
+        }
+
+        Ok(nanos)
+    }
+
+    /// Lets hooks know execution is stopping because of an error
+    fn report(&mut self, error: &RuntimeError) {
+        for hook in &mut self.hooks {
+            hook.on_error(error, &self.stack);
+        }
+    }
+
+    /// Executes instructions the host provided, such as a line entered in the repl,
+    /// returning whether they ended with an explicit `exit`
+    pub(crate) fn execute(
+        &mut self,
+        program_instructions: Vec<Instructions>,
+    ) -> Result<bool, RuntimeError> {
+        let mut execution = self.execution_of(program_instructions);
+        let exited = self.execute_for(&mut execution, usize::MAX)?;
+
+        Ok(exited.unwrap_or(false))
+    }
+
+    /// Executes at most `steps` instructions, returning `None` if the limit was reached
+    /// before the stream ended, or otherwise whether it ended with an explicit `exit`
+    fn execute_for(
+        &mut self,
+        execution: &mut Execution,
+        steps: usize,
+    ) -> Result<Option<bool>, RuntimeError> {
+        let mut result = self.execute_instructions(execution, steps);
+
+        // Point debug runs at where the values the failing instruction used came from
+        if let (true, Err(RuntimeError::Instruction(message))) = (self.options.debug, &mut result) {
+            if let Some(instruction) = execution.instructions.get(execution.ic) {
+                for origin in self.shadow.describe_operands(instruction) {
+                    message.push_str(&format!("\n  operand {origin}"));
+                }
+            }
+        }
+
+        if let Err(error) = &result {
+            self.error_context = Some(format!(
+                "{}stack: {}",
+                disassemble_around(&execution.instructions, execution.ic, 3),
+                self.stack.render()
+            ));
+            self.error_at = section_at(execution);
+            self.report(error);
+        }
+
+        result
+    }
+
+    fn execute_instructions(
+        &mut self,
+        execution: &mut Execution,
+        steps: usize,
+    ) -> Result<Option<bool>, RuntimeError> {
+        let Execution {
+            instructions: program_instructions,
+            ic,
+            starts,
+            calls,
+        } = execution;
+        let splices = self.splices();
+        let mut exited = false;
+
+        for _ in 0..steps {
+            // Reaching the end of a section returns from the call that ran it, or otherwise
+            // ends the program
+            loop {
+                match program_instructions.get(*ic) {
+                    None => {}
+                    Some(Instructions::EndSection(section)) if !splices && self.options.debug => {
+                        self.check_contracts(section, ContractKind::Post)?;
+                    }
+                    Some(Instructions::EndSection(_)) if !splices => {}
+                    Some(_) => break,
+                }
+
+                let Some((caller, return_to)) = calls.pop() else {
+                    return Ok(Some(exited));
+                };
+
+                if splices {
+                    *program_instructions = caller;
+                }
+                *ic = return_to;
+            }
+
+            if self.cancellation.is_cancelled() {
+                return Err(RuntimeError::Cancelled);
+            }
+
+            if !self.budgets.is_empty() {
+                self.check_budgets(calls.len())?;
+            }
+
+            if let Some(every) = self.options.checkpoint_every {
+                if every > 0 && self.steps.is_multiple_of(every) {
+                    self.write_checkpoint(program_instructions, *ic, calls);
+                }
+            }
+
+            if self.options.debugger {
+                let at = section_position(program_instructions, starts, *ic);
+                let paused = matches!(program_instructions[*ic], Instructions::Break)
+                    || self.debugger.should_pause(at.as_ref());
+
+                if paused && self.debug_prompt(program_instructions, *ic, starts, calls) {
+                    exited = true;
+                    break;
+                }
+            }
+
+            self.steps += 1;
+
+            let instruction = program_instructions[*ic].clone();
+
+            if self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                return Err(RuntimeError::Timeout {
+                    limit: self.options.timeout.unwrap_or_default(),
+                    instruction,
+                    stack: self.stack.to_vec(),
+                });
+            }
+
+            if let Some(metering) = &self.options.metering {
+                let spent = self
+                    .spent
+                    .checked_add(metering.cost(&instruction))
+                    .filter(|spent| *spent <= metering.budget);
+
+                let Some(spent) = spent else {
+                    return Err(RuntimeError::BudgetExhausted {
+                        budget: metering.budget,
+                        spent: self.spent,
+                        instruction,
+                    });
+                };
+
+                self.spent = spent;
+            }
+
+            for hook in &mut self.hooks {
+                hook.on_instruction(*ic, &instruction, &self.stack);
+            }
+
+            let before = self.stack.len();
+            let ran = (self.options.step || !self.hooks.is_empty()).then(|| instruction.clone());
+            // The values the instruction will take, so hooks can be told what changed
+            let taken = if self.hooks.is_empty() {
+                Vec::new()
+            } else {
+                self.stack[before - instruction.operands().min(before)..].to_vec()
+            };
+            // Jumps replace the current instruction with the target, which then runs next
+            let mut jumped = false;
+
+            match instruction {
+                Instructions::Push(value) => {
+                    self.stack.push(value);
+                }
+                Instructions::Add => {
+                    let (Some(a), Some(b)) = (self.stack.pop(), self.stack.pop()) else {
+                        bail!("Not enough values on the stack to add");
+                    };
+
+                    match (&a, &b) {
+                        (DataType::Int(a), DataType::Int(b)) => {
+                            let Some(result) = a.checked_add(*b) else {
+                                bail!("Integer overflow: {a} + {b} does not fit in an Int");
+                            };
+
+                            self.stack.push(DataType::Int(result));
+                        }
+                        (DataType::Float(a), DataType::Float(b)) => {
+                            self.stack.push(DataType::Float(a + b));
+                        }
+                        (DataType::String(a), DataType::String(b)) => {
+                            self.stack.push(DataType::String(format!("{b}{a}")));
+                        }
+                        (DataType::Matrix(_), _) | (_, DataType::Matrix(_)) => {
+                            let result = Matrix::elementwise(&a, &b, "add", |a, b| Some(a + b))
+                                .map_err(RuntimeError::Instruction)?;
+                            self.stack.push(DataType::Matrix(result));
+                        }
+                        (DataType::Complex(_), _) | (_, DataType::Complex(_)) => {
+                            let result = Complex::combine(&a, &b, "add", |a, b| Some(a + b))
+                                .map_err(RuntimeError::Instruction)?;
+                            self.stack.push(DataType::Complex(result));
+                        }
+                        _ => {
+                            bail!("Cannot add non-numeric values {:?} and {:?}", a, b);
+                        }
+                    }
+                }
+                Instructions::Sub => {
+                    let (Some(a), Some(b)) = (self.stack.pop(), self.stack.pop()) else {
+                        bail!("Not enough values on the stack to subtract");
+                    };
+
+                    match (&a, &b) {
+                        (DataType::Int(a), DataType::Int(b)) => {
+                            let Some(result) = a.checked_sub(*b) else {
+                                bail!("Integer overflow: {a} - {b} does not fit in an Int");
+                            };
+
+                            self.stack.push(DataType::Int(result));
+                        }
+                        (DataType::Float(a), DataType::Float(b)) => {
+                            self.stack.push(DataType::Float(a - b));
+                        }
+                        (DataType::Matrix(_), _) | (_, DataType::Matrix(_)) => {
+                            let result =
+                                Matrix::elementwise(&a, &b, "subtract", |a, b| Some(a - b))
+                                    .map_err(RuntimeError::Instruction)?;
+                            self.stack.push(DataType::Matrix(result));
+                        }
+                        (DataType::Complex(_), _) | (_, DataType::Complex(_)) => {
+                            let result = Complex::combine(&a, &b, "subtract", |a, b| Some(a - b))
+                                .map_err(RuntimeError::Instruction)?;
+                            self.stack.push(DataType::Complex(result));
+                        }
+                        _ => {
+                            bail!("Cannot subtract non-numeric values {:?} and {:?}", a, b);
+                        }
+                    }
+                }
+                Instructions::Mul => {
+                    let (Some(a), Some(b)) = (self.stack.pop(), self.stack.pop()) else {
+                        bail!("Not enough values on the stack to multiply");
+                    };
+
+                    match (&a, &b) {
+                        (DataType::Int(a), DataType::Int(b)) => {
+                            let Some(result) = a.checked_mul(*b) else {
+                                bail!("Integer overflow: {a} * {b} does not fit in an Int");
+                            };
+
+                            self.stack.push(DataType::Int(result));
+                        }
+                        (DataType::Float(a), DataType::Float(b)) => {
+                            self.stack.push(DataType::Float(a * b));
+                        }
+                        (DataType::Matrix(_), _) | (_, DataType::Matrix(_)) => {
+                            let result =
+                                Matrix::elementwise(&a, &b, "multiply", |a, b| Some(a * b))
+                                    .map_err(RuntimeError::Instruction)?;
+                            self.stack.push(DataType::Matrix(result));
+                        }
+                        (DataType::Complex(_), _) | (_, DataType::Complex(_)) => {
+                            let result = Complex::combine(&a, &b, "multiply", |a, b| Some(a * b))
+                                .map_err(RuntimeError::Instruction)?;
+                            self.stack.push(DataType::Complex(result));
+                        }
+                        _ => {
+                            bail!("Cannot multiply non-numeric values {:?} and {:?}", a, b);
+                        }
+                    }
+                }
+                Instructions::Div => {
+                    let (Some(a), Some(b)) = (self.stack.pop(), self.stack.pop()) else {
+                        bail!("Not enough values on the stack to divide");
+                    };
+
+                    match (&a, &b) {
+                        (DataType::Int(a), DataType::Int(b)) => {
+                            if b == &0 {
+                                bail!("Cannot divide by zero");
+                            }
+
+                            let Some(result) = a.checked_div(*b) else {
+                                bail!("Integer overflow: {a} / {b} does not fit in an Int");
+                            };
+
+                            self.stack.push(DataType::Int(result));
+                        }
+                        (DataType::Float(a), DataType::Float(b)) => {
+                            if b == &0.0 {
+                                bail!("Cannot divide by zero");
+                            }
+
+                            self.stack.push(DataType::Float(a / b));
+                        }
+                        (DataType::Matrix(_), _) | (_, DataType::Matrix(_)) => {
+                            let result = Matrix::elementwise(&a, &b, "divide", |a, b| {
+                                (b != 0.0).then_some(a / b)
+                            })
+                            .map_err(RuntimeError::Instruction)?;
+                            self.stack.push(DataType::Matrix(result));
+                        }
+                        (DataType::Complex(_), _) | (_, DataType::Complex(_)) => {
+                            let result = Complex::combine(&a, &b, "divide", |a, b| {
+                                (b != Complex::new(0.0, 0.0)).then(|| a / b)
+                            })
+                            .map_err(RuntimeError::Instruction)?;
+                            self.stack.push(DataType::Complex(result));
+                        }
+                        _ => {
+                            bail!("Cannot divide non-numeric values {:?} and {:?}", a, b);
+                        }
+                    };
+                }
+                Instructions::Vector(length) => {
+                    let vector = self.pop_matrix(1, length, "vector")?;
+                    self.stack.push(DataType::Matrix(vector));
+                }
+                Instructions::Matrix(rows, cols) => {
+                    let matrix = self.pop_matrix(rows, cols, "matrix")?;
+                    self.stack.push(DataType::Matrix(matrix));
+                }
+                Instructions::MGet => {
+                    let (
+                        Some(DataType::Int(col)),
+                        Some(DataType::Int(row)),
+                        Some(DataType::Matrix(matrix)),
+                    ) = (self.stack.pop(), self.stack.pop(), self.stack.pop())
+                    else {
+                        bail!("mget requires a Matrix and Int row and column indices on the stack");
+                    };
+
+                    let Some(value) = matrix.get(to_index(row), to_index(col)) else {
+                        bail!(
+                            "Element {row},{col} is outside the {}x{} matrix",
+                            matrix.rows(),
+                            matrix.cols()
+                        );
+                    };
+
+                    self.stack.push(DataType::Float(value));
+                }
+                Instructions::MatMul => {
+                    let (Some(DataType::Matrix(a)), Some(DataType::Matrix(b))) =
+                        (self.stack.pop(), self.stack.pop())
+                    else {
+                        bail!("matmul requires two matrices on the stack");
+                    };
+
+                    let product = a.matmul(&b).map_err(RuntimeError::Instruction)?;
+                    self.stack.push(DataType::Matrix(product));
+                }
+                Instructions::Complex => {
+                    let number = |value| match value {
+                        Some(DataType::Int(a)) => Some(a as f64),
+                        Some(DataType::Float(a)) => Some(a),
+                        _ => None,
+                    };
+
+                    let (Some(im), Some(re)) = (number(self.stack.pop()), number(self.stack.pop()))
+                    else {
+                        bail!("complex requires a real and an imaginary part on the stack, both Ints or Floats");
+                    };
+
+                    self.stack.push(DataType::Complex(Complex::new(re, im)));
+                }
+                Instructions::Re | Instructions::Im | Instructions::CAbs => {
+                    let Some(DataType::Complex(complex)) = self.stack.pop() else {
+                        bail!("{instruction} requires a Complex on the stack");
+                    };
+
+                    self.stack.push(DataType::Float(match instruction {
+                        Instructions::Re => complex.re,
+                        Instructions::Im => complex.im,
+                        _ => complex.abs(),
+                    }));
+                }
+                Instructions::Transpose => {
+                    let Some(DataType::Matrix(matrix)) = self.stack.pop() else {
+                        bail!("transpose requires a Matrix on the stack");
+                    };
+
+                    self.stack.push(DataType::Matrix(matrix.transpose()));
+                }
+                Instructions::Mod => {
+                    let (Some(a), Some(b)) = (self.stack.pop(), self.stack.pop()) else {
+                        bail!("Not enough values on the stack to modulo");
+                    };
+
+                    match (&a, &b) {
+                        (DataType::Int(a), DataType::Int(b)) => {
+                            if b == &0 {
+                                bail!("Cannot divide by zero");
+                            }
+
+                            // The remainder takes the sign of the dividend, like in Rust
+                            self.stack.push(DataType::Int(a.wrapping_rem(*b)));
+                        }
+                        _ => {
+                            bail!("Cannot modulo non-numeric values {:?} and {:?}", a, b);
+                        }
+                    }
+                }
+                Instructions::Dup => {
+                    let Some(a) = self.stack.last().cloned() else {
+                        bail!("Nothing to duplicate");
+                    };
+
+                    self.stack.push(a);
+                }
+                Instructions::Swap => {
+                    let (Some(a), Some(b)) = (self.stack.pop(), self.stack.pop()) else {
+                        bail!("Not enough values on the stack to swap");
+                    };
+
+                    self.stack.push(a);
+                    self.stack.push(b);
+                }
+                Instructions::Over => {
+                    let Some(b) = self
+                        .stack
+                        .len()
+                        .checked_sub(2)
+                        .and_then(|index| self.stack.get(index))
+                        .cloned()
+                    else {
+                        bail!("Not enough values on the stack to duplicate");
+                    };
+
+                    self.stack.push(b);
+                }
+                Instructions::Rot => {
+                    let (Some(a), Some(b), Some(c)) =
+                        (self.stack.pop(), self.stack.pop(), self.stack.pop())
+                    else {
+                        bail!("Not enough values on the stack to rotate");
+                    };
+
+                    self.stack.push(b);
+                    self.stack.push(a);
+                    self.stack.push(c);
+                }
+                Instructions::EQ => {
+                    let (Some(a), Some(b)) = (self.stack.pop(), self.stack.pop()) else {
+                        bail!("Not enough values on the stack to compare");
+                    };
+
+                    self.stack.push(DataType::Bool(a == b));
+                }
+                Instructions::NE => {
+                    let (Some(a), Some(b)) = (self.stack.pop(), self.stack.pop()) else {
+                        bail!("Not enough values on the stack to compare");
+                    };
+
+                    self.stack.push(DataType::Bool(a != b));
+                }
+                comparison @ (Instructions::LT
+                | Instructions::GT
+                | Instructions::LE
+                | Instructions::GE) => {
+                    let (Some(a), Some(b)) = (self.stack.pop(), self.stack.pop()) else {
+                        bail!("Not enough values on the stack to compare");
+                    };
+
+                    // NaN is not ordered against anything, so every comparison with it fails
+                    let ordering = match (&a, &b) {
+                        (DataType::Int(a), DataType::Int(b)) => a.partial_cmp(b),
+                        (DataType::Float(a), DataType::Float(b)) => a.partial_cmp(b),
+                        _ => bail!(
+                            "{comparison} requires two Ints or two Floats, found {} and {}",
+                            a.type_name(),
+                            b.type_name()
+                        ),
+                    };
+
+                    let result = ordering.is_some_and(|ordering| match comparison {
+                        Instructions::LT => ordering.is_lt(),
+                        Instructions::GT => ordering.is_gt(),
+                        Instructions::LE => ordering.is_le(),
+                        _ => ordering.is_ge(),
+                    });
+
+                    self.stack.push(DataType::Bool(result));
+                }
+                Instructions::And => {
+                    let (Some(a), Some(b)) = (self.stack.pop(), self.stack.pop()) else {
+                        bail!("Not enough values on the stack to compare");
+                    };
+
+                    match (&a, &b) {
+                        (DataType::Bool(a), DataType::Bool(b)) => {
+                            self.stack.push(DataType::Bool(*a && *b));
+                        }
+                        _ => {
+                            bail!("Cannot compare non-boolean values {:?} and {:?}", a, b);
+                        }
+                    }
+                }
+                Instructions::Or => {
+                    let (Some(a), Some(b)) = (self.stack.pop(), self.stack.pop()) else {
+                        bail!("Not enough values on the stack to compare");
+                    };
+
+                    match (&a, &b) {
+                        (DataType::Bool(a), DataType::Bool(b)) => {
+                            self.stack.push(DataType::Bool(*a || *b));
+                        }
+                        _ => {
+                            bail!("Cannot compare non-boolean values {:?} and {:?}", a, b);
+                        }
+                    }
+                }
+                Instructions::Not => {
+                    let Some(a) = self.stack.pop() else {
+                        bail!("Not enough values on the stack to compare");
+                    };
+
+                    match a {
+                        DataType::Bool(a) => self.stack.push(DataType::Bool(!a)),
+                        _ => {
+                            bail!("Cannot compare non-boolean value {:?}", a);
+                        }
+                    }
+                }
+                Instructions::Drop => {
+                    self.stack.pop();
+                }
+                Instructions::Exit => {
+                    exited = true;
+                    break;
+                }
+                Instructions::Jump(label) => {
+                    self.jump(&label, program_instructions, ic, starts)?;
+                    jumped = true;
+                }
+                Instructions::Call(label) => {
+                    if calls.len() >= MAX_CALL_DEPTH {
+                        bail!(
+                            "Call stack overflow: calls are nested more than {MAX_CALL_DEPTH} deep"
+                        );
+                    }
+
+                    if splices {
+                        let callee = self.enter(&label)?;
+                        let caller = std::mem::replace(program_instructions, callee);
+                        calls.push((caller, *ic + 1));
+                        *ic = 0;
+                    } else {
+                        let start = self.enter_at(&label, starts)?;
+                        calls.push((Vec::new(), *ic + 1));
+                        *ic = start;
+                    }
+                    jumped = true;
+                }
+                Instructions::Ret => {
+                    let Some((caller, return_to)) = calls.pop() else {
+                        bail!("ret outside of a call");
+                    };
+
+                    // The end of the section is marked in debug runs, and its #post
+                    // contracts hold for an early return too
+                    let end =
+                        program_instructions[*ic..].iter().find_map(
+                            |instruction| match instruction {
+                                Instructions::EndSection(section) => Some(section),
+                                _ => None,
+                            },
+                        );
+
+                    if let (true, Some(section)) = (self.options.debug, end) {
+                        self.check_contracts(section, ContractKind::Post)?;
+                    }
+
+                    if splices {
+                        *program_instructions = caller;
+                    }
+                    *ic = return_to;
+                    jumped = true;
+                }
+                Instructions::EndSection(section) => {
+                    self.check_contracts(&section, ContractKind::Post)?;
+                }
+                Instructions::Switch(group) => {
+                    let states = self.program.iter().find_map(|states| match states {
+                        Program::States(other, states) if *other == group => Some(states),
+                        _ => None,
+                    });
+
+                    let Some(states) = states else {
+                        bail!("Unknown states group: {group}");
+                    };
+
+                    let Some(DataType::Int(state)) = self.stack.pop() else {
+                        bail!("switch {group} requires an Int state on the stack");
+                    };
+
+                    let Some(label) = states.get(to_index(state)).cloned() else {
+                        bail!("{state} is not a state of {group}");
+                    };
+
+                    self.jump(&label, program_instructions, ic, starts)?;
+                    jumped = true;
+                }
+                Instructions::IfJmp(label) => {
+                    let Some(a) = self.stack.pop() else {
+                        bail!("Not enough values on the stack to compare");
+                    };
+
+                    let should_jump = match a {
+                        DataType::Bool(a) => a,
+                        DataType::Int(a) => a == 0,
+                        _ => {
+                            bail!("Cannot compare non-numeric values {:?}", a);
+                        }
+                    };
+
+                    if should_jump {
+                        self.jump(&label, program_instructions, ic, starts)?;
+                        jumped = true;
+                    }
+                }
+                Instructions::Trace(message) => match self.stack.last() {
+                    Some(top) => eprintln!("[trace] {message}: {:?}", top),
+                    None => eprintln!("[trace] {message}: <empty stack>"),
+                },
+                // The debugger has already paused before it
+                Instructions::Break => {}
+                Instructions::Log(level) => {
+                    let Some(DataType::String(message)) = self.stack.pop() else {
+                        bail!("log requires a string on the stack");
+                    };
+
+                    self.options.logger.log(level, &message);
+                }
+                Instructions::Tick => {
+                    self.stopwatches.push(now("tick")?);
+                }
+                Instructions::Tock => {
+                    let Some(start) = self.stopwatches.pop() else {
+                        bail!("tock without a matching tick");
+                    };
+
+                    let nanos = self.elapsed(start)?;
+                    self.stack.push(DataType::Int(nanos as i64));
+                }
+                Instructions::Frame(fps) => {
+                    if !self.options.frames {
+                        bail!("frame is disabled in this run");
+                    }
+
+                    // Whatever the frame drew is shown before waiting for the next one
+                    self.out.flush().unwrap();
+
+                    let now = now("frame")?;
+                    let period = Duration::from_secs(1) / fps;
+
+                    // Replays run as fast as they can, and a frame that overran starts the
+                    // next one straight away rather than rushing later frames to catch up
+                    self.frame_start = Some(match self.frame_start {
+                        Some(start) if self.replaying.is_none() && now < start + period => {
+                            std::thread::sleep(start + period - now);
+                            start + period
+                        }
+                        _ => now,
+                    });
+                }
+                Instructions::Tone => {
+                    let (Some(DataType::Int(duration)), Some(DataType::Int(frequency))) =
+                        (self.stack.pop(), self.stack.pop())
+                    else {
+                        bail!("tone requires an Int frequency and an Int duration on the stack");
+                    };
+
+                    let (Ok(frequency), Ok(duration)) =
+                        (usize::try_from(frequency), u64::try_from(duration))
+                    else {
+                        bail!("tone requires a frequency and a duration that are not negative");
+                    };
+
+                    let Some(speaker) = &self.options.speaker else {
+                        bail!("tone is not allowed, sound is disabled for this run");
+                    };
+
+                    speaker.tone(frequency, Duration::from_millis(duration));
+                }
+                Instructions::MemInfo => {
+                    // Nothing is heap allocated outside the stack, globals and variables yet
+                    let depth = self.stack.len();
+                    let heap: usize = self
+                        .stack
+                        .iter()
+                        .chain(self.globals.values())
+                        .chain(self.variables.values())
+                        .map(DataType::heap_cells)
+                        .sum();
+                    let names = self.globals.len() + self.variables.len();
+                    self.stack.push(DataType::Int(depth as i64));
+                    self.stack.push(DataType::Int(heap as i64));
+                    self.stack.push(DataType::Int(names as i64));
+                }
+                Instructions::PushData(name) => {
+                    let text = self.program.iter().find_map(|section| match section {
+                        Program::Data(other, text) if other.0 == name => Some(text.clone()),
+                        _ => None,
+                    });
+
+                    let Some(text) = text else {
+                        bail!("Unknown data section: {name}");
+                    };
+
+                    self.stack.push(DataType::String(text));
+                }
+                Instructions::GetConst(name, index) => {
+                    let values = self.program.iter().find_map(|constant| match constant {
+                        Program::Const(other, values) if *other == name => Some(values),
+                        _ => None,
+                    });
+
+                    let Some(values) = values else {
+                        bail!("Unknown const: {name}");
+                    };
+
+                    let index = match index {
+                        Some(index) => index as i64,
+                        None => match self.stack.pop() {
+                            Some(DataType::Int(index)) => index,
+                            _ => bail!("getconst {name} requires an Int index on the stack"),
+                        },
+                    };
+
+                    let Some(value) = values.get(to_index(index)) else {
+                        bail!(
+                            "Index {index} out of bounds for const {name} of length {}",
+                            values.len()
+                        );
+                    };
+
+                    self.stack.push(value.clone());
+                }
+                Instructions::Argc => {
+                    self.stack
+                        .push(DataType::Int(self.options.args.len() as i64));
+                }
+                Instructions::Argv(index) => {
+                    let index = match index {
+                        Some(index) => index as i64,
+                        None => match self.stack.pop() {
+                            Some(DataType::Int(index)) => index,
+                            _ => bail!("argv requires an Int index on the stack"),
+                        },
+                    };
+
+                    let Some(arg) = self.options.args.get(to_index(index)) else {
+                        let count = self.options.args.len();
+                        bail!(
+                            "Index {index} out of bounds for {count} argument{}",
+                            if count == 1 { "" } else { "s" }
+                        );
+                    };
+
+                    self.stack.push(DataType::String(arg.clone()));
+                }
+                Instructions::GetGlobal(name) => {
+                    let Some(value) = self.globals.get(&name) else {
+                        bail!("Unknown global: {name}");
+                    };
+
+                    self.stack.push(value.clone());
+                }
+                Instructions::SetGlobal(name) => {
+                    let Some(value) = self.stack.pop() else {
+                        bail!("Not enough values on the stack to set global {name}");
+                    };
+
+                    self.set_global(name, value);
+                }
+                Instructions::Store(name) => {
+                    let Some(value) = self.stack.pop() else {
+                        bail!("Not enough values on the stack to store {name}");
+                    };
+
+                    self.store(name, value);
+                }
+                Instructions::Load(name) => {
+                    let Some(value) = self.variables.get(&name) else {
+                        bail!("Unknown variable: {name}, store a value in it first");
+                    };
+
+                    self.stack.push(value.clone());
+                }
+                Instructions::Host { name, .. } => {
+                    let Some(function) = self
+                        .options
+                        .host_functions
+                        .iter()
+                        .find(|function| function.name == name)
+                    else {
+                        bail!("Unknown host function: {name}");
+                    };
+
+                    // Type errors are about the function's arguments, so name it
+                    (function.call)(&mut self.stack).map_err(|error| match error {
+                        RuntimeError::Instruction(message) => {
+                            RuntimeError::Instruction(format!("{name}: {message}"))
+                        }
+                        error => error,
+                    })?;
+                }
+                Instructions::Flush => {
+                    if let Err(error) = self.out.flush() {
+                        bail!("flush failed: {error}");
+                    }
+                }
+                Instructions::ByteAt => {
+                    let (Some(DataType::Int(index)), Some(DataType::Bytes(bytes))) =
+                        (self.stack.pop(), self.stack.pop())
+                    else {
+                        bail!("byteat requires Bytes and an Int index on the stack");
+                    };
+
+                    let Some(byte) = bytes.get(to_index(index)) else {
+                        bail!(
+                            "Byte index {index} out of bounds for length {}",
+                            bytes.len()
+                        );
+                    };
+
+                    self.stack.push(DataType::Int(i64::from(*byte)));
+                }
+                Instructions::ByteSlice => {
+                    let (
+                        Some(DataType::Int(end)),
+                        Some(DataType::Int(start)),
+                        Some(DataType::Bytes(bytes)),
+                    ) = (self.stack.pop(), self.stack.pop(), self.stack.pop())
+                    else {
+                        bail!(
+                            "byteslice requires Bytes and Int start and end indices on the stack"
+                        );
+                    };
+
+                    let Some(slice) = bytes.get(to_index(start)..to_index(end)) else {
+                        bail!(
+                            "Byte slice {start}..{end} out of bounds for length {}",
+                            bytes.len()
+                        );
+                    };
+
+                    self.stack.push(DataType::Bytes(slice.to_vec()));
+                }
+                Instructions::ByteLen => {
+                    let Some(DataType::Bytes(bytes)) = self.stack.pop() else {
+                        bail!("bytelen requires Bytes on the stack");
+                    };
+
+                    self.stack.push(DataType::Int(bytes.len() as i64));
+                }
+                Instructions::ToBytes => {
+                    let Some(DataType::String(a)) = self.stack.pop() else {
+                        bail!("tobytes requires a String on the stack");
+                    };
+
+                    self.stack.push(DataType::Bytes(a.into_bytes()));
+                }
+                Instructions::FromBytes => {
+                    let Some(DataType::Bytes(a)) = self.stack.pop() else {
+                        bail!("frombytes requires Bytes on the stack");
+                    };
+
+                    let Ok(a) = String::from_utf8(a) else {
+                        bail!("Bytes are not valid UTF-8");
+                    };
+
+                    self.stack.push(DataType::String(a));
+                }
+                Instructions::Len => {
+                    let length = match self.stack.pop() {
+                        Some(DataType::String(string)) => string.chars().count(),
+                        Some(DataType::List(items)) => items.len(),
+                        _ => bail!("len requires a String or a List on the stack"),
+                    };
+
+                    self.stack.push(DataType::Int(length as i64));
+                }
+                Instructions::Substr => {
+                    let (
+                        Some(DataType::Int(end)),
+                        Some(DataType::Int(start)),
+                        Some(DataType::String(string)),
+                    ) = (self.stack.pop(), self.stack.pop(), self.stack.pop())
+                    else {
+                        bail!(
+                            "substr requires a String and Int start and end indices on the stack"
+                        );
+                    };
+
+                    let length = string.chars().count();
+                    let (start, end) = (to_index(start), to_index(end));
+                    if start > end || end > length {
+                        bail!("Substring {start}..{end} out of bounds for length {length}");
+                    }
+
+                    let substring = string.chars().skip(start).take(end - start).collect();
+                    self.stack.push(DataType::String(substring));
+                }
+                Instructions::StrIndex => {
+                    let (Some(DataType::String(needle)), Some(DataType::String(string))) =
+                        (self.stack.pop(), self.stack.pop())
+                    else {
+                        bail!("strindex requires two Strings on the stack");
+                    };
+
+                    let index = string
+                        .find(&needle)
+                        .map_or(-1, |index| string[..index].chars().count() as i64);
+                    self.stack.push(DataType::Int(index));
+                }
+                Instructions::ToInt => {
+                    let value = match self.stack.pop() {
+                        Some(DataType::Int(a)) => a,
+                        Some(DataType::Float(a)) => {
+                            // `as` would saturate, and turn NaN into 0
+                            if !(a.trunc() >= i64::MIN as f64 && a.trunc() < i64::MAX as f64) {
+                                bail!("toint: {a:?} does not fit in an Int");
+                            }
+                            a as i64
+                        }
+                        Some(DataType::Bool(a)) => a as i64,
+                        Some(DataType::String(a)) => match a.trim().parse() {
+                            Ok(value) => value,
+                            Err(_) => bail!("toint: cannot convert {a:?} to an Int"),
+                        },
+                        Some(value) => {
+                            bail!("toint: cannot convert a {} to an Int", value.type_name())
+                        }
+                        None => bail!("toint requires a value on the stack"),
+                    };
+
+                    self.stack.push(DataType::Int(value));
+                }
+                Instructions::ToFloat => {
+                    let value = match self.stack.pop() {
+                        Some(DataType::Int(a)) => a as f64,
+                        Some(DataType::Float(a)) => a,
+                        Some(DataType::Bool(a)) => f64::from(u8::from(a)),
+                        Some(DataType::String(a)) => match a.trim().parse() {
+                            Ok(value) => value,
+                            Err(_) => bail!("tofloat: cannot convert {a:?} to a Float"),
+                        },
+                        Some(value) => {
+                            bail!("tofloat: cannot convert a {} to a Float", value.type_name())
+                        }
+                        None => bail!("tofloat requires a value on the stack"),
+                    };
+
+                    self.stack.push(DataType::Float(value));
+                }
+                Instructions::ToString => {
+                    let Some(value) = self.stack.pop() else {
+                        bail!("tostring requires a value on the stack");
+                    };
+
+                    self.stack.push(DataType::String(value.to_string()));
+                }
+                Instructions::ToBool => {
+                    let value = match self.stack.pop() {
+                        Some(DataType::Bool(a)) => a,
+                        Some(DataType::Int(a)) => a != 0,
+                        Some(DataType::Float(a)) => a != 0.0,
+                        Some(DataType::String(a)) => match a.trim() {
+                            "true" => true,
+                            "false" => false,
+                            _ => bail!("tobool: cannot convert {a:?} to a Bool"),
+                        },
+                        Some(value) => {
+                            bail!("tobool: cannot convert a {} to a Bool", value.type_name())
+                        }
+                        None => bail!("tobool requires a value on the stack"),
+                    };
+
+                    self.stack.push(DataType::Bool(value));
+                }
+                Instructions::List => self.stack.push(DataType::List(Vec::new())),
+                // Lists are changed where they are on the stack, so these take as long as
+                // the item they touch rather than the whole List
+                Instructions::Append => {
+                    let value = self.stack.pop();
+                    let (Some(value), Some(DataType::List(items))) = (value, self.stack.last_mut())
+                    else {
+                        bail!("append requires a List and a value on the stack");
+                    };
+
+                    let added = value.size();
+                    items.push(value);
+                    self.stack.resize_last(added, 0);
+                }
+                Instructions::Get => {
+                    let index = self.stack.pop();
+                    let (Some(DataType::Int(index)), Some(DataType::List(items))) =
+                        (index, self.stack.last())
+                    else {
+                        bail!("get requires a List and an Int index on the stack");
+                    };
+
+                    let Some(item) = items.get(to_index(index)).cloned() else {
+                        bail!(
+                            "List index {index} out of bounds for length {}",
+                            items.len()
+                        );
+                    };
+
+                    self.stack.push(item);
+                }
+                Instructions::Set => {
+                    let (value, index) = (self.stack.pop(), self.stack.pop());
+                    let (Some(value), Some(DataType::Int(index)), Some(DataType::List(items))) =
+                        (value, index, self.stack.last_mut())
+                    else {
+                        bail!("set requires a List, an Int index and a value on the stack");
+                    };
+
+                    let length = items.len();
+                    let Some(item) = items.get_mut(to_index(index)) else {
+                        bail!("List index {index} out of bounds for length {length}");
+                    };
+
+                    let (added, removed) = (value.size(), item.size());
+                    *item = value;
+                    self.stack.resize_last(added, removed);
+                }
+                Instructions::Pop => {
+                    let Some(DataType::List(items)) = self.stack.last_mut() else {
+                        bail!("pop requires a List on the stack");
+                    };
+
+                    let Some(item) = items.pop() else {
+                        bail!("pop requires a List with at least one item");
+                    };
+
+                    self.stack.resize_last(0, item.size());
+                    self.stack.push(item);
+                }
+                Instructions::FOpen => {
+                    let (Some(DataType::String(mode)), Some(DataType::String(path))) =
+                        (self.stack.pop(), self.stack.pop())
+                    else {
+                        bail!("fopen requires a String path and mode on the stack");
+                    };
+
+                    if !self.options.files {
+                        bail!("Cannot open file {path}: file access is disabled");
+                    }
+
+                    let mut open_options = OpenOptions::new();
+
+                    for flag in mode.chars() {
+                        match flag {
+                            'r' => open_options.read(true),
+                            'w' => open_options.write(true).create(true).truncate(true),
+                            'a' => open_options.append(true).create(true),
+                            '+' => open_options.read(true).write(true),
+                            _ => bail!("Unknown file mode flag: {flag}"),
+                        };
+                    }
+
+                    let file = match open_options.open(&path) {
+                        Ok(file) => file,
+                        Err(error) => bail!("Cannot open file {path}: {error}"),
+                    };
+
+                    self.files.insert(self.next_handle, (path, file));
+                    self.stack.push(DataType::Handle(self.next_handle));
+                    self.next_handle += 1;
+                }
+                Instructions::FReadN => {
+                    let (Some(DataType::Int(count)), Some(DataType::Handle(handle))) =
+                        (self.stack.pop(), self.stack.pop())
+                    else {
+                        bail!("freadn requires a Handle and an Int count on the stack");
+                    };
+
+                    let Some((path, file)) = self.files.get_mut(&handle) else {
+                        bail!("File handle {handle} is not open");
+                    };
+
+                    let Ok(count) = u64::try_from(count) else {
+                        bail!("freadn requires a count that is not negative, found {count}");
+                    };
+
+                    let mut buffer = Vec::new();
+                    if let Err(error) = file.take(count).read_to_end(&mut buffer) {
+                        bail!("Cannot read from file {path}: {error}");
+                    }
+
+                    self.stack.push(DataType::Bytes(buffer));
+                }
+                Instructions::FWriteH => {
+                    let (Some(data), Some(DataType::Handle(handle))) =
+                        (self.stack.pop(), self.stack.pop())
+                    else {
+                        bail!("fwriteh requires a Handle and data on the stack");
+                    };
+
+                    let Some((path, file)) = self.files.get_mut(&handle) else {
+                        bail!("File handle {handle} is not open");
+                    };
+
+                    let bytes = match data {
+                        DataType::String(a) => a.into_bytes(),
+                        DataType::Bytes(a) => a,
+                        _ => bail!("Cannot write non-string value {:?} to a file", data),
+                    };
+
+                    if let Err(error) = file.write_all(&bytes) {
+                        bail!("Cannot write to file {path}: {error}");
+                    }
+                }
+                Instructions::FSeek => {
+                    let (Some(DataType::Int(offset)), Some(DataType::Handle(handle))) =
+                        (self.stack.pop(), self.stack.pop())
+                    else {
+                        bail!("fseek requires a Handle and an Int offset on the stack");
+                    };
+
+                    let Some((path, file)) = self.files.get_mut(&handle) else {
+                        bail!("File handle {handle} is not open");
+                    };
+
+                    let Ok(offset) = u64::try_from(offset) else {
+                        bail!("fseek requires an offset that is not negative, found {offset}");
+                    };
+
+                    if let Err(error) = file.seek(SeekFrom::Start(offset)) {
+                        bail!("Cannot seek in file {path}: {error}");
+                    }
+                }
+                Instructions::FClose => {
+                    let Some(DataType::Handle(handle)) = self.stack.pop() else {
+                        bail!("fclose requires a Handle on the stack");
+                    };
+
+                    if self.files.remove(&handle).is_none() {
+                        bail!("File handle {handle} is not open");
+                    }
+                }
+                Instructions::CNew => {
+                    let (Some(DataType::Int(height)), Some(DataType::Int(width))) =
+                        (self.stack.pop(), self.stack.pop())
+                    else {
+                        bail!("cnew requires an Int width and an Int height on the stack");
+                    };
+
+                    let sides = 1..=MAX_SIDE as i64;
+                    if !sides.contains(&width) || !sides.contains(&height) {
+                        bail!("Canvas of {width}x{height} pixels is not between 1x1 and {MAX_SIDE}x{MAX_SIDE}");
+                    }
+
+                    let (width, height) = (width as usize, height as usize);
+
+                    // Checked before the pixels are allocated, since the check after the
+                    // instruction would be too late to stop a canvas over the limit
+                    if let Some(limit) = self.options.max_memory {
+                        let replaced = self.canvas.as_ref().map_or(0, Canvas::bytes);
+                        let used = width
+                            .checked_mul(height)
+                            .and_then(|pixels| pixels.checked_mul(3))
+                            .and_then(|bytes| bytes.checked_add(self.memory_used() - replaced))
+                            .unwrap_or(usize::MAX);
+
+                        if used > limit {
+                            return Err(RuntimeError::OutOfMemory { used, limit });
+                        }
+                    }
+
+                    self.canvas = Some(Canvas::new(width, height));
+                    self.turtle = Some(Turtle::new(width, height));
+                }
+                Instructions::CSet => {
+                    let (
+                        Some(DataType::Int(color)),
+                        Some(DataType::Int(y)),
+                        Some(DataType::Int(x)),
+                    ) = (self.stack.pop(), self.stack.pop(), self.stack.pop())
+                    else {
+                        bail!(
+                            "cset requires Int x and y coordinates and an Int color on the stack"
+                        );
+                    };
+
+                    let Some(canvas) = &mut self.canvas else {
+                        bail!("cset requires a canvas, create one with cnew first");
+                    };
+
+                    let Ok(color @ 0..=0xff_ffff) = u32::try_from(color) else {
+                        bail!("Color {color:#x} is not of the form 0xRRGGBB");
+                    };
+
+                    if !canvas.set(to_index(x), to_index(y), color) {
+                        bail!("Pixel {x},{y} is outside the canvas");
+                    }
+                }
+                Instructions::CSave => self.save_canvas("csave")?,
+                Instructions::TSave => self.save_canvas("tsave")?,
+                Instructions::Forward => {
+                    let Some(DataType::Int(distance)) = self.stack.pop() else {
+                        bail!("forward requires an Int distance on the stack");
+                    };
+
+                    let (Some(canvas), Some(turtle)) = (&mut self.canvas, &mut self.turtle) else {
+                        bail!("forward requires a canvas, create one with cnew first");
+                    };
+
+                    turtle.forward(distance, canvas);
+                }
+                Instructions::Turn => {
+                    let Some(DataType::Int(degrees)) = self.stack.pop() else {
+                        bail!("turn requires an Int number of degrees on the stack");
+                    };
+
+                    self.turtle("turn")?.turn(degrees);
+                }
+                Instructions::PenUp => self.turtle("penup")?.pen(false),
+                Instructions::PenDown => self.turtle("pendown")?.pen(true),
+                Instructions::ReadAll => {
+                    let input = self.read_input(true)?;
+                    self.stack.push(DataType::String(input));
+                }
+                Instructions::ReadLine => {
+                    let mut line = self.read_input(false)?;
+                    let read = !line.is_empty();
+
+                    let trimmed = line.trim_end_matches(['\n', '\r']).len();
+                    line.truncate(trimmed);
+
+                    self.stack.push(DataType::String(line));
+                    self.stack.push(DataType::Bool(read));
+                }
+                Instructions::Read => {
+                    let line = self.read_line("read")?;
+                    self.stack.push(DataType::String(line));
+                }
+                Instructions::ReadInt => {
+                    let line = self.read_line("readint")?;
+                    let Ok(value) = line.trim().parse() else {
+                        bail!("readint: cannot read {line:?} as an Int");
+                    };
+
+                    self.stack.push(DataType::Int(value));
+                }
+                Instructions::ReadFloat => {
+                    let line = self.read_line("readfloat")?;
+                    let Ok(value) = line.trim().parse() else {
+                        bail!("readfloat: cannot read {line:?} as a Float");
+                    };
+
+                    self.stack.push(DataType::Float(value));
+                }
+                Instructions::CsvParse => {
+                    let Some(DataType::String(a)) = self.stack.pop() else {
+                        bail!("csvparse requires a String on the stack");
+                    };
+
+                    let rows = match parse_csv(&a) {
+                        Ok(rows) => rows,
+                        Err(error) => bail!("csvparse: {error}"),
+                    };
+
+                    let rows = rows
+                        .into_iter()
+                        .map(|row| DataType::List(row.into_iter().map(DataType::String).collect()))
+                        .collect();
+
+                    self.stack.push(DataType::List(rows));
+                }
+                Instructions::CsvEmit => {
+                    let Some(DataType::List(rows)) = self.stack.pop() else {
+                        bail!("csvemit requires a List of rows on the stack");
+                    };
+
+                    let text = match emit_csv(&rows) {
+                        Ok(text) => text,
+                        Err(error) => bail!("{error}"),
+                    };
+
+                    self.stack.push(DataType::String(text));
+                }
+                Instructions::SetFmt => {
+                    let Some(DataType::String(spec)) = self.stack.pop() else {
+                        bail!("setfmt requires a String format on the stack");
+                    };
+
+                    self.print_format = match PrintFormat::parse(&spec) {
+                        Ok(format) => format,
+                        Err(error) => bail!("Invalid print format {spec}: {error}"),
+                    };
+                }
+                Instructions::Print(format) => {
+                    if self.stack.is_empty() {
+                        bail!("Nothing to print");
+                    }
+
+                    let value = self.stack.pop().unwrap();
+
+                    for hook in &mut self.hooks {
+                        hook.on_print(&value);
+                    }
+
+                    let text = format.as_ref().unwrap_or(&self.print_format).apply(&value);
+                    self.write_output(&text)?;
+                }
+                Instructions::PrintTable => {
+                    let Some(DataType::List(rows)) = self.stack.pop() else {
+                        bail!("printtable requires a List of rows on the stack");
+                    };
+
+                    let mut cells = Vec::with_capacity(rows.len());
+                    for row in &rows {
+                        let DataType::List(row) = row else {
+                            bail!("printtable requires every row to be a List, got {row:?}");
+                        };
+                        cells.push(row.as_slice());
+                    }
+
+                    let text = render_table(&cells);
+
+                    let value = DataType::List(rows);
+                    for hook in &mut self.hooks {
+                        hook.on_print(&value);
+                    }
+
+                    self.write_output(&text)?;
+                }
+            }
+
+            if let (true, Some(ran)) = (self.options.debug, &ran) {
+                self.shadow.track(&self.stack, *ic, ran, before);
+            }
+
+            if let (false, Some(ran)) = (self.hooks.is_empty(), &ran) {
+                let base = (before - taken.len()).min(self.stack.len());
+                let diff = StackDiff::between(&taken, &self.stack[base..]);
+
+                for hook in &mut self.hooks {
+                    hook.on_stack_change(*ic, ran, &diff);
+                }
+            }
+
+            if let (true, Some(ran)) = (self.options.step, &ran) {
+                if self.pause(&format!("{ic} | {ran}")) {
+                    exited = true;
+                    break;
+                }
+            }
+
+            self.peak_depth = self.peak_depth.max(self.stack.len());
+
+            if let Some(limit) = self.options.max_memory {
+                let used = self.memory_used();
+
+                if used > limit {
+                    return Err(RuntimeError::OutOfMemory { used, limit });
+                }
+            }
+
+            if !jumped {
+                *ic += 1;
+            }
+        }
+
+        // The limit was reached, unless the last instruction happened to end the program
+        let ended = match program_instructions.get(*ic) {
+            None => true,
+            Some(Instructions::EndSection(_)) => !splices,
+            Some(_) => false,
+        };
+
+        if exited || (ended && calls.is_empty()) {
+            Ok(Some(exited))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// The current time, for `what` to measure it with. `wasm32-unknown-unknown` has no clock,
+/// and reading the time there panics instead of failing
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+fn now(_what: &str) -> Result<Instant, RuntimeError> {
+    Ok(Instant::now())
+}
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+fn now(what: &str) -> Result<Instant, RuntimeError> {
+    bail!("{what} needs a clock, which this platform does not have")
+}
+
+/// Turns an Int into an index. Negative Ints are never valid, so they become an index past
+/// the end of anything
+fn to_index(value: i64) -> usize {
+    usize::try_from(value).unwrap_or(usize::MAX)
+}
+
+/// Lays out the sections of a program one after another, each followed by a marker of its
+/// end, returning the instructions and where each section starts
+fn flatten(program: &[Program]) -> (Vec<Instructions>, HashMap<String, usize>) {
+    let mut instructions = Vec::new();
+    let mut starts = HashMap::new();
+
+    for item in program {
+        let Program::Section(name, section) = item else {
+            continue;
+        };
+
+        starts.insert(name.0.clone(), instructions.len());
+        instructions.extend(section.iter().cloned());
+        instructions.push(Instructions::EndSection(name.0.clone()));
+    }
+
+    (instructions, starts)
+}
+
+/// The section the instruction at the current position belongs to and its position
+/// within it, if it is part of the program laid out by [`flatten`]
+fn section_at(execution: &Execution) -> Option<(String, usize)> {
+    section_position(&execution.instructions, &execution.starts, execution.ic)
+}
+
+/// The section the instruction at `ic` belongs to and its position within it
+fn section_position(
+    instructions: &[Instructions],
+    starts: &HashMap<String, usize>,
+    ic: usize,
+) -> Option<(String, usize)> {
+    let (name, start) = starts
+        .iter()
+        .filter(|(_, start)| **start <= ic)
+        .max_by_key(|(_, start)| **start)?;
+
+    // Past the end of the section are the instructions the host ran directly
+    let ended = instructions
+        .get(*start..ic)?
+        .iter()
+        .any(|instruction| matches!(instruction, Instructions::EndSection(_)));
+
+    (!ended).then(|| (name.clone(), ic - start))
+}
+
+/// Lists the instructions within `radius` of `index`, marking the one at `index`
+fn disassemble_around(instructions: &[Instructions], index: usize, radius: usize) -> String {
+    let start = index.saturating_sub(radius);
+    let end = (index + radius + 1).min(instructions.len());
+    let width = end.to_string().len();
+
+    (start..end)
+        .map(|i| {
+            let marker = if i == index { ">" } else { " " };
+            format!("{marker} {i:>width$} | {}\n", instructions[i])
+        })
+        .collect()
+}
+
+/// The largest length of at most `length` bytes that does not split a character of `text`
+fn floor_char_boundary(text: &str, length: usize) -> usize {
+    if length >= text.len() {
+        return text.len();
+    }
+
+    (0..=length)
+        .rev()
+        .find(|&index| text.is_char_boundary(index))
+        .unwrap_or(0)
+}
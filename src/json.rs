@@ -0,0 +1,365 @@
+//! A small JSON reader and writer, enough for the tool formats the interpreter produces and
+//! consumes (checkpoints, reports and editor integration).
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// Keys keep their order so output is stable
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Builds an object from key-value pairs
+    pub fn object<K: Into<String>>(entries: impl IntoIterator<Item = (K, Json)>) -> Json {
+        Json::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.into(), value))
+                .collect(),
+        )
+    }
+
+    /// Looks up a key of an object
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(entries) => entries
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The number as a non-negative integer, if it is one
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Number(value) if *value >= 0.0 && value.fract() == 0.0 => Some(*value as usize),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, Json)]> {
+        match self {
+            Json::Object(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// Parses a JSON document
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = JsonParser {
+            text: text.as_bytes(),
+            position: 0,
+        };
+
+        let value = parser.value()?;
+        parser.whitespace();
+
+        if parser.position != parser.text.len() {
+            return Err(parser.error("trailing characters"));
+        }
+
+        Ok(value)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::String(value)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<f64> for Json {
+    fn from(value: f64) -> Self {
+        Json::Number(value)
+    }
+}
+
+impl fmt::Display for Json {
+    /// Writes compact JSON
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{value}"),
+            Json::Number(value) if value.is_finite() => write!(f, "{value}"),
+            // JSON has no representation for NaN or infinities
+            Json::Number(_) => write!(f, "null"),
+            Json::String(value) => write_string(f, value),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// Quotes and escapes a string
+fn write_string(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    write!(f, "\"")?;
+
+    for c in value.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{c}")?,
+        }
+    }
+
+    write!(f, "\"")
+}
+
+struct JsonParser<'a> {
+    text: &'a [u8],
+    position: usize,
+}
+
+impl JsonParser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("Invalid JSON at byte {}: {message}", self.position)
+    }
+
+    fn whitespace(&mut self) {
+        while self
+            .text
+            .get(self.position)
+            .is_some_and(|c| c.is_ascii_whitespace())
+        {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), String> {
+        if self.text[self.position..].starts_with(literal.as_bytes()) {
+            self.position += literal.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {literal}")))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.whitespace();
+
+        match self.text.get(self.position) {
+            Some(b'n') => self.expect("null").map(|_| Json::Null),
+            Some(b't') => self.expect("true").map(|_| Json::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'[') => {
+                self.position += 1;
+                let mut values = Vec::new();
+
+                self.whitespace();
+                if self.text.get(self.position) == Some(&b']') {
+                    self.position += 1;
+                    return Ok(Json::Array(values));
+                }
+
+                loop {
+                    values.push(self.value()?);
+                    self.whitespace();
+
+                    match self.text.get(self.position) {
+                        Some(b',') => self.position += 1,
+                        Some(b']') => {
+                            self.position += 1;
+                            return Ok(Json::Array(values));
+                        }
+                        _ => return Err(self.error("expected , or ]")),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.position += 1;
+                let mut entries = Vec::new();
+
+                self.whitespace();
+                if self.text.get(self.position) == Some(&b'}') {
+                    self.position += 1;
+                    return Ok(Json::Object(entries));
+                }
+
+                loop {
+                    self.whitespace();
+                    let key = self.string()?;
+                    self.whitespace();
+                    self.expect(":")?;
+                    entries.push((key, self.value()?));
+                    self.whitespace();
+
+                    match self.text.get(self.position) {
+                        Some(b',') => self.position += 1,
+                        Some(b'}') => {
+                            self.position += 1;
+                            return Ok(Json::Object(entries));
+                        }
+                        _ => return Err(self.error("expected , or }")),
+                    }
+                }
+            }
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.position;
+
+        while self
+            .text
+            .get(self.position)
+            .is_some_and(|c| matches!(c, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+        {
+            self.position += 1;
+        }
+
+        std::str::from_utf8(&self.text[start..self.position])
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .map(Json::Number)
+            .ok_or_else(|| self.error("invalid number"))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+        let mut bytes = Vec::new();
+
+        loop {
+            let Some(&c) = self.text.get(self.position) else {
+                return Err(self.error("unterminated string"));
+            };
+            self.position += 1;
+
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let Some(&escape) = self.text.get(self.position) else {
+                        return Err(self.error("unterminated string"));
+                    };
+                    self.position += 1;
+
+                    match escape {
+                        b'"' => bytes.push(b'"'),
+                        b'\\' => bytes.push(b'\\'),
+                        b'/' => bytes.push(b'/'),
+                        b'b' => bytes.push(0x08),
+                        b'f' => bytes.push(0x0c),
+                        b'n' => bytes.push(b'\n'),
+                        b'r' => bytes.push(b'\r'),
+                        b't' => bytes.push(b'\t'),
+                        b'u' => {
+                            let c = self.unicode_escape()?;
+                            let mut buffer = [0; 4];
+                            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    }
+                }
+                c => bytes.push(c),
+            }
+        }
+
+        String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8"))
+    }
+
+    /// Reads the digits of a `\u` escape, combining surrogate pairs
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+
+        let code = if (0xd800..0xdc00).contains(&high) {
+            self.expect("\\u")?;
+            let low = self.hex4()?;
+            0x10000 + ((high - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff)
+        } else {
+            high
+        };
+
+        char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = self
+            .text
+            .get(self.position..self.position + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+
+        self.position += 4;
+        Ok(digits)
+    }
+}
@@ -0,0 +1,95 @@
+//! A small stack based programming language.
+//!
+//! Programs are parsed into sections with [`parse`], checked with [`validate`] and then
+//! executed by an [`Interpreter`], which can also be driven section by section by a host
+//! application.
+//!
+//! ```
+//! use toylang::{parse, validate, Interpreter, RunOptions, Value};
+//!
+//! let options = RunOptions::default();
+//! let program = parse("::main:\npush 2\npush 3\nadd\n", &options);
+//! validate(&program);
+//!
+//! let mut interpreter = Interpreter::new(program, options);
+//! let stack = interpreter.call_section("main", Vec::new());
+//! assert_eq!(stack, Ok(vec![Value::Int(5)]));
+//! ```
+
+mod analysis;
+pub mod bytecode;
+mod cancellation;
+mod canvas;
+pub mod check;
+mod checkpoint;
+mod complex;
+pub mod conformance;
+mod contract;
+mod convert;
+mod csv;
+pub mod debugger;
+pub mod dependencies;
+pub mod depth_chart;
+mod error;
+pub mod explain;
+mod format;
+pub mod highlight;
+mod hooks;
+mod host;
+pub mod index;
+mod instructions;
+mod interpreter;
+pub mod json;
+pub mod lint;
+mod log;
+pub mod lsp;
+pub mod manifest;
+mod matrix;
+mod metering;
+pub mod minimize;
+pub mod mmap;
+pub mod pack;
+mod parser;
+pub mod refactor;
+pub mod reference;
+pub mod repl;
+mod replay;
+mod sandbox;
+mod shadow;
+mod sound;
+mod stack;
+pub mod stats;
+pub mod test_report;
+pub mod trace;
+mod turtle;
+mod value;
+mod version;
+
+pub use cancellation::CancellationToken;
+pub use checkpoint::Checkpoint;
+pub use complex::Complex;
+pub use contract::{Comparison, Contract, ContractKind};
+pub use convert::{FromStack, ToStack};
+pub use error::{ParseError, RuntimeError, SourceLocation};
+pub use format::{Align, PrintFormat};
+pub use hooks::InterpreterHooks;
+pub use host::HostFunction;
+pub use instructions::Instructions;
+pub use interpreter::{Compat, Interpreter, RunOptions, RunStatus};
+pub use log::{LogLevel, Logger, StderrLogger};
+pub use matrix::Matrix;
+pub use metering::Metering;
+pub use parser::{
+    fallthrough_warnings, parse, parse_reader, try_parse, try_parse_reader, try_validate, validate,
+    Parser, Program, SectionName,
+};
+pub use replay::{Replay, ReplayEvent};
+pub use sandbox::{run_sandboxed, run_source, Limits, SandboxResult, SharedBuffer};
+pub use sound::{Speaker, TerminalBell};
+pub use stack::{Stack, StackDiff};
+pub use toylang_macros::toy_fn;
+pub use value::DataType;
+pub use version::LanguageVersion;
+
+/// The values a program operates on, as seen by embedders
+pub type Value = DataType;
@@ -0,0 +1,451 @@
+//! Style and correctness checks for programs that are valid but probably not what was meant.
+//!
+//! Rules can be switched off and tuned in the `[lint]` table of a `toy.toml` file:
+//!
+//! ```toml
+//! [lint]
+//! allow = ["magic-number"]
+//! magic-number-threshold = 5
+//! ```
+//!
+//! Problems with a mechanical fix can be fixed in the source with [`fix`], which finds the
+//! line behind every instruction by feeding the source through the parser a line at a
+//! time and leaves every other line as it was.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use crate::{
+    parser::{instruction_lines, Program},
+    DataType, Instructions, RunOptions,
+};
+
+/// Every rule, by the name used to allow it
+pub const RULES: &[(&str, &str)] = &[
+    ("push-drop", "a value is pushed and immediately dropped"),
+    (
+        "unused-comparison",
+        "the result of a comparison is never used",
+    ),
+    (
+        "never-returns",
+        "every path through a section loops forever without reaching an exit",
+    ),
+    (
+        "magic-number",
+        "the same number is pushed in many places instead of being named",
+    ),
+    (
+        "non-canonical",
+        "an instruction is not written the way it is documented (only fixed, never reported)",
+    ),
+];
+
+/// Which rules run, and how they are tuned
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintConfig {
+    /// Rules that are not reported
+    pub allow: HashSet<String>,
+    /// How often a number may be pushed before `magic-number` reports it
+    pub magic_number_threshold: usize,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        LintConfig {
+            allow: HashSet::new(),
+            magic_number_threshold: 3,
+        }
+    }
+}
+
+impl LintConfig {
+    /// Reads the `[lint]` table of a `toy.toml` file
+    pub fn load(path: &Path) -> Result<LintConfig, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| format!("cannot read {}: {error}", path.display()))?;
+
+        LintConfig::parse(&contents).map_err(|error| format!("{}: {error}", path.display()))
+    }
+
+    /// Parses the `[lint]` table out of a `toy.toml` file, ignoring every other table. Only
+    /// the parts of TOML the table needs are understood: integers and arrays of strings
+    pub fn parse(contents: &str) -> Result<LintConfig, String> {
+        let mut config = LintConfig::default();
+        let mut in_lint = false;
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(table) = line.strip_prefix('[') {
+                in_lint = table.strip_suffix(']').map(str::trim) == Some("lint");
+                continue;
+            }
+
+            if !in_lint {
+                continue;
+            }
+
+            let error = |message: &str| format!("line {}: {message}", number + 1);
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(error("expected `key = value`"));
+            };
+
+            match (key.trim(), value.trim()) {
+                ("allow", value) => {
+                    let Some(names) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']'))
+                    else {
+                        return Err(error("allow must be an array of rule names"));
+                    };
+
+                    for name in names.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+                        let Some(name) = name.strip_prefix('"').and_then(|n| n.strip_suffix('"'))
+                        else {
+                            return Err(error("rule names must be quoted strings"));
+                        };
+
+                        if !RULES.iter().any(|(rule, _)| *rule == name) {
+                            return Err(error(&format!("unknown rule: {name}")));
+                        }
+
+                        config.allow.insert(name.to_string());
+                    }
+                }
+                ("magic-number-threshold", value) => {
+                    config.magic_number_threshold = value
+                        .parse()
+                        .map_err(|_| error("magic-number-threshold must be a number"))?;
+                }
+                (key, _) => return Err(error(&format!("unknown setting: {key}"))),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Something a rule found
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub rule: &'static str,
+    pub section: String,
+    /// The instruction the warning is about, if it is about one
+    pub index: Option<usize>,
+    pub message: String,
+    /// How the problem could be fixed, where there is an obvious way
+    pub fix: Option<String>,
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "{}:{index}", self.section)?,
+            None => write!(f, "{}", self.section)?,
+        }
+
+        write!(f, ": {} [{}]", self.message, self.rule)?;
+
+        if let Some(fix) = &self.fix {
+            write!(f, "\n  fix: {fix}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs every rule that is not allowed by `config`
+pub fn lint(program: &[Program], config: &LintConfig) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+
+    for item in program {
+        let Program::Section(name, instructions) = item else {
+            continue;
+        };
+
+        for (index, pair) in instructions.windows(2).enumerate() {
+            match pair {
+                [Instructions::Push(value), Instructions::Drop] => warnings.push(Warning {
+                    rule: "push-drop",
+                    section: name.0.clone(),
+                    index: Some(index),
+                    message: format!("push {value} is dropped straight away"),
+                    fix: Some("remove both instructions".to_string()),
+                }),
+                [comparison, next]
+                    if comparison.is_comparison()
+                        && matches!(next, Instructions::Drop | Instructions::Exit) =>
+                {
+                    warnings.push(Warning {
+                        rule: "unused-comparison",
+                        section: name.0.clone(),
+                        index: Some(index),
+                        message: format!("the result of {comparison} is never used"),
+                        fix: matches!(next, Instructions::Drop).then(|| {
+                            format!("replace `{comparison}` and `drop` with `drop` twice")
+                        }),
+                    })
+                }
+                _ => {}
+            }
+        }
+    }
+
+    warnings.extend(never_returns(program));
+    warnings.extend(magic_numbers(program, config.magic_number_threshold));
+
+    warnings.retain(|warning| !config.allow.contains(warning.rule));
+    warnings
+}
+
+/// Sections from which no path leads to an `exit` or the end of a section. Jumps never come
+/// back, so a section finishes exactly when it exits or runs out of instructions without
+/// jumping
+fn never_returns(program: &[Program]) -> Vec<Warning> {
+    let groups: HashMap<&str, &Vec<String>> = program
+        .iter()
+        .filter_map(|item| match item {
+            Program::States(group, states) => Some((group.as_str(), states)),
+            _ => None,
+        })
+        .collect();
+
+    let sections: HashMap<&str, &Vec<Instructions>> = program
+        .iter()
+        .filter_map(|item| match item {
+            Program::Section(name, instructions) => Some((name.0.as_str(), instructions)),
+            _ => None,
+        })
+        .collect();
+
+    let targets = |instructions: &Vec<Instructions>| -> Vec<String> {
+        instructions
+            .iter()
+            .flat_map(|instruction| match instruction {
+                Instructions::Jump(label)
+                | Instructions::IfJmp(label)
+                | Instructions::Call(label) => {
+                    vec![label.clone()]
+                }
+                Instructions::Switch(group) => groups
+                    .get(group.as_str())
+                    .map(|states| states.to_vec())
+                    .unwrap_or_default(),
+                _ => Vec::new(),
+            })
+            .collect()
+    };
+
+    let finishes = |instructions: &Vec<Instructions>| {
+        instructions.contains(&Instructions::Exit)
+            || !matches!(
+                instructions.last(),
+                Some(Instructions::Jump(_) | Instructions::Switch(_))
+            )
+    };
+
+    let mut warnings = Vec::new();
+
+    for item in program {
+        let Program::Section(name, _) = item else {
+            continue;
+        };
+
+        let mut seen = HashSet::from([name.0.as_str()]);
+        let mut pending = vec![name.0.as_str()];
+        let mut returns = false;
+
+        while let Some(section) = pending.pop() {
+            // Unknown targets are reported by validation
+            let Some(instructions) = sections.get(section) else {
+                returns = true;
+                break;
+            };
+
+            if finishes(instructions) {
+                returns = true;
+                break;
+            }
+
+            for target in targets(instructions) {
+                if let Some((&target, _)) = sections.get_key_value(target.as_str()) {
+                    if seen.insert(target) {
+                        pending.push(target);
+                    }
+                }
+            }
+        }
+
+        if !returns {
+            warnings.push(Warning {
+                rule: "never-returns",
+                section: name.0.clone(),
+                index: None,
+                message: "every path from this section loops forever".to_string(),
+                fix: None,
+            });
+        }
+    }
+
+    warnings
+}
+
+/// Numbers other than 0 and 1 pushed at least `threshold` times, reported where they are
+/// first pushed
+fn magic_numbers(program: &[Program], threshold: usize) -> Vec<Warning> {
+    // The source form of each number, where it is first pushed and how often. Numbers are
+    // told apart by their source form so Ints and Floats stay apart
+    let mut uses: Vec<(String, String, usize, usize)> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+
+    for item in program {
+        let Program::Section(name, instructions) = item else {
+            continue;
+        };
+
+        for (index, instruction) in instructions.iter().enumerate() {
+            let literal = match instruction {
+                Instructions::Push(DataType::Int(0 | 1)) => continue,
+                Instructions::Push(DataType::Float(value)) if *value == 0.0 || *value == 1.0 => {
+                    continue
+                }
+                Instructions::Push(DataType::Int(_) | DataType::Float(_)) => {
+                    instruction.to_string()
+                }
+                _ => continue,
+            };
+
+            match positions.get(&literal) {
+                Some(&position) => uses[position].3 += 1,
+                None => {
+                    positions.insert(literal.clone(), uses.len());
+                    uses.push((literal, name.0.clone(), index, 1));
+                }
+            }
+        }
+    }
+
+    uses.into_iter()
+        .filter(|(.., count)| *count >= threshold)
+        .map(|(literal, section, index, count)| {
+            let value = literal.trim_start_matches("push ");
+
+            Warning {
+                rule: "magic-number",
+                section,
+                index: Some(index),
+                message: format!("{value} is pushed {count} times"),
+                fix: Some(format!(
+                    "declare `const name = [{value}]` and push it with `getconst name 0`"
+                )),
+            }
+        })
+        .collect()
+}
+
+/// Rewrites `source` with every mechanical fix for the rules `config` does not allow:
+/// removing `push`/`drop` pairs, dropping unused comparison operands instead of comparing
+/// them, and writing instructions in lowercase and numbers in their shortest form. Returns
+/// the new source and how many fixes were made. Fixes are applied until none are left, as
+/// one fix can make way for another
+pub fn fix(source: &str, options: &RunOptions, config: &LintConfig) -> (String, usize) {
+    let (mut fixed, mut fixes) = fix_once(source, options, config);
+
+    loop {
+        let (next, more) = fix_once(&fixed, options, config);
+
+        if more == 0 {
+            return (fixed, fixes);
+        }
+
+        fixed = next;
+        fixes += more;
+    }
+}
+
+fn fix_once(source: &str, options: &RunOptions, config: &LintConfig) -> (String, usize) {
+    let lines: Vec<&str> = source.lines().collect();
+    let enabled = |rule: &str| !config.allow.contains(rule);
+
+    // What each line becomes, with `None` removing it
+    let mut edits: HashMap<usize, Option<String>> = HashMap::new();
+
+    for (_, instructions, numbers) in instruction_lines(&lines, options) {
+        let mut index = 0;
+
+        while index < instructions.len() {
+            let pair = (&instructions[index], instructions.get(index + 1));
+
+            match pair {
+                (Instructions::Push(_), Some(Instructions::Drop)) if enabled("push-drop") => {
+                    edits.insert(numbers[index], None);
+                    edits.insert(numbers[index + 1], None);
+                    index += 2;
+                    continue;
+                }
+                (comparison, Some(Instructions::Drop))
+                    if comparison.is_comparison() && enabled("unused-comparison") =>
+                {
+                    edits.insert(numbers[index], Some("drop".to_string()));
+                    index += 1;
+                    continue;
+                }
+                _ => {}
+            }
+
+            if enabled("non-canonical") {
+                let line = lines[numbers[index]];
+                let canonical = canonical(line, &instructions[index]);
+
+                if canonical != line {
+                    edits.insert(numbers[index], Some(canonical));
+                }
+            }
+
+            index += 1;
+        }
+    }
+
+    // Both halves of a removed pair count as one fix
+    let fixes = edits.values().filter(|edit| edit.is_some()).count()
+        + edits.values().filter(|edit| edit.is_none()).count() / 2;
+
+    let mut fixed = String::new();
+    for (number, line) in lines.iter().enumerate() {
+        match edits.get(&number) {
+            Some(Some(replacement)) => fixed.push_str(replacement),
+            Some(None) => continue,
+            None => fixed.push_str(line),
+        }
+        fixed.push('\n');
+    }
+
+    if !source.ends_with('\n') {
+        fixed.pop();
+    }
+
+    (fixed, fixes)
+}
+
+/// How a line should be written: with a lowercase instruction name and, for numbers, the
+/// shortest literal for the same value. Anything else is kept as written
+fn canonical(line: &str, instruction: &Instructions) -> String {
+    let (name, operand) = line.split_once(' ').unwrap_or((line, ""));
+
+    // `push` of a state is written with its name rather than its value
+    let numeric = operand.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.');
+
+    match instruction {
+        Instructions::Push(DataType::Int(_) | DataType::Float(_)) if numeric => {
+            instruction.to_string()
+        }
+        _ if operand.is_empty() => name.to_lowercase(),
+        _ => format!("{} {operand}", name.to_lowercase()),
+    }
+}
@@ -0,0 +1,28 @@
+use clap::ValueEnum;
+
+#[derive(PartialEq, PartialOrd, Debug, Clone, Copy, ValueEnum)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+/// Receives messages emitted by the `log` instruction, keeping them separate from `print` output
+pub trait Logger {
+    fn log(&self, level: LogLevel, message: &str);
+}
+
+/// Writes log messages at or above a minimum level to stderr
+pub struct StderrLogger {
+    pub level: LogLevel,
+}
+
+impl Logger for StderrLogger {
+    fn log(&self, level: LogLevel, message: &str) {
+        if level >= self.level {
+            eprintln!("[{:?}] {message}", level);
+        }
+    }
+}
@@ -0,0 +1,783 @@
+//! A language server for editors, speaking the Language Server Protocol over stdin and
+//! stdout (`toylang lsp`). It keeps the documents the editor has open in memory and reads
+//! the rest of the project from disk, through the [symbol index](crate::index), so names
+//! can be followed across files.
+//!
+//! Supported requests: go to definition, find references, the document outline, semantic
+//! highlighting, signature help with the stack effect of the instruction being typed and
+//! formatting each line as it is finished. Open documents are checked as they change, and
+//! parse errors and the findings of the stack [analysis](crate::analysis) are published as
+//! diagnostics.
+
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    analysis::{analyze, Place, Severity},
+    highlight::{tokens, Token},
+    index::{project_files, symbols, Symbol, SymbolIndex, SymbolKind},
+    json::Json,
+    parser::{instruction_lines, is_section_header, split_instruction, try_parse},
+    reference::instruction_info,
+    RunOptions,
+};
+
+/// JSON-RPC error for requests the server does not support
+const METHOD_NOT_FOUND: f64 = -32601.0;
+
+/// The protocol's names for each kind of [`Token`], in the order of its variants
+const TOKEN_TYPES: &[&str] = &[
+    "keyword",
+    "function",
+    "variable",
+    "enumMember",
+    "number",
+    "string",
+    "comment",
+    "macro",
+];
+
+/// Instructions whose operand names a section, data section, global or states group
+const NAMING_INSTRUCTIONS: &[&str] = &[
+    "jump",
+    "ifjmp",
+    "call",
+    "switch",
+    "pushdata",
+    "getglobal",
+    "setglobal",
+    "getconst",
+    "push",
+];
+
+/// Answers requests until the editor sends `exit` or closes the connection
+pub fn serve(mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut server = Server::default();
+
+    while let Some(message) = read_message(&mut input)? {
+        if message.get("method").and_then(Json::as_str) == Some("exit") {
+            break;
+        }
+
+        if let Some(response) = server.handle(&message) {
+            write_message(&mut output, &response)?;
+        }
+
+        for notification in server.notifications.drain(..) {
+            write_message(&mut output, &notification)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Default)]
+struct Server {
+    /// The text of every open document, by URI
+    documents: HashMap<String, String>,
+    /// The project directory, if the editor opened one
+    root: Option<PathBuf>,
+    /// Definitions in the project's files on disk
+    index: SymbolIndex,
+    /// Messages to send the editor unprompted once the current one is handled
+    notifications: Vec<Json>,
+}
+
+impl Server {
+    /// Handles a request or notification, returning the response to a request
+    fn handle(&mut self, message: &Json) -> Option<Json> {
+        let method = message.get("method").and_then(Json::as_str)?;
+        let params = message.get("params").unwrap_or(&Json::Null);
+        let document = params
+            .get("textDocument")
+            .and_then(|document| document.get("uri"))
+            .and_then(Json::as_str)
+            .map(str::to_string);
+
+        let result = match method {
+            "initialize" => {
+                self.root = params
+                    .get("rootUri")
+                    .and_then(Json::as_str)
+                    .and_then(uri_to_path);
+
+                Json::object([(
+                    "capabilities",
+                    Json::object([
+                        // Documents are sent whole on every change
+                        ("textDocumentSync", Json::from(1)),
+                        ("definitionProvider", Json::from(true)),
+                        ("referencesProvider", Json::from(true)),
+                        ("documentSymbolProvider", Json::from(true)),
+                        (
+                            "semanticTokensProvider",
+                            Json::object([
+                                (
+                                    "legend",
+                                    Json::object([
+                                        (
+                                            "tokenTypes",
+                                            Json::Array(
+                                                TOKEN_TYPES
+                                                    .iter()
+                                                    .map(|&t| Json::from(t))
+                                                    .collect(),
+                                            ),
+                                        ),
+                                        ("tokenModifiers", Json::Array(Vec::new())),
+                                    ]),
+                                ),
+                                ("full", Json::from(true)),
+                            ]),
+                        ),
+                        (
+                            "signatureHelpProvider",
+                            Json::object([(
+                                "triggerCharacters",
+                                Json::Array(vec![Json::from(" ")]),
+                            )]),
+                        ),
+                        (
+                            "documentOnTypeFormattingProvider",
+                            Json::object([("firstTriggerCharacter", Json::from("\n"))]),
+                        ),
+                    ]),
+                )])
+            }
+            "textDocument/didOpen" => {
+                let text = params
+                    .get("textDocument")
+                    .and_then(|document| document.get("text"))
+                    .and_then(Json::as_str);
+
+                if let (Some(uri), Some(text)) = (document, text) {
+                    self.publish_diagnostics(&uri, text);
+                    self.documents.insert(uri, text.to_string());
+                }
+                return None;
+            }
+            "textDocument/didChange" => {
+                let text = params
+                    .get("contentChanges")
+                    .and_then(Json::as_array)
+                    .and_then(<[Json]>::last)
+                    .and_then(|change| change.get("text"))
+                    .and_then(Json::as_str);
+
+                if let (Some(uri), Some(text)) = (document, text) {
+                    self.publish_diagnostics(&uri, text);
+                    self.documents.insert(uri, text.to_string());
+                }
+                return None;
+            }
+            "textDocument/didClose" => {
+                if let Some(uri) = document {
+                    self.documents.remove(&uri);
+                }
+                return None;
+            }
+            "textDocument/definition" => match self.word_at(params) {
+                Some(name) => Json::Array(
+                    self.definitions(&name)
+                        .into_iter()
+                        .map(|(uri, symbol)| {
+                            let line = symbol.line - 1;
+                            let text = self.text(&uri).unwrap_or_default();
+                            let text = text.lines().nth(line).unwrap_or_default();
+                            let span = find_word(text, &symbol.name).unwrap_or((0, 0));
+
+                            location(&uri, text, line, span)
+                        })
+                        .collect(),
+                ),
+                None => Json::Null,
+            },
+            "textDocument/references" => match self.word_at(params) {
+                Some(name) => {
+                    let declarations = params
+                        .get("context")
+                        .and_then(|context| context.get("includeDeclaration"))
+                        .and_then(Json::as_bool)
+                        .unwrap_or(true);
+
+                    let mut locations = Vec::new();
+                    for (uri, text) in self.sources() {
+                        let lines: Vec<&str> = text.lines().collect();
+
+                        for (line, span) in occurrences(&text, &name, declarations) {
+                            locations.push(location(&uri, lines[line], line, span));
+                        }
+                    }
+
+                    Json::Array(locations)
+                }
+                None => Json::Null,
+            },
+            "textDocument/documentSymbol" => {
+                let text = document
+                    .and_then(|uri| self.documents.get(&uri))
+                    .cloned()
+                    .unwrap_or_default();
+                let lines: Vec<&str> = text.lines().collect();
+
+                Json::Array(
+                    symbols_of(&text)
+                        .iter()
+                        .map(|symbol| document_symbol(symbol, &lines))
+                        .collect(),
+                )
+            }
+            "textDocument/semanticTokens/full" => {
+                let text = document.and_then(|uri| self.text(&uri)).unwrap_or_default();
+
+                Json::object([("data", Json::Array(encode_tokens(&text, &tokens(&text))))])
+            }
+            "textDocument/signatureHelp" => self.signature_help(params).unwrap_or(Json::Null),
+            "textDocument/onTypeFormatting" => {
+                Json::Array(self.format_finished_line(params).into_iter().collect())
+            }
+            "shutdown" => Json::Null,
+            _ => {
+                // Notifications the server has no use for are ignored
+                let id = message.get("id")?;
+
+                return Some(Json::object([
+                    ("jsonrpc", Json::from("2.0")),
+                    ("id", id.clone()),
+                    (
+                        "error",
+                        Json::object([
+                            ("code", Json::Number(METHOD_NOT_FOUND)),
+                            (
+                                "message",
+                                Json::from(format!("unsupported method {method}")),
+                            ),
+                        ]),
+                    ),
+                ]));
+            }
+        };
+
+        Some(Json::object([
+            ("jsonrpc", Json::from("2.0")),
+            ("id", message.get("id").cloned().unwrap_or(Json::Null)),
+            ("result", result),
+        ]))
+    }
+
+    /// The name under the cursor of a request with a text document position
+    fn word_at(&self, params: &Json) -> Option<String> {
+        let (line, _, offset) = self.line_at(params)?;
+
+        let is_name = |c: char| !c.is_whitespace() && !matches!(c, ':' | '"' | ',' | '[' | ']');
+        let start = line[..offset]
+            .rfind(|c: char| !is_name(c))
+            .map_or(0, |index| index + 1);
+        let end = line[offset..]
+            .find(|c: char| !is_name(c))
+            .map_or(line.len(), |index| offset + index);
+
+        (start < end).then(|| line[start..end].to_string())
+    }
+
+    /// Checks a document, replacing the diagnostics the editor shows for it
+    fn publish_diagnostics(&mut self, uri: &str, text: &str) {
+        self.notifications.push(Json::object([
+            ("jsonrpc", Json::from("2.0")),
+            ("method", Json::from("textDocument/publishDiagnostics")),
+            (
+                "params",
+                Json::object([
+                    ("uri", Json::from(uri)),
+                    ("diagnostics", Json::Array(diagnostics(uri, text))),
+                ]),
+            ),
+        ]));
+    }
+
+    /// The line of a request with a text document position, and the position's line number
+    /// and byte offset within it
+    fn line_at(&self, params: &Json) -> Option<(String, usize, usize)> {
+        let uri = params.get("textDocument")?.get("uri")?.as_str()?;
+        let position = params.get("position")?;
+        let number = position.get("line")?.as_usize()?;
+        let character = position.get("character")?.as_usize()?;
+
+        let text = self.text(uri)?;
+        let line = text.lines().nth(number).unwrap_or_default().to_string();
+        let offset = byte_offset(&line, character);
+
+        Some((line, number, offset))
+    }
+
+    /// The name and stack effect of the instruction being typed, from the reference
+    fn signature_help(&self, params: &Json) -> Option<Json> {
+        let (line, _, offset) = self.line_at(params)?;
+        let typed = line[..offset].trim_start();
+        let (name, _) = typed.split_once(' ')?;
+        let info = instruction_info(name)?;
+
+        let label = match info.operand {
+            Some(operand) => format!("{} {operand} {}", info.name, info.stack),
+            None => format!("{} {}", info.name, info.stack),
+        };
+
+        let mut documentation = info.description.to_string();
+        if !info.errors.is_empty() {
+            documentation.push_str("\n\nFails if ");
+            documentation.push_str(&info.errors.join(", or if "));
+        }
+
+        let parameters = info
+            .operand
+            .map(|operand| vec![Json::object([("label", Json::from(operand))])])
+            .unwrap_or_default();
+
+        Some(Json::object([
+            (
+                "signatures",
+                Json::Array(vec![Json::object([
+                    ("label", Json::from(label)),
+                    ("documentation", Json::from(documentation)),
+                    ("parameters", Json::Array(parameters)),
+                ])]),
+            ),
+            ("activeSignature", Json::from(0)),
+            ("activeParameter", Json::from(0)),
+        ]))
+    }
+
+    /// Tidies the line finished by pressing Enter, see [`format_line`]
+    fn format_finished_line(&self, params: &Json) -> Option<Json> {
+        let uri = params.get("textDocument")?.get("uri")?.as_str()?;
+        let number = params
+            .get("position")?
+            .get("line")?
+            .as_usize()?
+            .checked_sub(1)?;
+        let text = self.text(uri)?;
+
+        // Data sections are text, not instructions
+        let mut in_data = false;
+        for line in text.lines().take(number) {
+            let header = line.strip_prefix("override ").map_or(line, str::trim_start);
+
+            if is_section_header(header) {
+                in_data = header.starts_with("::data ");
+            }
+        }
+
+        let line = text.lines().nth(number)?;
+        let formatted = format_line(line).filter(|formatted| !in_data && formatted != line)?;
+
+        Some(Json::object([
+            ("range", range(line, number, (0, line.len()))),
+            ("newText", Json::from(formatted)),
+        ]))
+    }
+
+    /// Where `name` is defined, in open documents and the project's files on disk
+    fn definitions(&mut self, name: &str) -> Vec<(String, Symbol)> {
+        let mut found: Vec<(String, Symbol)> = self
+            .documents
+            .iter()
+            .flat_map(|(uri, text)| {
+                symbols_of(text)
+                    .into_iter()
+                    .filter(|symbol| symbol.name == name)
+                    .map(|symbol| (uri.clone(), symbol))
+            })
+            .collect();
+
+        if let Some(root) = &self.root {
+            let files = project_files(root).unwrap_or_default();
+
+            // A file the index cannot read is left out rather than failing the request
+            if self.index.update(&files).is_ok() {
+                for (path, symbol) in self.index.find(name) {
+                    let uri = path_to_uri(path);
+
+                    if !self.documents.contains_key(&uri) {
+                        found.push((uri, symbol.clone()));
+                    }
+                }
+            }
+        }
+
+        found.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.line.cmp(&b.1.line)));
+        found
+    }
+
+    /// The text of a document, from the editor if it is open and from disk otherwise
+    fn text(&self, uri: &str) -> Option<String> {
+        match self.documents.get(uri) {
+            Some(text) => Some(text.clone()),
+            None => std::fs::read_to_string(uri_to_path(uri)?).ok(),
+        }
+    }
+
+    /// Every open document and every other file of the project, by URI
+    fn sources(&self) -> Vec<(String, String)> {
+        let mut sources: Vec<(String, String)> = self
+            .documents
+            .iter()
+            .map(|(uri, text)| (uri.clone(), text.clone()))
+            .collect();
+
+        if let Some(root) = &self.root {
+            for path in project_files(root).unwrap_or_default() {
+                let uri = path_to_uri(&path);
+
+                if !self.documents.contains_key(&uri) {
+                    if let Ok(text) = std::fs::read_to_string(&path) {
+                        sources.push((uri, text));
+                    }
+                }
+            }
+        }
+
+        sources.sort();
+        sources
+    }
+}
+
+/// Where a document fails to parse, or else everything the stack analysis finds in it
+fn diagnostics(uri: &str, text: &str) -> Vec<Json> {
+    let lines: Vec<&str> = text.lines().collect();
+    let options = RunOptions::default();
+
+    let program = match try_parse(text, &options) {
+        Ok(program) => program,
+        Err(error) => {
+            let number = error.line.map_or(0, |line| line - 1);
+            let line = lines.get(number).copied().unwrap_or_default();
+
+            return vec![diagnostic(
+                Severity::Error,
+                &error.message,
+                range(line, number, (0, line.len())),
+                Vec::new(),
+            )];
+        }
+    };
+
+    let sections: HashMap<String, Vec<usize>> = instruction_lines(&lines, &options)
+        .into_iter()
+        .map(|(name, _, numbers)| (name, numbers))
+        .collect();
+    let headers = symbols_of(text);
+
+    // Instructions are marked from their first to their last character
+    let locate = |place: &Place| -> Json {
+        let number = match place.index {
+            Some(index) => sections
+                .get(&place.section)
+                .and_then(|numbers| numbers.get(index))
+                .copied(),
+            None => headers
+                .iter()
+                .find(|symbol| symbol.kind == SymbolKind::Section && symbol.name == place.section)
+                .map(|symbol| symbol.line - 1),
+        };
+
+        let number = number.unwrap_or(0);
+        let line = lines.get(number).copied().unwrap_or_default();
+        let indent = line.len() - line.trim_start().len();
+
+        range(line, number, (indent, line.trim_end().len()))
+    };
+
+    analyze(&program)
+        .into_iter()
+        .map(|finding| {
+            let related = finding
+                .related
+                .iter()
+                .map(|(place, message)| {
+                    Json::object([
+                        (
+                            "location",
+                            Json::object([("uri", Json::from(uri)), ("range", locate(place))]),
+                        ),
+                        ("message", Json::from(message.as_str())),
+                    ])
+                })
+                .collect();
+
+            diagnostic(
+                finding.severity,
+                &finding.message,
+                locate(&finding.place),
+                related,
+            )
+        })
+        .collect()
+}
+
+fn diagnostic(severity: Severity, message: &str, range: Json, related: Vec<Json>) -> Json {
+    // Severities as numbered by the protocol
+    let severity = match severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+    };
+
+    Json::object([
+        ("range", range),
+        ("severity", Json::from(severity)),
+        ("source", Json::from("toylang")),
+        ("message", Json::from(message)),
+        ("relatedInformation", Json::Array(related)),
+    ])
+}
+
+/// The definitions in a document, or none while it does not parse
+fn symbols_of(text: &str) -> Vec<Symbol> {
+    symbols(text).unwrap_or_default()
+}
+
+/// Every place `name` is used in `source` as a line and a byte range within it. Section
+/// headers and other definitions are included if `declarations` is set
+fn occurrences(source: &str, name: &str, declarations: bool) -> Vec<(usize, (usize, usize))> {
+    let mut found = Vec::new();
+    let mut in_data = false;
+
+    for (number, line) in source.lines().enumerate() {
+        let header = line.strip_prefix("override ").map_or(line, str::trim_start);
+
+        if is_section_header(header) {
+            let section = header.trim_matches(':');
+            let section = section.strip_prefix("data ").map_or(section, str::trim);
+            in_data = header.starts_with("::data ");
+
+            if declarations && section == name {
+                found.extend(find_word(line, name).map(|span| (number, span)));
+            }
+            continue;
+        }
+
+        if in_data {
+            continue;
+        }
+
+        let (instruction, operand) = split_instruction(line);
+        let instruction = instruction.to_ascii_lowercase();
+
+        let names: Vec<&str> = match instruction.as_str() {
+            "const" if declarations => operand.split('=').next().into_iter().collect(),
+            "#states" => {
+                let mut words = operand.split_whitespace();
+                let group = words.next().filter(|_| declarations);
+                group.into_iter().chain(words).collect()
+            }
+            "getconst" => operand.split_whitespace().take(1).collect(),
+            instruction if NAMING_INSTRUCTIONS.contains(&instruction) => vec![operand],
+            _ => Vec::new(),
+        };
+
+        if names.iter().any(|candidate| candidate.trim() == name) {
+            let offset = line.len() - operand.len();
+
+            found.extend(
+                find_word(&line[offset..], name)
+                    .map(|(start, end)| (number, (offset + start, offset + end))),
+            );
+        }
+    }
+
+    found
+}
+
+/// Writes an instruction the way the parser reads it: unindented, with its name in
+/// lowercase and a single space before its operand. `None` for lines that are not
+/// instructions
+fn format_line(line: &str) -> Option<String> {
+    let trimmed = line.trim();
+    let (name, operand) = trimmed
+        .split_once(char::is_whitespace)
+        .map_or((trimmed, ""), |(name, operand)| {
+            (name, operand.trim_start())
+        });
+
+    instruction_info(name)?;
+
+    Some(match operand {
+        "" => name.to_ascii_lowercase(),
+        operand => format!("{} {operand}", name.to_ascii_lowercase()),
+    })
+}
+
+/// Encodes tokens the way the protocol expects: five numbers per token, giving its line
+/// and start relative to the token before it, its length and its kind
+fn encode_tokens(source: &str, tokens: &[(usize, (usize, usize), Token)]) -> Vec<Json> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut data = Vec::with_capacity(tokens.len() * 5);
+    let (mut previous_line, mut previous_start) = (0, 0);
+
+    for &(line, (start, end), token) in tokens {
+        let text = lines[line];
+        let character = text[..start].encode_utf16().count();
+        let length = text[start..end].encode_utf16().count();
+
+        if line != previous_line {
+            previous_start = 0;
+        }
+
+        data.extend([
+            line - previous_line,
+            character - previous_start,
+            length,
+            token as usize,
+            0,
+        ]);
+
+        (previous_line, previous_start) = (line, character);
+    }
+
+    data.into_iter().map(Json::from).collect()
+}
+
+/// The byte range of `word` in `line` where it is not part of a longer name
+fn find_word(line: &str, word: &str) -> Option<(usize, usize)> {
+    let is_name = |c: char| !c.is_whitespace() && !matches!(c, ':' | '"' | ',' | '[' | ']' | '=');
+
+    line.match_indices(word)
+        .map(|(start, _)| (start, start + word.len()))
+        .find(|&(start, end)| {
+            !line[..start].ends_with(is_name) && !line[end..].starts_with(is_name)
+        })
+}
+
+fn document_symbol(symbol: &Symbol, lines: &[&str]) -> Json {
+    let line = symbol.line - 1;
+    let text = lines.get(line).copied().unwrap_or_default();
+    let span = find_word(text, &symbol.name).unwrap_or((0, text.len()));
+
+    // Kinds as numbered by the protocol
+    let kind = match symbol.kind {
+        SymbolKind::Section => 12,
+        SymbolKind::Data => 15,
+        SymbolKind::Const => 14,
+        SymbolKind::States => 10,
+    };
+
+    Json::object([
+        ("name", Json::from(symbol.name.as_str())),
+        ("kind", Json::from(kind)),
+        ("range", range(text, line, (0, text.len()))),
+        ("selectionRange", range(text, line, span)),
+    ])
+}
+
+fn location(uri: &str, text: &str, line: usize, span: (usize, usize)) -> Json {
+    Json::object([("uri", Json::from(uri)), ("range", range(text, line, span))])
+}
+
+/// A range within line `line`, whose text is `text`, from a byte range
+fn range(text: &str, line: usize, (start, end): (usize, usize)) -> Json {
+    // The protocol counts characters in UTF-16 code units
+    let position = |offset: usize| {
+        Json::object([
+            ("line", Json::from(line)),
+            (
+                "character",
+                Json::from(text[..offset].encode_utf16().count()),
+            ),
+        ])
+    };
+
+    Json::object([("start", position(start)), ("end", position(end))])
+}
+
+/// Converts a position in UTF-16 code units, as the protocol counts them, to a byte offset
+fn byte_offset(line: &str, character: usize) -> usize {
+    let mut units = 0;
+
+    for (offset, c) in line.char_indices() {
+        if units >= character {
+            return offset;
+        }
+        units += c.len_utf16();
+    }
+
+    line.len()
+}
+
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let mut decoded = Vec::with_capacity(path.len());
+    let mut bytes = path.bytes();
+
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            decoded.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            decoded.push(byte);
+        }
+    }
+
+    Some(PathBuf::from(String::from_utf8(decoded).ok()?))
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut uri = String::from("file://");
+
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    uri
+}
+
+/// Reads a message framed by a `Content-Length` header, or `None` at the end of input
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Json>> {
+    let mut length = None;
+
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let Some(length) = length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message without a Content-Length header",
+        ));
+    };
+
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+
+    let body = String::from_utf8(body)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+    Json::parse(&body)
+        .map(Some)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+fn write_message(output: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()
+}
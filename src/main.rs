@@ -1,6 +1,29 @@
-use std::path::PathBuf;
+use std::{
+    io::Read,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use toylang::{
+    bytecode, check,
+    conformance::{evaluate_all, Limits, TestCase},
+    debugger::Breakpoint,
+    dependencies, explain, fallthrough_warnings, highlight,
+    index::{project_files, SymbolIndex},
+    lint::{self, lint, LintConfig},
+    lsp,
+    manifest::Manifest,
+    minimize::{minimize, Check},
+    mmap::Mmap,
+    pack, refactor, reference, repl,
+    stats::Stats,
+    test_report::{self, Status},
+    trace, try_parse, try_parse_reader, try_validate, Checkpoint, Compat, Interpreter, LogLevel,
+    ParseError, Program, Replay, RunOptions, SourceLocation, Speaker, Stack, StderrLogger,
+    TerminalBell,
+};
 
 /// Simple program to greet a person
 #[derive(Parser)]
@@ -10,475 +33,1345 @@ struct Args {
     cmd: Commands,
 }
 
+// Parsed once at startup, so the size of `Run` does not matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
     /// Run the program
     Run {
         /// Path to the program to run
-        path: PathBuf,
+        #[arg(required_unless_present = "bin")]
+        path: Option<PathBuf>,
+
+        /// Run an entry point defined by a `[[bin]]` table of toy.toml instead of a path
+        #[arg(long, value_name = "NAME", conflicts_with = "path")]
+        bin: Option<String>,
 
+        /// Run under the interactive debugger, pausing before the first instruction or at
+        /// the breakpoints given with --break
         #[arg(short, long, default_value_t = false)]
         debug: bool,
+
+        /// Pause the debugger before a line or at the start of a section, may be given
+        /// multiple times
+        #[arg(long = "break", value_name = "LINE|SECTION", requires = "debug")]
+        breakpoints: Vec<Breakpoint>,
+
+        /// Print the stack and each instruction before it runs
+        #[arg(long, default_value_t = false, conflicts_with = "debug")]
+        debug_dump: bool,
+
+        /// Only show this many values from the top of the stack in debug output
+        #[arg(long, value_name = "N", requires = "debug_dump")]
+        debug_depth: Option<usize>,
+
+        /// Only show every Nth instruction in debug output
+        #[arg(long, value_name = "N", default_value_t = 1, requires = "debug_dump")]
+        debug_every: usize,
+
+        /// Show what each instruction changed on the stack instead of the whole stack
+        #[arg(long, default_value_t = false, requires = "debug_dump")]
+        debug_diff: bool,
+
+        /// Write debug output to a file instead of stdout
+        #[arg(long, value_name = "FILE", requires = "debug_dump")]
+        debug_to: Option<PathBuf>,
+
+        /// Pause after every instruction, showing it and the stack, until Enter is pressed
+        #[arg(long, default_value_t = false)]
+        step: bool,
+
+        /// Treat reaching the end of the program without an explicit `exit` as an error
+        #[arg(long, default_value_t = false)]
+        trap_fallthrough: bool,
+
+        /// Define a flag for `#if` directives, may be given multiple times
+        #[arg(short = 'D', long = "define", value_name = "FLAG")]
+        defines: Vec<String>,
+
+        /// Minimum level of `log` messages written to stderr
+        #[arg(long, value_enum, default_value_t = LogLevel::Info)]
+        log_level: LogLevel,
+
+        /// Write program output immediately instead of buffering it until a flush
+        #[arg(long, default_value_t = false)]
+        unbuffered: bool,
+
+        /// Stop the program once its values take up more than this much memory (e.g. 512K, 64M)
+        #[arg(long, value_name = "BYTES", value_parser = parse_bytes)]
+        max_memory: Option<usize>,
+
+        /// Stop the program once it has printed more than this much output (e.g. 512K, 64M)
+        #[arg(long, value_name = "BYTES", value_parser = parse_bytes)]
+        max_output_bytes: Option<usize>,
+
+        /// Let the program play tones, which ring the terminal bell
+        #[arg(long, default_value_t = false)]
+        allow_sound: bool,
+
+        /// Print how the program exited, how many instructions it ran, how long it took and
+        /// its deepest stack to stderr once it ends
+        #[arg(long, default_value_t = false)]
+        summary: bool,
+
+        /// Stop the program with an error once it has run for this long (e.g. 500ms, 2s, 1m)
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        timeout: Option<Duration>,
+
+        /// Reproduce older language behaviour that existing programs may depend on
+        #[arg(long, value_enum)]
+        compat: Option<Compat>,
+
+        /// Save the program's state every N instructions, to load into `debug --checkpoint`
+        #[arg(long, value_name = "N")]
+        checkpoint_every: Option<usize>,
+
+        /// Directory the checkpoint is written to
+        #[arg(
+            long,
+            value_name = "DIR",
+            default_value = ".",
+            requires = "checkpoint_every"
+        )]
+        checkpoint_dir: PathBuf,
+
+        /// Save everything the program reads from stdin and the clock to a replay file
+        #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+        record: Option<PathBuf>,
+
+        /// Feed the program the input and clock readings saved by `--record` instead
+        #[arg(long, value_name = "FILE")]
+        replay: Option<PathBuf>,
+
+        /// Write every instruction run and the stack it ran on to a file, one JSON object
+        /// per line, to compare runs with `tracediff`
+        #[arg(long, value_name = "FILE")]
+        trace: Option<PathBuf>,
+
+        /// Fail once a section runs more steps than its `#budget` allows, as `test` does
+        #[arg(long, default_value_t = false)]
+        budgets: bool,
+
+        /// Write an SVG chart of how deep the stack was at every step once the run ends
+        #[arg(long, value_name = "FILE")]
+        depth_chart: Option<PathBuf>,
+
+        /// Map the program into memory instead of reading it, for very large programs. The
+        /// file must not change while it runs. Falls back to reading it where mapping is
+        /// not possible
+        #[arg(long, default_value_t = false)]
+        mmap: bool,
+
+        /// Arguments for the program, read with `argc` and `argv`
+        #[arg(last = true, value_name = "ARGS")]
+        args: Vec<String>,
+    },
+    /// Compile a program to bytecode, which `run` executes without parsing it again
+    Compile {
+        /// Path to the program to compile
+        path: PathBuf,
+
+        /// Where to write the bytecode (defaults to the program's path with a `.toyc`
+        /// extension)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Define a flag for `#if` directives, may be given multiple times
+        #[arg(short = 'D', long = "define", value_name = "FLAG")]
+        defines: Vec<String>,
+    },
+    /// Write a program out without its comments and with its sections, constants and
+    /// variables renamed, to hand it out without revealing how it is built
+    Pack {
+        /// Path to the program to pack
+        path: PathBuf,
+
+        /// Where to write the packed program (defaults to stdout, or to the program's path
+        /// with a `.toyc` extension for bytecode)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Write compiled bytecode instead of source
+        #[arg(long, default_value_t = false)]
+        bytecode: bool,
+
+        /// Define a flag for `#if` directives, may be given multiple times
+        #[arg(short = 'D', long = "define", value_name = "FLAG")]
+        defines: Vec<String>,
+    },
+    /// Start an interactive session
+    Repl,
+    /// Run the program under the debugger, pausing at every `break` instruction
+    Debug {
+        /// Path to the program to debug
+        #[arg(required_unless_present = "checkpoint")]
+        path: Option<PathBuf>,
+
+        /// Continue from a checkpoint written by `run --checkpoint-every` instead
+        #[arg(long, value_name = "FILE", conflicts_with = "path")]
+        checkpoint: Option<PathBuf>,
+
+        /// Also pause before a line or at the start of a section, may be given multiple
+        /// times
+        #[arg(long = "break", value_name = "LINE|SECTION")]
+        breakpoints: Vec<Breakpoint>,
+
+        /// Define a flag for `#if` directives, may be given multiple times
+        #[arg(short = 'D', long = "define", value_name = "FLAG")]
+        defines: Vec<String>,
+    },
+    /// Run every conformance test case (`.tyt` file) in a directory
+    Conformance {
+        /// Directory holding the test cases
+        dir: PathBuf,
+    },
+    /// Run test programs, feeding them the input their `#stdin:` directives give and
+    /// checking their output against `#stdout:`, `#error:` and `#exit:`
+    Test {
+        /// Test files or directories of `.tyl` and `.tyt` files (the current directory if
+        /// none are given), and filters: only tests whose name contains a filter are run
+        #[arg(value_name = "PATH|FILTER")]
+        args: Vec<String>,
+
+        /// Only run tests whose name is exactly one of the filters
+        #[arg(long)]
+        exact: bool,
+
+        /// Only run the tests that failed in the previous run
+        #[arg(long)]
+        rerun_failed: bool,
+
+        /// Stop each test once it has run for this long (e.g. 500ms, 2s, 1m)
+        #[arg(long, value_name = "DURATION", default_value = "10s", value_parser = parse_duration)]
+        timeout: Duration,
+
+        /// Stop each test once its values take up more than this much memory (e.g. 512K, 64M)
+        #[arg(long, value_name = "BYTES", value_parser = parse_bytes)]
+        max_memory: Option<usize>,
+
+        /// How results are reported: as they happen, or as a JUnit or JSON report at the end
+        #[arg(long, value_enum, default_value_t = TestFormat::Text)]
+        format: TestFormat,
+
+        /// How many tests run at the same time (defaults to the number of CPUs)
+        #[arg(short, long, value_name = "N")]
+        jobs: Option<usize>,
+    },
+    /// Check a program for code that is valid but probably not what was meant
+    Lint {
+        /// Path to the program
+        path: PathBuf,
+
+        /// Configuration file whose `[lint]` table allows and tunes rules (defaults to
+        /// toy.toml in the current directory, if there is one)
+        #[arg(long, value_name = "FILE")]
+        config: Option<PathBuf>,
+
+        /// Fix the problems that have a mechanical fix, rewriting the program in place
+        #[arg(long)]
+        fix: bool,
+
+        /// Show the changes `--fix` would make as a diff instead of making them
+        #[arg(long, requires = "fix")]
+        dry_run: bool,
+    },
+    /// Copy the libraries listed in the `[dependencies]` of toy.toml into vendor/ and record
+    /// them in toy.lock
+    Fetch {
+        /// Fetch git libraries at the rev toy.toml asks for instead of the commit toy.lock
+        /// recorded
+        #[arg(long)]
+        update: bool,
+    },
+    /// Check a program for problems that would keep it from running, without running it
+    Check {
+        /// Path to the program
+        path: PathBuf,
+
+        /// Define a flag for `#if` directives, may be given multiple times
+        #[arg(short = 'D', long = "define", value_name = "FLAG")]
+        defines: Vec<String>,
+    },
+    /// Start a language server for editors, speaking the Language Server Protocol on stdin
+    /// and stdout
+    Lsp,
+    /// Update the index of everything the programs in a project define, and look names up
+    Index {
+        /// Root directory of the project
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+
+        /// Print where a name is defined
+        #[arg(long, value_name = "NAME")]
+        find: Option<String>,
+    },
+    /// Rewrite programs without changing what they do
+    Refactor {
+        #[command(subcommand)]
+        refactoring: Refactoring,
+    },
+    /// Report the size and complexity of a program
+    Stats {
+        /// Path to the program
+        path: PathBuf,
+
+        #[arg(long, value_enum, default_value_t = StatsFormat::Text)]
+        format: StatsFormat,
+    },
+    /// Print a program with its syntax highlighted
+    Highlight {
+        /// Path to the program
+        path: PathBuf,
+
+        /// Colour it for a terminal, or mark it up as HTML
+        #[arg(long, value_enum, default_value_t = HighlightFormat::Ansi)]
+        format: HighlightFormat,
+    },
+    /// Shrink a failing program to a small one that still fails the same way
+    Minimize {
+        /// Path to the failing program
+        path: PathBuf,
+
+        /// How the program fails: `exits nonzero`, `exits <code>`, `error contains <text>`
+        /// or `stdout contains <text>`
+        #[arg(long, default_value = "exits nonzero")]
+        check: Check,
+
+        /// Write the smaller program to a file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Stop each attempt once it has run for this long (e.g. 500ms, 2s, 1m)
+        #[arg(long, value_name = "DURATION", default_value = "2s", value_parser = parse_duration)]
+        timeout: Duration,
+    },
+    /// Compare two traces written by `run --trace`, showing where the runs first differ
+    Tracediff {
+        /// Trace of the first run
+        a: PathBuf,
+
+        /// Trace of the second run
+        b: PathBuf,
+    },
+    /// Run the first steps of a program without touching the machine, describing what
+    /// each instruction does
+    ExplainRun {
+        /// Path to the program to explain
+        path: PathBuf,
+
+        /// How many instructions to run
+        #[arg(long, value_name = "N", default_value_t = 20)]
+        steps: usize,
+
+        /// Define a flag for `#if` directives, may be given multiple times
+        #[arg(short = 'D', long = "define", value_name = "FLAG")]
+        defines: Vec<String>,
+    },
+    /// Print the reference for an instruction
+    Doc {
+        /// Instruction to document
+        #[arg(required_unless_present = "all")]
+        name: Option<String>,
+
+        /// Document every instruction
+        #[arg(long, conflicts_with = "name")]
+        all: bool,
+
+        #[arg(long, value_enum, default_value_t = DocFormat::Markdown)]
+        format: DocFormat,
     },
 }
 
-fn main() {
-    let args = Args::parse();
+#[derive(Subcommand)]
+enum Refactoring {
+    /// Rename a section and every reference to it across the files of a project
+    RenameSection {
+        old: String,
+        new: String,
 
-    match args.cmd {
-        Commands::Run { path, debug } => {
-            interpret(path, debug);
-        }
-    }
+        /// Every file of the project (defaults to the indexed files of the current directory)
+        files: Vec<PathBuf>,
+    },
+    /// Move a range of instructions into a new section, jumping to it in their place
+    Extract {
+        file: PathBuf,
+
+        /// The lines to move, counted from 1 (e.g. 4-9)
+        #[arg(long, value_name = "A-B", value_parser = parse_line_range)]
+        lines: RangeInclusive<usize>,
+
+        /// Name of the new section
+        #[arg(long)]
+        name: String,
+    },
 }
 
-#[derive(PartialEq, Debug, Clone)]
-enum DataType {
-    Bool(bool),
-    Int(usize),
-    Float(f64),
-    String(String),
+/// Project settings, read by `toylang lint` and `toylang run --bin`
+const CONFIG_FILE: &str = "toy.toml";
+
+/// Where the symbol index of a project is kept, relative to its root
+const INDEX_FILE: &str = ".toylang-index";
+
+/// Where `toylang test` remembers which tests failed, for `--rerun-failed`
+const FAILURES_FILE: &str = ".toylang-failures";
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TestFormat {
+    Text,
+    Junit,
+    Json,
 }
 
-#[derive(Debug, Clone)]
-enum Instructions {
-    Push(DataType),
-    Jump(String),
-    IfJmp(String),
-    EQ,
-    NE,
-    And,
-    Or,
-    Not,
-    Add,
-    Sub,
-    Mul,
-    Div,
-    Mod,
-    Dup,
-    Swap,
-    Over,
-    Rot,
-    Drop,
-    Print,
-    Exit,
+#[derive(Clone, Copy, ValueEnum)]
+enum StatsFormat {
+    Text,
+    Json,
 }
 
-#[derive(Debug, Clone)]
-struct SectionName(String);
+#[derive(Clone, Copy, ValueEnum)]
+enum HighlightFormat {
+    Ansi,
+    Html,
+}
 
-#[derive(Debug, Clone)]
-enum Program {
-    Section(SectionName, Vec<Instructions>),
+#[derive(Clone, Copy, ValueEnum)]
+enum DocFormat {
+    Markdown,
+    Json,
 }
 
-fn interpret(path: PathBuf, debug: bool) {
-    let contents = std::fs::read_to_string(path).unwrap();
-    let lines = contents.lines();
+fn main() {
+    let args = Args::parse();
 
-    // Lexing the source code into an "AST" 
-    let mut program: Vec<Program> = Vec::new();
-    let mut current_section: Option<SectionName> = None;
-    let mut instructions: Vec<Instructions> = Vec::new();
+    match args.cmd {
+        Commands::Run {
+            path,
+            bin,
+            debug,
+            mut breakpoints,
+            debug_dump,
+            debug_depth,
+            debug_every,
+            debug_to,
+            debug_diff,
+            step,
+            trap_fallthrough,
+            defines,
+            log_level,
+            unbuffered,
+            max_memory,
+            max_output_bytes,
+            allow_sound,
+            summary,
+            timeout,
+            compat,
+            checkpoint_every,
+            checkpoint_dir,
+            record,
+            replay,
+            trace,
+            budgets,
+            depth_chart,
+            mmap,
+            args,
+        } => {
+            let debug_to = debug_to.as_deref().map(create_output);
+            let trace_to = trace.as_deref().map(create_output);
+            let depth_chart_to = depth_chart.as_deref().map(create_output);
 
-    for line in lines {
-        if line.starts_with(&['/', '#']) || line.is_empty() {
-            continue;
-        }
+            let (path, entry) = match (path, bin) {
+                (Some(path), _) => (path, "main".to_string()),
+                (None, Some(bin)) => find_bin(&bin),
+                (None, None) => unreachable!("clap requires a path or a bin"),
+            };
 
-        // We have found a section
-        if line.starts_with("::") && line.ends_with(':') {
-            if !instructions.is_empty() {
-                program.push(Program::Section(
-                    current_section
-                        .take()
-                        .unwrap_or_else(|| SectionName("main".to_string())),
-                    instructions.drain(..).collect(),
-                ));
+            // Without breakpoints the debugger pauses before the first instruction
+            if debug && breakpoints.is_empty() {
+                breakpoints.push(Breakpoint::Section(entry.clone()));
             }
 
-            current_section = Some(SectionName(line.trim_matches(':').to_string()));
-            continue;
+            let replay = replay.map(|path| match Replay::load(&path) {
+                Ok(replay) => replay,
+                Err(error) => {
+                    eprintln!("error: {error}");
+                    std::process::exit(1);
+                }
+            });
+
+            interpret(
+                path,
+                RunOptions {
+                    debug: debug || debug_dump,
+                    debug_depth,
+                    debug_every,
+                    debug_to,
+                    trace_to,
+                    depth_chart_to,
+                    debug_diff,
+                    step,
+                    trap_fallthrough,
+                    defines,
+                    debugger: debug,
+                    breakpoints,
+                    logger: Box::new(StderrLogger { level: log_level }),
+                    buffered: !unbuffered,
+                    max_memory,
+                    max_output_bytes,
+                    timeout,
+                    files: true,
+                    frames: true,
+                    speaker: allow_sound.then(|| Box::new(TerminalBell) as Box<dyn Speaker>),
+                    compat,
+                    checkpoint_every,
+                    checkpoint_dir,
+                    record,
+                    replay,
+                    host_functions: Vec::new(),
+                    metering: None,
+                    budgets,
+                    args,
+                    entry,
+                },
+                mmap,
+                summary,
+            );
         }
+        Commands::Debug {
+            path,
+            checkpoint,
+            breakpoints,
+            defines,
+        } => {
+            let options = RunOptions {
+                defines,
+                debugger: true,
+                breakpoints,
+                logger: Box::new(StderrLogger {
+                    level: LogLevel::Trace,
+                }),
+                buffered: false,
+                ..RunOptions::default()
+            };
 
-        let (instruction, value) = line.split_once(" ").unwrap_or_else(|| (line, ""));
+            match (path, checkpoint) {
+                (_, Some(checkpoint)) => resume(&checkpoint, options),
+                (Some(path), None) => interpret(path, options, false, false),
+                (None, None) => unreachable!("clap requires a path or a checkpoint"),
+            }
+        }
+        Commands::Compile {
+            path,
+            output,
+            defines,
+        } => {
+            let options = RunOptions {
+                defines,
+                ..RunOptions::default()
+            };
 
-        instructions.push(match instruction.to_lowercase().as_str() {
-            "push" => {
-                if value == "" {
-                    panic!("push requires a value");
-                };
+            let source = read_source(&path);
 
-                if value.starts_with('"') && value.ends_with('"') {
-                    Instructions::Push(DataType::String(value.trim_matches('"').replace("\\n", "\n").replace("\\r", "\r").to_string()))
-                } else if value.contains('.') {
-                    Instructions::Push(DataType::Float(value.parse::<f64>().unwrap()))
-                } else if value == "true" || value == "false" {
-                    Instructions::Push(DataType::Bool(value.parse::<bool>().unwrap()))
-                } else {
-                    Instructions::Push(DataType::Int(value.parse::<usize>().unwrap()))
-                }
-            }
-            "eq" => Instructions::EQ,
-            "ne" => Instructions::NE,
-            "and" => Instructions::And,
-            "or" => Instructions::Or,
-            "not" => Instructions::Not,
-            "add" => Instructions::Add,
-            "sub" => Instructions::Sub,
-            "mul" => Instructions::Mul,
-            "div" => Instructions::Div,
-            "mod" => Instructions::Mod,
-            "drop" => Instructions::Drop,
-            "dup" => Instructions::Dup,
-            "swap" => Instructions::Swap,
-            "over" => Instructions::Over,
-            "rot" => Instructions::Rot,
-            "print" => Instructions::Print,
-            "exit" => Instructions::Exit,
-            "jump" => {
-                if value == "" {
-                    panic!("jump requires a label");
-                };
+            let mut program =
+                try_parse(&source, &options).unwrap_or_else(|error| parse_failed(error, &path));
+            link_imports(&mut program, &path, &options);
+            validate(&program, &path);
 
-                Instructions::Jump(value.to_string())
+            let output = output.unwrap_or_else(|| path.with_extension("toyc"));
+            if let Err(error) = std::fs::write(&output, bytecode::compile(&program)) {
+                eprintln!("error: cannot write {}: {error}", output.display());
+                std::process::exit(1);
             }
-            "ifjmp" => {
-                if value == "" {
-                    panic!("ifjmp requires a label");
-                };
+        }
+        Commands::Pack {
+            path,
+            output,
+            bytecode,
+            defines,
+        } => {
+            let options = RunOptions {
+                defines,
+                ..RunOptions::default()
+            };
 
-                Instructions::IfJmp(value.to_string())
-            }
-            _ => {
-                panic!("Unknown instruction: {line}");
-            }
-        });
-    }
+            let source = read_source(&path);
+
+            let program =
+                pack::pack(&source, &options).unwrap_or_else(|error| parse_failed(error, &path));
+
+            let (packed, output) = if bytecode {
+                let output = output.unwrap_or_else(|| path.with_extension("toyc"));
+                (bytecode::compile(&program), Some(output))
+            } else {
+                (pack::to_source(&program).into_bytes(), output)
+            };
 
-    if !instructions.is_empty() {
-        if current_section.is_none() {
-            current_section = Some(SectionName("main".to_string()));
+            let written = match &output {
+                Some(output) => std::fs::write(output, packed)
+                    .map_err(|error| format!("cannot write {}: {error}", output.display())),
+                None => std::io::Write::write_all(&mut std::io::stdout(), &packed)
+                    .map_err(|error| format!("cannot write to stdout: {error}")),
+            };
+
+            if let Err(error) = written {
+                eprintln!("error: {error}");
+                std::process::exit(1);
+            }
+        }
+        Commands::Repl => {
+            repl::run(RunOptions {
+                logger: Box::new(StderrLogger {
+                    level: LogLevel::Trace,
+                }),
+                buffered: false,
+                ..RunOptions::default()
+            });
         }
+        Commands::Conformance { dir } => run_tests(
+            TestCase::load_dir(&dir),
+            Limits::default(),
+            default_jobs(),
+            TestFormat::Text,
+            None,
+        ),
+        Commands::Test {
+            args,
+            exact,
+            rerun_failed,
+            timeout,
+            max_memory,
+            format,
+            jobs,
+        } => {
+            let (paths, filters): (Vec<_>, Vec<_>) =
+                args.into_iter().partition(|arg| Path::new(arg).exists());
 
-        program.push(Program::Section(
-            current_section.take().unwrap(),
-            instructions.drain(..).collect(),
-        ));
-    }
+            let paths: Vec<PathBuf> = if paths.is_empty() {
+                vec![PathBuf::from(".")]
+            } else {
+                paths.into_iter().map(PathBuf::from).collect()
+            };
 
-    // Do static analysis on AST
+            let previous_failures = rerun_failed.then(|| {
+                std::fs::read_to_string(FAILURES_FILE)
+                    .map(|names| names.lines().map(str::to_string).collect::<Vec<_>>())
+                    .unwrap_or_default()
+            });
 
-    for Program::Section(_, instructions) in &program {
-        
-        instructions.iter().for_each(|instruction| {
-            match instruction {
-                Instructions::Jump(label) | Instructions::IfJmp(label) => {
-                    let mut found = false;
+            let cases = TestCase::discover(&paths).map(|cases| {
+                cases
+                    .into_iter()
+                    .filter(|case| {
+                        filters.is_empty()
+                            || filters.iter().any(|filter| {
+                                if exact {
+                                    case.name == *filter
+                                } else {
+                                    case.name.contains(filter.as_str())
+                                }
+                            })
+                    })
+                    .filter(|case| {
+                        previous_failures
+                            .as_ref()
+                            .is_none_or(|failures| failures.contains(&case.name))
+                    })
+                    .collect()
+            });
+
+            run_tests(
+                cases,
+                Limits {
+                    timeout,
+                    max_memory,
+                },
+                jobs.unwrap_or_else(default_jobs),
+                format,
+                Some(Path::new(FAILURES_FILE)),
+            );
+        }
+        Commands::Lint {
+            path,
+            config,
+            fix,
+            dry_run,
+        } => {
+            let config = match config {
+                Some(config) => LintConfig::load(&config),
+                None if Path::new(CONFIG_FILE).exists() => LintConfig::load(Path::new(CONFIG_FILE)),
+                None => Ok(LintConfig::default()),
+            };
+            let config = config.unwrap_or_else(|error| {
+                eprintln!("error: {error}");
+                std::process::exit(1);
+            });
 
-                    for Program::Section(name, _) in &program {
-                        if &name.0 == label {
-                            found = true;
-                            break;
+            let mut source = read_source(&path);
+            let options = RunOptions::default();
+
+            if fix {
+                let (fixed, fixes) = lint::fix(&source, &options, &config);
+
+                if dry_run {
+                    for line in test_report::diff_lines(&source, &fixed).lines() {
+                        if !line.starts_with(' ') {
+                            println!("{line}");
                         }
                     }
-
-                    if !found {
-                        panic!("jump found to unknown label: {label}");
+                } else if fixes > 0 {
+                    if let Err(error) = std::fs::write(&path, &fixed) {
+                        eprintln!("error: cannot write {}: {error}", path.display());
+                        std::process::exit(1);
                     }
                 }
-                _ => {}
-            }
-        });
-
-    }
 
+                eprintln!(
+                    "{} {fixes} problem{}",
+                    if dry_run { "Would fix" } else { "Fixed" },
+                    if fixes == 1 { "" } else { "s" }
+                );
+                source = fixed;
+            }
 
-    // Interpret the "AST" to run the program
+            let program =
+                try_parse(&source, &options).unwrap_or_else(|error| parse_failed(error, &path));
+            let warnings = lint(&program, &config);
 
-    let mut stack: Vec<DataType> = Vec::new();
+            for warning in &warnings {
+                println!("{warning}");
+            }
 
-    let mut program_instructions: Vec<Instructions> = Vec::new();
-    let mut ic = 0;
+            if !warnings.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Commands::Fetch { update } => {
+            let manifest = Manifest::load(Path::new(CONFIG_FILE)).unwrap_or_else(|error| {
+                eprintln!("error: {error}");
+                std::process::exit(1);
+            });
 
-    for section in &program {
-        match section {
-            Program::Section(name, instructions) => {
-                if name.0 == "main" {
-                    program_instructions = instructions.to_vec();
-                    break;
+            match dependencies::fetch(Path::new(""), &manifest, update) {
+                Ok(lock) => {
+                    for locked in &lock.dependencies {
+                        match &locked.rev {
+                            Some(rev) => {
+                                println!("Fetched {} from {} at {rev}", locked.name, locked.source)
+                            }
+                            None => println!("Fetched {} from {}", locked.name, locked.source),
+                        }
+                    }
+                }
+                Err(error) => {
+                    eprintln!("error: {error}");
+                    std::process::exit(1);
                 }
             }
         }
-    }
+        Commands::Check { path, defines } => {
+            let options = RunOptions {
+                defines,
+                ..RunOptions::default()
+            };
 
-    if program_instructions.is_empty() {
-        panic!("No main section found");
-    }
+            let source = read_source(&path);
 
-    while ic < program_instructions.len() {
-        let instruction = program_instructions[ic].clone();
+            let problems = check::check(&source, &options);
 
-        if debug {
-            println!("Stack: {:?}", stack);
-            println!("Running Instruction: {:?}", instruction);
-        }
-
-        match instruction {
-            Instructions::Push(value) => {
-                stack.push(value);
+            for problem in &problems {
+                eprintln!("error: {}", problem.clone().in_file(&path));
             }
-            Instructions::Add => {
-                let (Some(a), Some(b)) = (stack.pop(), stack.pop()) else {
-                    panic!("Not enough values on the stack to add");
-                };
 
-                match (&a, &b) {
-                    (DataType::Int(a), DataType::Int(b)) => {
-                        stack.push(DataType::Int(a + b));
-                    }
-                    (DataType::Float(a), DataType::Float(b)) => {
-                        stack.push(DataType::Float(a + b));
-                    }
-                    _ => {
-                        panic!("Cannot add non-numeric values {:?} and {:?}", a, b);
-                    }
-                }
+            if !problems.is_empty() {
+                eprintln!(
+                    "Found {} problem{}",
+                    problems.len(),
+                    if problems.len() == 1 { "" } else { "s" }
+                );
+                std::process::exit(1);
             }
-            Instructions::Sub => {
-                let (Some(a), Some(b)) = (stack.pop(), stack.pop()) else {
-                    panic!("Not enough values on the stack to subtract");
-                };
 
-                match (&a, &b) {
-                    (DataType::Int(a), DataType::Int(b)) => {
-                        stack.push(DataType::Int(a - b));
-                    }
-                    (DataType::Float(a), DataType::Float(b)) => {
-                        stack.push(DataType::Float(a - b));
-                    }
-                    _ => {
-                        panic!("Cannot subtract non-numeric values {:?} and {:?}", a, b);
-                    }
-                }
+            println!("No problems found");
+        }
+        Commands::Lsp => {
+            if let Err(error) = lsp::serve(std::io::stdin().lock(), std::io::stdout().lock()) {
+                eprintln!("error: {error}");
+                std::process::exit(1);
             }
-            Instructions::Mul => {
-                let (Some(a), Some(b)) = (stack.pop(), stack.pop()) else {
-                    panic!("Not enough values on the stack to multiply");
-                };
+        }
+        Commands::Index { dir, find } => {
+            let index = update_index(&dir);
 
-                match (&a, &b) {
-                    (DataType::Int(a), DataType::Int(b)) => {
-                        stack.push(DataType::Int(a * b));
-                    }
-                    (DataType::Float(a), DataType::Float(b)) => {
-                        stack.push(DataType::Float(a * b));
+            match find {
+                Some(name) => {
+                    let mut found = false;
+
+                    for (path, symbol) in index.find(&name) {
+                        found = true;
+                        println!(
+                            "{}:{} {} {}",
+                            path.display(),
+                            symbol.line,
+                            symbol.kind,
+                            name
+                        );
                     }
-                    _ => {
-                        panic!("Cannot multiply non-numeric values {:?} and {:?}", a, b);
+
+                    if !found {
+                        eprintln!("error: {name} is not defined");
+                        std::process::exit(1);
                     }
                 }
+                None => eprintln!("Indexed {} files", index.files.len()),
             }
-            Instructions::Div => {
-                let (Some(a), Some(b)) = (stack.pop(), stack.pop()) else {
-                    panic!("Not enough values on the stack to divide");
+        }
+        Commands::Refactor { refactoring } => match refactoring {
+            Refactoring::RenameSection { old, new, files } => {
+                let files = if files.is_empty() {
+                    update_index(Path::new("."))
+                        .files
+                        .into_iter()
+                        .map(|file| file.path)
+                        .collect()
+                } else {
+                    files
                 };
 
-                match (&a, &b) {
-                    (DataType::Int(a), DataType::Int(b)) => {
-                        if b == &0 {
-                            panic!("Cannot divide by zero");
-                        }
-
-                        stack.push(DataType::Int(a / b));
-                    }
-                    (DataType::Float(a), DataType::Float(b)) => {
-                        if b == &0.0 {
-                            panic!("Cannot divide by zero");
-                        }
+                let sources: Vec<String> = files.iter().map(|path| read_source(path)).collect();
 
-                        stack.push(DataType::Float(a / b));
+                let (renamed, changed) = match refactor::rename_section(&sources, &old, &new) {
+                    Ok(renamed) => renamed,
+                    Err(error) => {
+                        eprintln!("error: {error}");
+                        std::process::exit(1);
                     }
-                    _ => {
-                        panic!("Cannot divide non-numeric values {:?} and {:?}", a, b);
-                    }
-                };
-            }
-            Instructions::Mod => {
-                let (Some(a), Some(b)) = (stack.pop(), stack.pop()) else {
-                    panic!("Not enough values on the stack to modulo");
                 };
 
-                match (&a, &b) {
-                    (DataType::Int(a), DataType::Int(b)) => {
-                        stack.push(DataType::Int(a % b));
-                    }
-                    _ => {
-                        panic!("Cannot modulo non-numeric values {:?} and {:?}", a, b);
-                    }
-                }
+                write_sources(&files, &sources, &renamed);
+                eprintln!("Renamed {old} to {new} on {changed} lines");
             }
-            Instructions::Dup => {
-                let Some(a) = stack.last().cloned() else {
-                    panic!("Nothing to duplicate");
-                };
+            Refactoring::Extract { file, lines, name } => {
+                let source = read_source(&file);
 
-                stack.push(a);
-            }
-            Instructions::Swap => {
-                let (Some(a), Some(b)) = (stack.pop(), stack.pop()) else {
-                    panic!("Not enough values on the stack to swap");
-                };
+                let (extracted, (pops, pushes)) =
+                    match refactor::extract_section(&source, lines, &name) {
+                        Ok(extracted) => extracted,
+                        Err(error) => {
+                            eprintln!("error: {error}");
+                            std::process::exit(1);
+                        }
+                    };
 
-                stack.push(a);
-                stack.push(b);
+                write_sources(&[file], &[source], &[extracted]);
+                eprintln!("Extracted {name} ( {pops} -- {pushes} )");
             }
-            Instructions::Over => {
-                let Some(b) = stack.get(stack.len() - 2).cloned() else {
-                    panic!("Not enough values on the stack to duplicate");
-                };
+        },
+        Commands::Stats { path, format } => {
+            let file = open_source(&path);
+            let program = try_parse_reader(std::io::BufReader::new(file), &RunOptions::default())
+                .unwrap_or_else(|error| parse_failed(error, &path));
+            let stats = Stats::new(&program);
 
-                stack.push(b);
+            match format {
+                StatsFormat::Text => print!("{stats}"),
+                StatsFormat::Json => println!("{}", stats.to_json()),
             }
-            Instructions::Rot => {
-                let (Some(a), Some(b), Some(c)) = (stack.pop(), stack.pop(), stack.pop()) else {
-                    panic!("Not enough values on the stack to rotate");
-                };
+        }
+        Commands::Highlight { path, format } => {
+            let source = read_source(&path);
 
-                stack.push(b);
-                stack.push(a);
-                stack.push(c);
+            match format {
+                HighlightFormat::Ansi => print!("{}", highlight::to_ansi(&source)),
+                HighlightFormat::Html => print!("{}", highlight::to_html(&source)),
             }
-            Instructions::EQ => {
-                let (Some(a), Some(b)) = (stack.pop(), stack.pop()) else {
-                    panic!("Not enough values on the stack to compare");
-                };
+        }
+        Commands::Minimize {
+            path,
+            check,
+            output,
+            timeout,
+        } => {
+            let source = read_source(&path);
 
-                stack.push(DataType::Bool(a == b));
-            }
-            Instructions::NE => {
-                let (Some(a), Some(b)) = (stack.pop(), stack.pop()) else {
-                    panic!("Not enough values on the stack to compare");
-                };
+            let limits = Limits {
+                timeout,
+                max_memory: None,
+            };
+            let minimized = minimize(&source, &check, limits).unwrap_or_else(|error| {
+                eprintln!("error: {}: {error}", path.display());
+                std::process::exit(1);
+            });
 
-                stack.push(DataType::Bool(a != b));
-            }
-            Instructions::And => {
-                let (Some(a), Some(b)) = (stack.pop(), stack.pop()) else {
-                    panic!("Not enough values on the stack to compare");
-                };
+            eprintln!(
+                "Reduced {} lines to {} in {} runs",
+                source.lines().count(),
+                minimized.source.lines().count(),
+                minimized.runs
+            );
 
-                match (&a, &b) {
-                    (DataType::Bool(a), DataType::Bool(b)) => {
-                        stack.push(DataType::Bool(*a && *b));
-                    }
-                    _ => {
-                        panic!("Cannot compare non-boolean values {:?} and {:?}", a, b);
+            match output {
+                Some(output) => {
+                    if let Err(error) = std::fs::write(&output, &minimized.source) {
+                        eprintln!("error: cannot write {}: {error}", output.display());
+                        std::process::exit(1);
                     }
                 }
+                None => print!("{}", minimized.source),
             }
-            Instructions::Or => {
-                let (Some(a), Some(b)) = (stack.pop(), stack.pop()) else {
-                    panic!("Not enough values on the stack to compare");
-                };
+        }
+        Commands::ExplainRun {
+            path,
+            steps,
+            defines,
+        } => {
+            let options = RunOptions {
+                defines,
+                ..RunOptions::default()
+            };
 
-                match (&a, &b) {
-                    (DataType::Bool(a), DataType::Bool(b)) => {
-                        stack.push(DataType::Bool(*a || *b));
-                    }
-                    _ => {
-                        panic!("Cannot compare non-boolean values {:?} and {:?}", a, b);
-                    }
-                }
-            }
-            Instructions::Not => {
-                let Some(a) = stack.pop() else {
-                    panic!("Not enough values on the stack to compare");
-                };
+            let source = read_source(&path);
 
-                match a {
-                    DataType::Bool(a) => stack.push(DataType::Bool(!a)),
-                    _ => {
-                        panic!("Cannot compare non-boolean value {:?}", a);
-                    }
+            let program =
+                try_parse(&source, &options).unwrap_or_else(|error| parse_failed(error, &path));
+            validate(&program, &path);
+
+            print!("{}", explain::explain_run(program, steps));
+        }
+        Commands::Tracediff { a, b } => {
+            let load = |path: &Path| {
+                trace::load(path).unwrap_or_else(|error| {
+                    eprintln!("error: {error}");
+                    std::process::exit(1);
+                })
+            };
+            let (a, b) = (load(&a), load(&b));
+
+            match trace::diff(&a, &b) {
+                Some(divergence) => {
+                    print!("{divergence}");
+                    std::process::exit(1);
                 }
+                None => println!("traces match for all {} steps", a.len()),
             }
-            Instructions::Drop => {
-                stack.pop();
-            }
-            Instructions::Exit => {
-                break;
-            }
-            Instructions::Jump(label) => {
-                let mut found = false;
-
-                for section in &program {
-                    match section {
-                        Program::Section(name, instructions) => {
-                            if name.0 == label {
-                                program_instructions.splice(ic..ic+1, instructions.to_vec());
-                                found = true;
-                                break;
-                            }
-                        }
+        }
+        Commands::Doc { name, all, format } => {
+            let instructions = match name {
+                Some(name) if !all => match reference::instruction_info(&name) {
+                    Some(info) => std::slice::from_ref(info),
+                    None => {
+                        eprintln!("error: unknown instruction: {name}");
+                        std::process::exit(1);
                     }
-                }
+                },
+                _ => reference::INSTRUCTION_SET,
+            };
 
-                if !found {
-                    panic!("Unknown label: {label}");
-                }
+            match format {
+                DocFormat::Markdown => print!("{}", reference::to_markdown(instructions)),
+                DocFormat::Json => print!("{}", reference::to_json(instructions)),
+            }
+        }
+    }
+}
+
+fn interpret(path: PathBuf, options: RunOptions, mmap: bool, summary: bool) {
+    if let Some(program) = load_bytecode(&path) {
+        validate(&program, &path);
 
-                continue;
+        // Bytecode keeps no line numbers, so errors cannot point into the source
+        return execute(Interpreter::new(program, options), summary, None);
+    }
+
+    // SAFETY: `--mmap` asks the user to leave the program alone while it runs, and the
+    // mapping is dropped once it has been parsed
+    let mapped = mmap
+        .then(|| unsafe { Mmap::open(&path) })
+        .and_then(|mapped| {
+            mapped
+                .inspect_err(|error| {
+                    eprintln!(
+                        "warning: cannot map {}, reading it instead: {error}",
+                        path.display()
+                    )
+                })
+                .ok()
+        });
+
+    let program = match mapped {
+        Some(mapped) => match mapped.as_str() {
+            Ok(source) => try_parse(source, &options),
+            Err(error) => {
+                eprintln!("error: cannot read {}: {error}", path.display());
+                std::process::exit(1);
             }
-            Instructions::IfJmp(label) => {
-                let Some(a) = stack.pop() else {
-                    panic!("Not enough values on the stack to compare");
-                };
+        },
+        None => try_parse_reader(std::io::BufReader::new(open_source(&path)), &options),
+    };
 
-                let should_jump = match a {
-                    DataType::Bool(a) => a,
-                    DataType::Int(a) => a == 0,
-                    _ => {
-                        panic!("Cannot compare non-numeric values {:?}", a);
-                    }
-                };
+    let mut program = program.unwrap_or_else(|error| parse_failed(error, &path));
+    link_imports(&mut program, &path, &options);
+    validate(&program, &path);
 
-                if should_jump {
-                    let mut found = false;
+    let debugger = options.debugger;
+    let mut interpreter = Interpreter::new(program, options);
+    if debugger {
+        if let Ok(source) = std::fs::read_to_string(&path) {
+            interpreter.set_source(&source);
+        }
+    }
 
-                    for section in &program {
-                        match section {
-                            Program::Section(name, instructions) => {
-                                if name.0 == label {
-                                    program_instructions
-                                        .splice(ic..ic+1, instructions.to_vec());
-                                    found = true;
-                                    break;
-                                }
-                            }
-                        }
-                    }
+    execute(interpreter, summary, Some(&path));
+}
 
-                    if !found {
-                        panic!("Unknown label: {label}");
-                    }
+/// Loads the program at `path` if it is bytecode written by `compile`, exiting with an
+/// error if it is bytecode that cannot be loaded
+fn load_bytecode(path: &Path) -> Option<Vec<Program>> {
+    let mut magic = [0; bytecode::MAGIC.len()];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .ok()?;
 
-                    continue;
-                }
+    if !bytecode::is_bytecode(&magic) {
+        return None;
+    }
+
+    let program = std::fs::read(path)
+        .map_err(|error| format!("cannot read {}: {error}", path.display()))
+        .and_then(|bytes| {
+            bytecode::load(&bytes).map_err(|error| format!("{}: {error}", path.display()))
+        });
+
+    match program {
+        Ok(program) => Some(program),
+        Err(error) => {
+            eprintln!("error: {error}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Reads the program at `path`, exiting with an error if it cannot be read
+fn read_source(path: &Path) -> String {
+    std::fs::read_to_string(path).unwrap_or_else(|error| {
+        eprintln!("error: cannot read {}: {error}", path.display());
+        std::process::exit(1);
+    })
+}
+
+/// Opens the program at `path` to be read a line at a time, exiting with an error if it
+/// cannot be opened
+fn open_source(path: &Path) -> std::fs::File {
+    std::fs::File::open(path).unwrap_or_else(|error| {
+        eprintln!("error: cannot read {}: {error}", path.display());
+        std::process::exit(1);
+    })
+}
+
+/// Opens a file for debug or trace output, exiting with an error if it cannot be created
+fn create_output(path: &Path) -> Box<dyn std::io::Write> {
+    match std::fs::File::create(path) {
+        Ok(file) => Box::new(std::io::BufWriter::new(file)),
+        Err(error) => {
+            eprintln!("error: cannot create {}: {error}", path.display());
+            std::process::exit(1);
+        }
+    }
+}
+
+/// The path and entry section of the `[[bin]]` called `name` in the project's toy.toml,
+/// exiting with an error if there is none
+fn find_bin(name: &str) -> (PathBuf, String) {
+    let manifest = Manifest::load(Path::new(CONFIG_FILE)).unwrap_or_else(|error| {
+        eprintln!("error: {error}");
+        std::process::exit(1);
+    });
+
+    let Some(bin) = manifest.bin(name) else {
+        let names: Vec<&str> = manifest.bins.iter().map(|bin| bin.name.as_str()).collect();
+
+        match names.as_slice() {
+            [] => eprintln!("error: {CONFIG_FILE} defines no [[bin]] entry points"),
+            names => eprintln!(
+                "error: no [[bin]] named {name} in {CONFIG_FILE}, expected one of: {}",
+                names.join(", ")
+            ),
+        }
+        std::process::exit(1);
+    };
+
+    (bin.path.clone(), bin.section.clone())
+}
+
+/// Reports a program that does not parse and exits with an error
+fn parse_failed(error: ParseError, path: &Path) -> ! {
+    // Errors in an imported library already name its file
+    let error = match error.file {
+        Some(_) => error,
+        None => error.in_file(path),
+    };
+
+    eprintln!("error: {error}");
+    std::process::exit(1);
+}
+
+/// Validates the program at `path`, exiting with an error if it is invalid and printing a
+/// warning for each section that looks like it falls through into the next one
+fn validate(program: &[Program], path: &Path) {
+    try_validate(program).unwrap_or_else(|error| parse_failed(error, path));
+
+    for warning in fallthrough_warnings(program) {
+        eprintln!("warning: {warning}");
+    }
+}
+
+/// Adds the libraries the program at `path` `#import`s from the project's vendored
+/// dependencies, exiting with an error if they cannot be found
+fn link_imports(program: &mut Vec<Program>, path: &Path, options: &RunOptions) {
+    let Ok(file) = std::fs::File::open(path) else {
+        return;
+    };
+
+    let imports = dependencies::imports(std::io::BufReader::new(file));
+    if imports.is_empty() {
+        return;
+    }
+
+    let manifest = Manifest::load(Path::new(CONFIG_FILE)).unwrap_or_else(|error| {
+        eprintln!("error: {error}");
+        std::process::exit(1);
+    });
+
+    dependencies::link(program, imports, Path::new(""), &manifest, options)
+        .unwrap_or_else(|error| parse_failed(error, path));
+}
+
+/// Brings the symbol index of the project in `dir` up to date, exiting with an error if
+/// that fails
+fn update_index(dir: &Path) -> SymbolIndex {
+    let path = dir.join(INDEX_FILE);
+
+    let result = SymbolIndex::load(&path).and_then(|mut index| {
+        index.update(&project_files(dir)?)?;
+        index
+            .save(&path)
+            .map_err(|error| format!("Cannot write {}: {error}", path.display()))?;
+        Ok(index)
+    });
+
+    result.unwrap_or_else(|error| {
+        eprintln!("error: {error}");
+        std::process::exit(1);
+    })
+}
+
+/// Writes back the files whose source changed
+fn write_sources(paths: &[PathBuf], before: &[String], after: &[String]) {
+    for ((path, before), after) in paths.iter().zip(before).zip(after) {
+        if before == after {
+            continue;
+        }
+
+        if let Err(error) = std::fs::write(path, after) {
+            eprintln!("error: cannot write {}: {error}", path.display());
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Continues a program from a checkpoint
+fn resume(path: &Path, options: RunOptions) {
+    let checkpoint = match Checkpoint::load(path) {
+        Ok(checkpoint) => checkpoint,
+        Err(error) => {
+            eprintln!("error: {error}");
+            std::process::exit(1);
+        }
+    };
+
+    eprintln!("Resuming after {} instructions", checkpoint.step);
+    if let Some(instruction) = checkpoint.instructions.get(checkpoint.ic) {
+        eprintln!("Next: {} | {instruction}", checkpoint.ic);
+    }
+    eprintln!("Stack: {}", Stack::from(checkpoint.stack.clone()).render());
+
+    execute(Interpreter::restore(checkpoint, options), false, None);
+}
+
+/// Runs the program to its end, exiting with an error if it fails. Errors point at the
+/// line of `path` the failing instruction was read from
+fn execute(mut interpreter: Interpreter, summary: bool, path: Option<&Path>) {
+    let started = Instant::now();
+    let result = interpreter.run();
+    let elapsed = started.elapsed();
+
+    if let Err(error) = &result {
+        let location = path.and_then(|path| {
+            let source = std::fs::read_to_string(path).ok()?;
+            let location = interpreter.error_location(&source)?;
+
+            Some(SourceLocation {
+                file: Some(path.to_path_buf()),
+                ..location
+            })
+        });
+
+        match location {
+            Some(location) => {
+                eprintln!("error: {location}: {error}");
+                eprintln!("    {} | {}", location.line, location.instruction);
             }
-            Instructions::Print => {
-                if stack.is_empty() {
-                    panic!("Nothing to print");
-                }
+            None => eprintln!("error: {error}"),
+        }
 
-                match stack.pop().unwrap() {
-                    DataType::Bool(a) => print!("{}", a),
-                    DataType::Int(a) => print!("{}", a),
-                    DataType::Float(a) => print!("{}", a),
-                    DataType::String(a) => print!("{}", a),
-                }
+        if let Some(context) = interpreter.error_context() {
+            eprintln!("{context}");
+        }
+    }
+
+    let code = if result.is_ok() { 0 } else { 1 };
+
+    if summary {
+        eprintln!(
+            "[toylang] exited with code {code} after {} steps in {elapsed:.1?} (peak stack {})",
+            interpreter.steps(),
+            interpreter.peak_depth()
+        );
+    }
+
+    if code != 0 {
+        // Exiting skips destructors, so drop the interpreter first to flush debug output
+        drop(interpreter);
+        std::process::exit(code);
+    }
+}
+
+/// Runs test cases, reporting each one and exiting with an error if any failed. The names
+/// of the ones that failed are saved to `failures_file` for `--rerun-failed`
+fn run_tests(
+    cases: Result<Vec<TestCase>, String>,
+    limits: Limits,
+    jobs: usize,
+    format: TestFormat,
+    failures_file: Option<&Path>,
+) {
+    let cases = match cases {
+        Ok(cases) => cases,
+        Err(error) => {
+            eprintln!("error: {error}");
+            std::process::exit(1);
+        }
+    };
+
+    if cases.is_empty() {
+        eprintln!("warning: no tests found");
+    }
+
+    // Instructions that fail by panicking are turned into failures by the runner
+    std::panic::set_hook(Box::new(|_| {}));
+
+    let results = evaluate_all(&cases, limits, jobs, |result| {
+        if let TestFormat::Text = format {
+            let (label, details) = match &result.status {
+                Status::Passed => ("ok  ", ""),
+                Status::Failed(problems) => ("FAIL", problems.as_str()),
+                Status::TimedOut(message) => ("TIME", message.as_str()),
+            };
+
+            println!("{label} {}", result.name);
+            for line in details.lines() {
+                println!("     {line}");
             }
         }
-        ic += 1;
+    });
+
+    let _ = std::panic::take_hook();
+
+    let count = |failure: fn(&Status) -> bool| {
+        results
+            .iter()
+            .filter(|result| failure(&result.status))
+            .count()
+    };
+    let failed = count(|status| matches!(status, Status::Failed(_)));
+    let timed_out = count(|status| matches!(status, Status::TimedOut(_)));
+
+    match format {
+        TestFormat::Text => println!(
+            "{} passed, {failed} failed, {timed_out} timed out",
+            results.len() - failed - timed_out
+        ),
+        TestFormat::Junit => print!("{}", test_report::to_junit(&results)),
+        TestFormat::Json => println!("{}", test_report::to_json(&results)),
+    }
+
+    if let Some(path) = failures_file {
+        let names: String = results
+            .iter()
+            .filter(|result| result.status != Status::Passed)
+            .map(|result| format!("{}\n", result.name))
+            .collect();
+
+        if let Err(error) = std::fs::write(path, names) {
+            eprintln!("warning: cannot write {}: {error}", path.display());
+        }
+    }
+
+    if failed + timed_out > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// Runs one test per CPU
+fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(1, |count| count.get())
+}
+
+/// Parses a byte count with an optional K, M or G suffix
+fn parse_bytes(value: &str) -> Result<usize, String> {
+    let value = value.trim().to_uppercase();
+    let value = value.strip_suffix('B').unwrap_or(&value);
+
+    let (digits, multiplier) = match value.chars().last() {
+        Some('K') => (&value[..value.len() - 1], 1 << 10),
+        Some('M') => (&value[..value.len() - 1], 1 << 20),
+        Some('G') => (&value[..value.len() - 1], 1 << 30),
+        _ => (value, 1),
+    };
+
+    digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|count| count.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid byte count: {value}"))
+}
+
+/// Parses a duration given in milliseconds, seconds or minutes, defaulting to seconds
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+
+    let (digits, scale) = if let Some(ms) = value.strip_suffix("ms") {
+        (ms, 1.0 / 1000.0)
+    } else if let Some(s) = value.strip_suffix('s') {
+        (s, 1.0)
+    } else if let Some(m) = value.strip_suffix('m') {
+        (m, 60.0)
+    } else {
+        (value, 1.0)
+    };
+
+    // Negative, infinite, NaN and overly long durations are all rejected here
+    digits
+        .parse::<f64>()
+        .ok()
+        .and_then(|count| Duration::try_from_secs_f64(count * scale).ok())
+        .ok_or_else(|| format!("invalid duration: {value}"))
+}
+
+/// Parses an inclusive range of line numbers like `4-9`, or a single line
+fn parse_line_range(value: &str) -> Result<RangeInclusive<usize>, String> {
+    let (start, end) = value.split_once('-').unwrap_or((value, value));
+
+    match (start.trim().parse(), end.trim().parse()) {
+        (Ok(start), Ok(end)) => Ok(start..=end),
+        _ => Err(format!("invalid line range: {value}")),
     }
 }
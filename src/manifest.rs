@@ -0,0 +1,282 @@
+//! The entry points a project defines in its `toy.toml`, so a project of exercises can keep
+//! them side by side and run each with `toylang run --bin NAME`:
+//!
+//! ```toml
+//! [[bin]]
+//! name = "fizzbuzz"
+//! path = "exercises/basics.toy"
+//! section = "fizzbuzz"
+//! ```
+//!
+//! `section` is where the program starts, `main` if it is left out.
+//!
+//! It also lists the libraries the project's programs `#import`, which
+//! [`toylang fetch`](crate::dependencies) vendors into the project. A library is a single
+//! `.toy` file, either on disk or in a git repository (`lib.toy` unless `file` says
+//! otherwise), optionally at a given `rev`:
+//!
+//! ```toml
+//! [dependencies]
+//! strings = { path = "../shared/strings.toy" }
+//! maths = { git = "https://example.com/maths.git", file = "maths.toy", rev = "v1.2" }
+//! ```
+//!
+//! Paths are relative to the directory of the `toy.toml`.
+
+use std::path::{Path, PathBuf};
+
+use crate::dependencies::is_relative_inside;
+
+/// A program of the project and the section it starts from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bin {
+    pub name: String,
+    pub path: PathBuf,
+    pub section: String,
+}
+
+/// A library the project's programs can `#import`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dependency {
+    pub name: String,
+    pub source: Source,
+}
+
+/// Where a library comes from
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    /// A `.toy` file on disk
+    Path(PathBuf),
+    /// A `.toy` file in a git repository, at `rev` or the default branch
+    Git {
+        url: String,
+        file: String,
+        rev: Option<String>,
+    },
+}
+
+/// What a `toy.toml` says about the project's programs
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Manifest {
+    pub bins: Vec<Bin>,
+    pub dependencies: Vec<Dependency>,
+}
+
+impl Manifest {
+    /// Reads the `[[bin]]` tables and `[dependencies]` of a `toy.toml` file, resolving their
+    /// paths against the directory it is in
+    pub fn load(path: &Path) -> Result<Manifest, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| format!("cannot read {}: {error}", path.display()))?;
+
+        let mut manifest =
+            Manifest::parse(&contents).map_err(|error| format!("{}: {error}", path.display()))?;
+
+        let root = path.parent().unwrap_or(Path::new(""));
+        for bin in &mut manifest.bins {
+            bin.path = root.join(&bin.path);
+        }
+        for dependency in &mut manifest.dependencies {
+            if let Source::Path(path) = &mut dependency.source {
+                *path = root.join(&*path);
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    /// Parses the `[[bin]]` tables and `[dependencies]` out of a `toy.toml` file, ignoring
+    /// every other table. Only the parts of TOML they need are understood: quoted strings
+    /// and inline tables of them
+    pub fn parse(contents: &str) -> Result<Manifest, String> {
+        let mut manifest = Manifest::default();
+        // The line each table starts on and the name, path and section given so far
+        let mut current: Option<(usize, [Option<String>; 3])> = None;
+        let mut in_dependencies = false;
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let error = |message: &str| format!("line {}: {message}", number + 1);
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if line.starts_with('[') {
+                if let Some((start, fields)) = current.take() {
+                    manifest.add(start, fields)?;
+                }
+
+                if line.strip_prefix("[[").and_then(|t| t.strip_suffix("]]")) == Some("bin") {
+                    current = Some((number + 1, Default::default()));
+                }
+                in_dependencies = line == "[dependencies]";
+                continue;
+            }
+
+            if in_dependencies {
+                let dependency = parse_dependency(line).map_err(|message| error(&message))?;
+
+                if manifest.dependency(&dependency.name).is_some() {
+                    return Err(error(&format!(
+                        "dependency {} is listed more than once",
+                        dependency.name
+                    )));
+                }
+
+                manifest.dependencies.push(dependency);
+                continue;
+            }
+
+            let Some((_, fields)) = &mut current else {
+                continue;
+            };
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(error("expected `key = value`"));
+            };
+
+            let field = match key.trim() {
+                "name" => &mut fields[0],
+                "path" => &mut fields[1],
+                "section" => &mut fields[2],
+                key => return Err(error(&format!("unknown setting: {key}"))),
+            };
+
+            *field = Some(unquote(key.trim(), value).map_err(|message| error(&message))?);
+        }
+
+        if let Some((start, fields)) = current {
+            manifest.add(start, fields)?;
+        }
+
+        Ok(manifest)
+    }
+
+    /// The entry point called `name`
+    pub fn bin(&self, name: &str) -> Option<&Bin> {
+        self.bins.iter().find(|bin| bin.name == name)
+    }
+
+    /// The library called `name`
+    pub fn dependency(&self, name: &str) -> Option<&Dependency> {
+        self.dependencies
+            .iter()
+            .find(|dependency| dependency.name == name)
+    }
+
+    /// Adds the entry point of the `[[bin]]` table starting on line `start`
+    fn add(
+        &mut self,
+        start: usize,
+        [name, path, section]: [Option<String>; 3],
+    ) -> Result<(), String> {
+        let error = |message: &str| format!("line {start}: {message}");
+
+        let Some(name) = name else {
+            return Err(error("[[bin]] requires a name"));
+        };
+
+        let Some(path) = path else {
+            return Err(error(&format!("[[bin]] {name} requires a path")));
+        };
+
+        if self.bin(&name).is_some() {
+            return Err(error(&format!("[[bin]] {name} is defined more than once")));
+        }
+
+        self.bins.push(Bin {
+            name,
+            path: PathBuf::from(path),
+            section: section.unwrap_or_else(|| "main".to_string()),
+        });
+
+        Ok(())
+    }
+}
+
+/// Parses a `name = { path = "..." }` or `name = { git = "...", ... }` line of the
+/// `[dependencies]` table
+fn parse_dependency(line: &str) -> Result<Dependency, String> {
+    let Some((name, table)) = line.split_once('=') else {
+        return Err("expected `name = { path = \"...\" }`".to_string());
+    };
+    let name = name.trim().to_string();
+
+    // The name becomes the file the dependency is vendored to, so it must not lead elsewhere
+    if matches!(name.as_str(), "" | ".") || name.contains(['/', '\\']) || !is_relative_inside(&name)
+    {
+        return Err(format!("dependency name must be a plain file name: {name}"));
+    }
+
+    let Some(table) = table
+        .trim()
+        .strip_prefix('{')
+        .and_then(|table| table.strip_suffix('}'))
+    else {
+        return Err(format!(
+            "dependency {name} must be an inline table like {{ path = \"...\" }}"
+        ));
+    };
+
+    let [mut path, mut git, mut file, mut rev] = [None, None, None, None];
+
+    for setting in table.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let Some((key, value)) = setting.split_once('=') else {
+            return Err(format!("expected `key = value` in dependency {name}"));
+        };
+
+        let field = match key.trim() {
+            "path" => &mut path,
+            "git" => &mut git,
+            "file" => &mut file,
+            "rev" => &mut rev,
+            key => return Err(format!("unknown setting of dependency {name}: {key}")),
+        };
+
+        *field = Some(unquote(key.trim(), value)?);
+    }
+
+    let source = match (path, git) {
+        (Some(path), None) if file.is_none() && rev.is_none() => Source::Path(PathBuf::from(path)),
+        (Some(_), None) => {
+            return Err(format!(
+                "dependency {name} is a path, file and rev only apply to git"
+            ))
+        }
+        (None, Some(_))
+            if file
+                .as_deref()
+                .is_some_and(|file| !is_relative_inside(file)) =>
+        {
+            return Err(format!(
+                "dependency {name} must name a file inside the repository"
+            ))
+        }
+        (None, Some(_)) if rev.as_deref().is_some_and(|rev| rev.starts_with('-')) => {
+            return Err(format!("dependency {name} has an invalid rev"))
+        }
+        (None, Some(url)) => Source::Git {
+            url,
+            file: file.unwrap_or_else(|| "lib.toy".to_string()),
+            rev,
+        },
+        _ => {
+            return Err(format!(
+                "dependency {name} requires either a path or a git URL"
+            ))
+        }
+    };
+
+    Ok(Dependency { name, source })
+}
+
+/// The text of a quoted string value
+fn unquote(key: &str, value: &str) -> Result<String, String> {
+    value
+        .trim()
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .map(str::to_string)
+        .ok_or_else(|| format!("{key} must be a quoted string"))
+}
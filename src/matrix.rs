@@ -0,0 +1,183 @@
+use std::fmt;
+
+use crate::value::DataType;
+
+/// A grid of numbers that arithmetic works on element by element, made with `matrix` or
+/// `vector`. A vector is a matrix with a single row
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    rows: usize,
+    cols: usize,
+    /// The elements row by row
+    values: Vec<f64>,
+}
+
+impl Matrix {
+    /// A matrix of the given size, or `None` if it is empty or `values` does not fill it
+    pub fn new(rows: usize, cols: usize, values: Vec<f64>) -> Option<Matrix> {
+        (rows > 0 && cols > 0 && rows.checked_mul(cols) == Some(values.len())).then_some(Matrix {
+            rows,
+            cols,
+            values,
+        })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// The elements row by row
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// The element at a row and column, counting from 0
+    pub fn get(&self, row: usize, col: usize) -> Option<f64> {
+        (row < self.rows && col < self.cols).then(|| self.values[row * self.cols + col])
+    }
+
+    /// Approximate number of bytes the elements occupy
+    pub(crate) fn bytes(&self) -> usize {
+        self.values.len() * std::mem::size_of::<f64>()
+    }
+
+    pub(crate) fn transpose(&self) -> Matrix {
+        let values = (0..self.cols)
+            .flat_map(|col| (0..self.rows).map(move |row| self.values[row * self.cols + col]))
+            .collect();
+
+        Matrix {
+            rows: self.cols,
+            cols: self.rows,
+            values,
+        }
+    }
+
+    /// The matrix product of `self` and `other`
+    pub(crate) fn matmul(&self, other: &Matrix) -> Result<Matrix, String> {
+        if self.cols != other.rows {
+            return Err(format!(
+                "Cannot multiply a {} matrix by a {} matrix: the first needs as many columns as the second has rows",
+                self.shape(),
+                other.shape()
+            ));
+        }
+
+        let values = (0..self.rows)
+            .flat_map(|row| {
+                (0..other.cols).map(move |col| {
+                    (0..self.cols)
+                        .map(|k| {
+                            self.values[row * self.cols + k] * other.values[k * other.cols + col]
+                        })
+                        .sum()
+                })
+            })
+            .collect();
+
+        Ok(Matrix {
+            rows: self.rows,
+            cols: other.cols,
+            values,
+        })
+    }
+
+    /// Combines a matrix with another of the same size, or with a number, one element at a
+    /// time. `op` is given the element of `a` first and returns `None` for a division by zero
+    pub(crate) fn elementwise(
+        a: &DataType,
+        b: &DataType,
+        verb: &str,
+        op: impl Fn(f64, f64) -> Option<f64>,
+    ) -> Result<Matrix, String> {
+        let number = |value: &DataType| match value {
+            DataType::Int(a) => Some(*a as f64),
+            DataType::Float(a) => Some(*a),
+            _ => None,
+        };
+
+        let (shape, pairs): (&Matrix, Vec<(f64, f64)>) = match (a, b) {
+            (DataType::Matrix(a), DataType::Matrix(b)) => {
+                if (a.rows, a.cols) != (b.rows, b.cols) {
+                    return Err(format!(
+                        "Cannot {verb} matrices of different sizes, {} and {}",
+                        a.shape(),
+                        b.shape()
+                    ));
+                }
+
+                (
+                    a,
+                    a.values
+                        .iter()
+                        .copied()
+                        .zip(b.values.iter().copied())
+                        .collect(),
+                )
+            }
+            (DataType::Matrix(a), b) if number(b).is_some() => {
+                let b = number(b).unwrap_or_default();
+                (a, a.values.iter().map(|a| (*a, b)).collect())
+            }
+            (a, DataType::Matrix(b)) if number(a).is_some() => {
+                let a = number(a).unwrap_or_default();
+                (b, b.values.iter().map(|b| (a, *b)).collect())
+            }
+            (a, b) => {
+                return Err(format!(
+                    "Cannot {verb} {} and {}",
+                    a.type_name(),
+                    b.type_name()
+                ))
+            }
+        };
+
+        let values = pairs
+            .into_iter()
+            .map(|(a, b)| op(a, b).ok_or_else(|| "Cannot divide by zero".to_string()))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Matrix {
+            rows: shape.rows,
+            cols: shape.cols,
+            values,
+        })
+    }
+
+    fn shape(&self) -> String {
+        format!("{}x{}", self.rows, self.cols)
+    }
+}
+
+impl fmt::Display for Matrix {
+    /// Writes a vector as `[1, 2, 3]` and any other matrix as a list of its rows
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let row = |f: &mut fmt::Formatter<'_>, row: &[f64]| {
+            write!(f, "[")?;
+            for (i, value) in row.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{value}")?;
+            }
+            write!(f, "]")
+        };
+
+        if self.rows == 1 {
+            return row(f, &self.values);
+        }
+
+        write!(f, "[")?;
+        for (i, values) in self.values.chunks(self.cols).enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            row(f, values)?;
+        }
+        write!(f, "]")
+    }
+}
@@ -0,0 +1,35 @@
+use std::collections::HashMap;
+
+use crate::instructions::Instructions;
+
+/// What each instruction costs and how much a run may spend, so a host can bound the work
+/// a program does the same way on every machine. The run stops with
+/// [`RuntimeError::BudgetExhausted`](crate::RuntimeError::BudgetExhausted) before the
+/// instruction that would overspend, so an instruction costing more than the budget is
+/// never run at all
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metering {
+    pub budget: u64,
+    /// The cost of instructions by [`Instructions::mnemonic`], e.g. `"fopen"`
+    pub costs: HashMap<String, u64>,
+    /// The cost of instructions missing from `costs`
+    pub default_cost: u64,
+}
+
+impl Metering {
+    /// A budget where every instruction costs 1
+    pub fn new(budget: u64) -> Self {
+        Metering {
+            budget,
+            costs: HashMap::new(),
+            default_cost: 1,
+        }
+    }
+
+    pub fn cost(&self, instruction: &Instructions) -> u64 {
+        self.costs
+            .get(instruction.mnemonic())
+            .copied()
+            .unwrap_or(self.default_cost)
+    }
+}
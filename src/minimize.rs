@@ -0,0 +1,202 @@
+//! Shrinks a failing program to a small one that still fails the same way, for
+//! `toylang minimize`, so bug reports can come with a reproducer that is quick to read.
+//!
+//! Whole sections are removed first, then single lines, using delta debugging: the
+//! program is split into chunks, and any chunk whose removal keeps the failure is dropped
+//! before the chunks get smaller. A smaller program only counts as failing the same way if
+//! it parses when the original does and does not time out unless the original did, so the
+//! failure cannot turn into a different one along the way. Unless the check is about the
+//! error, it must also stop with exactly the error the original stopped with, or every
+//! program would shrink to an empty one failing with `No main section found`.
+
+use std::{fmt, str::FromStr};
+
+use crate::{
+    conformance::{Limits, Outcome, TestCase},
+    parser::{is_section_header, try_parse, try_validate},
+    RunOptions,
+};
+
+/// What a run has to do to still count as the failure being minimized
+#[derive(Debug, Clone, PartialEq)]
+pub enum Check {
+    /// `exits nonzero`
+    ExitsNonzero,
+    /// `exits <code>`
+    Exits(i32),
+    /// `error contains <text>`
+    ErrorContains(String),
+    /// `stdout contains <text>`
+    StdoutContains(String),
+}
+
+impl Check {
+    pub fn holds(&self, outcome: &Outcome) -> bool {
+        match self {
+            Check::ExitsNonzero => outcome.exit_code != 0,
+            Check::Exits(code) => outcome.exit_code == *code,
+            Check::ErrorContains(text) => outcome
+                .error
+                .as_ref()
+                .is_some_and(|error| error.contains(text.as_str())),
+            Check::StdoutContains(text) => outcome.stdout.contains(text.as_str()),
+        }
+    }
+}
+
+impl FromStr for Check {
+    type Err = String;
+
+    fn from_str(check: &str) -> Result<Check, String> {
+        // Text may be quoted, to keep spaces at its ends
+        let text = |text: &str| {
+            let text = text.trim();
+            text.strip_prefix('"')
+                .and_then(|text| text.strip_suffix('"'))
+                .unwrap_or(text)
+                .to_string()
+        };
+
+        let check = check.trim();
+        if let Some(rest) = check.strip_prefix("error contains ") {
+            return Ok(Check::ErrorContains(text(rest)));
+        }
+        if let Some(rest) = check.strip_prefix("stdout contains ") {
+            return Ok(Check::StdoutContains(text(rest)));
+        }
+
+        match check.strip_prefix("exits ").map(str::trim) {
+            Some("nonzero") => Ok(Check::ExitsNonzero),
+            Some(code) => code
+                .parse()
+                .map(Check::Exits)
+                .map_err(|_| format!("Invalid exit code `{code}`")),
+            None => Err(format!(
+                "Unknown check `{check}`, expected `exits nonzero`, `exits <code>`, \
+                 `error contains <text>` or `stdout contains <text>`"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Check::ExitsNonzero => write!(f, "exits nonzero"),
+            Check::Exits(code) => write!(f, "exits {code}"),
+            Check::ErrorContains(text) => write!(f, "error contains {text:?}"),
+            Check::StdoutContains(text) => write!(f, "stdout contains {text:?}"),
+        }
+    }
+}
+
+/// The smallest program found and how many runs it took to find it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Minimized {
+    pub source: String,
+    pub runs: usize,
+}
+
+/// Removes sections and lines from `source` for as long as the check still holds.
+/// Fails if it does not hold for `source` itself. Input given with `#stdin:` directives
+/// in the original is fed to every smaller program, whether or not they keep the
+/// directives
+pub fn minimize(source: &str, check: &Check, limits: Limits) -> Result<Minimized, String> {
+    let original = TestCase::from_program("original", source)?;
+    let mut runs = 0;
+
+    let mut run = |lines: &[&str]| {
+        runs += 1;
+
+        let mut program = lines.join("\n");
+        program.push('\n');
+
+        let options = RunOptions::default();
+        let parses = try_parse(&program, &options)
+            .and_then(|program| try_validate(&program))
+            .is_ok();
+
+        let outcome = TestCase {
+            program,
+            ..original.clone()
+        }
+        .run_isolated(limits);
+
+        (parses, outcome)
+    };
+
+    let lines: Vec<&str> = source.lines().collect();
+    let (parses, outcome) = run(&lines);
+    if !check.holds(&outcome) {
+        return Err(format!("The program does not fail the check `{check}`"));
+    }
+
+    let mut reproduces = |lines: &[&str]| {
+        let (candidate_parses, candidate) = run(lines);
+        let same_error =
+            matches!(check, Check::ErrorContains(_)) || candidate.error == outcome.error;
+
+        candidate_parses == parses
+            && candidate.timed_out == outcome.timed_out
+            && same_error
+            && check.holds(&candidate)
+    };
+
+    // Sections first, each with the lines before the first one as a section of its own
+    let mut sections: Vec<Vec<&str>> = Vec::new();
+    for line in lines {
+        match sections.last_mut() {
+            Some(section) if !is_section_header(line.trim()) => section.push(line),
+            _ => sections.push(vec![line]),
+        }
+    }
+
+    let sections = reduce(sections, |sections| reproduces(&sections.concat()));
+    let lines = sections
+        .concat()
+        .into_iter()
+        .map(|line| vec![line])
+        .collect();
+    let lines = reduce(lines, |lines| reproduces(&lines.concat())).concat();
+
+    let mut source = lines.join("\n");
+    source.push('\n');
+
+    Ok(Minimized { source, runs })
+}
+
+/// Delta debugging: tries removing ever smaller chunks of `units`, keeping every removal
+/// for which `reproduces` holds
+fn reduce<T: Clone>(mut units: Vec<T>, mut reproduces: impl FnMut(&[T]) -> bool) -> Vec<T> {
+    let mut chunks = 2;
+
+    while !units.is_empty() {
+        let size = units.len().div_ceil(chunks);
+        let mut removed = false;
+
+        for start in (0..units.len()).step_by(size) {
+            let candidate: Vec<T> = units[..start]
+                .iter()
+                .chain(&units[(start + size).min(units.len())..])
+                .cloned()
+                .collect();
+
+            if reproduces(&candidate) {
+                units = candidate;
+                chunks = (chunks - 1).max(2);
+                removed = true;
+                break;
+            }
+        }
+
+        if !removed {
+            if size == 1 {
+                break;
+            }
+
+            chunks = (chunks * 2).min(units.len());
+        }
+    }
+
+    units
+}
@@ -0,0 +1,127 @@
+//! Read-only memory maps of program files, so large programs can be parsed without copying
+//! their source into memory first. Only available on 64 bit unix systems with the `mmap`
+//! feature, anywhere else [`Mmap::open`] fails and callers are expected to read the file
+//! instead.
+//!
+//! Nothing stops another process from changing a file while it is mapped, which would
+//! change the bytes under a `&[u8]` that promised they would not. [`Mmap::open`] is
+//! `unsafe` for that reason, and reading the file stays the default.
+
+use std::{fs::File, io, ops::Deref, path::Path};
+
+/// A file mapped into memory for reading
+pub struct Mmap {
+    pointer: *const u8,
+    length: usize,
+}
+
+// The mapping is read-only and private, so sharing it is no different to sharing a slice
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+    /// Maps the whole file at `path`. Fails for files that cannot be mapped, like empty
+    /// files and pipes, and on platforms without memory maps
+    ///
+    /// # Safety
+    /// The file must not be modified or truncated, by this process or any other, until the
+    /// `Mmap` is dropped. Writes would change the mapped bytes while they are borrowed, and
+    /// reading past a truncated end raises `SIGBUS`
+    pub unsafe fn open(path: &Path) -> io::Result<Mmap> {
+        let file = File::open(path)?;
+        let length = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file is too large"))?;
+
+        if length == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot map an empty file",
+            ));
+        }
+
+        Ok(Mmap {
+            pointer: sys::map(&file, length)?,
+            length,
+        })
+    }
+
+    /// The mapped file as source code
+    pub fn as_str(&self) -> Result<&str, std::str::Utf8Error> {
+        std::str::from_utf8(self)
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the mapping is `length` bytes long and lives until `self` is dropped
+        unsafe { std::slice::from_raw_parts(self.pointer, self.length) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        sys::unmap(self.pointer, self.length);
+    }
+}
+
+#[cfg(all(feature = "mmap", unix, target_pointer_width = "64"))]
+mod sys {
+    use std::{ffi::c_void, fs::File, io, os::fd::AsRawFd};
+
+    const PROT_READ: i32 = 1;
+    const MAP_PRIVATE: i32 = 2;
+
+    extern "C" {
+        fn mmap(
+            address: *mut c_void,
+            length: usize,
+            protection: i32,
+            flags: i32,
+            descriptor: i32,
+            offset: i64,
+        ) -> *mut c_void;
+        fn munmap(address: *mut c_void, length: usize) -> i32;
+    }
+
+    pub fn map(file: &File, length: usize) -> io::Result<*const u8> {
+        // SAFETY: a fresh private read-only mapping of an open file has no other aliases
+        let pointer = unsafe {
+            mmap(
+                std::ptr::null_mut(),
+                length,
+                PROT_READ,
+                MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+
+        // MAP_FAILED
+        if pointer as isize == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(pointer as *const u8)
+    }
+
+    pub fn unmap(pointer: *const u8, length: usize) {
+        // SAFETY: the pointer and length came from a successful `map`
+        unsafe { munmap(pointer as *mut c_void, length) };
+    }
+}
+
+#[cfg(not(all(feature = "mmap", unix, target_pointer_width = "64")))]
+mod sys {
+    use std::{fs::File, io};
+
+    pub fn map(_file: &File, _length: usize) -> io::Result<*const u8> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "memory maps are not supported on this platform",
+        ))
+    }
+
+    pub fn unmap(_pointer: *const u8, _length: usize) {}
+}
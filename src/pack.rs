@@ -0,0 +1,116 @@
+//! Packs a program for distribution, for `toylang pack`, so puzzle and challenge authors
+//! can hand out a program that runs the same without giving away how it is built.
+//!
+//! The program is parsed, which drops comments, blank lines and `#if` blocks that are not
+//! taken, and written back out with every section, data section, `#states` group, constant
+//! and variable renamed to a short meaningless name in the order they first appear. `main`
+//! keeps its name so the program still runs, and globals keep theirs because the host
+//! reads and writes them by name.
+
+use std::collections::HashMap;
+
+use crate::{
+    instructions::Instructions,
+    parser::{self, try_parse, try_validate},
+    ParseError, Program, RunOptions, SectionName,
+};
+
+/// New names handed out in the order names are first seen
+struct Names {
+    prefix: &'static str,
+    names: HashMap<String, String>,
+    count: usize,
+}
+
+impl Names {
+    fn new(prefix: &'static str) -> Names {
+        Names {
+            prefix,
+            names: HashMap::new(),
+            count: 0,
+        }
+    }
+
+    /// Keeps a name as it is
+    fn keep(&mut self, name: &str) {
+        self.names.insert(name.to_string(), name.to_string());
+    }
+
+    fn rename(&mut self, name: &str) -> String {
+        if let Some(renamed) = self.names.get(name) {
+            return renamed.clone();
+        }
+
+        let renamed = format!("{}{}", self.prefix, self.count);
+        self.count += 1;
+        self.names.insert(name.to_string(), renamed.clone());
+        renamed
+    }
+}
+
+/// Parses `source` and renames everything in it that can be renamed. Fails if the program
+/// does not parse or is not valid
+pub fn pack(source: &str, options: &RunOptions) -> Result<Vec<Program>, ParseError> {
+    let program = try_parse(source, options)?;
+    try_validate(&program)?;
+
+    let mut sections = Names::new("s");
+    let mut groups = Names::new("g");
+    let mut constants = Names::new("c");
+    let mut variables = Names::new("v");
+    sections.keep("main");
+
+    // Sections are named in the order they are defined, not the order they are used
+    for item in &program {
+        if let Some(name) = item.name() {
+            sections.rename(&name.0);
+        }
+    }
+
+    let program = program
+        .into_iter()
+        .map(|item| match item {
+            Program::Section(name, instructions) => Program::Section(
+                SectionName(sections.rename(&name.0)),
+                instructions
+                    .into_iter()
+                    .map(|instruction| match instruction {
+                        Instructions::Jump(label) => Instructions::Jump(sections.rename(&label)),
+                        Instructions::Call(label) => Instructions::Call(sections.rename(&label)),
+                        Instructions::IfJmp(label) => Instructions::IfJmp(sections.rename(&label)),
+                        Instructions::PushData(label) => {
+                            Instructions::PushData(sections.rename(&label))
+                        }
+                        Instructions::Switch(group) => Instructions::Switch(groups.rename(&group)),
+                        Instructions::GetConst(name, index) => {
+                            Instructions::GetConst(constants.rename(&name), index)
+                        }
+                        Instructions::Store(name) => Instructions::Store(variables.rename(&name)),
+                        Instructions::Load(name) => Instructions::Load(variables.rename(&name)),
+                        instruction => instruction,
+                    })
+                    .collect(),
+            ),
+            Program::Data(name, text) => Program::Data(SectionName(sections.rename(&name.0)), text),
+            Program::Const(name, values) => Program::Const(constants.rename(&name), values),
+            Program::Contract(name, contract) => {
+                Program::Contract(SectionName(sections.rename(&name.0)), contract)
+            }
+            Program::Budget(name, steps) => {
+                Program::Budget(SectionName(sections.rename(&name.0)), steps)
+            }
+            // `switch` goes to the section named after the state
+            Program::States(group, states) => Program::States(
+                groups.rename(&group),
+                states.iter().map(|state| sections.rename(state)).collect(),
+            ),
+        })
+        .collect();
+
+    Ok(program)
+}
+
+/// Writes a packed program back out as source
+pub fn to_source(program: &[Program]) -> String {
+    parser::to_source(program)
+}
@@ -0,0 +1,1284 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::BufRead,
+};
+
+use clap::ValueEnum;
+
+use crate::{
+    complex::Complex,
+    contract::{Contract, ContractKind},
+    error::ParseError,
+    format::PrintFormat,
+    instructions::Instructions,
+    interpreter::RunOptions,
+    log::LogLevel,
+    value::DataType,
+    version::LanguageVersion,
+};
+
+#[derive(Debug, Clone)]
+pub struct SectionName(pub String);
+
+#[derive(Debug, Clone)]
+pub enum Program {
+    Section(SectionName, Vec<Instructions>),
+    /// A `::data name:` section, holding raw text instead of instructions
+    Data(SectionName, String),
+    /// A `const name = [...]` table, read with `getconst`
+    Const(String, Vec<DataType>),
+    /// A `#pre` or `#post` annotation on a section
+    Contract(SectionName, Contract),
+    /// A `#states group A B C` declaration. Each state is an Int constant (its position in
+    /// the group) and `switch group` jumps to the section named after the state
+    States(String, Vec<String>),
+    /// A `#budget 10000 steps` annotation, limiting how many instructions a section may run
+    /// each time it is entered
+    Budget(SectionName, usize),
+}
+
+impl Program {
+    /// The name of a section or data section, or `None` for declarations
+    pub fn name(&self) -> Option<&SectionName> {
+        match self {
+            Program::Section(name, _) | Program::Data(name, _) => Some(name),
+            Program::Const(..)
+            | Program::States(..)
+            | Program::Contract(..)
+            | Program::Budget(..) => None,
+        }
+    }
+}
+
+/// Whether a line (without any `override` prefix) starts a new section
+pub(crate) fn is_section_header(line: &str) -> bool {
+    line.starts_with("::") && line.ends_with(':')
+}
+
+/// Joins the lines of a data section, dropping the blank lines that separate it from the
+/// next section
+fn data_text(mut lines: Vec<String>) -> String {
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+
+    lines.iter().map(|line| format!("{line}\n")).collect()
+}
+
+/// Parses a `const name = [a, b, ...]` declaration
+fn parse_const(declaration: &str) -> Result<Program, String> {
+    let Some((name, values)) = declaration.split_once('=') else {
+        return Err(format!(
+            "const requires a name and a list of values: const {declaration}"
+        ));
+    };
+
+    let name = name.trim();
+    let values = values.trim();
+
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(format!("Invalid const name: {name}"));
+    }
+
+    let Some(values) = values
+        .strip_prefix('[')
+        .and_then(|values| values.strip_suffix(']'))
+    else {
+        return Err(format!(
+            "const {name} must be a list of values in square brackets"
+        ));
+    };
+
+    // Split on commas that are not inside a string
+    let mut items = Vec::new();
+    let mut start = 0;
+    let mut in_string = false;
+
+    for (index, c) in values.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            ',' if !in_string => {
+                items.push(&values[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+
+    items.push(&values[start..]);
+
+    let values = items
+        .into_iter()
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(parse_literal)
+        .collect::<Result<_, _>>()?;
+
+    Ok(Program::Const(name.to_string(), values))
+}
+
+/// Parses a literal value as written after `push`
+fn parse_literal(value: &str) -> Result<DataType, String> {
+    Ok(
+        if value.starts_with("x\"") && value.ends_with('"') && value.len() >= 3 {
+            DataType::Bytes(parse_hex(&value[2..value.len() - 1])?)
+        } else if value.starts_with('"') && value.ends_with('"') {
+            let text = value.trim_matches('"');
+
+            // Most strings have no escapes, so only pay for replacing them when there are some
+            DataType::String(if text.contains('\\') {
+                text.replace("\\n", "\n").replace("\\r", "\r")
+            } else {
+                text.to_string()
+            })
+        } else if let Some(complex) = value.strip_suffix('i') {
+            DataType::Complex(parse_complex(complex)?)
+        } else if value.contains('.') {
+            DataType::Float(
+                value
+                    .parse::<f64>()
+                    .map_err(|_| format!("Invalid Float: {value}"))?,
+            )
+        } else if value == "true" || value == "false" {
+            DataType::Bool(value == "true")
+        } else {
+            DataType::Int(
+                value
+                    .parse::<i64>()
+                    .map_err(|_| format!("Invalid value: {value}"))?,
+            )
+        },
+    )
+}
+
+/// Parses a Complex literal such as `1.5-2` without its trailing `i`
+fn parse_complex(value: &str) -> Result<Complex, String> {
+    // The sign of the imaginary part, rather than one the real part starts with or one of
+    // an exponent
+    let split = value
+        .char_indices()
+        .skip(1)
+        .filter(|(index, c)| matches!(c, '+' | '-') && !value[..*index].ends_with(['e', 'E']))
+        .last()
+        .map(|(index, _)| index);
+
+    let parts = split.and_then(|split| {
+        let re = value[..split].parse::<f64>().ok()?;
+        let im = value[split..].trim_start_matches('+').parse::<f64>().ok()?;
+        Some(Complex::new(re, im))
+    });
+
+    parts.ok_or_else(|| format!("Invalid complex number {value}i, expected e.g. 1+2i"))
+}
+
+/// Decodes the hex digits of a `x"..."` literal
+fn parse_hex(digits: &str) -> Result<Vec<u8>, String> {
+    // Checked before slicing, which would panic in the middle of a multi-byte character
+    if !digits.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+        return Err(format!("Invalid hex digits in byte literal: {digits}"));
+    }
+
+    if !digits.len().is_multiple_of(2) {
+        return Err(format!(
+            "Byte literal must have an even number of hex digits: {digits}"
+        ));
+    }
+
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .map_err(|_| format!("Invalid hex digits in byte literal: {digits}"))
+        })
+        .collect()
+}
+
+/// Handles a `#lang toy <version>` directive, rejecting programs written for a language
+/// this interpreter cannot run and warning about ones that may use newer features
+fn parse_lang_directive(argument: &str) -> Result<LanguageVersion, String> {
+    let Some(version) = argument
+        .trim()
+        .strip_prefix("toy ")
+        .and_then(LanguageVersion::parse)
+    else {
+        return Err(format!("#lang expects `toy <major>.<minor>`, found: {}", argument.trim()));
+    };
+
+    let current = LanguageVersion::CURRENT;
+
+    if version.major != current.major {
+        return Err(format!(
+            "Program targets toy {version}, but this interpreter only supports toy {current}"
+        ));
+    }
+
+    if version > current {
+        eprintln!(
+            "warning: program targets toy {version}, but this interpreter only supports toy {current} so newer features will not parse"
+        );
+    }
+
+    Ok(version)
+}
+
+/// Rejects `feature` if it was introduced after the version the program targets
+fn require_version(
+    version: LanguageVersion,
+    since: LanguageVersion,
+    feature: &str,
+) -> Result<(), String> {
+    if version < since {
+        return Err(format!(
+            "{feature} requires toy {since}, but the program targets toy {version}"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Parses source code into its sections, panicking with a [`ParseError`] if it is invalid
+pub fn parse(contents: &str, options: &RunOptions) -> Vec<Program> {
+    try_parse(contents, options).unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// Parses source code into its sections
+pub fn try_parse(contents: &str, options: &RunOptions) -> Result<Vec<Program>, ParseError> {
+    parse_lines(contents.lines().map(Ok), options)
+}
+
+/// Parses source code from a reader a line at a time, so the source is never held in
+/// memory as a whole. Panics with a [`ParseError`] if it is invalid
+pub fn parse_reader(reader: impl BufRead, options: &RunOptions) -> Vec<Program> {
+    try_parse_reader(reader, options).unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// Parses source code from a reader a line at a time, so the source is never held in
+/// memory as a whole
+pub fn try_parse_reader(
+    reader: impl BufRead,
+    options: &RunOptions,
+) -> Result<Vec<Program>, ParseError> {
+    parse_lines(reader.lines(), options)
+}
+
+fn parse_lines(
+    lines: impl Iterator<Item = std::io::Result<impl AsRef<str>>>,
+    options: &RunOptions,
+) -> Result<Vec<Program>, ParseError> {
+    let mut parser = Parser::new(options);
+    let mut program = Vec::new();
+
+    for line in lines {
+        let line =
+            line.map_err(|error| ParseError::program(format!("Cannot read program: {error}")))?;
+
+        if let Some(item) = parser.feed(line.as_ref())? {
+            place(&mut program, parser.replaced(), item);
+        }
+    }
+
+    if let Some(item) = parser.finish()? {
+        place(&mut program, parser.replaced(), item);
+    }
+
+    Ok(program)
+}
+
+/// Adds an item handed out by a [`Parser`] to the program, or puts it in place of the
+/// section it overrides
+pub(crate) fn place(program: &mut Vec<Program>, replaced: Option<usize>, item: Program) {
+    match replaced {
+        Some(index) => program[index] = item,
+        None => program.push(item),
+    }
+}
+
+/// Parses source a line at a time, holding on to nothing but the section being read. Each
+/// section and declaration is handed out as soon as it is complete, so memory stays
+/// proportional to the largest section. A section marked `override` is handed out again
+/// and replaces the original, see [`Parser::replaced`]
+pub struct Parser<'a> {
+    options: &'a RunOptions,
+    /// Where each section handed out so far is among the items that replaced nothing
+    sections: HashMap<String, usize>,
+    /// How many items were handed out that replaced nothing
+    count: usize,
+    /// The position of the section the one being completed overrides
+    replacing: Option<usize>,
+    /// The position of the item the last one handed out replaced
+    replaced: Option<usize>,
+    consts: HashSet<String>,
+    groups: HashSet<String>,
+    current_section: Option<SectionName>,
+    /// The number and text of the current section's header line, where errors about the
+    /// section as a whole are reported
+    current_header: Option<(usize, String)>,
+    current_override: bool,
+    instructions: Vec<Instructions>,
+    /// The raw lines of the current section while it is a data section
+    data: Option<Vec<String>>,
+    /// One entry per open `#if`: whether its current branch is being kept
+    conditions: Vec<bool>,
+    /// The number and text of the line each open `#if` is on
+    opened: Vec<(usize, String)>,
+    /// The value of every state declared by `#states`, usable wherever an Int literal is
+    states: HashMap<String, usize>,
+    /// Programs without a `#lang` directive are assumed to target the current version
+    version: Option<LanguageVersion>,
+    seen_code: bool,
+    /// How many lines have been read
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(options: &'a RunOptions) -> Self {
+        Parser {
+            options,
+            sections: HashMap::new(),
+            count: 0,
+            replacing: None,
+            replaced: None,
+            consts: HashSet::new(),
+            groups: HashSet::new(),
+            current_section: None,
+            current_header: None,
+            current_override: false,
+            instructions: Vec::new(),
+            data: None,
+            conditions: Vec::new(),
+            opened: Vec::new(),
+            states: HashMap::new(),
+            version: None,
+            seen_code: false,
+            line: 0,
+        }
+    }
+
+    /// Parses the next line, returning the section or declaration it completed, if any
+    pub fn feed(&mut self, line: &str) -> Result<Option<Program>, ParseError> {
+        self.line += 1;
+        let item = self.read(line)?;
+        Ok(self.hand_out(item))
+    }
+
+    /// Ends the source, returning the last section if there is one
+    pub fn finish(&mut self) -> Result<Option<Program>, ParseError> {
+        if let Some((number, line)) = self.opened.last() {
+            return Err(ParseError::at(*number, line, "#if without matching #endif"));
+        }
+
+        let item = self.finish_section()?;
+        Ok(self.hand_out(item))
+    }
+
+    /// If the item handed out last is an `override`, the position of the section it
+    /// replaces, counting only the items that did not replace one
+    pub fn replaced(&self) -> Option<usize> {
+        self.replaced
+    }
+
+    /// Whether lines are being skipped by an `#if` whose flag is not defined
+    pub(crate) fn skipping(&self) -> bool {
+        self.conditions.last() == Some(&false)
+    }
+
+    /// How many instructions the section being read has so far
+    pub(crate) fn pending_instructions(&self) -> usize {
+        self.instructions.len()
+    }
+
+    /// Keeps track of where items end up as they are handed out
+    fn hand_out(&mut self, item: Option<Program>) -> Option<Program> {
+        self.replaced = None;
+
+        if item.is_some() {
+            match self.replacing.take() {
+                Some(index) => self.replaced = Some(index),
+                None => self.count += 1,
+            }
+        }
+
+        item
+    }
+
+    fn read(&mut self, line: &str) -> Result<Option<Program>, ParseError> {
+        let number = self.line;
+        let at = |message: String| ParseError::at(number, line, message);
+
+        // Data sections take every line verbatim until the next section starts
+        if let Some(data) = &mut self.data {
+            let header = line.strip_prefix("override ").map_or(line, str::trim_start);
+
+            if !is_section_header(header) {
+                data.push(line.to_string());
+                return Ok(None);
+            }
+        }
+
+        if let Some(directive) = line.strip_prefix('#') {
+            let (directive, flag) = directive.split_once(' ').unwrap_or((directive, ""));
+
+            if ["if", "else", "endif"].contains(&directive) {
+                require_version(
+                    self.version.unwrap_or(LanguageVersion::CURRENT),
+                    LanguageVersion::OVERRIDES_AND_CONDITIONS,
+                    "#if",
+                )
+                .map_err(at)?;
+            }
+
+            match directive {
+                "lang" => {
+                    if self.version.is_some() || self.seen_code {
+                        return Err(at(
+                            "#lang must come before any other code and appear only once"
+                                .to_string(),
+                        ));
+                    }
+
+                    self.version = Some(parse_lang_directive(flag).map_err(at)?);
+                    return Ok(None);
+                }
+                "states" if self.conditions.last() != Some(&false) => {
+                    let mut names = flag.split_whitespace().map(str::to_string);
+
+                    let Some(group) = names.next() else {
+                        return Err(at(
+                            "#states requires a group name followed by its states".to_string()
+                        ));
+                    };
+
+                    let names: Vec<String> = names.collect();
+
+                    if names.is_empty() {
+                        return Err(at(format!("#states {group} requires at least one state")));
+                    }
+
+                    if !self.groups.insert(group.clone()) {
+                        return Err(at(format!("Duplicate states group: {group}")));
+                    }
+
+                    for (value, name) in names.iter().enumerate() {
+                        if self.states.insert(name.clone(), value).is_some() {
+                            return Err(at(format!("State {name} is declared more than once")));
+                        }
+                    }
+
+                    return Ok(Some(Program::States(group, names)));
+                }
+                "pre" | "post" if self.conditions.last() != Some(&false) => {
+                    let kind = match directive {
+                        "pre" => ContractKind::Pre,
+                        _ => ContractKind::Post,
+                    };
+
+                    let Some(contract) = Contract::parse(kind, flag) else {
+                        return Err(at(format!(
+                            "#{directive} expects a condition like depth>=2, found: {flag}"
+                        )));
+                    };
+
+                    let section = self
+                        .current_section
+                        .clone()
+                        .unwrap_or(SectionName("main".to_string()));
+
+                    return Ok(Some(Program::Contract(section, contract)));
+                }
+                "budget" if self.conditions.last() != Some(&false) => {
+                    let steps = match flag.split_whitespace().collect::<Vec<_>>()[..] {
+                        [steps] | [steps, "steps"] => steps.parse().ok(),
+                        _ => None,
+                    };
+
+                    let Some(steps) = steps else {
+                        return Err(at(format!(
+                            "#budget expects a number of steps like `#budget 10000 steps`, found: {flag}"
+                        )));
+                    };
+
+                    let section = self
+                        .current_section
+                        .clone()
+                        .unwrap_or(SectionName("main".to_string()));
+
+                    return Ok(Some(Program::Budget(section, steps)));
+                }
+                "if" => {
+                    if flag.is_empty() {
+                        return Err(at("#if requires a flag".to_string()));
+                    }
+
+                    let enclosing = self.conditions.last().copied().unwrap_or(true);
+                    let defined = self.options.defines.iter().any(|d| d == flag.trim());
+                    self.conditions.push(enclosing && defined);
+                    self.opened.push((number, line.to_string()));
+                    return Ok(None);
+                }
+                "else" => {
+                    let Some(active) = self.conditions.pop() else {
+                        return Err(at("#else without matching #if".to_string()));
+                    };
+
+                    let enclosing = self.conditions.last().copied().unwrap_or(true);
+                    self.conditions.push(enclosing && !active);
+                    return Ok(None);
+                }
+                "endif" => {
+                    self.opened.pop();
+                    if self.conditions.pop().is_none() {
+                        return Err(at("#endif without matching #if".to_string()));
+                    }
+                    return Ok(None);
+                }
+                _ => {}
+            }
+        }
+
+        if self.conditions.last() == Some(&false) {
+            return Ok(None);
+        }
+
+        if line.starts_with(['/', '#']) || line.is_empty() {
+            return Ok(None);
+        }
+
+        self.seen_code = true;
+
+        // Constants are declarations rather than instructions, so they can appear anywhere
+        if let Some(declaration) = line.strip_prefix("const ") {
+            let constant = parse_const(declaration).map_err(at)?;
+            let Program::Const(name, _) = &constant else {
+                unreachable!();
+            };
+
+            if !self.consts.insert(name.clone()) {
+                return Err(at(format!("Duplicate const: {name}")));
+            }
+
+            return Ok(Some(constant));
+        }
+
+        // An `override` prefix marks an intentional redefinition of an existing section
+        let (is_override, header) = match line.strip_prefix("override ") {
+            Some(header) => (true, header.trim_start()),
+            None => (false, line),
+        };
+
+        if is_override {
+            require_version(
+                self.version.unwrap_or(LanguageVersion::CURRENT),
+                LanguageVersion::OVERRIDES_AND_CONDITIONS,
+                "override",
+            )
+            .map_err(at)?;
+        }
+
+        // We have found a section
+        if is_section_header(header) {
+            // The new section is started even if the last one is rejected, so a parser fed
+            // past the error carries on from here
+            let finished = self.finish_section();
+            let name = header.trim_matches(':');
+
+            if let Some(name) = name.strip_prefix("data ") {
+                self.current_section = Some(SectionName(name.trim().to_string()));
+                self.data = Some(Vec::new());
+            } else {
+                self.current_section = Some(SectionName(name.to_string()));
+            }
+
+            self.current_header = Some((number, line.to_string()));
+            self.current_override = is_override;
+            return finished;
+        }
+
+        if is_override {
+            return Err(at(format!(
+                "override must be followed by a section: {line}"
+            )));
+        }
+
+        // States stand in for the Int they were assigned
+        if let Some(state) = line
+            .strip_prefix("push ")
+            .and_then(|value| self.states.get(value.trim()))
+        {
+            self.instructions
+                .push(Instructions::Push(DataType::Int(*state as i64)));
+            return Ok(None);
+        }
+
+        if let Some(instruction) = parse_instruction(line, self.options).map_err(at)? {
+            self.instructions.push(instruction);
+        }
+
+        Ok(None)
+    }
+
+    /// Completes the section being read, rejecting duplicate names unless the section
+    /// was explicitly marked with `override`, in which case it replaces the original
+    fn finish_section(&mut self) -> Result<Option<Program>, ParseError> {
+        if self.current_section.is_none() && self.instructions.is_empty() {
+            return Ok(None);
+        }
+
+        // Programs without a header start with an implicit main section on their first line
+        let (number, header) = self
+            .current_header
+            .take()
+            .unwrap_or((1, "::main:".to_string()));
+        let at = |message: String| ParseError::at(number, &header, message);
+
+        let name = self
+            .current_section
+            .take()
+            .unwrap_or(SectionName("main".to_string()));
+        let instructions = std::mem::take(&mut self.instructions);
+        let data = self.data.take();
+
+        match (self.sections.get(&name.0), self.current_override) {
+            (Some(&index), true) => self.replacing = Some(index),
+            (Some(_), false) => {
+                return Err(at(format!(
+                    "Duplicate section: {}. Use `override ::{}:` to redefine it",
+                    name.0, name.0
+                )))
+            }
+            (None, true) => {
+                return Err(at(format!("Cannot override undefined section: {}", name.0)))
+            }
+            (None, false) => {
+                self.sections.insert(name.0.clone(), self.count);
+            }
+        }
+
+        Ok(Some(match data {
+            Some(lines) => Program::Data(name, data_text(lines)),
+            None => Program::Section(name, instructions),
+        }))
+    }
+}
+
+/// The name and instructions of every section that ends up in the program, with the index
+/// of the line each instruction was read from, for tools that rewrite source. Source that
+/// does not parse is read up to its first error
+pub(crate) fn instruction_lines(
+    lines: &[&str],
+    options: &RunOptions,
+) -> Vec<(String, Vec<Instructions>, Vec<usize>)> {
+    let mut parser = Parser::new(options);
+    let mut sections: Vec<(String, Vec<Instructions>, Vec<usize>)> = Vec::new();
+    let mut numbers = Vec::new();
+
+    let mut complete = |item: Option<Program>, numbers: &mut Vec<usize>| {
+        let Some(Program::Section(name, instructions)) = item else {
+            return false;
+        };
+
+        let numbers = std::mem::take(numbers);
+
+        // Overridden sections are replaced, so their lines are left alone
+        match sections.iter_mut().find(|(other, ..)| *other == name.0) {
+            Some(section) => *section = (name.0, instructions, numbers),
+            None => sections.push((name.0, instructions, numbers)),
+        }
+
+        true
+    };
+
+    for (number, line) in lines.iter().enumerate() {
+        let before = parser.pending_instructions();
+        let Ok(item) = parser.feed(line) else {
+            return sections;
+        };
+
+        if !complete(item, &mut numbers) && parser.pending_instructions() > before {
+            numbers.push(number);
+        }
+    }
+
+    if let Ok(item) = parser.finish() {
+        complete(item, &mut numbers);
+    }
+
+    sections
+}
+
+/// Longer than any instruction name, so longer words can be rejected without lowercasing
+const MNEMONIC_LENGTH: usize = 16;
+
+/// Splits a line into the instruction name and its operand, if any
+pub(crate) fn split_instruction(line: &str) -> (&str, &str) {
+    match line.bytes().position(|byte| byte == b' ') {
+        Some(space) => (&line[..space], &line[space + 1..]),
+        None => (line, ""),
+    }
+}
+
+/// Lowercases an instruction name into `buffer` so it can be matched case insensitively
+/// without allocating. Names that cannot be an instruction are returned unchanged
+fn lowercase_mnemonic<'a>(name: &'a str, buffer: &'a mut [u8; MNEMONIC_LENGTH]) -> &'a str {
+    if name.len() > MNEMONIC_LENGTH || !name.is_ascii() {
+        return name;
+    }
+
+    let lowercase = &mut buffer[..name.len()];
+    lowercase.copy_from_slice(name.as_bytes());
+    lowercase.make_ascii_lowercase();
+
+    // Lowercasing ASCII keeps it ASCII
+    std::str::from_utf8(lowercase).unwrap()
+}
+
+/// Parses a single instruction, returning `None` for instructions that are stripped from this run
+pub(crate) fn parse_instruction(
+    line: &str,
+    options: &RunOptions,
+) -> Result<Option<Instructions>, String> {
+    let (instruction, value) = split_instruction(line);
+    let mut buffer = [0; MNEMONIC_LENGTH];
+
+    Ok(Some(match lowercase_mnemonic(instruction, &mut buffer) {
+        "push" => {
+            if value.is_empty() {
+                return Err("push requires a value".to_string());
+            };
+
+            Instructions::Push(parse_literal(value)?)
+        }
+        "eq" => Instructions::EQ,
+        "ne" => Instructions::NE,
+        "lt" => Instructions::LT,
+        "gt" => Instructions::GT,
+        "le" => Instructions::LE,
+        "ge" => Instructions::GE,
+        "and" => Instructions::And,
+        "or" => Instructions::Or,
+        "not" => Instructions::Not,
+        "add" => Instructions::Add,
+        "sub" => Instructions::Sub,
+        "mul" => Instructions::Mul,
+        "div" => Instructions::Div,
+        "mod" => Instructions::Mod,
+        "vector" => match value.parse::<usize>() {
+            Ok(length) if length > 0 => Instructions::Vector(length),
+            _ => return Err("vector requires a length of at least 1".to_string()),
+        },
+        "matrix" => {
+            let size = value
+                .split_once(' ')
+                .and_then(|(rows, cols)| Some((rows.parse().ok()?, cols.trim().parse().ok()?)));
+
+            match size {
+                Some((rows, cols))
+                    if rows > 0 && cols > 0 && usize::checked_mul(rows, cols).is_some() =>
+                {
+                    Instructions::Matrix(rows, cols)
+                }
+                _ => {
+                    return Err(
+                        "matrix requires a number of rows and a number of columns".to_string()
+                    )
+                }
+            }
+        }
+        "mget" => Instructions::MGet,
+        "matmul" => Instructions::MatMul,
+        "transpose" => Instructions::Transpose,
+        "complex" => Instructions::Complex,
+        "re" => Instructions::Re,
+        "im" => Instructions::Im,
+        "cabs" => Instructions::CAbs,
+        "drop" => Instructions::Drop,
+        "dup" => Instructions::Dup,
+        "swap" => Instructions::Swap,
+        "over" => Instructions::Over,
+        "rot" => Instructions::Rot,
+        "print" if value.is_empty() => Instructions::Print(None),
+        "print" => match PrintFormat::parse(value) {
+            Ok(format) => Instructions::Print(Some(format)),
+            Err(error) => return Err(format!("Invalid print format {value}: {error}")),
+        },
+        "setfmt" => Instructions::SetFmt,
+        "printtable" => Instructions::PrintTable,
+        "break" => Instructions::Break,
+        "tick" => Instructions::Tick,
+        "tock" => Instructions::Tock,
+        "meminfo" => Instructions::MemInfo,
+        "tone" => Instructions::Tone,
+        "frame" => match value.parse::<u32>() {
+            Ok(fps) if fps > 0 => Instructions::Frame(fps),
+            _ => return Err("frame requires a number of frames per second".to_string()),
+        },
+        "flush" => Instructions::Flush,
+        "byteat" => Instructions::ByteAt,
+        "byteslice" => Instructions::ByteSlice,
+        "bytelen" => Instructions::ByteLen,
+        "tobytes" => Instructions::ToBytes,
+        "frombytes" => Instructions::FromBytes,
+        "len" => Instructions::Len,
+        "substr" => Instructions::Substr,
+        "strindex" => Instructions::StrIndex,
+        "toint" => Instructions::ToInt,
+        "tofloat" => Instructions::ToFloat,
+        "tostring" => Instructions::ToString,
+        "tobool" => Instructions::ToBool,
+        "list" => Instructions::List,
+        "append" => Instructions::Append,
+        "get" => Instructions::Get,
+        "set" => Instructions::Set,
+        "pop" => Instructions::Pop,
+        "fopen" => Instructions::FOpen,
+        "freadn" => Instructions::FReadN,
+        "fwriteh" => Instructions::FWriteH,
+        "fseek" => Instructions::FSeek,
+        "fclose" => Instructions::FClose,
+        "cnew" => Instructions::CNew,
+        "cset" => Instructions::CSet,
+        "csave" => Instructions::CSave,
+        "forward" => Instructions::Forward,
+        "turn" => Instructions::Turn,
+        "penup" => Instructions::PenUp,
+        "pendown" => Instructions::PenDown,
+        "tsave" => Instructions::TSave,
+        "readall" => Instructions::ReadAll,
+        "readline" => Instructions::ReadLine,
+        "read" => Instructions::Read,
+        "readint" => Instructions::ReadInt,
+        "readfloat" => Instructions::ReadFloat,
+        "csvparse" => Instructions::CsvParse,
+        "csvemit" => Instructions::CsvEmit,
+        "exit" => Instructions::Exit,
+        "jump" => {
+            if value.is_empty() {
+                return Err("jump requires a label".to_string());
+            };
+
+            Instructions::Jump(value.to_string())
+        }
+        "call" => {
+            if value.is_empty() {
+                return Err("call requires a label".to_string());
+            };
+
+            Instructions::Call(value.to_string())
+        }
+        "ret" => Instructions::Ret,
+        "switch" => {
+            if value.is_empty() {
+                return Err("switch requires a states group".to_string());
+            };
+
+            Instructions::Switch(value.to_string())
+        }
+        "getconst" => {
+            let (name, index) = value.split_once(' ').unwrap_or((value, ""));
+
+            if name.is_empty() {
+                return Err("getconst requires a const name".to_string());
+            };
+
+            let index = match index.trim() {
+                "" => None,
+                index => Some(
+                    index
+                        .parse::<usize>()
+                        .map_err(|_| format!("Invalid getconst index: {index}"))?,
+                ),
+            };
+
+            Instructions::GetConst(name.to_string(), index)
+        }
+        "argc" => Instructions::Argc,
+        "argv" => Instructions::Argv(match value {
+            "" => None,
+            index => Some(
+                index
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid argv index: {index}"))?,
+            ),
+        }),
+        "pushdata" => {
+            if value.is_empty() {
+                return Err("pushdata requires a data section name".to_string());
+            };
+
+            Instructions::PushData(value.to_string())
+        }
+        "getglobal" => {
+            if value.is_empty() {
+                return Err("getglobal requires a name".to_string());
+            };
+
+            Instructions::GetGlobal(value.to_string())
+        }
+        "setglobal" => {
+            if value.is_empty() {
+                return Err("setglobal requires a name".to_string());
+            };
+
+            Instructions::SetGlobal(value.to_string())
+        }
+        "store" => {
+            if value.is_empty() {
+                return Err("store requires a variable name".to_string());
+            };
+
+            Instructions::Store(value.to_string())
+        }
+        "load" => {
+            if value.is_empty() {
+                return Err("load requires a variable name".to_string());
+            };
+
+            Instructions::Load(value.to_string())
+        }
+        "trace" => {
+            // Tracing is only compiled in for debug runs, otherwise it is stripped entirely
+            if !options.debug && !options.defines.iter().any(|d| d == "DEBUG") {
+                return Ok(None);
+            }
+
+            Instructions::Trace(value.trim_matches('"').to_string())
+        }
+        "log" => {
+            let Ok(level) = LogLevel::from_str(value, true) else {
+                return Err("log requires a level (trace, debug, info, warn or error)".to_string());
+            };
+
+            Instructions::Log(level)
+        }
+        "ifjmp" => {
+            if value.is_empty() {
+                return Err("ifjmp requires a label".to_string());
+            };
+
+            Instructions::IfJmp(value.to_string())
+        }
+        _ => {
+            let host = options
+                .host_functions
+                .iter()
+                .find(|function| value.is_empty() && function.name == instruction);
+
+            let Some(function) = host else {
+                return Err(format!("Unknown instruction: {line}"));
+            };
+
+            Instructions::Host {
+                name: function.name.to_string(),
+                pops: function.pops,
+                pushes: function.pushes,
+            }
+        }
+    }))
+}
+
+/// Does static analysis on the AST, panicking with a [`ParseError`] if it is invalid. See
+/// [`try_validate`]
+pub fn validate(program: &[Program]) {
+    try_validate(program).unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// Does static analysis on the AST, rejecting unknown jump targets and broken contracts.
+/// See [`fallthrough_warnings`] for what is worth warning about without rejecting
+pub fn try_validate(program: &[Program]) -> Result<(), ParseError> {
+    let unknown_labels = unknown_references(program);
+
+    if !unknown_labels.is_empty() {
+        return Err(ParseError::program(unknown_labels.join("\n")));
+    }
+
+    let violations = verify_contracts(program);
+
+    if !violations.is_empty() {
+        return Err(ParseError::program(violations.join("\n")));
+    }
+
+    Ok(())
+}
+
+/// Sections never fall through into the one that follows them in the file. This lists
+/// every section that looks like it expects to, since it will instead end (or return to
+/// wherever it was jumped from)
+pub fn fallthrough_warnings(program: &[Program]) -> Vec<String> {
+    let sections: Vec<(&SectionName, &Vec<Instructions>)> = program
+        .iter()
+        .filter_map(|section| match section {
+            Program::Section(name, instructions) => Some((name, instructions)),
+            Program::Data(..)
+            | Program::Const(..)
+            | Program::States(..)
+            | Program::Contract(..)
+            | Program::Budget(..) => None,
+        })
+        .collect();
+
+    sections
+        .windows(2)
+        .filter_map(|pair| {
+            let [(name, instructions), (next, _)] = pair else {
+                unreachable!();
+            };
+
+            let ends = matches!(
+                instructions.last(),
+                Some(
+                    Instructions::Exit
+                        | Instructions::Jump(_)
+                        | Instructions::Switch(_)
+                        | Instructions::Ret
+                )
+            );
+
+            (!ends).then(|| {
+                format!(
+                    "section {} does not end with exit, jump or ret and will not fall through into {}",
+                    name.0, next.0
+                )
+            })
+        })
+        .collect()
+}
+
+/// Every jump, call, `pushdata`, `switch` and `getconst` that refers to something the
+/// program does not define
+pub(crate) fn unknown_references(program: &[Program]) -> Vec<String> {
+    // Resolve every section name up front so jumps can target sections defined
+    // anywhere in the file, and report every unknown target before running anything
+    let labels = resolve_labels(program);
+
+    let mut unknown_labels: Vec<String> = Vec::new();
+
+    for section in program {
+        let Program::Section(section, instructions) = section else {
+            continue;
+        };
+
+        for instruction in instructions {
+            match instruction {
+                Instructions::Jump(label)
+                | Instructions::IfJmp(label)
+                | Instructions::Call(label)
+                    if !labels.contains_key(label) =>
+                {
+                    unknown_labels.push(format!(
+                        "jump found to unknown label: {label} (in section {})",
+                        section.0
+                    ));
+                }
+                Instructions::PushData(name)
+                    if !program.iter().any(
+                        |data| matches!(data, Program::Data(other, _) if other.0 == *name),
+                    ) =>
+                {
+                    unknown_labels.push(format!(
+                        "pushdata of unknown data section: {name} (in section {})",
+                        section.0
+                    ));
+                }
+                Instructions::Switch(group) => {
+                    let states = program.iter().find_map(|states| match states {
+                        Program::States(other, states) if other == group => Some(states),
+                        _ => None,
+                    });
+
+                    let Some(states) = states else {
+                        unknown_labels.push(format!(
+                            "switch on unknown states group: {group} (in section {})",
+                            section.0
+                        ));
+                        continue;
+                    };
+
+                    // Every state needs somewhere to go
+                    for state in states {
+                        if !labels.contains_key(state) {
+                            unknown_labels.push(format!(
+                                "switch on {group} does not handle {state}: no section named {state} (in section {})",
+                                section.0
+                            ));
+                        }
+                    }
+                }
+                Instructions::GetConst(name, index) => {
+                    let constant = program.iter().find_map(|constant| match constant {
+                        Program::Const(other, values) if other == name => Some(values),
+                        _ => None,
+                    });
+
+                    match (constant, index) {
+                        (None, _) => unknown_labels.push(format!(
+                            "getconst of unknown const: {name} (in section {})",
+                            section.0
+                        )),
+                        (Some(values), Some(index)) if *index >= values.len() => {
+                            unknown_labels.push(format!(
+                                "getconst index {index} out of bounds for {name} of length {} (in section {})",
+                                values.len(),
+                                section.0
+                            ))
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    unknown_labels
+}
+
+/// Maps every section of instructions to its index in the program
+pub(crate) fn resolve_labels(program: &[Program]) -> HashMap<String, usize> {
+    program
+        .iter()
+        .enumerate()
+        .filter_map(|(index, section)| match section {
+            Program::Section(name, _) => Some((name.0.clone(), index)),
+            Program::Data(..)
+            | Program::Const(..)
+            | Program::States(..)
+            | Program::Contract(..)
+            | Program::Budget(..) => None,
+        })
+        .collect()
+}
+
+/// The contracts declared for a section
+pub(crate) fn contracts<'a>(
+    program: &'a [Program],
+    section: &'a str,
+) -> impl Iterator<Item = &'a Contract> + 'a {
+    program.iter().filter_map(move |item| match item {
+        Program::Contract(name, contract) if name.0 == section => Some(contract),
+        _ => None,
+    })
+}
+
+/// The most steps a section may run each time it is entered, if it has a `#budget`
+pub(crate) fn budget(program: &[Program], section: &str) -> Option<usize> {
+    program.iter().find_map(|item| match item {
+        Program::Budget(name, steps) if name.0 == section => Some(*steps),
+        _ => None,
+    })
+}
+
+/// Renders a program back into source that parses to the same program
+pub(crate) fn to_source(program: &[Program]) -> String {
+    let mut source = String::new();
+
+    for section in program {
+        match section {
+            Program::Section(name, instructions) => {
+                source.push_str(&format!("::{}:\n", name.0));
+
+                for contract in contracts(program, &name.0) {
+                    source.push_str(&format!("{contract}\n"));
+                }
+
+                if let Some(steps) = budget(program, &name.0) {
+                    source.push_str(&format!("#budget {steps} steps\n"));
+                }
+
+                for instruction in instructions {
+                    source.push_str(&format!("{instruction}\n"));
+                }
+            }
+            Program::Data(name, text) => {
+                source.push_str(&format!("::data {}:\n{text}", name.0));
+            }
+            Program::Const(name, values) => {
+                // Constants are written the same way as `push` operands
+                let values: Vec<String> = values
+                    .iter()
+                    .map(|value| {
+                        let push = Instructions::Push(value.clone()).to_string();
+                        push.trim_start_matches("push ").to_string()
+                    })
+                    .collect();
+
+                source.push_str(&format!("const {name} = [{}]\n", values.join(", ")));
+            }
+            Program::States(group, states) => {
+                source.push_str(&format!("#states {group} {}\n", states.join(" ")));
+            }
+            // Written out with the section they belong to
+            Program::Contract(..) | Program::Budget(..) => continue,
+        }
+
+        source.push('\n');
+    }
+
+    source
+}
+
+/// Follows the stack depth through every section with a `#pre` contract for as long as its
+/// instructions run in a straight line, reporting where they would underflow the depth the
+/// contract guarantees and `#post` contracts that cannot hold
+pub(crate) fn verify_contracts(program: &[Program]) -> Vec<String> {
+    let mut violations = Vec::new();
+
+    for item in program {
+        let Program::Section(name, instructions) = item else {
+            continue;
+        };
+
+        // The strongest guarantee among the section's preconditions
+        let Some((mut depth, exact)) = contracts(program, &name.0)
+            .filter(|contract| contract.kind == ContractKind::Pre)
+            .map(Contract::guarantees)
+            .max_by_key(|(depth, exact)| (*exact, *depth))
+        else {
+            continue;
+        };
+
+        let mut reached_end = true;
+
+        for (index, instruction) in instructions.iter().enumerate() {
+            let Some((pops, pushes)) = instruction.stack_effect() else {
+                reached_end = false;
+                break;
+            };
+
+            if pops > depth {
+                violations.push(format!(
+                    "section {} may underflow the stack at instruction {index} ({instruction}): it needs {pops} values but its #pre only guarantees {depth}",
+                    name.0
+                ));
+                reached_end = false;
+                break;
+            }
+
+            depth = depth - pops + pushes;
+        }
+
+        if !reached_end {
+            continue;
+        }
+
+        for contract in contracts(program, &name.0) {
+            if contract.kind != ContractKind::Post {
+                continue;
+            }
+
+            let fails = if exact {
+                !contract.holds(depth)
+            } else {
+                contract.fails_from(depth)
+            };
+
+            if fails {
+                violations.push(format!(
+                    "section {} cannot satisfy {contract}: it ends with a stack depth of {}{depth}",
+                    name.0,
+                    if exact { "" } else { "at least " }
+                ));
+            }
+        }
+    }
+
+    violations
+}
@@ -0,0 +1,225 @@
+//! Source rewrites that keep a program's behaviour, for `toylang refactor`. Every file of a
+//! project is parsed before anything is changed, and only the lines that refer to what is
+//! being changed are rewritten, so comments and layout survive.
+
+use std::ops::RangeInclusive;
+
+use crate::{
+    parser::{instruction_lines, is_section_header, parse, split_instruction},
+    Program, RunOptions,
+};
+
+/// Renames a section across every file of a project: its definition, any `override` of
+/// it and every `jump`, `ifjmp` and `pushdata` that refers to it. Returns the rewritten
+/// sources in the same order and how many lines changed, or why the rename is refused
+pub fn rename_section(
+    sources: &[String],
+    old: &str,
+    new: &str,
+) -> Result<(Vec<String>, usize), String> {
+    check_name(new)?;
+
+    let options = RunOptions::default();
+    let programs: Vec<Vec<Program>> = sources
+        .iter()
+        .map(|source| parse(source, &options))
+        .collect();
+    let items = || programs.iter().flatten();
+
+    if !items().any(|item| item.name().is_some_and(|name| name.0 == old)) {
+        return Err(format!("no section named {old}"));
+    }
+
+    if items().any(|item| item.name().is_some_and(|name| name.0 == new)) {
+        return Err(format!("a section named {new} already exists"));
+    }
+
+    // `switch` finds its target by the name of a state, which would have to be renamed too
+    if let Some(Program::States(group, _)) = items()
+        .find(|item| matches!(item, Program::States(_, states) if states.iter().any(|s| s == old)))
+    {
+        return Err(format!(
+            "{old} is a state of the {group} group, rename the state and the section together"
+        ));
+    }
+
+    let mut changed = 0;
+    let sources = sources
+        .iter()
+        .map(|source| {
+            let mut in_data = false;
+            let mut renamed = String::with_capacity(source.len());
+
+            for line in source.split_inclusive('\n') {
+                let text = line.trim_end_matches(['\n', '\r']);
+                let ending = &line[text.len()..];
+
+                let (prefix, header) = match text.strip_prefix("override ") {
+                    Some(header) => ("override ", header.trim_start()),
+                    None => ("", text),
+                };
+
+                let rewritten = if is_section_header(header) {
+                    let name = header.trim_matches(':');
+                    in_data = name.starts_with("data ");
+
+                    match name.strip_prefix("data ") {
+                        Some(data) if data.trim() == old => Some(format!("{prefix}::data {new}:")),
+                        None if name == old => Some(format!("{prefix}::{new}:")),
+                        _ => None,
+                    }
+                } else if in_data {
+                    None
+                } else {
+                    let (instruction, operand) = split_instruction(text);
+                    let refers = ["jump", "ifjmp", "call", "pushdata"]
+                        .iter()
+                        .any(|name| instruction.eq_ignore_ascii_case(name));
+
+                    (refers && operand == old).then(|| format!("{instruction} {new}"))
+                };
+
+                match rewritten {
+                    Some(rewritten) => {
+                        changed += 1;
+                        renamed.push_str(&rewritten);
+                        renamed.push_str(ending);
+                    }
+                    None => renamed.push_str(line),
+                }
+            }
+
+            renamed
+        })
+        .collect();
+
+    Ok((sources, changed))
+}
+
+/// Moves the instructions on a range of lines (counted from 1) into a new section at the
+/// end of the file and replaces them with a `call` to it. A call comes back once its target
+/// ends, so this keeps the program's behaviour as long as the range runs straight
+/// through: it must hold only instructions and comments from a single section and must
+/// not transfer control. Returns the new source and what the range pops and pushes
+pub fn extract_section(
+    source: &str,
+    lines: RangeInclusive<usize>,
+    name: &str,
+) -> Result<(String, (usize, usize)), String> {
+    check_name(name)?;
+
+    // Traces count as instructions, whether or not they are stripped from a run
+    let options = RunOptions {
+        debug: true,
+        ..RunOptions::default()
+    };
+
+    if parse(source, &options)
+        .iter()
+        .any(|item| item.name().is_some_and(|other| other.0 == name))
+    {
+        return Err(format!("a section named {name} already exists"));
+    }
+
+    let text: Vec<&str> = source.lines().collect();
+    let (first, last) = (*lines.start(), *lines.end());
+
+    if first == 0 || first > last || last > text.len() {
+        return Err(format!(
+            "lines {first}-{last} are not within the file's {} lines",
+            text.len()
+        ));
+    }
+
+    // Instructions in the range, found through the parser
+    let range = first - 1..last;
+    let mut found: Option<(String, Vec<usize>)> = None;
+    let mut extracted = Vec::new();
+
+    for (section, instructions, numbers) in instruction_lines(&text, &options) {
+        for (index, number) in numbers.iter().enumerate() {
+            if !range.contains(number) {
+                continue;
+            }
+
+            match &mut found {
+                Some((other, _)) if *other != section => {
+                    return Err(format!(
+                        "lines {first}-{last} span sections {other} and {section}"
+                    ))
+                }
+                Some((_, numbers)) => numbers.push(*number),
+                None => found = Some((section.clone(), vec![*number])),
+            }
+
+            extracted.push(instructions[index].clone());
+        }
+    }
+
+    let Some((section, numbers)) = found else {
+        return Err(format!("lines {first}-{last} hold no instructions"));
+    };
+
+    // Everything else in the range has to be a comment, so nothing else moves
+    for number in range.clone() {
+        let line = text[number];
+
+        if !numbers.contains(&number) && !line.is_empty() && !line.starts_with('/') {
+            return Err(format!(
+                "line {} is not an instruction of {section}: {line}",
+                number + 1
+            ));
+        }
+    }
+
+    let mut depth: isize = 0;
+    let mut lowest: isize = 0;
+
+    for instruction in &extracted {
+        let Some((pops, pushes)) = instruction.stack_effect() else {
+            return Err(format!(
+                "{instruction} transfers control, so the stack effect of lines {first}-{last} is not known"
+            ));
+        };
+
+        depth -= pops as isize;
+        lowest = lowest.min(depth);
+        depth += pushes as isize;
+    }
+
+    let pops = lowest.unsigned_abs();
+    let pushes = (depth - lowest) as usize;
+
+    let mut rewritten = String::with_capacity(source.len());
+
+    for (number, line) in text.iter().enumerate() {
+        if number == range.start {
+            rewritten.push_str(&format!("call {name}\n"));
+        }
+
+        if !range.contains(&number) {
+            rewritten.push_str(line);
+            rewritten.push('\n');
+        }
+    }
+
+    if !rewritten.ends_with("\n\n") {
+        rewritten.push('\n');
+    }
+
+    rewritten.push_str(&format!("::{name}:\n// ( {pops} -- {pushes} )\n"));
+    for line in &text[range] {
+        rewritten.push_str(line);
+        rewritten.push('\n');
+    }
+
+    Ok((rewritten, (pops, pushes)))
+}
+
+fn check_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.contains(|c: char| c.is_whitespace() || c == ':') {
+        return Err(format!("{name:?} is not a valid section name"));
+    }
+
+    Ok(())
+}
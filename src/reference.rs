@@ -0,0 +1,782 @@
+//! The instruction set reference, kept next to the code so generated documentation always
+//! matches the interpreter it was generated from.
+
+use crate::json::Json;
+
+/// Documentation for a single instruction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InstructionInfo {
+    pub name: &'static str,
+    /// What follows the instruction name in source, if anything
+    pub operand: Option<&'static str>,
+    /// The values popped and pushed, written bottom to top as `( before -- after )`
+    pub stack: &'static str,
+    pub description: &'static str,
+    /// Conditions that stop the program with an error
+    pub errors: &'static [&'static str],
+}
+
+/// Every instruction the language understands
+pub const INSTRUCTION_SET: &[InstructionInfo] = &[
+    InstructionInfo {
+        name: "push",
+        operand: Some("value"),
+        stack: "( -- value )",
+        description: "Pushes a literal: an Int such as `-5`, a Float (containing `.`), a Complex such as `1-2i`, `true` or `false`, a \"String\" or x\"hex\" Bytes",
+        errors: &[],
+    },
+    InstructionInfo {
+        name: "drop",
+        operand: None,
+        stack: "( a -- )",
+        description: "Discards the top value, doing nothing if the stack is empty",
+        errors: &[],
+    },
+    InstructionInfo {
+        name: "dup",
+        operand: None,
+        stack: "( a -- a a )",
+        description: "Duplicates the top value",
+        errors: &["the stack is empty"],
+    },
+    InstructionInfo {
+        name: "swap",
+        operand: None,
+        stack: "( b a -- a b )",
+        description: "Swaps the top two values",
+        errors: &["fewer than two values on the stack"],
+    },
+    InstructionInfo {
+        name: "over",
+        operand: None,
+        stack: "( b a -- b a b )",
+        description: "Copies the second value to the top",
+        errors: &["fewer than two values on the stack"],
+    },
+    InstructionInfo {
+        name: "rot",
+        operand: None,
+        stack: "( c b a -- b a c )",
+        description: "Moves the third value to the top",
+        errors: &["fewer than three values on the stack"],
+    },
+    InstructionInfo {
+        name: "add",
+        operand: None,
+        stack: "( b a -- a+b )",
+        description: "Adds two Ints or two Floats, or joins two Strings with a on the end. Matrices are added element by element, to each other or to a number",
+        errors: &[
+            "fewer than two values on the stack",
+            "operands are not both Int, both Float or both String, or Matrices of the same size",
+            "the result does not fit in an Int",
+        ],
+    },
+    InstructionInfo {
+        name: "sub",
+        operand: None,
+        stack: "( b a -- a-b )",
+        description: "Subtracts the second value from the top value, element by element for Matrices",
+        errors: &[
+            "fewer than two values on the stack",
+            "operands are not both Int or both Float, or Matrices of the same size",
+            "the result does not fit in an Int",
+        ],
+    },
+    InstructionInfo {
+        name: "mul",
+        operand: None,
+        stack: "( b a -- a*b )",
+        description: "Multiplies two Ints or two Floats. Matrices are multiplied element by element, see `matmul` for the matrix product",
+        errors: &[
+            "fewer than two values on the stack",
+            "operands are not both Int or both Float, or Matrices of the same size",
+            "the result does not fit in an Int",
+        ],
+    },
+    InstructionInfo {
+        name: "div",
+        operand: None,
+        stack: "( b a -- a/b )",
+        description: "Divides the top value by the second value, element by element for Matrices",
+        errors: &[
+            "fewer than two values on the stack",
+            "operands are not both Int or both Float, or Matrices of the same size",
+            "the divisor is zero",
+            "the result does not fit in an Int",
+        ],
+    },
+    InstructionInfo {
+        name: "mod",
+        operand: None,
+        stack: "( b a -- a%b )",
+        description: "Remainder of dividing the top Int by the second Int, with the sign of the top Int",
+        errors: &["fewer than two values on the stack", "operands are not both Int", "the divisor is zero"],
+    },
+    InstructionInfo {
+        name: "complex",
+        operand: None,
+        stack: "( re im -- z )",
+        description: "Makes a Complex number from a real and an imaginary part, which may be Ints or Floats. Complex numbers can also be pushed as literals like `1.5-2i`, and add, sub, mul and div work on them and on a Complex with a number",
+        errors: &["the operands are not both Ints or Floats"],
+    },
+    InstructionInfo {
+        name: "re",
+        operand: None,
+        stack: "( z -- re )",
+        description: "Pushes the real part of a Complex as a Float",
+        errors: &["the top value is not a Complex"],
+    },
+    InstructionInfo {
+        name: "im",
+        operand: None,
+        stack: "( z -- im )",
+        description: "Pushes the imaginary part of a Complex as a Float",
+        errors: &["the top value is not a Complex"],
+    },
+    InstructionInfo {
+        name: "cabs",
+        operand: None,
+        stack: "( z -- |z| )",
+        description: "Pushes the distance of a Complex from zero as a Float",
+        errors: &["the top value is not a Complex"],
+    },
+    InstructionInfo {
+        name: "vector",
+        operand: Some("length"),
+        stack: "( x1 .. xn -- vector )",
+        description: "Makes a vector, a Matrix with a single row, of the given number of Ints and Floats in the order they were pushed",
+        errors: &["fewer values on the stack than the length", "the values are not all numbers"],
+    },
+    InstructionInfo {
+        name: "matrix",
+        operand: Some("rows cols"),
+        stack: "( x1 .. xn -- matrix )",
+        description: "Makes a Matrix of the given size from Ints and Floats pushed row by row",
+        errors: &["fewer values on the stack than the matrix has elements", "the values are not all numbers"],
+    },
+    InstructionInfo {
+        name: "mget",
+        operand: None,
+        stack: "( matrix row col -- value )",
+        description: "Pushes the element of a Matrix at a row and column, counting from 0, as a Float",
+        errors: &["the operands are not a Matrix and two Ints", "the element is outside the matrix"],
+    },
+    InstructionInfo {
+        name: "matmul",
+        operand: None,
+        stack: "( b a -- a*b )",
+        description: "Pushes the matrix product of the top Matrix and the second",
+        errors: &["the operands are not both Matrices", "the top Matrix has a different number of columns than the second has rows"],
+    },
+    InstructionInfo {
+        name: "transpose",
+        operand: None,
+        stack: "( matrix -- matrix )",
+        description: "Swaps the rows and columns of a Matrix",
+        errors: &["the top value is not a Matrix"],
+    },
+    InstructionInfo {
+        name: "eq",
+        operand: None,
+        stack: "( b a -- a==b )",
+        description: "Pushes whether the top two values are equal",
+        errors: &["fewer than two values on the stack"],
+    },
+    InstructionInfo {
+        name: "ne",
+        operand: None,
+        stack: "( b a -- a!=b )",
+        description: "Pushes whether the top two values differ",
+        errors: &["fewer than two values on the stack"],
+    },
+    InstructionInfo {
+        name: "lt",
+        operand: None,
+        stack: "( b a -- a<b )",
+        description: "Pushes whether the top value is less than the second",
+        errors: &["fewer than two values on the stack", "operands are not both Int or both Float"],
+    },
+    InstructionInfo {
+        name: "gt",
+        operand: None,
+        stack: "( b a -- a>b )",
+        description: "Pushes whether the top value is greater than the second",
+        errors: &["fewer than two values on the stack", "operands are not both Int or both Float"],
+    },
+    InstructionInfo {
+        name: "le",
+        operand: None,
+        stack: "( b a -- a<=b )",
+        description: "Pushes whether the top value is less than or equal to the second",
+        errors: &["fewer than two values on the stack", "operands are not both Int or both Float"],
+    },
+    InstructionInfo {
+        name: "ge",
+        operand: None,
+        stack: "( b a -- a>=b )",
+        description: "Pushes whether the top value is greater than or equal to the second",
+        errors: &["fewer than two values on the stack", "operands are not both Int or both Float"],
+    },
+    InstructionInfo {
+        name: "and",
+        operand: None,
+        stack: "( b a -- a&&b )",
+        description: "Logical and of two Bools",
+        errors: &["fewer than two values on the stack", "operands are not both Bool"],
+    },
+    InstructionInfo {
+        name: "or",
+        operand: None,
+        stack: "( b a -- a||b )",
+        description: "Logical or of two Bools",
+        errors: &["fewer than two values on the stack", "operands are not both Bool"],
+    },
+    InstructionInfo {
+        name: "not",
+        operand: None,
+        stack: "( a -- !a )",
+        description: "Logical not of a Bool",
+        errors: &["the stack is empty", "the operand is not a Bool"],
+    },
+    InstructionInfo {
+        name: "jump",
+        operand: Some("label"),
+        stack: "( -- )",
+        description: "Continues execution in the named section for good, use `call` to come back",
+        errors: &["the section does not exist"],
+    },
+    InstructionInfo {
+        name: "ifjmp",
+        operand: Some("label"),
+        stack: "( condition -- )",
+        description: "Jumps to the named section if the condition is `true` or the Int 0",
+        errors: &[
+            "the stack is empty",
+            "the condition is not a Bool or Int",
+            "the section does not exist",
+        ],
+    },
+    InstructionInfo {
+        name: "call",
+        operand: Some("label"),
+        stack: "( -- )",
+        description: "Runs the named section as a subroutine: once it reaches `ret` or its end, execution continues after the call. Calls can be nested and recursive",
+        errors: &["the section does not exist", "calls are nested more than 10000 deep"],
+    },
+    InstructionInfo {
+        name: "ret",
+        operand: None,
+        stack: "( -- )",
+        description: "Returns from the section that was called to just after the `call`",
+        errors: &["no call is in progress"],
+    },
+    InstructionInfo {
+        name: "switch",
+        operand: Some("group"),
+        stack: "( state -- )",
+        description: "Jumps to the section named after a state of a `#states group A B C` declaration. Every state of the group must have a section",
+        errors: &[
+            "the group does not exist",
+            "the state is not an Int",
+            "the Int is not one of the group's states",
+        ],
+    },
+    InstructionInfo {
+        name: "exit",
+        operand: None,
+        stack: "( -- )",
+        description: "Ends the program",
+        errors: &[],
+    },
+    InstructionInfo {
+        name: "print",
+        operand: None,
+        stack: "( a -- )",
+        description: "Writes the top value to the program output, in the format set by `setfmt`. `print <format>` uses that format for just this value instead",
+        errors: &["the stack is empty", "the output limit is reached"],
+    },
+    InstructionInfo {
+        name: "setfmt",
+        operand: None,
+        stack: "( format -- )",
+        description: "Sets how `print` lays out values for the rest of the run, as `[[fill]align][0][width][,][.precision]`: e.g. `>8` right aligns in 8 characters, `08` pads numbers with zeros, `,` separates thousands and `.2` shows 2 decimal places. The empty format prints values as they are",
+        errors: &["the top value is not a String", "the format is invalid"],
+    },
+    InstructionInfo {
+        name: "printtable",
+        operand: None,
+        stack: "( rows -- )",
+        description: "Writes a List of rows, each a List of values, as a table with aligned columns. The first row is set apart as a header, numbers are aligned right and short rows are padded with empty cells",
+        errors: &["the top value is not a List of Lists", "the output limit is reached"],
+    },
+    InstructionInfo {
+        name: "flush",
+        operand: None,
+        stack: "( -- )",
+        description: "Writes out any buffered program output",
+        errors: &[],
+    },
+    InstructionInfo {
+        name: "trace",
+        operand: Some("message"),
+        stack: "( -- )",
+        description: "Writes the message and the top value to stderr. Stripped unless running with --debug or -D DEBUG",
+        errors: &[],
+    },
+    InstructionInfo {
+        name: "log",
+        operand: Some("level"),
+        stack: "( message -- )",
+        description: "Sends a String to the logger at trace, debug, info, warn or error level",
+        errors: &["the top value is not a String"],
+    },
+    InstructionInfo {
+        name: "break",
+        operand: None,
+        stack: "( -- )",
+        description: "Pauses execution when running under `toylang debug`",
+        errors: &[],
+    },
+    InstructionInfo {
+        name: "tick",
+        operand: None,
+        stack: "( -- )",
+        description: "Starts a stopwatch. Stopwatches can be nested",
+        errors: &[],
+    },
+    InstructionInfo {
+        name: "tock",
+        operand: None,
+        stack: "( -- nanoseconds )",
+        description: "Stops the most recent stopwatch and pushes the elapsed time",
+        errors: &["no stopwatch is running"],
+    },
+    InstructionInfo {
+        name: "frame",
+        operand: Some("fps"),
+        stack: "( -- )",
+        description: "Flushes output, then sleeps for whatever is left of a frame at the given frames per second since the previous `frame`, for steady animation. A frame that took too long starts the next one immediately",
+        errors: &[],
+    },
+    InstructionInfo {
+        name: "tone",
+        operand: None,
+        stack: "( frequency duration -- )",
+        description: "Plays a tone of the frequency in hertz for the duration in milliseconds, waiting until it ends. Only allowed when sound is enabled, e.g. with `run --allow-sound`",
+        errors: &["the operands are not two Ints", "sound is disabled"],
+    },
+    InstructionInfo {
+        name: "meminfo",
+        operand: None,
+        stack: "( -- depth heap names )",
+        description: "Pushes the stack depth, the number of heap allocations its values and those of globals and variables own (one per String, Bytes and Matrix, and one per List plus those of its items), and the number of globals and variables",
+        errors: &[],
+    },
+    InstructionInfo {
+        name: "getconst",
+        operand: Some("name [index]"),
+        stack: "( [index] -- value )",
+        description: "Pushes an element of a `const name = [...]` table. The index is popped off the stack unless it is written after the name, in which case it is checked before the program runs",
+        errors: &[
+            "the const does not exist",
+            "the index is not an Int",
+            "the index is out of bounds",
+        ],
+    },
+    InstructionInfo {
+        name: "pushdata",
+        operand: Some("name"),
+        stack: "( -- text )",
+        description: "Pushes the raw text of a `::data name:` section as a String",
+        errors: &["the data section does not exist"],
+    },
+    InstructionInfo {
+        name: "store",
+        operand: Some("name"),
+        stack: "( value -- )",
+        description: "Pops a value into a variable of the program, which lasts until the end of the run",
+        errors: &["the stack is empty"],
+    },
+    InstructionInfo {
+        name: "load",
+        operand: Some("name"),
+        stack: "( -- value )",
+        description: "Pushes the value of a variable set with `store`",
+        errors: &["nothing has been stored in the variable"],
+    },
+    InstructionInfo {
+        name: "getglobal",
+        operand: Some("name"),
+        stack: "( -- value )",
+        description: "Pushes the value of a global shared with the host",
+        errors: &["the global is not set"],
+    },
+    InstructionInfo {
+        name: "setglobal",
+        operand: Some("name"),
+        stack: "( value -- )",
+        description: "Pops a value into a global shared with the host",
+        errors: &["the stack is empty"],
+    },
+    InstructionInfo {
+        name: "argc",
+        operand: None,
+        stack: "( -- count )",
+        description: "Pushes how many arguments the program was run with, given after `--` as in `toylang run program.toy -- 1 2`",
+        errors: &[],
+    },
+    InstructionInfo {
+        name: "argv",
+        operand: Some("[index]"),
+        stack: "( [index] -- argument )",
+        description: "Pushes one of the arguments the program was run with as a String, counting from 0. The index is popped off the stack unless it is written after the instruction",
+        errors: &["the index is not an Int", "the index is out of bounds"],
+    },
+    InstructionInfo {
+        name: "byteat",
+        operand: None,
+        stack: "( bytes index -- byte )",
+        description: "Pushes the byte at an index as an Int",
+        errors: &["the operands are not Bytes and an Int", "the index is out of bounds"],
+    },
+    InstructionInfo {
+        name: "byteslice",
+        operand: None,
+        stack: "( bytes start end -- slice )",
+        description: "Pushes the Bytes from start up to (not including) end",
+        errors: &["the operands are not Bytes and two Ints", "the range is out of bounds"],
+    },
+    InstructionInfo {
+        name: "bytelen",
+        operand: None,
+        stack: "( bytes -- length )",
+        description: "Pushes the number of Bytes",
+        errors: &["the top value is not Bytes"],
+    },
+    InstructionInfo {
+        name: "tobytes",
+        operand: None,
+        stack: "( string -- bytes )",
+        description: "Converts a String to its UTF-8 Bytes",
+        errors: &["the top value is not a String"],
+    },
+    InstructionInfo {
+        name: "frombytes",
+        operand: None,
+        stack: "( bytes -- string )",
+        description: "Converts UTF-8 Bytes back into a String",
+        errors: &["the top value is not Bytes", "the Bytes are not valid UTF-8"],
+    },
+    InstructionInfo {
+        name: "len",
+        operand: None,
+        stack: "( string -- length )",
+        description: "Pushes the number of characters in a String, or the number of items in a List",
+        errors: &["the top value is not a String or a List"],
+    },
+    InstructionInfo {
+        name: "substr",
+        operand: None,
+        stack: "( string start end -- substring )",
+        description: "Pushes the characters of a String from start up to (not including) end",
+        errors: &["the operands are not a String and two Ints", "the range is out of bounds"],
+    },
+    InstructionInfo {
+        name: "strindex",
+        operand: None,
+        stack: "( string needle -- index )",
+        description: "Pushes the character index where needle first appears in string, or -1 if it does not",
+        errors: &["the operands are not two Strings"],
+    },
+    InstructionInfo {
+        name: "toint",
+        operand: None,
+        stack: "( value -- int )",
+        description: "Converts a Float (rounding toward zero), a Bool (1 or 0) or a String holding a whole number to an Int",
+        errors: &[
+            "the stack is empty",
+            "the value is not an Int, Float, Bool or String",
+            "the Float does not fit in an Int",
+            "the String is not a whole number",
+        ],
+    },
+    InstructionInfo {
+        name: "tofloat",
+        operand: None,
+        stack: "( value -- float )",
+        description: "Converts an Int, a Bool (1.0 or 0.0) or a String holding a number to a Float",
+        errors: &[
+            "the stack is empty",
+            "the value is not an Int, Float, Bool or String",
+            "the String is not a number",
+        ],
+    },
+    InstructionInfo {
+        name: "tostring",
+        operand: None,
+        stack: "( value -- string )",
+        description: "Converts any value to the String `print` shows for it",
+        errors: &["the stack is empty"],
+    },
+    InstructionInfo {
+        name: "tobool",
+        operand: None,
+        stack: "( value -- bool )",
+        description: "Converts an Int or Float to a Bool that is true unless it is zero, or the String `true` or `false` to a Bool",
+        errors: &[
+            "the stack is empty",
+            "the value is not an Int, Float, Bool or String",
+            "the String is not `true` or `false`",
+        ],
+    },
+    InstructionInfo {
+        name: "list",
+        operand: None,
+        stack: "( -- list )",
+        description: "Pushes an empty List",
+        errors: &[],
+    },
+    InstructionInfo {
+        name: "append",
+        operand: None,
+        stack: "( list value -- list )",
+        description: "Adds a value to the end of a List",
+        errors: &["the operands are not a List and a value"],
+    },
+    InstructionInfo {
+        name: "get",
+        operand: None,
+        stack: "( list index -- list value )",
+        description: "Pushes the List back and then a copy of its item at an index, counting from 0",
+        errors: &[
+            "the operands are not a List and an Int",
+            "the index is out of bounds",
+        ],
+    },
+    InstructionInfo {
+        name: "set",
+        operand: None,
+        stack: "( list index value -- list )",
+        description: "Replaces the item of a List at an index, counting from 0",
+        errors: &[
+            "the operands are not a List, an Int and a value",
+            "the index is out of bounds",
+        ],
+    },
+    InstructionInfo {
+        name: "pop",
+        operand: None,
+        stack: "( list -- list value )",
+        description: "Takes the last item off a List, pushing the rest of the List and then the item",
+        errors: &["the top value is not a List", "the List is empty"],
+    },
+    InstructionInfo {
+        name: "fopen",
+        operand: None,
+        stack: "( path mode -- handle )",
+        description: "Opens a file with any of the mode flags `r`, `w`, `a` and `+`",
+        errors: &[
+            "the operands are not two Strings",
+            "the mode has an unknown flag",
+            "the file cannot be opened",
+        ],
+    },
+    InstructionInfo {
+        name: "freadn",
+        operand: None,
+        stack: "( handle count -- bytes )",
+        description: "Reads up to count Bytes from a file",
+        errors: &["the operands are not a Handle and an Int", "the handle is not open", "reading fails"],
+    },
+    InstructionInfo {
+        name: "fwriteh",
+        operand: None,
+        stack: "( handle data -- )",
+        description: "Writes a String or Bytes to a file",
+        errors: &[
+            "the operands are not a Handle and a String or Bytes",
+            "the handle is not open",
+            "writing fails",
+        ],
+    },
+    InstructionInfo {
+        name: "fseek",
+        operand: None,
+        stack: "( handle offset -- )",
+        description: "Moves the file position to an offset from the start of the file",
+        errors: &["the operands are not a Handle and an Int", "the handle is not open", "seeking fails"],
+    },
+    InstructionInfo {
+        name: "fclose",
+        operand: None,
+        stack: "( handle -- )",
+        description: "Closes a file",
+        errors: &["the top value is not a Handle", "the handle is not open"],
+    },
+    InstructionInfo {
+        name: "cnew",
+        operand: None,
+        stack: "( width height -- )",
+        description: "Replaces the canvas with a black one of the given size in pixels, up to 16384 on each side",
+        errors: &["the operands are not two Ints", "the size is out of range"],
+    },
+    InstructionInfo {
+        name: "cset",
+        operand: None,
+        stack: "( x y color -- )",
+        description: "Colours a pixel of the canvas, counting from the top left, with a color whose bytes are red, green and blue (0xRRGGBB, so 16711680 is red)",
+        errors: &["the operands are not three Ints", "there is no canvas", "the pixel is outside the canvas", "the color is over 0xffffff"],
+    },
+    InstructionInfo {
+        name: "csave",
+        operand: None,
+        stack: "( path -- )",
+        description: "Saves the canvas as a PNG file",
+        errors: &["the top value is not a String", "there is no canvas", "writing fails"],
+    },
+    InstructionInfo {
+        name: "forward",
+        operand: None,
+        stack: "( distance -- )",
+        description: "Moves the turtle forward by a number of pixels, drawing a white line behind it while its pen is down. `cnew` puts the turtle in the middle of the canvas facing up with its pen down, and it may wander off the canvas",
+        errors: &["the top value is not an Int", "there is no canvas"],
+    },
+    InstructionInfo {
+        name: "turn",
+        operand: None,
+        stack: "( degrees -- )",
+        description: "Turns the turtle clockwise by a number of degrees, so `push 270 turn` turns it left",
+        errors: &["the top value is not an Int", "there is no canvas"],
+    },
+    InstructionInfo {
+        name: "penup",
+        operand: None,
+        stack: "( -- )",
+        description: "Lifts the turtle's pen, so it moves without drawing",
+        errors: &["there is no canvas"],
+    },
+    InstructionInfo {
+        name: "pendown",
+        operand: None,
+        stack: "( -- )",
+        description: "Puts the turtle's pen down, so it draws as it moves",
+        errors: &["there is no canvas"],
+    },
+    InstructionInfo {
+        name: "tsave",
+        operand: None,
+        stack: "( path -- )",
+        description: "Saves what the turtle drew as a PNG file, the same as `csave`",
+        errors: &["the top value is not a String", "there is no canvas", "writing fails"],
+    },
+    InstructionInfo {
+        name: "readall",
+        operand: None,
+        stack: "( -- input )",
+        description: "Reads the rest of stdin into a String",
+        errors: &["reading fails"],
+    },
+    InstructionInfo {
+        name: "readline",
+        operand: None,
+        stack: "( -- line read )",
+        description: "Reads the next line of stdin without its line break, then pushes whether a line was read",
+        errors: &["reading fails"],
+    },
+    InstructionInfo {
+        name: "read",
+        operand: None,
+        stack: "( -- line )",
+        description: "Reads the next line of stdin without its line break",
+        errors: &["reading fails", "there is no more input"],
+    },
+    InstructionInfo {
+        name: "readint",
+        operand: None,
+        stack: "( -- int )",
+        description: "Reads the next line of stdin as an Int, ignoring surrounding whitespace",
+        errors: &["reading fails", "there is no more input", "the line is not an Int"],
+    },
+    InstructionInfo {
+        name: "readfloat",
+        operand: None,
+        stack: "( -- float )",
+        description: "Reads the next line of stdin as a Float, ignoring surrounding whitespace",
+        errors: &["reading fails", "there is no more input", "the line is not a number"],
+    },
+    InstructionInfo {
+        name: "csvparse",
+        operand: None,
+        stack: "( string -- rows )",
+        description: "Parses CSV into a List of rows, each a List of String fields",
+        errors: &["the top value is not a String"],
+    },
+    InstructionInfo {
+        name: "csvemit",
+        operand: None,
+        stack: "( rows -- string )",
+        description: "Renders a List of rows as CSV",
+        errors: &["the top value is not a List"],
+    },
+];
+
+/// Looks up the documentation for an instruction by name
+pub fn instruction_info(name: &str) -> Option<&'static InstructionInfo> {
+    INSTRUCTION_SET
+        .iter()
+        .find(|info| info.name.eq_ignore_ascii_case(name))
+}
+
+/// Renders instructions as a markdown reference
+pub fn to_markdown(instructions: &[InstructionInfo]) -> String {
+    let mut markdown = String::from("# Instruction reference\n");
+
+    for info in instructions {
+        markdown.push_str(&format!("\n## `{}`\n\n", info.name));
+
+        if let Some(operand) = info.operand {
+            markdown.push_str(&format!("Usage: `{} <{operand}>`\n\n", info.name));
+        }
+
+        markdown.push_str(&format!(
+            "Stack: `{}`\n\n{}\n",
+            info.stack, info.description
+        ));
+
+        if !info.errors.is_empty() {
+            markdown.push_str("\nFails when:\n\n");
+
+            for error in info.errors {
+                markdown.push_str(&format!("- {error}\n"));
+            }
+        }
+    }
+
+    markdown
+}
+
+/// Renders instructions as a JSON array of objects
+pub fn to_json(instructions: &[InstructionInfo]) -> String {
+    let entries: Vec<String> = instructions
+        .iter()
+        .map(|info| {
+            let operand = info.operand.map_or("null".to_string(), json_string);
+            let errors: Vec<String> = info.errors.iter().map(|error| json_string(error)).collect();
+
+            format!(
+                "  {{\"name\": {}, \"operand\": {operand}, \"stack\": {}, \"description\": {}, \"errors\": [{}]}}",
+                json_string(info.name),
+                json_string(info.stack),
+                json_string(info.description),
+                errors.join(", ")
+            )
+        })
+        .collect();
+
+    format!("[\n{}\n]\n", entries.join(",\n"))
+}
+
+/// Quotes and escapes a string for JSON
+fn json_string(value: &str) -> String {
+    Json::from(value).to_string()
+}
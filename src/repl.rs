@@ -0,0 +1,152 @@
+use std::io::Write;
+
+use crate::{
+    instructions::Instructions,
+    interpreter::{Interpreter, RunOptions},
+    parser::{parse_instruction, to_source, try_parse, Program, SectionName},
+};
+
+/// Runs an interactive session. Instructions are executed as they are entered, while a
+/// section header starts a definition that lasts until the next empty line. Entering a
+/// section that already exists replaces its body, so sections can be refined in place
+pub fn run(options: RunOptions) {
+    let mut interpreter = Interpreter::new(Vec::new(), options);
+    let mut definition: Option<(SectionName, Vec<Instructions>)> = None;
+
+    loop {
+        print!("{}", if definition.is_some() { "... " } else { "> " });
+        std::io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        if std::io::stdin().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+
+        let line = line.trim();
+
+        if let Some((name, instructions)) = &mut definition {
+            if line.is_empty() {
+                let (name, instructions) = definition.take().unwrap();
+                let section = name.0.clone();
+
+                if interpreter.define_section(name, instructions) {
+                    println!("redefined {section}");
+                } else {
+                    println!("defined {section}");
+                }
+                continue;
+            }
+
+            match parse_instruction(line, &interpreter.options) {
+                Ok(Some(instruction)) => instructions.push(instruction),
+                Ok(None) => {}
+                Err(error) => {
+                    eprintln!("error: {error}");
+                    eprintln!("(line ignored, still defining {})", name.0);
+                }
+            }
+            continue;
+        }
+
+        if line.is_empty() || line.starts_with(['/', '#']) {
+            continue;
+        }
+
+        if line.starts_with("::") && line.ends_with(':') {
+            definition = Some((SectionName(line.trim_matches(':').to_string()), Vec::new()));
+            continue;
+        }
+
+        if let Some(command) = line.strip_prefix(':') {
+            if let Err(error) = repl_command(&mut interpreter, command) {
+                eprintln!("error: {error}");
+            }
+            continue;
+        }
+
+        let instruction = match parse_instruction(line, &interpreter.options) {
+            Ok(Some(instruction)) => instruction,
+            Ok(None) => continue,
+            Err(error) => {
+                eprintln!("error: {error}");
+                continue;
+            }
+        };
+
+        let result = interpreter.execute(vec![instruction]);
+        interpreter.out.flush().unwrap();
+
+        match result {
+            Ok(true) => break,
+            Ok(false) => println!("{:?}", interpreter.stack),
+            Err(error) => eprintln!("error: {error}"),
+        }
+    }
+
+    interpreter.finish();
+}
+
+/// Handles a `:command` entered in the REPL, returning why it failed if it did
+fn repl_command(interpreter: &mut Interpreter, command: &str) -> Result<(), String> {
+    let (command, argument) = command.split_once(' ').unwrap_or((command, ""));
+    let argument = argument.trim();
+
+    match command {
+        "save" => {
+            if argument.is_empty() {
+                return Err(":save requires a path".to_string());
+            }
+
+            let source = to_source(&interpreter.program);
+
+            std::fs::write(argument, source)
+                .map_err(|error| format!("Cannot write {argument}: {error}"))?;
+
+            println!("saved {} sections to {argument}", interpreter.program.len());
+        }
+        "open" => {
+            if argument.is_empty() {
+                return Err(":open requires a path".to_string());
+            }
+
+            let contents = std::fs::read_to_string(argument)
+                .map_err(|error| format!("Cannot read {argument}: {error}"))?;
+
+            let program = try_parse(&contents, &interpreter.options)
+                .map_err(|error| error.in_file(argument).to_string())?;
+            let count = program.len();
+
+            for section in program {
+                match section {
+                    Program::Section(name, instructions) => {
+                        interpreter.define_section(name, instructions);
+                    }
+                    Program::Data(name, text) => {
+                        interpreter.define_data(name, text);
+                    }
+                    Program::Const(name, values) => {
+                        interpreter.define_const(name, values);
+                    }
+                    Program::States(group, states) => {
+                        interpreter.define_states(group, states);
+                    }
+                    Program::Contract(name, contract) => {
+                        interpreter.define_contract(name, contract);
+                    }
+                    Program::Budget(name, steps) => {
+                        interpreter.define_budget(name, steps);
+                    }
+                }
+            }
+
+            println!("loaded {count} sections from {argument}");
+        }
+        _ => {
+            return Err(format!(
+                "Unknown command: :{command} (expected :save or :open)"
+            ))
+        }
+    }
+
+    Ok(())
+}
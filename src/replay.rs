@@ -0,0 +1,85 @@
+//! Recordings of everything a run read from outside the program (input and clock readings),
+//! so a run that misbehaved can be reproduced exactly by feeding the same values back.
+
+use std::path::Path;
+
+use crate::json::Json;
+
+/// Bumped whenever the layout changes, so old recordings are rejected instead of misread
+const FORMAT_VERSION: usize = 1;
+
+/// A value the program read from outside
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayEvent {
+    /// Text returned by `readall` or `readline`, including the line break
+    Input(String),
+    /// Nanoseconds measured by `tock`
+    Elapsed(usize),
+}
+
+/// The values a run read, in the order it read them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Replay {
+    pub events: Vec<ReplayEvent>,
+}
+
+impl Replay {
+    /// Reads a recording written by a run with `record` set
+    pub fn load(path: &Path) -> Result<Replay, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| format!("Cannot read {}: {error}", path.display()))?;
+
+        Replay::from_json(&Json::parse(&contents)?)
+            .map_err(|error| format!("Invalid replay file {}: {error}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_json().to_string())
+    }
+
+    pub fn to_json(&self) -> Json {
+        let events = self
+            .events
+            .iter()
+            .map(|event| match event {
+                ReplayEvent::Input(text) => Json::object([("input", Json::from(text.as_str()))]),
+                // Written as a string since JSON numbers cannot hold every usize exactly
+                ReplayEvent::Elapsed(nanos) => {
+                    Json::object([("elapsed", Json::from(nanos.to_string()))])
+                }
+            })
+            .collect();
+
+        Json::object([
+            ("version", Json::from(FORMAT_VERSION)),
+            ("events", Json::Array(events)),
+        ])
+    }
+
+    pub fn from_json(json: &Json) -> Result<Replay, String> {
+        if json.get("version").and_then(Json::as_usize) != Some(FORMAT_VERSION) {
+            return Err("unsupported version".to_string());
+        }
+
+        let events = json
+            .get("events")
+            .and_then(Json::as_array)
+            .ok_or("`events` must be an array")?
+            .iter()
+            .map(|event| {
+                if let Some(text) = event.get("input").and_then(Json::as_str) {
+                    return Ok(ReplayEvent::Input(text.to_string()));
+                }
+
+                event
+                    .get("elapsed")
+                    .and_then(Json::as_str)
+                    .and_then(|nanos| nanos.parse().ok())
+                    .map(ReplayEvent::Elapsed)
+                    .ok_or(format!("invalid event {event}"))
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Replay { events })
+    }
+}
@@ -0,0 +1,209 @@
+//! Running untrusted programs, e.g. from a web playground, within fixed limits
+
+use std::{
+    cell::RefCell,
+    io::{Cursor, Write},
+    panic::AssertUnwindSafe,
+    rc::Rc,
+};
+
+use crate::{
+    error::RuntimeError,
+    interpreter::{Interpreter, RunOptions, RunStatus},
+    log::{LogLevel, Logger},
+    parser::{try_parse, try_validate},
+};
+
+/// How much a sandboxed run may do before it is stopped
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    /// Instructions the program may execute
+    pub steps: usize,
+    /// Approximate number of bytes the program's values may occupy
+    pub memory: usize,
+    /// Bytes the program may print, it is stopped by the print that goes past them
+    pub output_bytes: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            steps: 1_000_000,
+            memory: 16 * 1024 * 1024,
+            output_bytes: 64 * 1024,
+        }
+    }
+}
+
+/// What a sandboxed run printed and how it ended
+#[derive(Debug, Clone, PartialEq)]
+pub struct SandboxResult {
+    /// Everything the program printed, up to the output limit
+    pub output: String,
+    /// How many bytes of the print that went over the output limit were dropped
+    pub truncated: usize,
+    /// Why the program did not finish normally, whether it failed to parse, hit an error
+    /// while running or ran into one of its limits
+    pub error: Option<String>,
+}
+
+/// Parses and runs `src` within `limits`. The program reads empty input, cannot open
+/// files or wait for a `frame`, and its log messages are discarded. Errors of any kind,
+/// including parse errors, are returned in the result rather than panicking
+pub fn run_sandboxed(src: &str, limits: Limits) -> SandboxResult {
+    let output = SharedBuffer::default();
+
+    let options = RunOptions {
+        logger: Box::new(Discard),
+        max_memory: Some(limits.memory),
+        max_output_bytes: Some(limits.output_bytes),
+        files: false,
+        frames: false,
+        ..RunOptions::default()
+    };
+
+    // Parsing and instructions report bad programs as errors rather than panicking. This
+    // only guards against a bug in one of them, and only on targets that unwind
+    let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        let program = match try_parse(src, &options)
+            .and_then(|program| try_validate(&program).map(|_| program))
+        {
+            Ok(program) => program,
+            Err(error) => return (Some(error.to_string()), 0),
+        };
+
+        let mut interpreter = Interpreter::new(program, options);
+        interpreter.set_output(output.clone());
+        interpreter.set_input(Cursor::new(Vec::new()));
+
+        match interpreter.run_for(limits.steps) {
+            Ok(RunStatus::Finished) => (None, 0),
+            Ok(RunStatus::Yielded) => (
+                Some(format!(
+                    "Step limit reached: the program ran {} instructions without finishing",
+                    limits.steps
+                )),
+                0,
+            ),
+            Err(error @ RuntimeError::OutputLimit { truncated, .. }) => {
+                (Some(error.to_string()), truncated)
+            }
+            Err(error) => (Some(error.to_string()), 0),
+        }
+    }));
+
+    let (error, truncated) = result.unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| {
+                payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+            })
+            .unwrap_or_else(|| "unknown error".to_string());
+
+        (Some(message), 0)
+    });
+
+    let output = String::from_utf8_lossy(&output.0.borrow()).into_owned();
+
+    SandboxResult {
+        output,
+        truncated,
+        error,
+    }
+}
+
+/// Runs `source` in the sandbox with the default [`Limits`] and returns what it printed,
+/// followed by the error it stopped with, if any. Takes and returns nothing but strings
+/// so a web playground can export it as is with `#[wasm_bindgen]`
+pub fn run_source(source: &str) -> String {
+    let result = run_sandboxed(source, Limits::default());
+    let mut text = result.output;
+
+    if let Some(error) = result.error {
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push_str(&format!("error: {error}"));
+    }
+
+    text
+}
+
+/// [`run_source`] for pages that load the module without bindings generated by
+/// wasm-bindgen. The page writes the source into memory from `toylang_alloc`, calls
+/// `toylang_run` with it and reads as many bytes as that returns from `toylang_output`
+///
+/// wasm32 aborts on a panic instead of unwinding, so the guard in [`run_sandboxed`] does
+/// nothing here and a panic would take the page's module down with it. No program should
+/// cause one: malformed source is a parse error and every instruction fails with a
+/// [`RuntimeError`] on operands it cannot handle
+#[cfg(target_arch = "wasm32")]
+mod exports {
+    use std::cell::RefCell;
+
+    thread_local! {
+        /// What the last run returned, kept until the next one
+        static OUTPUT: RefCell<String> = const { RefCell::new(String::new()) };
+    }
+
+    /// Reserves `len` bytes for the page to write source into
+    #[no_mangle]
+    pub extern "C" fn toylang_alloc(len: usize) -> *mut u8 {
+        let mut buffer = std::mem::ManuallyDrop::new(Vec::<u8>::with_capacity(len));
+        buffer.as_mut_ptr()
+    }
+
+    /// Releases memory reserved with `toylang_alloc`
+    ///
+    /// # Safety
+    /// `ptr` and `len` must be exactly what was passed to and returned by `toylang_alloc`
+    #[no_mangle]
+    pub unsafe extern "C" fn toylang_free(ptr: *mut u8, len: usize) {
+        drop(Vec::from_raw_parts(ptr, 0, len));
+    }
+
+    /// Runs the `len` bytes of source at `ptr`, returning the length of the output
+    ///
+    /// # Safety
+    /// `ptr` must point to `len` initialised bytes
+    #[no_mangle]
+    pub unsafe extern "C" fn toylang_run(ptr: *const u8, len: usize) -> usize {
+        let source = String::from_utf8_lossy(std::slice::from_raw_parts(ptr, len));
+        let output = super::run_source(&source);
+        let length = output.len();
+
+        OUTPUT.with(|last| *last.borrow_mut() = output);
+        length
+    }
+
+    /// Where the output of the last `toylang_run` starts
+    #[no_mangle]
+    pub extern "C" fn toylang_output() -> *const u8 {
+        OUTPUT.with(|last| last.borrow().as_ptr())
+    }
+}
+
+/// Output buffer that stays readable after being handed to the interpreter, for embedders
+/// that want to see what a program printed
+#[derive(Clone, Default)]
+pub struct SharedBuffer(pub Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+struct Discard;
+
+impl Logger for Discard {
+    fn log(&self, _level: LogLevel, _message: &str) {}
+}
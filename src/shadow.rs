@@ -0,0 +1,73 @@
+use crate::{instructions::Instructions, value::DataType};
+
+/// Where a value on the stack came from
+#[derive(Debug, Clone)]
+struct Origin {
+    type_name: &'static str,
+    index: usize,
+    instruction: Instructions,
+}
+
+/// Runs alongside the stack in debug runs, remembering the type of every value and the
+/// instruction that produced it, so a type error can point at where the wrong value was
+/// introduced rather than only where it was finally used
+#[derive(Debug, Default)]
+pub(crate) struct ShadowStack {
+    origins: Vec<Option<Origin>>,
+}
+
+impl ShadowStack {
+    /// Updates the shadow after `instruction` (at `index`) ran successfully, changing the
+    /// stack from `before` values to what is now in `stack`
+    pub(crate) fn track(
+        &mut self,
+        stack: &[DataType],
+        index: usize,
+        instruction: &Instructions,
+        before: usize,
+    ) {
+        // Values the host added or removed between runs have no known origin
+        self.origins.resize(before, None);
+
+        let len = self.origins.len();
+
+        // Instructions that only move values around keep their origins
+        match instruction {
+            Instructions::Dup if len >= 1 => self.origins.push(self.origins[len - 1].clone()),
+            Instructions::Over if len >= 2 => self.origins.push(self.origins[len - 2].clone()),
+            Instructions::Swap if len >= 2 => self.origins.swap(len - 1, len - 2),
+            Instructions::Rot if len >= 3 => self.origins[len - 3..].rotate_left(1),
+            _ => {
+                self.origins.truncate(
+                    before
+                        .saturating_sub(instruction.operands())
+                        .min(stack.len()),
+                );
+
+                for value in &stack[self.origins.len()..] {
+                    self.origins.push(Some(Origin {
+                        type_name: value.type_name(),
+                        index,
+                        instruction: instruction.clone(),
+                    }));
+                }
+            }
+        }
+    }
+
+    /// Describes where the values `instruction` operates on came from, top first
+    pub(crate) fn describe_operands(&self, instruction: &Instructions) -> Vec<String> {
+        self.origins
+            .iter()
+            .rev()
+            .take(instruction.operands())
+            .flatten()
+            .map(|origin| {
+                format!(
+                    "{} pushed by instruction {} ({})",
+                    origin.type_name, origin.index, origin.instruction
+                )
+            })
+            .collect()
+    }
+}
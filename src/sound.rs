@@ -0,0 +1,20 @@
+use std::{io::Write, time::Duration};
+
+/// Plays the tones programs make with the `tone` instruction
+pub trait Speaker {
+    fn tone(&self, frequency: usize, duration: Duration);
+}
+
+/// Rings the terminal bell for each tone, which is the one sound every platform can make
+/// without an audio library. The pitch is lost, but the program still waits out the tone
+/// so its timing is kept
+pub struct TerminalBell;
+
+impl Speaker for TerminalBell {
+    fn tone(&self, _frequency: usize, duration: Duration) {
+        let mut stderr = std::io::stderr();
+        let _ = stderr.write_all(b"\x07").and_then(|_| stderr.flush());
+
+        std::thread::sleep(duration);
+    }
+}
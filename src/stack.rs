@@ -0,0 +1,137 @@
+use std::{fmt, ops::Deref};
+
+use crate::value::DataType;
+
+/// The operand stack, which keeps a running estimate of the memory held by its values so
+/// memory limits can be enforced without walking the whole stack after every instruction.
+/// Values can only be added and removed through [`Stack::push`] and [`Stack::pop`], while
+/// reads go through the slice it dereferences to
+#[derive(Clone, Default, PartialEq)]
+pub struct Stack {
+    values: Vec<DataType>,
+    /// The size of each value when it was pushed, so popping a List does not walk it
+    sizes: Vec<usize>,
+    bytes: usize,
+}
+
+impl Stack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, value: DataType) {
+        let size = value.size();
+        self.bytes += size;
+        self.sizes.push(size);
+        self.values.push(value);
+    }
+
+    pub fn pop(&mut self) -> Option<DataType> {
+        let value = self.values.pop()?;
+        self.bytes -= self.sizes.pop().unwrap_or_default();
+        Some(value)
+    }
+
+    /// The top value, for changing a List in place without popping and measuring it again.
+    /// How much its size changed is then recorded with [`Stack::resize_last`]
+    pub(crate) fn last_mut(&mut self) -> Option<&mut DataType> {
+        self.values.last_mut()
+    }
+
+    /// Records that the top value grew by `added` bytes and shrank by `removed`
+    pub(crate) fn resize_last(&mut self, added: usize, removed: usize) {
+        if let Some(size) = self.sizes.last_mut() {
+            *size = *size + added - removed;
+            self.bytes = self.bytes + added - removed;
+        }
+    }
+
+    /// Approximate number of bytes held by the values on the stack
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn into_vec(self) -> Vec<DataType> {
+        self.values
+    }
+
+    /// Renders the stack bottom first, e.g. `[1, "a", 2.5]`, with each value written by
+    /// [`DataType::display`]. Like it, this format is kept stable between releases
+    pub fn render(&self) -> String {
+        render(&self.values)
+    }
+}
+
+/// Renders values the way [`Stack::render`] does
+pub(crate) fn render(values: &[DataType]) -> String {
+    let values: Vec<String> = values.iter().map(DataType::display).collect();
+    format!("[{}]", values.join(", "))
+}
+
+impl Deref for Stack {
+    type Target = [DataType];
+
+    fn deref(&self) -> &Self::Target {
+        &self.values
+    }
+}
+
+impl From<Vec<DataType>> for Stack {
+    fn from(values: Vec<DataType>) -> Self {
+        let sizes: Vec<usize> = values.iter().map(DataType::size).collect();
+
+        Stack {
+            bytes: sizes.iter().sum(),
+            sizes,
+            values,
+        }
+    }
+}
+
+impl fmt::Debug for Stack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.values).finish()
+    }
+}
+
+/// How a single instruction changed the stack: values were popped off the top, then new
+/// ones were pushed
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackDiff {
+    pub popped: usize,
+    pub pushed: Vec<DataType>,
+}
+
+impl StackDiff {
+    /// Works out the change from the values an instruction took off the top of the stack
+    /// to what is now above them, leaving out values that were put back unchanged
+    pub(crate) fn between(taken: &[DataType], now: &[DataType]) -> StackDiff {
+        let unchanged = taken
+            .iter()
+            .zip(now)
+            .take_while(|(taken, now)| taken == now)
+            .count();
+
+        StackDiff {
+            popped: taken.len() - unchanged,
+            pushed: now[unchanged..].to_vec(),
+        }
+    }
+}
+
+impl fmt::Display for StackDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pushed: Vec<String> = self
+            .pushed
+            .iter()
+            .map(|value| format!("{value:?}"))
+            .collect();
+
+        match (self.popped, pushed.is_empty()) {
+            (0, true) => write!(f, "no change"),
+            (0, false) => write!(f, "pushed {}", pushed.join(", ")),
+            (popped, true) => write!(f, "popped {popped}"),
+            (popped, false) => write!(f, "popped {popped}, pushed {}", pushed.join(", ")),
+        }
+    }
+}
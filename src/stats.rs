@@ -0,0 +1,206 @@
+//! Size and complexity figures for a program, to help grade and refactor large programs
+//! without running them.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{json::Json, parser::Program, Instructions};
+
+/// Figures for a single section
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionStats {
+    pub name: String,
+    pub instructions: usize,
+    /// The most values the section has pushed on top of those it started with, following
+    /// its instructions until the first that transfers control unconditionally
+    pub peak_depth: usize,
+    /// How many values the section takes from the stack it started with over the same span
+    pub needs: usize,
+    /// `jump` instructions
+    pub jumps: usize,
+    /// Places execution may go one of several ways: each `ifjmp`, and each state past the
+    /// first of a `switch`
+    pub branches: usize,
+}
+
+impl SectionStats {
+    /// Independent paths through the section, in the spirit of cyclomatic complexity
+    pub fn complexity(&self) -> usize {
+        self.branches + 1
+    }
+}
+
+/// Figures for a whole program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    pub sections: Vec<SectionStats>,
+    /// How often each instruction is used, by name
+    pub opcodes: BTreeMap<String, usize>,
+}
+
+impl Stats {
+    pub fn new(program: &[Program]) -> Stats {
+        let groups: HashMap<&str, usize> = program
+            .iter()
+            .filter_map(|item| match item {
+                Program::States(group, states) => Some((group.as_str(), states.len())),
+                _ => None,
+            })
+            .collect();
+
+        let mut opcodes = BTreeMap::new();
+        let mut sections = Vec::new();
+
+        for item in program {
+            let Program::Section(name, instructions) = item else {
+                continue;
+            };
+
+            let mut stats = SectionStats {
+                name: name.0.clone(),
+                instructions: instructions.len(),
+                peak_depth: 0,
+                needs: 0,
+                jumps: 0,
+                branches: 0,
+            };
+
+            for instruction in instructions {
+                let source = instruction.to_string();
+                let opcode = source.split(' ').next().unwrap_or_default();
+                *opcodes.entry(opcode.to_string()).or_insert(0) += 1;
+
+                match instruction {
+                    Instructions::Jump(_) => stats.jumps += 1,
+                    Instructions::IfJmp(_) => stats.branches += 1,
+                    Instructions::Switch(group) => {
+                        let states = groups.get(group.as_str()).copied().unwrap_or(1);
+                        stats.branches += states.saturating_sub(1);
+                    }
+                    _ => {}
+                }
+            }
+
+            // Depth relative to the start of the section
+            let mut depth: isize = 0;
+            let mut lowest: isize = 0;
+
+            for instruction in instructions {
+                let (pops, pushes) = instruction
+                    .stack_effect()
+                    .unwrap_or((instruction.operands(), 0));
+
+                depth -= pops as isize;
+                lowest = lowest.min(depth);
+                depth += pushes as isize;
+                stats.peak_depth = stats.peak_depth.max(depth.max(0) as usize);
+
+                if matches!(
+                    instruction,
+                    Instructions::Jump(_)
+                        | Instructions::Switch(_)
+                        | Instructions::Exit
+                        | Instructions::Ret
+                ) {
+                    break;
+                }
+            }
+
+            stats.needs = lowest.unsigned_abs();
+            sections.push(stats);
+        }
+
+        Stats { sections, opcodes }
+    }
+
+    pub fn instructions(&self) -> usize {
+        self.sections
+            .iter()
+            .map(|section| section.instructions)
+            .sum()
+    }
+
+    /// Independent paths through the whole program
+    pub fn complexity(&self) -> usize {
+        self.sections
+            .iter()
+            .map(|section| section.branches)
+            .sum::<usize>()
+            + 1
+    }
+
+    pub fn to_json(&self) -> Json {
+        let sections = self
+            .sections
+            .iter()
+            .map(|section| {
+                Json::object([
+                    ("name", Json::from(section.name.as_str())),
+                    ("instructions", Json::from(section.instructions)),
+                    ("peak_depth", Json::from(section.peak_depth)),
+                    ("needs", Json::from(section.needs)),
+                    ("jumps", Json::from(section.jumps)),
+                    ("branches", Json::from(section.branches)),
+                    ("complexity", Json::from(section.complexity())),
+                ])
+            })
+            .collect();
+
+        let opcodes = self
+            .opcodes
+            .iter()
+            .map(|(opcode, count)| (opcode.clone(), Json::from(*count)))
+            .collect();
+
+        Json::object([
+            ("sections", Json::from(self.sections.len())),
+            ("instructions", Json::from(self.instructions())),
+            ("complexity", Json::from(self.complexity())),
+            ("opcodes", Json::Object(opcodes)),
+            ("by_section", Json::Array(sections)),
+        ])
+    }
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "sections:     {}", self.sections.len())?;
+        writeln!(f, "instructions: {}", self.instructions())?;
+        writeln!(f, "complexity:   {}", self.complexity())?;
+
+        writeln!(f, "\nopcodes:")?;
+        let mut opcodes: Vec<_> = self.opcodes.iter().collect();
+        opcodes.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (opcode, count) in opcodes {
+            writeln!(f, "  {opcode:<12} {count}")?;
+        }
+
+        let width = self
+            .sections
+            .iter()
+            .map(|section| section.name.len())
+            .max()
+            .unwrap_or(0)
+            .max("section".len());
+
+        writeln!(
+            f,
+            "\n{:<width$}  instructions  peak  needs  jumps  branches  complexity",
+            "section"
+        )?;
+        for section in &self.sections {
+            writeln!(
+                f,
+                "{:<width$}  {:>12}  {:>4}  {:>5}  {:>5}  {:>8}  {:>10}",
+                section.name,
+                section.instructions,
+                section.peak_depth,
+                section.needs,
+                section.jumps,
+                section.branches,
+                section.complexity()
+            )?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+//! Results of a test run and the machine readable formats they can be written in, for CI
+//! systems and grading scripts.
+
+use std::time::Duration;
+
+use crate::json::Json;
+
+/// How a test case ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    Passed,
+    /// The program did not behave as expected, with a description of every difference
+    Failed(String),
+    /// The program was stopped for running past its time limit
+    TimedOut(String),
+}
+
+/// The result of running one test case
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestResult {
+    pub name: String,
+    pub status: Status,
+    pub duration: Duration,
+    /// A line by line comparison of the expected and actual output, if they differ
+    pub diff: Option<String>,
+}
+
+/// Compares two texts line by line, prefixing lines only in `expected` with `-`, lines only
+/// in `actual` with `+` and lines in both with a space
+pub fn diff_lines(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    // Length of the longest common subsequence of every pair of suffixes
+    let mut common = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            common[i][j] = if expected[i] == actual[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = String::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            diff.push_str(&format!("  {}\n", expected[i]));
+            i += 1;
+            j += 1;
+        } else if i < expected.len() && (j == actual.len() || common[i + 1][j] >= common[i][j + 1])
+        {
+            diff.push_str(&format!("- {}\n", expected[i]));
+            i += 1;
+        } else {
+            diff.push_str(&format!("+ {}\n", actual[j]));
+            j += 1;
+        }
+    }
+
+    diff
+}
+
+/// Renders results as a JUnit XML report. Timeouts are reported as errors, everything
+/// else that went wrong as failures
+pub fn to_junit(results: &[TestResult]) -> String {
+    let count = |timed_out: bool| {
+        results
+            .iter()
+            .filter(|result| match result.status {
+                Status::Passed => false,
+                Status::Failed(_) => !timed_out,
+                Status::TimedOut(_) => timed_out,
+            })
+            .count()
+    };
+    let total: Duration = results.iter().map(|result| result.duration).sum();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuite name=\"toylang\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">\n",
+        results.len(),
+        count(false),
+        count(true),
+        total.as_secs_f64()
+    ));
+
+    for result in results {
+        xml.push_str(&format!(
+            "  <testcase name=\"{}\" time=\"{:.3}\"",
+            escape_xml(&result.name),
+            result.duration.as_secs_f64()
+        ));
+
+        let (element, message) = match &result.status {
+            Status::Passed => {
+                xml.push_str("/>\n");
+                continue;
+            }
+            Status::Failed(message) => ("failure", message),
+            Status::TimedOut(message) => ("error", message),
+        };
+
+        let mut details = message.clone();
+        if let Some(diff) = &result.diff {
+            details.push_str("\n\n");
+            details.push_str(diff);
+        }
+
+        xml.push_str(&format!(
+            ">\n    <{element} message=\"{}\">{}</{element}>\n  </testcase>\n",
+            escape_xml(message.lines().next().unwrap_or_default()),
+            escape_xml(&details)
+        ));
+    }
+
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+/// Renders results as a JSON object with a summary and an entry per test case
+pub fn to_json(results: &[TestResult]) -> Json {
+    let count = |name: &str| {
+        results
+            .iter()
+            .filter(|result| status_name(&result.status) == name)
+            .count()
+    };
+
+    let tests = results
+        .iter()
+        .map(|result| {
+            let message = match &result.status {
+                Status::Passed => Json::Null,
+                Status::Failed(message) | Status::TimedOut(message) => Json::from(message.as_str()),
+            };
+
+            Json::object([
+                ("name", Json::from(result.name.as_str())),
+                ("status", Json::from(status_name(&result.status))),
+                ("duration", Json::from(result.duration.as_secs_f64())),
+                ("message", message),
+                (
+                    "diff",
+                    result.diff.as_deref().map_or(Json::Null, Json::from),
+                ),
+            ])
+        })
+        .collect();
+
+    Json::object([
+        ("passed", Json::from(count("passed"))),
+        ("failed", Json::from(count("failed"))),
+        ("timed_out", Json::from(count("timed_out"))),
+        ("tests", Json::Array(tests)),
+    ])
+}
+
+fn status_name(status: &Status) -> &'static str {
+    match status {
+        Status::Passed => "passed",
+        Status::Failed(_) => "failed",
+        Status::TimedOut(_) => "timed_out",
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
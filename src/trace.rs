@@ -0,0 +1,260 @@
+//! Execution traces, written by `run --trace` as one JSON object per line for every
+//! instruction run, and compared by `toylang tracediff` to find where two runs of a
+//! program stop agreeing, for example before and after a change to the interpreter.
+//!
+//! Each line holds the step, the position of the instruction in the running stream, the
+//! instruction, the stack before it ran and how it changed the stack (or the error it
+//! stopped with). Values are written the way `Debug` shows them, so an Int and a String
+//! that print the same are still told apart.
+
+use std::{fmt, io::Write, path::Path};
+
+use crate::{
+    error::RuntimeError, hooks::InterpreterHooks, instructions::Instructions, json::Json,
+    stack::StackDiff, value::DataType,
+};
+
+/// One instruction of a run
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceStep {
+    /// How many instructions ran before this one
+    pub step: usize,
+    /// Position of the instruction in the running instruction stream
+    pub index: usize,
+    pub instruction: String,
+    /// The stack before the instruction ran, bottom first
+    pub stack: Vec<String>,
+    /// How many values the instruction took off the stack
+    pub popped: usize,
+    /// The values it left in their place
+    pub pushed: Vec<String>,
+    /// The error the run stopped with, if this instruction failed
+    pub error: Option<String>,
+}
+
+impl TraceStep {
+    pub fn to_json(&self) -> Json {
+        let strings = |values: &[String]| {
+            Json::Array(
+                values
+                    .iter()
+                    .map(|value| Json::from(value.as_str()))
+                    .collect(),
+            )
+        };
+
+        let mut entries = vec![
+            ("step", Json::from(self.step)),
+            ("index", Json::from(self.index)),
+            ("instruction", Json::from(self.instruction.as_str())),
+            ("stack", strings(&self.stack)),
+            ("popped", Json::from(self.popped)),
+            ("pushed", strings(&self.pushed)),
+        ];
+
+        if let Some(error) = &self.error {
+            entries.push(("error", Json::from(error.as_str())));
+        }
+
+        Json::object(entries)
+    }
+
+    pub fn from_json(json: &Json) -> Result<TraceStep, String> {
+        let number = |key: &str| {
+            json.get(key)
+                .and_then(Json::as_usize)
+                .ok_or(format!("`{key}` must be a number"))
+        };
+        let strings = |key: &str| {
+            json.get(key)
+                .and_then(Json::as_array)
+                .and_then(|values| {
+                    values
+                        .iter()
+                        .map(|value| value.as_str().map(str::to_string))
+                        .collect::<Option<Vec<_>>>()
+                })
+                .ok_or(format!("`{key}` must be an array of strings"))
+        };
+
+        Ok(TraceStep {
+            step: number("step")?,
+            index: number("index")?,
+            instruction: json
+                .get("instruction")
+                .and_then(Json::as_str)
+                .ok_or("`instruction` must be a string")?
+                .to_string(),
+            stack: strings("stack")?,
+            popped: number("popped")?,
+            pushed: strings("pushed")?,
+            error: json.get("error").and_then(Json::as_str).map(str::to_string),
+        })
+    }
+
+    /// Whether two runs did the same thing at this step. Where the instruction sits in
+    /// the stream is left out, so moving sections around does not count as a difference
+    fn agrees_with(&self, other: &TraceStep) -> bool {
+        self.instruction == other.instruction
+            && self.stack == other.stack
+            && self.popped == other.popped
+            && self.pushed == other.pushed
+            && self.error == other.error
+    }
+}
+
+/// Reads a trace written by a run with `--trace`
+pub fn load(path: &Path) -> Result<Vec<TraceStep>, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|error| format!("Cannot read {}: {error}", path.display()))?;
+
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| {
+            Json::parse(line)
+                .and_then(|json| TraceStep::from_json(&json))
+                .map_err(|error| format!("{}:{}: {error}", path.display(), number + 1))
+        })
+        .collect()
+}
+
+/// Where two traces first disagree
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    /// The step both traces agree up to
+    pub step: usize,
+    /// What each run did at that step, `None` for a run that had already ended
+    pub a: Option<TraceStep>,
+    pub b: Option<TraceStep>,
+}
+
+/// Walks both traces a step at a time, returning the first step at which they disagree
+pub fn diff(a: &[TraceStep], b: &[TraceStep]) -> Option<Divergence> {
+    let step = (0..a.len().max(b.len())).find(|&step| match (a.get(step), b.get(step)) {
+        (Some(a), Some(b)) => !a.agrees_with(b),
+        _ => true,
+    })?;
+
+    Some(Divergence {
+        step,
+        a: a.get(step).cloned(),
+        b: b.get(step).cloned(),
+    })
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "traces diverge at step {}", self.step)?;
+
+        for (name, step) in [("a", &self.a), ("b", &self.b)] {
+            match step {
+                Some(step) => {
+                    write!(f, "  {name}: {} | {}", step.index, step.instruction)?;
+
+                    match &step.error {
+                        Some(error) => writeln!(f, " failed: {error}")?,
+                        None => writeln!(
+                            f,
+                            " popped {}, pushed [{}]",
+                            step.popped,
+                            step.pushed.join(", ")
+                        )?,
+                    }
+                }
+                None => writeln!(f, "  {name}: ended after {} steps", self.step)?,
+            }
+        }
+
+        let (Some(a), Some(b)) = (&self.a, &self.b) else {
+            return Ok(());
+        };
+
+        // The stacks usually share their bottom, so only what differs above it is shown
+        let common = a
+            .stack
+            .iter()
+            .zip(&b.stack)
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        if common < a.stack.len() || common < b.stack.len() {
+            writeln!(f, "  stacks differ above the bottom {common} values:")?;
+            writeln!(f, "    a: [{}]", a.stack[common..].join(", "))?;
+            writeln!(f, "    b: [{}]", b.stack[common..].join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes a [`TraceStep`] for every instruction run, used for `--trace`
+pub(crate) struct TraceWriter {
+    out: Box<dyn Write>,
+    steps: usize,
+    /// The instruction running, written once it is known how it changed the stack
+    pending: Option<TraceStep>,
+}
+
+impl TraceWriter {
+    pub(crate) fn new(out: Box<dyn Write>) -> TraceWriter {
+        TraceWriter {
+            out,
+            steps: 0,
+            pending: None,
+        }
+    }
+
+    fn write_pending(&mut self) {
+        if let Some(step) = self.pending.take() {
+            writeln!(self.out, "{}", step.to_json()).unwrap();
+        }
+    }
+}
+
+impl InterpreterHooks for TraceWriter {
+    fn on_instruction(&mut self, index: usize, instruction: &Instructions, stack: &[DataType]) {
+        // Instructions that end the run are never told how they changed the stack
+        self.write_pending();
+
+        self.pending = Some(TraceStep {
+            step: self.steps,
+            index,
+            instruction: instruction.to_string(),
+            stack: stack.iter().map(|value| format!("{value:?}")).collect(),
+            popped: 0,
+            pushed: Vec::new(),
+            error: None,
+        });
+        self.steps += 1;
+    }
+
+    fn on_stack_change(&mut self, _index: usize, _instruction: &Instructions, diff: &StackDiff) {
+        if let Some(step) = &mut self.pending {
+            step.popped = diff.popped;
+            step.pushed = diff
+                .pushed
+                .iter()
+                .map(|value| format!("{value:?}"))
+                .collect();
+        }
+
+        self.write_pending();
+    }
+
+    fn on_error(&mut self, error: &RuntimeError, _stack: &[DataType]) {
+        if let Some(step) = &mut self.pending {
+            step.error = Some(error.to_string());
+        }
+
+        self.write_pending();
+    }
+}
+
+impl Drop for TraceWriter {
+    fn drop(&mut self) {
+        self.write_pending();
+        let _ = self.out.flush();
+    }
+}
@@ -0,0 +1,52 @@
+use crate::canvas::Canvas;
+
+/// The colour the turtle draws in, white so it shows up on a new canvas
+const PEN_COLOR: u32 = 0xff_ffff;
+
+/// A pen moving over the canvas, which programs steer with `forward` and `turn`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Turtle {
+    x: f64,
+    y: f64,
+    /// Degrees clockwise from facing the top of the canvas
+    heading: f64,
+    down: bool,
+}
+
+impl Turtle {
+    /// A turtle in the middle of a canvas of the given size, facing up with its pen down
+    pub(crate) fn new(width: usize, height: usize) -> Turtle {
+        Turtle {
+            x: (width / 2) as f64,
+            y: (height / 2) as f64,
+            heading: 0.0,
+            down: true,
+        }
+    }
+
+    /// Turns clockwise, or anticlockwise by a negative number of degrees
+    pub(crate) fn turn(&mut self, degrees: i64) {
+        self.heading = (self.heading + degrees.rem_euclid(360) as f64) % 360.0;
+    }
+
+    pub(crate) fn pen(&mut self, down: bool) {
+        self.down = down;
+    }
+
+    /// Moves in the direction the turtle is facing, drawing a line behind it while its pen
+    /// is down, or backwards by a negative distance. The turtle may leave the canvas, only
+    /// what is on it is drawn
+    pub(crate) fn forward(&mut self, distance: i64, canvas: &mut Canvas) {
+        let (sin, cos) = self.heading.to_radians().sin_cos();
+        let to = (
+            self.x + distance as f64 * sin,
+            self.y - distance as f64 * cos,
+        );
+
+        if self.down {
+            canvas.line((self.x, self.y), to, PEN_COLOR);
+        }
+
+        (self.x, self.y) = to;
+    }
+}
@@ -0,0 +1,144 @@
+use crate::{complex::Complex, matrix::Matrix};
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum DataType {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    /// An open file in the VM's resource table
+    Handle(usize),
+    List(Vec<DataType>),
+    Matrix(Matrix),
+    Complex(Complex),
+}
+
+impl DataType {
+    /// The name of the value's type, as used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            DataType::Bool(_) => "Bool",
+            DataType::Int(_) => "Int",
+            DataType::Float(_) => "Float",
+            DataType::String(_) => "String",
+            DataType::Bytes(_) => "Bytes",
+            DataType::Handle(_) => "Handle",
+            DataType::List(_) => "List",
+            DataType::Matrix(_) => "Matrix",
+            DataType::Complex(_) => "Complex",
+        }
+    }
+
+    /// Renders the value for people reading a stack dump, so that values of different types
+    /// never look alike. Unlike `print`, which writes Strings and Bytes as they are, and
+    /// unlike `Debug`, this format is kept stable between releases:
+    ///
+    /// - Ints are written in decimal, `-5`, and Floats always with a `.` or an exponent,
+    ///   `2.0` or `1e-7`, or as `NaN`, `inf` and `-inf`
+    /// - Bools are `true` and `false`, Complex numbers `1.5-2i`
+    /// - Strings are quoted, with `\`, `"`, newlines, carriage returns and tabs escaped:
+    ///   `"a \"b\"\n"`
+    /// - Bytes are written like their literals, `x"0aff"`
+    /// - Handles are `<file 3>`
+    /// - Lists are `[1, "a"]`, with their items rendered the same way
+    /// - Matrices are their rows of numbers after `matrix`, `matrix[[1.0, 2.0], [3.0, 4.0]]`
+    pub fn display(&self) -> String {
+        match self {
+            DataType::Float(a) => format!("{a:?}"),
+            DataType::String(a) => {
+                let mut quoted = String::with_capacity(a.len() + 2);
+                quoted.push('"');
+                for c in a.chars() {
+                    match c {
+                        '\\' => quoted.push_str("\\\\"),
+                        '"' => quoted.push_str("\\\""),
+                        '\n' => quoted.push_str("\\n"),
+                        '\r' => quoted.push_str("\\r"),
+                        '\t' => quoted.push_str("\\t"),
+                        c => quoted.push(c),
+                    }
+                }
+                quoted.push('"');
+                quoted
+            }
+            DataType::Bytes(_) => format!("x\"{self}\""),
+            DataType::List(items) => {
+                let items: Vec<String> = items.iter().map(DataType::display).collect();
+                format!("[{}]", items.join(", "))
+            }
+            DataType::Matrix(matrix) => {
+                let rows: Vec<String> = matrix
+                    .values()
+                    .chunks(matrix.cols())
+                    .map(|row| {
+                        let row: Vec<String> =
+                            row.iter().map(|value| format!("{value:?}")).collect();
+                        format!("[{}]", row.join(", "))
+                    })
+                    .collect();
+                format!("matrix[{}]", rows.join(", "))
+            }
+            DataType::Bool(_) | DataType::Int(_) | DataType::Handle(_) | DataType::Complex(_) => {
+                self.to_string()
+            }
+        }
+    }
+
+    /// Approximate number of bytes this value occupies, including anything it owns on the heap
+    pub fn size(&self) -> usize {
+        let heap = match self {
+            DataType::String(a) => a.len(),
+            DataType::Bytes(a) => a.len(),
+            DataType::List(a) => a.iter().map(DataType::size).sum(),
+            DataType::Matrix(a) => a.bytes(),
+            _ => 0,
+        };
+
+        std::mem::size_of::<DataType>() + heap
+    }
+
+    /// How many heap allocations this value owns, counting a List's own buffer and those of
+    /// its items
+    pub fn heap_cells(&self) -> usize {
+        match self {
+            DataType::String(_) | DataType::Bytes(_) | DataType::Matrix(_) => 1,
+            DataType::List(a) => 1 + a.iter().map(DataType::heap_cells).sum::<usize>(),
+            DataType::Bool(_)
+            | DataType::Int(_)
+            | DataType::Float(_)
+            | DataType::Handle(_)
+            | DataType::Complex(_) => 0,
+        }
+    }
+}
+
+impl std::fmt::Display for DataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DataType::Bool(a) => write!(f, "{}", a),
+            DataType::Int(a) => write!(f, "{}", a),
+            DataType::Float(a) => write!(f, "{}", a),
+            DataType::String(a) => write!(f, "{}", a),
+            DataType::Bytes(a) => {
+                for byte in a {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+            DataType::Handle(a) => write!(f, "<file {}>", a),
+            DataType::List(a) => {
+                write!(f, "[")?;
+                for (i, item) in a.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            DataType::Matrix(a) => write!(f, "{}", a),
+            DataType::Complex(a) => write!(f, "{}", a),
+        }
+    }
+}
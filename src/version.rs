@@ -0,0 +1,33 @@
+use std::fmt;
+
+/// A version of the language, as declared by a `#lang toy <major>.<minor>` directive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LanguageVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl LanguageVersion {
+    /// The newest version this interpreter understands
+    pub const CURRENT: LanguageVersion = LanguageVersion { major: 0, minor: 2 };
+
+    /// `override` sections and `#if` directives
+    pub(crate) const OVERRIDES_AND_CONDITIONS: LanguageVersion =
+        LanguageVersion { major: 0, minor: 2 };
+
+    /// Parses a `<major>.<minor>` version number
+    pub fn parse(version: &str) -> Option<LanguageVersion> {
+        let (major, minor) = version.trim().split_once('.')?;
+
+        Some(LanguageVersion {
+            major: major.parse().ok()?,
+            minor: minor.parse().ok()?,
+        })
+    }
+}
+
+impl fmt::Display for LanguageVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
@@ -0,0 +1,77 @@
+//! Checks programs can read the arguments they were run with through `argc` and `argv`
+
+use toylang::{bytecode, parse, Interpreter, RunOptions, RuntimeError, Value};
+
+mod common;
+
+use common::run_with;
+
+fn run(source: &str, args: &[&str]) -> Result<Vec<Value>, RuntimeError> {
+    let options = RunOptions {
+        args: args.iter().map(|arg| arg.to_string()).collect(),
+        ..RunOptions::default()
+    };
+    run_with(source, options)
+}
+
+#[test]
+fn argc_counts_the_arguments() {
+    assert_eq!(run("::main:\nargc\n", &[]), Ok(vec![Value::Int(0)]));
+    assert_eq!(
+        run("::main:\nargc\n", &["1", "2", "hello"]),
+        Ok(vec![Value::Int(3)])
+    );
+}
+
+#[test]
+fn argv_pushes_arguments_as_strings() {
+    assert_eq!(
+        run("::main:\nargv 2\npush 0\nargv\n", &["1", "2", "hello"]),
+        Ok(vec![
+            Value::String("hello".to_string()),
+            Value::String("1".to_string())
+        ])
+    );
+}
+
+#[test]
+fn arguments_can_be_converted() {
+    assert_eq!(
+        run("::main:\nargv 0\ntoint\npush 1\nadd\n", &["41"]),
+        Ok(vec![Value::Int(42)])
+    );
+}
+
+#[test]
+fn argv_fails_past_the_last_argument() {
+    assert_eq!(
+        run("::main:\nargv 1\n", &["only"]),
+        Err(RuntimeError::Instruction(
+            "Index 1 out of bounds for 1 argument".to_string()
+        ))
+    );
+    assert_eq!(
+        run("::main:\npush \"0\"\nargv\n", &["only"]),
+        Err(RuntimeError::Instruction(
+            "argv requires an Int index on the stack".to_string()
+        ))
+    );
+}
+
+#[test]
+fn argv_survives_compilation() {
+    let options = RunOptions {
+        args: vec!["a".to_string(), "b".to_string()],
+        ..RunOptions::default()
+    };
+    let source = "::main:\nargv 1\npush 0\nargv\n";
+    let program = bytecode::load(&bytecode::compile(&parse(source, &options))).unwrap();
+
+    assert_eq!(
+        Interpreter::new(program, options).call_section("main", Vec::new()),
+        Ok(vec![
+            Value::String("b".to_string()),
+            Value::String("a".to_string())
+        ])
+    );
+}
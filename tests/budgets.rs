@@ -0,0 +1,82 @@
+//! Checks `#budget` annotations limit how many steps a section may run
+
+use toylang::{bytecode, parse, try_parse, Interpreter, RunOptions, RuntimeError, Value};
+
+mod common;
+
+use common::run_with;
+
+/// Counts down from 5 in a loop of 8 steps, so `countdown` takes 42 steps in all
+fn program(budget: usize, calls: usize) -> String {
+    format!(
+        "::main:\n{}exit\n\n::countdown:\n#budget {budget} steps\npush 1\nswap\nsub\ndup\npush 0\nswap\ngt\nifjmp countdown\ndrop\nret\n",
+        "push 5\ncall countdown\n".repeat(calls)
+    )
+}
+
+fn run(source: &str, budgets: bool) -> Result<Vec<Value>, RuntimeError> {
+    let options = RunOptions {
+        budgets,
+        ..RunOptions::default()
+    };
+    run_with(source, options)
+}
+
+#[test]
+fn sections_fail_once_they_go_over_their_budget() {
+    assert_eq!(run(&program(42, 1), true), Ok(Vec::new()));
+    assert_eq!(
+        run(&program(41, 1), true),
+        Err(RuntimeError::Instruction(
+            "Section countdown went over its budget of 41 steps".to_string()
+        ))
+    );
+}
+
+#[test]
+fn budgets_start_again_on_every_call() {
+    assert_eq!(run(&program(42, 3), true), Ok(Vec::new()));
+}
+
+#[test]
+fn budgets_cover_the_sections_a_section_calls() {
+    let source = "::main:\n#budget 3 steps\ncall helper\nexit\n\n::helper:\npush 1\ndrop\nret\n";
+
+    assert_eq!(
+        run(source, true),
+        Err(RuntimeError::Instruction(
+            "Section main went over its budget of 3 steps".to_string()
+        ))
+    );
+}
+
+#[test]
+fn budgets_are_only_enforced_when_asked_for() {
+    assert_eq!(run(&program(1, 1), false), Ok(Vec::new()));
+}
+
+#[test]
+fn budgets_must_be_a_number_of_steps() {
+    let options = RunOptions::default();
+
+    assert!(try_parse("::main:\n#budget 10 steps\n", &options).is_ok());
+    assert!(try_parse("::main:\n#budget 10\n", &options).is_ok());
+
+    let error = try_parse("::main:\n#budget lots\n", &options).unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("#budget expects a number of steps like `#budget 10000 steps`, found: lots"));
+}
+
+#[test]
+fn budgets_survive_compilation() {
+    let options = RunOptions {
+        budgets: true,
+        ..RunOptions::default()
+    };
+    let program = bytecode::load(&bytecode::compile(&parse(&program(41, 1), &options))).unwrap();
+
+    assert!(Interpreter::new(program, options)
+        .call_section("main", Vec::new())
+        .is_err());
+}
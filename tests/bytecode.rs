@@ -0,0 +1,166 @@
+//! Checks that compiled programs load back to the program they were compiled from
+
+use std::fs;
+
+use toylang::{bytecode, parse, Interpreter, RunOptions, Value};
+
+const PROGRAM: &str = r#"#states Light RED GREEN
+const table = [1, 2.5, "three", true]
+
+::main:
+#pre depth==0
+push -7
+push 1.5
+push 1-2i
+push x"00ff"
+push "hello"
+push RED
+drop
+drop
+drop
+drop
+drop
+print <3
+getconst table 2
+getconst table
+drop
+drop
+log info
+vector 2
+drop
+store x
+load x
+call twice
+pushdata greeting
+drop
+exit
+
+::twice:
+dup
+add
+ret
+
+::data greeting:
+Hello
+"#;
+
+#[test]
+fn programs_survive_a_round_trip() {
+    let options = RunOptions::default();
+    let program = parse(PROGRAM, &options);
+    let loaded = bytecode::load(&bytecode::compile(&program)).unwrap();
+
+    assert_eq!(format!("{loaded:?}"), format!("{program:?}"));
+
+    for entry in fs::read_dir("samples").unwrap() {
+        let source = fs::read_to_string(entry.unwrap().path()).unwrap();
+        let Ok(program) = toylang::try_parse(&source, &options) else {
+            continue;
+        };
+
+        let loaded = bytecode::load(&bytecode::compile(&program)).unwrap();
+        assert_eq!(format!("{loaded:?}"), format!("{program:?}"));
+    }
+}
+
+#[test]
+fn loaded_programs_run() {
+    let options = RunOptions::default();
+    let program = parse("::main:\npush 2\npush 3\nmul\n", &options);
+    let loaded = bytecode::load(&bytecode::compile(&program)).unwrap();
+
+    let result = Interpreter::new(loaded, options).call_section("main", Vec::new());
+    assert_eq!(result, Ok(vec![Value::Int(6)]));
+}
+
+#[test]
+fn strings_are_stored_once() {
+    let options = RunOptions::default();
+    let once = bytecode::compile(&parse("::main:\npush \"a long string\"\nret\n", &options));
+    let twice = bytecode::compile(&parse(
+        "::main:\npush \"a long string\"\npush \"a long string\"\nret\n",
+        &options,
+    ));
+
+    assert!(twice.len() < once.len() + "a long string".len());
+}
+
+#[test]
+fn invalid_bytecode_is_rejected() {
+    let program = parse("::main:\npush 1\nexit\n", &RunOptions::default());
+    let bytes = bytecode::compile(&program);
+
+    assert!(bytecode::is_bytecode(&bytes));
+    assert!(!bytecode::is_bytecode(b"::main:\n"));
+    assert!(bytecode::load(b"::main:\n").is_err());
+
+    let mut newer = bytes.clone();
+    newer[4] = 99;
+    assert_eq!(
+        bytecode::load(&newer).unwrap_err(),
+        "Bytecode format 99 is not supported, recompile the program"
+    );
+
+    for length in 0..bytes.len() {
+        assert!(bytecode::load(&bytes[..length]).is_err());
+    }
+
+    let mut trailing = bytes;
+    trailing.push(0);
+    assert!(bytecode::load(&trailing).is_err());
+}
+
+/// Compiles `source` and replaces the first occurrence of `from` in the result with `to`
+fn patched(source: &str, from: &[u8], to: &[u8]) -> Vec<u8> {
+    let bytes = bytecode::compile(&parse(source, &RunOptions::default()));
+    let at = bytes
+        .windows(from.len())
+        .position(|window| window == from)
+        .unwrap();
+
+    [&bytes[..at], to, &bytes[at + from.len()..]].concat()
+}
+
+#[test]
+fn operands_are_checked_like_source() {
+    // 127 encodes as the single byte 0x7f, which nothing else in these programs does
+    assert_eq!(
+        bytecode::load(&patched("::main:\nframe 127\nexit\n", &[127], &[0])).unwrap_err(),
+        "frame requires a number of frames per second"
+    );
+    assert_eq!(
+        bytecode::load(&patched("::main:\nvector 127\nexit\n", &[127], &[0])).unwrap_err(),
+        "vector requires a length of at least 1"
+    );
+    assert_eq!(
+        bytecode::load(&patched("::main:\nmatrix 2 127\nexit\n", &[127], &[0])).unwrap_err(),
+        "matrix requires a number of rows and a number of columns"
+    );
+
+    // Rows and columns that multiply past the largest usize
+    let huge = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+    assert_eq!(
+        bytecode::load(&patched(
+            "::main:\nmatrix 127 127\nexit\n",
+            &[127, 127],
+            &[huge, huge].concat()
+        ))
+        .unwrap_err(),
+        "matrix requires a number of rows and a number of columns"
+    );
+}
+
+#[test]
+fn deeply_nested_lists_are_rejected() {
+    let marker = 0x1122_3344_5566_7788_i64;
+    let int = [&[1][..], &marker.to_le_bytes()].concat();
+    let source = format!("::main:\npush {marker}\nexit\n");
+
+    let nested = |depth: usize| [[6, 1].repeat(depth), vec![6, 0]].concat();
+
+    assert!(bytecode::load(&patched(&source, &int, &nested(63))).is_ok());
+    assert_eq!(
+        bytecode::load(&patched(&source, &int, &nested(100_000))).unwrap_err(),
+        "Lists nested more than 64 deep in bytecode"
+    );
+}
@@ -0,0 +1,77 @@
+//! Checks that `call` and `ret` run sections as subroutines
+
+use toylang::{RunOptions, Value};
+
+mod common;
+
+use common::interpreter;
+
+fn run(source: &str) -> Result<Vec<Value>, String> {
+    let mut interpreter = interpreter(source, RunOptions::default());
+
+    interpreter.run().map_err(|error| error.to_string())?;
+    Ok(interpreter.global("result").into_iter().cloned().collect())
+}
+
+#[test]
+fn calls_return_to_the_caller() {
+    let source = "::main:
+push 1
+call double
+call double
+setglobal result
+exit
+
+::double:
+call twice
+ret
+push \"never\"
+
+::twice:
+dup
+add
+";
+
+    assert_eq!(run(source), Ok(vec![Value::Int(4)]));
+}
+
+#[test]
+fn calls_can_recurse() {
+    // Sums the numbers from 10 down to 1
+    let source = "::main:
+push 0
+push 10
+call sum
+drop
+setglobal result
+exit
+
+::sum:
+dup
+push 0
+eq
+ifjmp done
+dup
+rot
+add
+swap
+push 1
+swap
+sub
+call sum
+
+::done:
+ret
+";
+
+    assert_eq!(run(source), Ok(vec![Value::Int(55)]));
+}
+
+#[test]
+fn runaway_recursion_and_stray_returns_fail() {
+    let error = run("::main:\ncall loop\nexit\n\n::loop:\ncall loop\n").unwrap_err();
+    assert!(error.contains("Call stack overflow"), "{error}");
+
+    let error = run("::main:\nret\n").unwrap_err();
+    assert!(error.contains("ret outside of a call"), "{error}");
+}
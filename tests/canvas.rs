@@ -0,0 +1,67 @@
+//! Checks that canvas drawings are saved as PNG files
+
+use toylang::{RunOptions, RuntimeError};
+
+mod common;
+
+use common::interpreter;
+
+fn run(source: &str) -> Result<(), String> {
+    interpreter(source, RunOptions::default())
+        .run()
+        .map_err(|error| error.to_string())
+}
+
+#[test]
+fn drawing_is_saved_as_png() {
+    let path = std::env::temp_dir().join(format!("toylang-canvas-{}.png", std::process::id()));
+    let source = format!(
+        "::main:\npush 3\npush 2\ncnew\npush 2\npush 1\npush 16711680\ncset\npush \"{}\"\ncsave\nexit\n",
+        path.display()
+    );
+
+    run(&source).unwrap();
+    let png = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    // The header chunk holds the width and height
+    assert_eq!(&png[12..16], b"IHDR");
+    assert_eq!(&png[16..24], &[0, 0, 0, 3, 0, 0, 0, 2]);
+
+    // Rows are stored uncompressed, each after a filter byte, so the red pixel at the end
+    // of the second row closes the image data
+    let data = png.windows(4).position(|window| window == b"IDAT").unwrap() + 4;
+    assert_eq!(&png[data + 7 + 17..data + 7 + 20], &[0xff, 0, 0]);
+}
+
+#[test]
+fn drawing_outside_the_canvas_fails() {
+    let error =
+        run("::main:\npush 2\npush 2\ncnew\npush 2\npush 0\npush 0\ncset\nexit\n").unwrap_err();
+    assert!(error.contains("outside the canvas"), "{error}");
+
+    let error = run("::main:\npush 0\npush 0\npush 0\ncset\nexit\n").unwrap_err();
+    assert!(error.contains("cnew first"), "{error}");
+}
+
+#[test]
+fn canvases_over_the_memory_limit_are_never_allocated() {
+    let run = |side: i64| {
+        let options = RunOptions {
+            max_memory: Some(1_000_000),
+            ..RunOptions::default()
+        };
+        let source = format!("::main:\npush {side}\npush {side}\ncnew\nexit\n");
+        interpreter(&source, options).run()
+    };
+
+    assert_eq!(run(100), Ok(()));
+    assert_eq!(
+        run(16384),
+        Err(RuntimeError::OutOfMemory {
+            used: 16384 * 16384 * 3,
+            limit: 1_000_000
+        })
+    );
+}
@@ -0,0 +1,85 @@
+//! Checks the `toint`, `tofloat`, `tostring` and `tobool` conversion instructions
+
+use toylang::{RuntimeError, Value};
+
+mod common;
+
+use common::run;
+
+/// Pushes `value` and converts it with `instruction`
+fn convert(value: &str, instruction: &str) -> Result<Vec<Value>, RuntimeError> {
+    run(&format!("::main:\npush {value}\n{instruction}\n"))
+}
+
+fn error(message: &str) -> Result<Vec<Value>, RuntimeError> {
+    Err(RuntimeError::Instruction(message.to_string()))
+}
+
+#[test]
+fn values_convert_to_ints() {
+    assert_eq!(convert("-2.9", "toint"), Ok(vec![Value::Int(-2)]));
+    assert_eq!(convert("true", "toint"), Ok(vec![Value::Int(1)]));
+    assert_eq!(convert("\" 42 \"", "toint"), Ok(vec![Value::Int(42)]));
+    assert_eq!(
+        convert("\"4.5\"", "toint"),
+        error("toint: cannot convert \"4.5\" to an Int")
+    );
+    assert_eq!(
+        run("::main:\npush \"1e300\"\ntofloat\ntoint\n"),
+        error("toint: 1e300 does not fit in an Int")
+    );
+}
+
+#[test]
+fn values_convert_to_floats() {
+    assert_eq!(convert("3", "tofloat"), Ok(vec![Value::Float(3.0)]));
+    assert_eq!(convert("false", "tofloat"), Ok(vec![Value::Float(0.0)]));
+    assert_eq!(convert("\"2.5\"", "tofloat"), Ok(vec![Value::Float(2.5)]));
+    assert_eq!(
+        convert("\"two\"", "tofloat"),
+        error("tofloat: cannot convert \"two\" to a Float")
+    );
+}
+
+#[test]
+fn ints_can_be_divided_as_floats() {
+    assert_eq!(
+        run("::main:\npush 2\ntofloat\npush 7\ntofloat\ndiv\n"),
+        Ok(vec![Value::Float(3.5)])
+    );
+}
+
+#[test]
+fn values_convert_to_strings() {
+    assert_eq!(
+        run("::main:\npush \"n = \"\npush 12\ntostring\nadd\n"),
+        Ok(vec![Value::String("n = 12".to_string())])
+    );
+    assert_eq!(
+        convert("true", "tostring"),
+        Ok(vec![Value::String("true".to_string())])
+    );
+}
+
+#[test]
+fn values_convert_to_bools() {
+    assert_eq!(convert("0", "tobool"), Ok(vec![Value::Bool(false)]));
+    assert_eq!(convert("-0.5", "tobool"), Ok(vec![Value::Bool(true)]));
+    assert_eq!(convert("\"true\"", "tobool"), Ok(vec![Value::Bool(true)]));
+    assert_eq!(
+        convert("\"yes\"", "tobool"),
+        error("tobool: cannot convert \"yes\" to a Bool")
+    );
+}
+
+#[test]
+fn other_values_do_not_convert() {
+    assert_eq!(
+        convert("x\"00\"", "toint"),
+        error("toint: cannot convert a Bytes to an Int")
+    );
+    assert_eq!(
+        run("::main:\ntobool\n"),
+        error("tobool requires a value on the stack")
+    );
+}
@@ -0,0 +1,57 @@
+//! Checks `check` reports every problem in a program instead of stopping at the first
+
+use toylang::{check::check, RunOptions};
+
+fn problems(source: &str) -> Vec<String> {
+    check(source, &RunOptions::default())
+        .into_iter()
+        .map(|problem| match problem.line {
+            Some(line) => format!("{line}: {}", problem.message),
+            None => problem.message,
+        })
+        .collect()
+}
+
+#[test]
+fn valid_programs_have_no_problems() {
+    assert!(problems("::main:\npush 1\ncall helper\nexit\n\n::helper:\nret\n").is_empty());
+}
+
+#[test]
+fn every_problem_is_reported_in_order() {
+    let source = "::main:\nfrob\npush 1.2.3\ncall nowhere\nexit\n\n::helper:\nret\n\n::helper:\npush \"x\nret\n";
+
+    assert_eq!(
+        problems(source),
+        [
+            "2: Unknown instruction: frob",
+            "3: Invalid Float: 1.2.3",
+            "10: Duplicate section: helper. Use `override ::helper:` to redefine it",
+            "11: Invalid value: \"x",
+            "jump found to unknown label: nowhere (in section main)",
+        ]
+    );
+}
+
+#[test]
+fn programs_need_a_main_section() {
+    assert_eq!(
+        problems("::start:\nexit\n"),
+        ["The program has no main section to start from"]
+    );
+    // Source without a header starts in an implicit main section
+    assert!(problems("push 1\nexit\n").is_empty());
+}
+
+#[test]
+fn sections_after_a_problem_are_still_checked() {
+    let source = "::main:\npush nope\njump next\n\n::next:\njump gone\n";
+
+    assert_eq!(
+        problems(source),
+        [
+            "2: Invalid value: nope",
+            "jump found to unknown label: gone (in section next)",
+        ]
+    );
+}
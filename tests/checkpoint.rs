@@ -0,0 +1,70 @@
+//! Checks that a checkpointed run can be restored and continued where it left off
+
+use toylang::{parse, Checkpoint, DataType, Interpreter, RunOptions};
+
+#[test]
+fn restored_checkpoint_continues_the_run() {
+    let dir = std::env::temp_dir().join(format!("toylang-checkpoint-{}", std::process::id()));
+    let options = RunOptions {
+        checkpoint_every: Some(7),
+        checkpoint_dir: dir.clone(),
+        ..RunOptions::default()
+    };
+    let program = parse(
+        "::main:\npush 1\npush 2\nadd\nsetglobal x\npush x\"0aff\"\npush 5\njump rest\n\n\
+         ::rest:\npush 1\nadd\nsetglobal y",
+        &options,
+    );
+
+    let mut interpreter = Interpreter::new(program, options);
+    interpreter.run().unwrap();
+
+    let checkpoint = Checkpoint::load(&dir.join("checkpoint.json")).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // The last checkpoint is taken before the 8th instruction, `push 1` in rest
+    assert_eq!(checkpoint.step, 7);
+    assert_eq!(checkpoint.instructions[checkpoint.ic].to_string(), "push 1");
+    assert_eq!(
+        checkpoint.stack,
+        vec![DataType::Bytes(vec![0x0a, 0xff]), DataType::Int(5)]
+    );
+    assert_eq!(
+        checkpoint.globals,
+        vec![("x".to_string(), DataType::Int(3))]
+    );
+
+    let mut restored = Interpreter::restore(checkpoint, RunOptions::default());
+    restored.run().unwrap();
+
+    assert_eq!(restored.global("x"), Some(&DataType::Int(3)));
+    assert_eq!(restored.global("y"), Some(&DataType::Int(6)));
+}
+
+#[test]
+fn checkpoint_inside_a_call_returns_to_the_caller() {
+    let dir = std::env::temp_dir().join(format!("toylang-checkpoint-call-{}", std::process::id()));
+    let options = RunOptions {
+        checkpoint_every: Some(3),
+        checkpoint_dir: dir.clone(),
+        ..RunOptions::default()
+    };
+    let program = parse(
+        "::main:\ncall sum\nsetglobal y\nexit\n\n::sum:\npush 1\npush 2\nadd\n",
+        &options,
+    );
+
+    Interpreter::new(program, options).run().unwrap();
+
+    let checkpoint = Checkpoint::load(&dir.join("checkpoint.json")).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // Taken before `add`, while `sum` is still running
+    assert_eq!(checkpoint.step, 3);
+    assert_eq!(checkpoint.calls.len(), 1);
+
+    let mut restored = Interpreter::restore(checkpoint, RunOptions::default());
+    restored.run().unwrap();
+
+    assert_eq!(restored.global("y"), Some(&DataType::Int(3)));
+}
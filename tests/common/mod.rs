@@ -0,0 +1,27 @@
+//! Helpers shared by the integration tests. Each test file only uses some of them
+#![allow(dead_code)]
+
+use toylang::{parse, Interpreter, RunOptions, RuntimeError, Value};
+
+/// Output buffer that stays readable after being handed to the interpreter
+pub type SharedBuffer = toylang::SharedBuffer;
+
+/// Parses `source` into an interpreter that runs it with `options`
+pub fn interpreter(source: &str, options: RunOptions) -> Interpreter {
+    Interpreter::new(parse(source, &options), options)
+}
+
+/// Runs the `main` section of `source`, returning what it left on the stack
+pub fn run(source: &str) -> Result<Vec<Value>, RuntimeError> {
+    run_with(source, RunOptions::default())
+}
+
+/// Runs the `main` section of `source` with `options`
+pub fn run_with(source: &str, options: RunOptions) -> Result<Vec<Value>, RuntimeError> {
+    interpreter(source, options).call_section("main", Vec::new())
+}
+
+/// Runs `body` as the `main` section, with the error it stopped with as text
+pub fn run_main(body: &str) -> Result<Vec<Value>, String> {
+    run(&format!("::main:\n{body}")).map_err(|error| error.to_string())
+}
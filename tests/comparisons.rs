@@ -0,0 +1,35 @@
+//! Checks the ordering comparisons `lt`, `gt`, `le` and `ge`
+
+use toylang::Value;
+
+mod common;
+
+use common::run_main;
+
+/// Compares `a` against `b` with each comparison in turn
+fn compare(b: &str, a: &str) -> Result<Vec<Value>, String> {
+    let source = ["lt", "gt", "le", "ge"]
+        .iter()
+        .map(|comparison| format!("push {b}\npush {a}\n{comparison}\n"))
+        .collect::<String>();
+
+    run_main(&source)
+}
+
+#[test]
+fn comparisons_order_the_top_value_against_the_second() {
+    let bools = |values: [bool; 4]| values.map(Value::Bool).to_vec();
+
+    assert_eq!(compare("5", "3"), Ok(bools([true, false, true, false])));
+    assert_eq!(compare("3", "3"), Ok(bools([false, false, true, true])));
+    assert_eq!(compare("1.5", "2.5"), Ok(bools([false, true, false, true])));
+}
+
+#[test]
+fn comparing_different_types_fails() {
+    let error = compare("1", "1.0").unwrap_err();
+    assert!(
+        error.contains("lt requires two Ints or two Floats, found Float and Int"),
+        "{error}"
+    );
+}
@@ -0,0 +1,39 @@
+//! Checks Complex numbers and the arithmetic on them
+
+use toylang::{parse, Complex, RunOptions, Value};
+
+mod common;
+
+use common::run_main;
+
+#[test]
+fn complex_arithmetic() {
+    let complex = |re, im| Ok(vec![Value::Complex(Complex::new(re, im))]);
+
+    assert_eq!(run_main("push 1+2i\npush 3-1i\nadd"), complex(4.0, 1.0));
+    assert_eq!(run_main("push 1+2i\npush 3-1i\nmul"), complex(5.0, 5.0));
+    assert_eq!(run_main("push 2\npush 1+2i\nsub"), complex(-1.0, 2.0));
+    assert_eq!(run_main("push 1+1i\npush 2+2i\ndiv"), complex(2.0, 0.0));
+    assert_eq!(run_main("push 1\npush 2.5\ncomplex"), complex(1.0, 2.5));
+
+    assert_eq!(run_main("push 3+4i\ncabs"), Ok(vec![Value::Float(5.0)]));
+    assert_eq!(
+        run_main("push 3-4i\ndup\nre\nswap\nim"),
+        Ok(vec![Value::Float(3.0), Value::Float(-4.0)])
+    );
+}
+
+#[test]
+fn complex_numbers_round_trip_through_source() {
+    let options = RunOptions::default();
+    let program = parse("::main:\npush -1.5-2i\npush 1e-3+1i\n", &options);
+    let [toylang::Program::Section(_, instructions)] = program.as_slice() else {
+        panic!("expected a single section");
+    };
+
+    let source: Vec<String> = instructions.iter().map(ToString::to_string).collect();
+    assert_eq!(source, ["push -1.5-2i", "push 0.001+1i"]);
+
+    let error = run_main("push 0+0i\npush 1+1i\ndiv").unwrap_err();
+    assert!(error.contains("Cannot divide by zero"), "{error}");
+}
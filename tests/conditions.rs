@@ -0,0 +1,90 @@
+//! Checks that `#if`, `#else` and `#endif` keep lines only for the flags a run defines, and
+//! that `trace` is only kept in debug runs
+
+use toylang::{try_parse, DataType, Instructions, Program, RunOptions, Value};
+
+mod common;
+
+use common::{run, run_with};
+
+const PROGRAM: &str = "::main:\n#if DEBUG\npush 1\n#else\npush 2\n#endif\npush 3\n";
+
+#[test]
+fn lines_are_kept_for_defined_flags() {
+    let options = RunOptions {
+        defines: vec!["DEBUG".to_string()],
+        ..RunOptions::default()
+    };
+
+    assert_eq!(
+        run_with(PROGRAM, options),
+        Ok(vec![Value::Int(1), Value::Int(3)])
+    );
+    assert_eq!(run(PROGRAM), Ok(vec![Value::Int(2), Value::Int(3)]));
+}
+
+#[test]
+fn unbalanced_conditions_are_errors() {
+    let options = RunOptions::default();
+    let message = |source: &str| try_parse(source, &options).unwrap_err().message;
+
+    assert_eq!(
+        message("::main:\n#if\npush 1\n#endif\n"),
+        "#if requires a flag"
+    );
+    assert_eq!(
+        message("::main:\npush 1\n#else\n"),
+        "#else without matching #if"
+    );
+    assert_eq!(
+        message("::main:\npush 1\n#endif\n"),
+        "#endif without matching #if"
+    );
+}
+
+/// The instructions of the only section in `source`
+fn instructions(source: &str, options: &RunOptions) -> Vec<Instructions> {
+    match try_parse(source, options).unwrap().as_slice() {
+        [Program::Section(_, instructions)] => instructions.clone(),
+        program => panic!("expected a single section, found {program:?}"),
+    }
+}
+
+#[test]
+fn traces_are_stripped_outside_debug_runs() {
+    let source = "::main:\npush 1\ntrace \"counter\"\n";
+    let debug = RunOptions {
+        debug: true,
+        ..RunOptions::default()
+    };
+
+    assert_eq!(
+        instructions(source, &debug),
+        [
+            Instructions::Push(DataType::Int(1)),
+            Instructions::Trace("counter".to_string())
+        ]
+    );
+    assert_eq!(
+        instructions(source, &RunOptions::default()),
+        [Instructions::Push(DataType::Int(1))]
+    );
+}
+
+#[test]
+fn traces_leave_the_stack_alone() {
+    let defined = || RunOptions {
+        defines: vec!["DEBUG".to_string()],
+        ..RunOptions::default()
+    };
+
+    // Tracing an empty stack is not an error, and traced values stay where they were
+    assert_eq!(
+        run_with("::main:\ntrace empty\n", defined()),
+        Ok(Vec::new())
+    );
+    assert_eq!(
+        run_with("::main:\npush 2\ntrace top\npush 3\nmul\n", defined()),
+        Ok(vec![Value::Int(6)])
+    );
+}
@@ -0,0 +1,85 @@
+//! Runs the conformance suite in `conformance/` against the reference interpreter
+
+use std::{path::Path, time::Duration};
+
+use toylang::{
+    conformance::{evaluate_all, Limits, TestCase},
+    test_report::Status,
+};
+
+#[test]
+fn reference_interpreter_passes_conformance_suite() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("conformance");
+    let cases = TestCase::load_dir(&dir).unwrap();
+    assert!(!cases.is_empty());
+
+    let failures: Vec<String> = cases
+        .iter()
+        .filter_map(|case| {
+            case.check(&case.run())
+                .err()
+                .map(|problems| format!("{}:\n{problems}", case.name))
+        })
+        .collect();
+
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}
+
+#[test]
+fn program_directives_supply_input_and_expected_output() {
+    let case = TestCase::from_program(
+        "echo",
+        "#stdin: 42\n#stdin: 7\n#stdout: number? 42\n#stdout: number? 7\n\
+         ::main:\npush \"number? \"\nprint\nreadline\ndrop\nprint\npush \"\\n\"\nprint\n\
+         push \"number? \"\nprint\nreadline\ndrop\nprint\nexit",
+    )
+    .unwrap();
+
+    assert_eq!(case.stdin, "42\n7\n");
+    assert_eq!(case.check(&case.run()), Ok(()));
+}
+
+#[test]
+fn isolated_runs_report_timeouts_separately() {
+    let case = TestCase::from_program("loop", "::main:\njump main").unwrap();
+
+    let outcome = case.run_isolated(Limits {
+        timeout: Duration::from_millis(50),
+        max_memory: None,
+    });
+
+    assert!(outcome.timed_out);
+    assert!(outcome.error.unwrap().starts_with("Timed out after 50ms"));
+}
+
+#[test]
+fn parallel_results_are_reported_in_order() {
+    let cases: Vec<TestCase> = (0..8)
+        .map(|i| {
+            // The first case runs until its time limit, long after the others are done
+            let program = if i == 0 {
+                "::main:\njump main".to_string()
+            } else {
+                format!("#stdout: {i}\npush {i}\nprint")
+            };
+            TestCase::from_program(format!("case {i}"), &program).unwrap()
+        })
+        .collect();
+
+    let mut reported = Vec::new();
+    let limits = Limits {
+        timeout: Duration::from_millis(100),
+        max_memory: None,
+    };
+    let results = evaluate_all(&cases, limits, 4, |result| {
+        reported.push(result.name.clone())
+    });
+
+    let names: Vec<String> = cases.iter().map(|case| case.name.clone()).collect();
+    assert_eq!(reported, names);
+    assert_eq!(results.len(), 8);
+    assert!(matches!(results[0].status, Status::TimedOut(_)));
+    assert!(results[1..]
+        .iter()
+        .all(|result| result.status == Status::Passed));
+}
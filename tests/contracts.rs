@@ -0,0 +1,59 @@
+//! Checks that debug runs enforce `#pre` and `#post` contracts as sections are entered and left
+
+use toylang::RunOptions;
+
+mod common;
+
+use common::interpreter;
+
+fn run(source: &str, debug: bool) -> Result<(), String> {
+    let options = RunOptions {
+        debug,
+        ..RunOptions::default()
+    };
+    interpreter(source, options)
+        .run()
+        .map_err(|error| error.to_string())
+}
+
+const UNBALANCED: &str = "
+jump produce
+push \"unreachable\"
+print
+exit
+
+::produce:
+#post depth==1
+push 1
+push 2
+";
+
+#[test]
+fn post_contract_is_checked_when_a_section_ends() {
+    assert_eq!(
+        run(UNBALANCED, true),
+        Err("Contract #post depth==1 of section produce failed with a stack depth of 2".into())
+    );
+}
+
+#[test]
+fn contracts_are_only_checked_in_debug_runs() {
+    assert_eq!(run(UNBALANCED, false), Ok(()));
+}
+
+#[test]
+fn pre_contract_is_checked_on_entry() {
+    let source = "
+push 1
+jump consume
+
+::consume:
+#pre depth>=2
+exit
+";
+
+    assert_eq!(
+        run(source, true),
+        Err("Contract #pre depth>=2 of section consume failed with a stack depth of 1".into())
+    );
+}
@@ -0,0 +1,57 @@
+//! Checks that values convert to and from Rust types
+
+use std::collections::HashMap;
+
+use toylang::{parse, FromStack, Interpreter, RunOptions, Stack, ToStack, Value};
+
+#[test]
+fn values_convert_to_and_from_rust_types() {
+    assert_eq!(Value::from(3), Value::Int(3));
+    assert_eq!(Value::from("a"), Value::String("a".to_string()));
+    assert_eq!(i64::try_from(Value::Int(-4)), Ok(-4));
+    assert_eq!(
+        Vec::<Value>::try_from(Value::from(vec![Value::from(true)])),
+        Ok(vec![Value::Bool(true)])
+    );
+
+    let error = f64::try_from(Value::Int(1)).unwrap_err();
+    assert_eq!(error.to_string(), "Expected Float, found Int");
+
+    let map = HashMap::from([
+        ("b".to_string(), Value::Int(2)),
+        ("a".to_string(), Value::Int(1)),
+    ]);
+    let list = Value::from(map.clone());
+
+    assert_eq!(
+        list.to_string(),
+        Value::List(vec![
+            Value::List(vec![Value::from("a"), Value::Int(1)]),
+            Value::List(vec![Value::from("b"), Value::Int(2)]),
+        ])
+        .to_string()
+    );
+    assert_eq!(HashMap::try_from(list), Ok(map));
+    assert!(HashMap::<String, Value>::try_from(Value::List(vec![Value::Int(1)])).is_err());
+}
+
+#[test]
+fn tuples_are_taken_from_the_top_of_the_stack() {
+    let options = RunOptions::default();
+    let program = parse("::main:\npush 7\npush \"seven\"\npush 7.5\n", &options);
+    let values = Interpreter::new(program, options)
+        .call_section("main", Vec::new())
+        .unwrap();
+
+    let mut stack = Stack::from(values);
+    let (number, name, float) = <(i64, String, f64)>::from_stack(&mut stack).unwrap();
+    assert_eq!((number, name.as_str(), float), (7, "seven", 7.5));
+    assert!(stack.is_empty());
+
+    (number + 1, name).to_stack(&mut stack);
+    assert_eq!(stack.render(), "[8, \"seven\"]");
+
+    let error = <(bool, i64)>::from_stack(&mut stack).unwrap_err();
+    assert_eq!(error.to_string(), "Expected Int, found String");
+    assert!(i64::from_stack(&mut Stack::new()).is_err());
+}
@@ -0,0 +1,35 @@
+//! Checks `csvparse` and `csvemit`, and that malformed input is an error rather than a crash
+
+use toylang::{RuntimeError, Value};
+
+mod common;
+
+use common::run;
+
+#[test]
+fn quoted_fields_round_trip() {
+    assert_eq!(
+        run("::main:\npush \"x,\"a,b\"\\n\"\ncsvparse\ncsvemit\n"),
+        Ok(vec![Value::String("x,\"a,b\"\n".to_string())])
+    );
+}
+
+#[test]
+fn unterminated_quotes_are_errors() {
+    assert_eq!(
+        run("::main:\npush \"a,\"bc\"\ncsvparse\n"),
+        Err(RuntimeError::Instruction(
+            "csvparse: Unterminated quoted field in CSV".to_string()
+        ))
+    );
+}
+
+#[test]
+fn emitted_rows_must_be_lists() {
+    assert_eq!(
+        run("::main:\nlist\npush 1\nappend\ncsvemit\n"),
+        Err(RuntimeError::Instruction(
+            "csvemit rows must be Lists, found Int".to_string()
+        ))
+    );
+}
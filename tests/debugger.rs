@@ -0,0 +1,140 @@
+//! Checks the interactive debugger pauses where it should and answers its commands
+
+use std::io::Cursor;
+
+use toylang::{debugger::Breakpoint, RunOptions, Value};
+
+mod common;
+
+use common::{interpreter, run_main, SharedBuffer};
+
+const PROGRAM: &str = "\
+::main:
+push 1
+push 2
+call helper
+push 10
+::helper:
+add
+store x
+load x
+ret
+";
+
+/// Runs `source` under the debugger, answering its prompts with `commands`. Returns the
+/// final stack and everything the debugger said
+fn debug(source: &str, breakpoints: Vec<Breakpoint>, commands: &str) -> (Vec<Value>, String) {
+    let options = RunOptions {
+        debugger: true,
+        breakpoints,
+        ..RunOptions::default()
+    };
+
+    let said = SharedBuffer::default();
+    let mut interpreter = interpreter(source, options);
+    interpreter.set_source(source);
+    interpreter.set_debugger_io(Cursor::new(commands.as_bytes().to_vec()), said.clone());
+
+    let stack = interpreter.call_section("main", Vec::new()).unwrap();
+    let said = String::from_utf8(said.0.borrow().clone()).unwrap();
+    (stack, said)
+}
+
+#[test]
+fn breakpoints_are_parsed_from_lines_and_sections() {
+    assert_eq!("12".parse(), Ok(Breakpoint::Line(12)));
+    assert_eq!(
+        "helper".parse(),
+        Ok(Breakpoint::Section("helper".to_string()))
+    );
+    assert_eq!(
+        "::helper:".parse(),
+        Ok(Breakpoint::Section("helper".to_string()))
+    );
+    assert!(" ".parse::<Breakpoint>().is_err());
+}
+
+#[test]
+fn stepping_shows_the_stack() {
+    let (stack, said) = debug(
+        PROGRAM,
+        vec![Breakpoint::Section("main".to_string())],
+        "step\nstep\nstack\nc\n",
+    );
+
+    assert_eq!(stack, vec![Value::Int(3), Value::Int(10)]);
+    assert_eq!(
+        said,
+        "Paused before `push 1` at main+0 (line 2)\n\
+         (debug) Paused before `push 2` at main+1 (line 3)\n\
+         (debug) Paused before `call helper` at main+2 (line 4)\n\
+         (debug) [1, 2]\n\
+         (debug) "
+    );
+}
+
+#[test]
+fn line_breakpoints_show_variables_and_calls() {
+    let (_, said) = debug(
+        PROGRAM,
+        vec![Breakpoint::Line(9)],
+        "vars\ncalls\ncontinue\n",
+    );
+
+    assert_eq!(
+        said,
+        "Paused before `load x` at helper+2 (line 9)\n\
+         (debug) x = 3\n\
+         (debug) #0 helper+2 (line 9)\n\
+         #1 main+2 (line 4)\n\
+         (debug) "
+    );
+}
+
+#[test]
+fn breakpoints_can_be_set_while_paused() {
+    let (_, said) = debug(
+        PROGRAM,
+        vec![Breakpoint::Section("main".to_string())],
+        "break helper\nc\nc\n",
+    );
+
+    assert!(said.contains("Breakpoint set at section helper\n"));
+    assert!(said.contains("Paused before `add` at helper+0 (line 7)\n"));
+}
+
+#[test]
+fn break_instructions_always_pause() {
+    let (stack, said) = debug("::main:\npush 1\nbreak\npush 2\n", Vec::new(), "c\n");
+
+    assert_eq!(stack, vec![Value::Int(1), Value::Int(2)]);
+    assert_eq!(said, "Paused before `break` at main+1 (line 3)\n(debug) ");
+}
+
+#[test]
+fn break_instructions_do_nothing_outside_the_debugger() {
+    assert_eq!(
+        run_main("push 1\nbreak\npush 2\n"),
+        Ok(vec![Value::Int(1), Value::Int(2)])
+    );
+}
+
+#[test]
+fn quitting_at_a_break_instruction_stops_there() {
+    let (stack, said) = debug("::main:\npush 1\nbreak\npush 2\n", Vec::new(), "q\n");
+
+    assert_eq!(stack, vec![Value::Int(1)]);
+    assert_eq!(said, "Paused before `break` at main+1 (line 3)\n(debug) ");
+}
+
+#[test]
+fn quitting_stops_the_program() {
+    let (stack, said) = debug(
+        PROGRAM,
+        vec![Breakpoint::Section("helper".to_string())],
+        "bogus\nq\n",
+    );
+
+    assert_eq!(stack, vec![Value::Int(1), Value::Int(2)]);
+    assert!(said.contains("Unknown command `bogus`, try `help`\n"));
+}
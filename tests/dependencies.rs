@@ -0,0 +1,290 @@
+//! Checks dependencies are listed in the manifest, vendored by `fetch` and linked in by
+//! `#import`
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use toylang::{
+    dependencies::{fetch, imports, link, Lock, Locked},
+    manifest::{Dependency, Manifest, Source},
+    parse, Interpreter, RunOptions, Value,
+};
+
+/// A fresh directory for a test to build a project in
+fn project(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("toylang-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write(path: &Path, contents: &str) {
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, contents).unwrap();
+}
+
+#[test]
+fn dependencies_are_paths_or_git_urls() {
+    let manifest = Manifest::parse(
+        "[dependencies]\nstrings = { path = \"../strings.toy\" }\nmaths = { git = \"https://example.com/maths.git\", rev = \"v1\" }\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        manifest.dependencies,
+        [
+            Dependency {
+                name: "strings".to_string(),
+                source: Source::Path(PathBuf::from("../strings.toy")),
+            },
+            Dependency {
+                name: "maths".to_string(),
+                source: Source::Git {
+                    url: "https://example.com/maths.git".to_string(),
+                    file: "lib.toy".to_string(),
+                    rev: Some("v1".to_string()),
+                },
+            },
+        ]
+    );
+
+    assert_eq!(
+        Manifest::parse("[dependencies]\nstrings = \"../strings.toy\"\n").unwrap_err(),
+        "line 2: dependency strings must be an inline table like { path = \"...\" }"
+    );
+    assert_eq!(
+        Manifest::parse("[dependencies]\nstrings = { rev = \"v1\" }\n").unwrap_err(),
+        "line 2: dependency strings requires either a path or a git URL"
+    );
+    assert_eq!(
+        Manifest::parse(
+            "[dependencies]\nmaths = { git = \"maths.git\", file = \"../../etc/passwd\" }\n"
+        )
+        .unwrap_err(),
+        "line 2: dependency maths must name a file inside the repository"
+    );
+    assert_eq!(
+        Manifest::parse(
+            "[dependencies]\nmaths = { git = \"maths.git\", file = \"/etc/passwd\" }\n"
+        )
+        .unwrap_err(),
+        "line 2: dependency maths must name a file inside the repository"
+    );
+    assert_eq!(
+        Manifest::parse(
+            "[dependencies]\nmaths = { git = \"maths.git\", rev = \"--upload-pack=x\" }\n"
+        )
+        .unwrap_err(),
+        "line 2: dependency maths has an invalid rev"
+    );
+}
+
+#[test]
+fn dependency_names_cannot_leave_the_vendor_directory() {
+    for name in ["..", "../maths", "nested/maths", "C:\\maths", "/etc/maths"] {
+        assert_eq!(
+            Manifest::parse(&format!(
+                "[dependencies]\n{name} = {{ path = \"maths.toy\" }}\n"
+            ))
+            .unwrap_err(),
+            format!("line 2: dependency name must be a plain file name: {name}")
+        );
+    }
+
+    let manifest =
+        Manifest::parse("[dependencies]\nmaths.v2 = { path = \"maths.toy\" }\n").unwrap();
+    assert_eq!(manifest.dependencies[0].name, "maths.v2");
+}
+
+#[test]
+fn locks_round_trip() {
+    let lock = Lock {
+        dependencies: vec![
+            Locked {
+                name: "strings".to_string(),
+                source: "../strings.toy".to_string(),
+                rev: None,
+            },
+            Locked {
+                name: "maths".to_string(),
+                source: "https://example.com/maths.git".to_string(),
+                rev: Some("0123abcd".to_string()),
+            },
+        ],
+    };
+
+    assert_eq!(Lock::parse(&lock.to_string()), Ok(lock));
+}
+
+#[test]
+fn imports_are_linked_from_the_vendored_copies() {
+    let root = project("link");
+    write(
+        &root.join("shared/strings.toy"),
+        "#import maths\n::main:\nexit\n\n::greet:\npush \"hi\"\nret\n",
+    );
+    write(&root.join("shared/maths.toy"), "::square:\ndup\nmul\nret\n");
+    write(
+        &root.join("toy.toml"),
+        "[dependencies]\nstrings = { path = \"shared/strings.toy\" }\nmaths = { path = \"shared/maths.toy\" }\n",
+    );
+    let manifest = Manifest::load(&root.join("toy.toml")).unwrap();
+
+    let source = "#import strings\n::main:\ncall greet\npush 7\ncall square\n";
+    let options = RunOptions::default();
+    let mut program = parse(source, &options);
+
+    // Nothing is linked until the dependencies are fetched
+    let error = link(
+        &mut program.clone(),
+        imports(source.as_bytes()),
+        &root,
+        &manifest,
+        &options,
+    )
+    .unwrap_err();
+    assert_eq!(error.line, Some(1));
+    assert_eq!(
+        error.message,
+        "dependency strings has not been fetched, run `toylang fetch`"
+    );
+
+    let lock = fetch(&root, &manifest, false).unwrap();
+    assert_eq!(lock.dependencies.len(), 2);
+    assert!(root.join("vendor/strings.toy").exists());
+    assert_eq!(Lock::load(&root.join("toy.lock")), Ok(lock));
+
+    link(
+        &mut program,
+        imports(source.as_bytes()),
+        &root,
+        &manifest,
+        &options,
+    )
+    .unwrap();
+    std::fs::remove_dir_all(&root).unwrap();
+
+    // The library's main section is left out
+    assert_eq!(
+        Interpreter::new(program, options).call_section("main", Vec::new()),
+        Ok(vec![Value::String("hi".to_string()), Value::Int(49)])
+    );
+}
+
+#[test]
+fn imports_must_be_dependencies_that_do_not_clash() {
+    let root = project("clash");
+    write(&root.join("vendor/maths.toy"), "::square:\ndup\nmul\nret\n");
+    let manifest = Manifest::parse("[dependencies]\nmaths = { path = \"maths.toy\" }\n").unwrap();
+    let options = RunOptions::default();
+
+    let source = "::main:\nexit\n#import nope\n";
+    let error = link(
+        &mut parse(source, &options),
+        imports(source.as_bytes()),
+        &root,
+        &manifest,
+        &options,
+    )
+    .unwrap_err();
+    assert_eq!(
+        (error.line, error.message.as_str()),
+        (Some(3), "nope is not a dependency of the project")
+    );
+
+    let source = "#import maths\n::main:\nexit\n::square:\nret\n";
+    let error = link(
+        &mut parse(source, &options),
+        imports(source.as_bytes()),
+        &root,
+        &manifest,
+        &options,
+    )
+    .unwrap_err();
+    std::fs::remove_dir_all(&root).unwrap();
+
+    assert_eq!(
+        error.message,
+        "maths defines square, which is already defined"
+    );
+}
+
+#[test]
+fn git_dependencies_stay_at_the_locked_commit() {
+    let root = project("git");
+    let repository = root.join("maths");
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .args(["-c", "user.name=toy", "-c", "user.email=toy@example.com"])
+            .arg("-C")
+            .arg(&repository)
+            .args(args)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {args:?}");
+    };
+
+    write(&repository.join("lib.toy"), "::answer:\npush 1\nret\n");
+    git(&["init", "--quiet"]);
+    git(&["add", "."]);
+    git(&["commit", "--quiet", "-m", "first"]);
+
+    let manifest = Manifest {
+        dependencies: vec![Dependency {
+            name: "maths".to_string(),
+            source: Source::Git {
+                url: repository.display().to_string(),
+                file: "lib.toy".to_string(),
+                rev: None,
+            },
+        }],
+        ..Manifest::default()
+    };
+    let first = fetch(&root, &manifest, false).unwrap();
+
+    write(&repository.join("lib.toy"), "::answer:\npush 2\nret\n");
+    git(&["commit", "--quiet", "-am", "second"]);
+
+    let vendored = || std::fs::read_to_string(root.join("vendor/maths.toy")).unwrap();
+
+    assert_eq!(fetch(&root, &manifest, false).unwrap(), first);
+    assert!(vendored().contains("push 1"));
+
+    assert_ne!(fetch(&root, &manifest, true).unwrap(), first);
+    assert!(vendored().contains("push 2"));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn git_dependencies_cannot_reach_outside_the_checkout() {
+    let root = project("git-outside");
+    let fetch_with = |file: &str, rev: Option<&str>| {
+        let manifest = Manifest {
+            dependencies: vec![Dependency {
+                name: "maths".to_string(),
+                source: Source::Git {
+                    url: "https://example.com/maths.git".to_string(),
+                    file: file.to_string(),
+                    rev: rev.map(str::to_string),
+                },
+            }],
+            ..Manifest::default()
+        };
+        fetch(&root, &manifest, false).unwrap_err()
+    };
+
+    assert_eq!(
+        fetch_with("../secret.toy", None),
+        "cannot fetch dependency maths: file must be a path inside the repository: ../secret.toy"
+    );
+    assert_eq!(
+        fetch_with("lib.toy", Some("--orphan=x")),
+        "cannot fetch dependency maths: invalid rev: --orphan=x"
+    );
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
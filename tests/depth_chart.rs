@@ -0,0 +1,48 @@
+//! Checks the stack depth sparkline and the SVG chart written by `--depth-chart`
+
+use toylang::{
+    depth_chart::{sparkline, svg},
+    RunOptions,
+};
+
+mod common;
+
+use common::{interpreter, SharedBuffer};
+
+#[test]
+fn sparklines_are_scaled_to_the_deepest_step() {
+    assert_eq!(sparkline(&[0, 1, 2, 4, 2, 0]), "▁▂▄█▄▁");
+    assert_eq!(sparkline(&[0, 0]), "▁▁");
+    assert_eq!(sparkline(&[]), "");
+}
+
+#[test]
+fn long_runs_keep_their_peaks() {
+    let mut depths = vec![1; 10_000];
+    depths[5_000] = 9;
+
+    let chart = svg(&depths);
+    assert!(chart.contains("<title>Stack depth over 10000 steps (peak 9)</title>"));
+    // The deepest step is drawn at the top of the plot
+    assert!(chart.contains(",40.0 "));
+}
+
+#[test]
+fn runs_write_a_chart_when_they_end() {
+    let chart = SharedBuffer::default();
+    let options = RunOptions {
+        depth_chart_to: Some(Box::new(chart.clone())),
+        ..RunOptions::default()
+    };
+
+    let source = "::main:\npush 1\npush 2\nadd\ndrop\n";
+    let mut interpreter = interpreter(source, options);
+    interpreter.call_section("main", Vec::new()).unwrap();
+    drop(interpreter);
+
+    let chart = String::from_utf8(chart.0.borrow().clone()).unwrap();
+    assert!(chart.starts_with("<svg "));
+    assert!(chart.contains("<title>Stack depth over 4 steps (peak 2)</title>"));
+    // One point for each step, the deepest at the top of the plot
+    assert!(chart.contains("points=\"40.0,200.0 226.7,120.0 413.3,40.0 600.0,120.0\""));
+}
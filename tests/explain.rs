@@ -0,0 +1,49 @@
+//! Checks the narration `explain-run` gives of a program's first steps
+
+use toylang::{explain::explain_run, parse, RunOptions};
+
+fn explain(source: &str, steps: usize) -> String {
+    explain_run(parse(source, &RunOptions::default()), steps)
+}
+
+#[test]
+fn steps_are_narrated_with_the_stack_after_them() {
+    let narration = explain("::main:\npush 5\npush 2\nadd\nprint\nexit\n", 3);
+
+    assert_eq!(
+        narration
+            .lines()
+            .filter(|line| !line.starts_with("   "))
+            .collect::<Vec<_>>(),
+        [
+            "1. push 5 → stack is [5]",
+            "2. push 2 → stack is [5, 2]",
+            "3. add → stack is [7]",
+            "Stopped after 3 steps",
+        ]
+    );
+}
+
+#[test]
+fn steps_are_described_from_the_instruction_reference() {
+    let narration = explain("::main:\npush 5\n", 1);
+
+    assert!(narration.starts_with("1. push 5 → stack is [5]\n   Pushes a literal"));
+}
+
+#[test]
+fn printing_and_finishing_are_narrated() {
+    let narration = explain("::main:\npush \"hi\"\nprint\nexit\n", 10);
+
+    assert!(narration.contains("2. print → prints \"hi\", stack is []\n"));
+    assert!(narration.contains("3. exit → stack is []\n"));
+    assert!(narration.ends_with("The program finished after 3 steps\n"));
+}
+
+#[test]
+fn failures_are_narrated() {
+    let narration = explain("::main:\nreadint\n", 10);
+
+    assert!(narration.contains("1. readint → fails: readint: no more input to read\n"));
+    assert!(narration.ends_with("The program failed at step 1\n"));
+}
@@ -0,0 +1,29 @@
+//! Checks reading files through handles
+
+use toylang::{RuntimeError, Value};
+
+mod common;
+
+use common::run;
+
+#[test]
+fn seeking_moves_where_reads_start() {
+    let path = std::env::temp_dir().join(format!("toylang-seek-{}", std::process::id()));
+    std::fs::write(&path, "hello").unwrap();
+
+    let open = format!(
+        "::main:\npush \"{}\"\npush \"r\"\nfopen\ndup\n",
+        path.display()
+    );
+    let read = run(&format!("{open}push 3\nfseek\npush 2\nfreadn\nfrombytes\n"));
+    let seek_back = run(&format!("{open}push -1\nfseek\n"));
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(read.unwrap().last(), Some(&Value::String("lo".to_string())));
+    assert_eq!(
+        seek_back,
+        Err(RuntimeError::Instruction(
+            "fseek requires an offset that is not negative, found -1".to_string()
+        ))
+    );
+}
@@ -0,0 +1,61 @@
+//! Checks that `flush` sends buffered output on before the program carries on
+
+use std::io::{self, BufWriter, Write};
+
+use toylang::{RunOptions, RunStatus, RuntimeError};
+
+mod common;
+
+use common::{interpreter, SharedBuffer};
+
+/// Output whose first flush fails, like a pipe that was briefly unwritable
+#[derive(Default)]
+struct FlakyOutput {
+    failed: bool,
+}
+
+impl Write for FlakyOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if std::mem::replace(&mut self.failed, true) {
+            return Ok(());
+        }
+
+        Err(io::Error::other("disk full"))
+    }
+}
+
+#[test]
+fn flushed_output_is_written_straight_away() {
+    let source = "::main:\npush \"prompt> \"\nprint\nflush\nexit\n";
+
+    // Stopping before and after the flush shows what had been written by then
+    let written = |steps| {
+        let output = SharedBuffer::default();
+        let mut interpreter = interpreter(source, RunOptions::default());
+        interpreter.set_output(BufWriter::new(output.clone()));
+
+        assert_eq!(interpreter.run_for(steps), Ok(RunStatus::Yielded));
+        let written = String::from_utf8(output.0.borrow().clone()).unwrap();
+        written
+    };
+
+    assert_eq!(written(2), "");
+    assert_eq!(written(3), "prompt> ");
+}
+
+#[test]
+fn failed_flushes_are_errors() {
+    let mut interpreter = interpreter("::main:\nflush\nexit\n", RunOptions::default());
+    interpreter.set_output(FlakyOutput::default());
+
+    assert_eq!(
+        interpreter.run(),
+        Err(RuntimeError::Instruction(
+            "flush failed: disk full".to_string()
+        ))
+    );
+}
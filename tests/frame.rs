@@ -0,0 +1,27 @@
+//! Checks that `frame` paces a loop at a steady rate
+
+use std::time::{Duration, Instant};
+
+use toylang::{parse, RunOptions};
+
+mod common;
+
+use common::interpreter;
+
+#[test]
+fn frames_are_paced() {
+    let source = "::main:\nframe 50\nframe 50\nframe 50\nframe 50\nexit\n";
+    let mut interpreter = interpreter(source, RunOptions::default());
+
+    let started = Instant::now();
+    interpreter.run().unwrap();
+
+    // The first frame starts the clock, each one after it waits out a 20ms frame
+    assert!(started.elapsed() >= Duration::from_millis(60));
+}
+
+#[test]
+#[should_panic(expected = "frame requires a number of frames per second")]
+fn frame_rate_must_be_positive() {
+    parse("::main:\nframe 0\n", &RunOptions::default());
+}
@@ -0,0 +1,58 @@
+//! Checks that source is highlighted with the same tokens the language server reports
+
+use toylang::highlight::{to_ansi, to_html, tokens, Token};
+
+#[test]
+fn tokens_follow_the_parser() {
+    let source = "# comment\n::main:\npush \"a<b\"\njump done\n\n::data text:\n<raw>\n";
+    let kinds: Vec<Token> = tokens(source)
+        .into_iter()
+        .map(|(_, _, kind)| kind)
+        .collect();
+
+    assert_eq!(
+        kinds,
+        [
+            Token::Comment,
+            Token::Label,
+            Token::Keyword,
+            Token::String,
+            Token::Keyword,
+            Token::Label,
+            Token::Keyword,
+            Token::Label,
+            Token::String,
+        ]
+    );
+}
+
+#[test]
+fn html_is_escaped_and_keeps_every_character() {
+    let source = "::main:\npush \"a<b\" # &\n";
+    let html = to_html(source);
+
+    assert_eq!(
+        html,
+        "<pre class=\"toylang\"><code>::<span class=\"toy-label\">main</span>:\n\
+         <span class=\"toy-keyword\">push</span> \
+         <span class=\"toy-string\">&quot;a&lt;b&quot; # &amp;</span>\n\
+         </code></pre>\n"
+    );
+}
+
+#[test]
+fn ansi_output_is_the_source_once_colours_are_removed() {
+    let source = std::fs::read_to_string("samples/fizz_buzz.tyl").unwrap();
+    let ansi = to_ansi(&source);
+
+    let mut plain = String::new();
+    let mut rest = ansi.as_str();
+    while let Some(start) = rest.find('\x1b') {
+        plain.push_str(&rest[..start]);
+        rest = &rest[start + rest[start..].find('m').unwrap() + 1..];
+    }
+    plain.push_str(rest);
+
+    assert!(ansi.contains("\x1b[34mpush\x1b[0m"));
+    assert_eq!(plain.trim_end(), source.trim_end());
+}
@@ -0,0 +1,60 @@
+//! Checks that Rust functions can be used as instructions
+
+use toylang::{toy_fn, try_parse, RunOptions, Value};
+
+mod common;
+
+use common::run_with;
+
+#[toy_fn]
+fn clamp(value: i64, low: i64, high: i64) -> i64 {
+    value.max(low).min(high)
+}
+
+#[toy_fn(name = "divmod")]
+fn div_mod(a: i64, b: i64) -> Result<(i64, i64), String> {
+    if b == 0 {
+        return Err("Division by zero".to_string());
+    }
+
+    Ok((a / b, a % b))
+}
+
+fn run(source: &str) -> Result<Vec<Value>, String> {
+    let options = RunOptions {
+        host_functions: vec![CLAMP, DIV_MOD],
+        ..RunOptions::default()
+    };
+    run_with(&format!("::main:\n{source}"), options).map_err(|error| error.to_string())
+}
+
+#[test]
+fn host_functions_pop_their_arguments_and_push_their_result() {
+    assert_eq!(CLAMP.name, "clamp");
+    assert_eq!((CLAMP.pops, CLAMP.pushes), (3, 1));
+    assert_eq!((DIV_MOD.pops, DIV_MOD.pushes), (2, 2));
+
+    assert_eq!(
+        run("push 12\npush 0\npush 10\nclamp"),
+        Ok(vec![Value::Int(10)])
+    );
+    assert_eq!(
+        run("push 17\npush 5\ndivmod"),
+        Ok(vec![Value::Int(3), Value::Int(2)])
+    );
+}
+
+#[test]
+fn host_function_errors_name_the_function() {
+    assert_eq!(
+        run("push 1\npush 0\ndivmod"),
+        Err("divmod: Division by zero".to_string())
+    );
+    assert_eq!(
+        run("push 1\npush \"a\"\npush 3\nclamp"),
+        Err("clamp: Expected Int, found String".to_string())
+    );
+
+    // Functions that were not registered are not instructions
+    assert!(try_parse("push 1\npush 2\ndivmod", &RunOptions::default()).is_err());
+}
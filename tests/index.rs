@@ -0,0 +1,49 @@
+//! Checks the project symbol index
+
+use toylang::index::{project_files, symbols, SymbolIndex, SymbolKind};
+
+#[test]
+fn symbols_skip_excluded_and_data_lines() {
+    let source = "const primes = [2, 3]\n#states light Red Green\n::main:\nexit\n#if NEVER\n::hidden:\n#endif\n::data text:\n::not a header\n";
+
+    let found: Vec<_> = symbols(source)
+        .unwrap()
+        .into_iter()
+        .map(|symbol| (symbol.name, symbol.kind, symbol.line))
+        .collect();
+
+    assert_eq!(
+        found,
+        [
+            ("primes".to_string(), SymbolKind::Const, 1),
+            ("light".to_string(), SymbolKind::States, 2),
+            ("main".to_string(), SymbolKind::Section, 3),
+            ("text".to_string(), SymbolKind::Data, 8),
+        ]
+    );
+}
+
+#[test]
+fn update_only_parses_changed_files() {
+    let dir = std::env::temp_dir().join(format!("toylang-index-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::write(dir.join("main.tyl"), "::main:\njump helper\n").unwrap();
+    std::fs::write(dir.join("lib/helper.tyl"), "::helper:\nexit\n").unwrap();
+
+    let mut index = SymbolIndex::default();
+    assert_eq!(index.update(&project_files(&dir).unwrap()), Ok(2));
+
+    std::fs::write(dir.join("lib/helper.tyl"), "::other:\nexit\n").unwrap();
+    let path = dir.join("index.json");
+    index.save(&path).unwrap();
+
+    let mut index = SymbolIndex::load(&path).unwrap();
+    assert_eq!(index.update(&project_files(&dir).unwrap()), Ok(1));
+    assert_eq!(index.find("helper").count(), 0);
+    assert_eq!(
+        index.find("other").next().unwrap().0,
+        dir.join("lib/helper.tyl")
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,46 @@
+//! Checks the instruction reference against the parser so the two cannot drift apart
+
+use toylang::{parse, reference::INSTRUCTION_SET, Program, RunOptions};
+
+#[test]
+fn every_documented_instruction_parses() {
+    let options = RunOptions {
+        debug: true,
+        ..RunOptions::default()
+    };
+
+    for info in INSTRUCTION_SET {
+        let operand = match info.operand {
+            None => "",
+            Some("value") => " 1",
+            Some("level") => " info",
+            Some("fps") => " 30",
+            Some("length") => " 3",
+            Some("rows cols") => " 2 3",
+            Some("[index]") => " 1",
+            Some(_) => " target",
+        };
+
+        let source = format!("{}{operand}", info.name);
+        let program = parse(&source, &options);
+        let [Program::Section(_, instructions)] = program.as_slice() else {
+            panic!("{source} did not parse into a single section");
+        };
+
+        assert_eq!(instructions.len(), 1, "{source}");
+        assert_eq!(instructions[0].to_string(), source);
+    }
+}
+
+#[test]
+fn instruction_names_are_unique() {
+    for (index, info) in INSTRUCTION_SET.iter().enumerate() {
+        assert!(
+            INSTRUCTION_SET[index + 1..]
+                .iter()
+                .all(|other| other.name != info.name),
+            "{} is documented twice",
+            info.name
+        );
+    }
+}
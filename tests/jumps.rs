@@ -0,0 +1,60 @@
+//! Checks that jumps move to their target for good, unless splicing is asked for
+
+use toylang::{Compat, RunOptions, Value};
+
+mod common;
+
+use common::interpreter;
+
+fn run(source: &str, compat: Option<Compat>) -> Vec<Value> {
+    let options = RunOptions {
+        compat,
+        ..RunOptions::default()
+    };
+    let mut interpreter = interpreter(source, options);
+
+    interpreter.run().unwrap();
+    interpreter.global("result").into_iter().cloned().collect()
+}
+
+#[test]
+fn jumps_do_not_come_back() {
+    let source = "::main:
+push 1
+setglobal result
+jump set
+push 3
+setglobal result
+
+::set:
+push 2
+setglobal result
+";
+
+    assert_eq!(run(source, None), vec![Value::Int(2)]);
+    assert_eq!(run(source, Some(Compat::Splice)), vec![Value::Int(3)]);
+}
+
+#[test]
+fn long_loops_jump_backwards() {
+    // Counts down from 100000, jumping back to the top of the loop each time
+    let source = "::main:
+push 100000
+jump loop
+
+::loop:
+dup
+push 0
+eq
+ifjmp done
+push 1
+swap
+sub
+jump loop
+
+::done:
+setglobal result
+";
+
+    assert_eq!(run(source, None), vec![Value::Int(0)]);
+}
@@ -0,0 +1,55 @@
+//! Checks the rules run by `toylang lint`
+
+use toylang::{
+    lint::{lint, LintConfig},
+    parse, RunOptions,
+};
+
+const PROGRAM: &str =
+    "::main:\npush 7\ndrop\npush 7\npush 7\neq\ndrop\npush 7\njump spin\n::spin:\njump spin\n";
+
+#[test]
+fn reports_each_rule() {
+    let program = parse(PROGRAM, &RunOptions::default());
+    let rules: Vec<_> = lint(&program, &LintConfig::default())
+        .iter()
+        .map(|warning| (warning.rule, warning.section.clone()))
+        .collect();
+
+    assert_eq!(
+        rules,
+        [
+            ("push-drop", "main".to_string()),
+            ("unused-comparison", "main".to_string()),
+            ("never-returns", "main".to_string()),
+            ("never-returns", "spin".to_string()),
+            ("magic-number", "main".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn config_allows_and_tunes_rules() {
+    let config = LintConfig::parse(
+        "[package]\nname = \"demo\"\n\n[lint]\nallow = [\"never-returns\", \"push-drop\"]\nmagic-number-threshold = 5 # per program\n",
+    )
+    .unwrap();
+    let program = parse(PROGRAM, &RunOptions::default());
+
+    let warnings = lint(&program, &config);
+
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].rule, "unused-comparison");
+    assert!(LintConfig::parse("[lint]\nallow = [\"typo\"]\n").is_err());
+}
+
+#[test]
+fn fix_rewrites_only_the_affected_lines() {
+    let source =
+        "::main:\n// keep me\nPUSH 007\npush 2\npush 3\ndrop\ndrop\nprint\npush 4\npush 4\nne\ndrop\nexit\n";
+
+    let (fixed, fixes) = toylang::lint::fix(source, &RunOptions::default(), &LintConfig::default());
+
+    assert_eq!(fixed, "::main:\n// keep me\npush 7\nprint\nexit\n");
+    assert_eq!(fixes, 6);
+}
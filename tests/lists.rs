@@ -0,0 +1,111 @@
+//! Checks building and reading Lists with `list`, `append`, `get`, `set`, `len` and `pop`
+
+use toylang::{RunOptions, RuntimeError, Stack, Value};
+
+mod common;
+
+use common::{interpreter, run};
+
+/// A program starting with the List `[10, "b", true]` on the stack
+fn with_list(rest: &str) -> Result<Vec<Value>, RuntimeError> {
+    run(&format!(
+        "::main:\nlist\npush 10\nappend\npush \"b\"\nappend\npush true\nappend\n{rest}"
+    ))
+}
+
+#[test]
+fn lists_are_built_by_appending() {
+    assert_eq!(
+        with_list(""),
+        Ok(vec![Value::List(vec![
+            Value::Int(10),
+            Value::String("b".to_string()),
+            Value::Bool(true),
+        ])])
+    );
+    assert_eq!(with_list("len\n"), Ok(vec![Value::Int(3)]));
+    assert_eq!(run("::main:\nlist\nlen\n"), Ok(vec![Value::Int(0)]));
+}
+
+#[test]
+fn items_are_read_and_replaced_by_index() {
+    assert_eq!(
+        with_list("push 1\nget\nswap\nlen\n"),
+        Ok(vec![Value::String("b".to_string()), Value::Int(3)])
+    );
+    assert_eq!(
+        with_list("push 0\npush 2.5\nset\npush 0\nget\nswap\nlen\n"),
+        Ok(vec![Value::Float(2.5), Value::Int(3)])
+    );
+}
+
+#[test]
+fn get_leaves_the_list_in_place() {
+    assert_eq!(
+        with_list("push 0\nget\nswap\npush 0\nget\nswap\npush 2\nget\nswap\n"),
+        Ok(vec![
+            Value::Int(10),
+            Value::Int(10),
+            Value::Bool(true),
+            Value::List(vec![
+                Value::Int(10),
+                Value::String("b".to_string()),
+                Value::Bool(true),
+            ]),
+        ])
+    );
+}
+
+#[test]
+fn pop_takes_the_last_item() {
+    assert_eq!(
+        with_list("pop\nswap\nlen\n"),
+        Ok(vec![Value::Bool(true), Value::Int(2)])
+    );
+    assert_eq!(
+        run("::main:\nlist\npop\n"),
+        Err(RuntimeError::Instruction(
+            "pop requires a List with at least one item".to_string()
+        ))
+    );
+}
+
+#[test]
+fn indices_outside_the_list_are_errors() {
+    let out_of_bounds = |index: i64| {
+        Err(RuntimeError::Instruction(format!(
+            "List index {index} out of bounds for length 3"
+        )))
+    };
+
+    assert_eq!(with_list("push 3\nget\n"), out_of_bounds(3));
+    assert_eq!(with_list("push -1\nget\n"), out_of_bounds(-1));
+    assert_eq!(with_list("push 5\npush 0\nset\n"), out_of_bounds(5));
+}
+
+#[test]
+fn other_values_are_not_lists() {
+    assert_eq!(
+        run("::main:\npush 1\npush 2\nappend\n"),
+        Err(RuntimeError::Instruction(
+            "append requires a List and a value on the stack".to_string()
+        ))
+    );
+}
+
+#[test]
+fn lists_changed_in_place_are_measured_like_new_ones() {
+    let options = RunOptions::default();
+    let source = "::main:\nlist\npush \"a\"\nappend\npush \"bb\"\nappend\npush 0\npush \"a longer string\"\nset\npop\ndrop\npush 1\nappend\npush 1\nget\n";
+    let mut interpreter = interpreter(source, options);
+    interpreter.run().unwrap();
+
+    let expected = Stack::from(vec![
+        Value::List(vec![
+            Value::String("a longer string".to_string()),
+            Value::Int(1),
+        ]),
+        Value::Int(1),
+    ]);
+    assert_eq!(interpreter.memory_used(), expected.bytes());
+}
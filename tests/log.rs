@@ -0,0 +1,57 @@
+//! Checks that `log` hands its messages to the host's logger rather than printing them
+
+use std::{cell::RefCell, rc::Rc};
+
+use toylang::{try_parse, LogLevel, Logger, RunOptions, RuntimeError};
+
+mod common;
+
+use common::{interpreter, run_with, SharedBuffer};
+
+struct Recorder(Rc<RefCell<Vec<(LogLevel, String)>>>);
+
+impl Logger for Recorder {
+    fn log(&self, level: LogLevel, message: &str) {
+        self.0.borrow_mut().push((level, message.to_string()));
+    }
+}
+
+#[test]
+fn messages_reach_the_logger_at_their_level() {
+    let logged = Rc::default();
+    let options = RunOptions {
+        logger: Box::new(Recorder(Rc::clone(&logged))),
+        ..RunOptions::default()
+    };
+
+    let source = "::main:\npush \"starting\"\nlog info\npush \"careful\"\nlog warn\n";
+    let printed = SharedBuffer::default();
+    let mut interpreter = interpreter(source, options);
+    interpreter.set_output(printed.clone());
+    interpreter.run().unwrap();
+
+    assert_eq!(
+        *logged.borrow(),
+        [
+            (LogLevel::Info, "starting".to_string()),
+            (LogLevel::Warn, "careful".to_string())
+        ]
+    );
+    assert!(printed.0.borrow().is_empty());
+}
+
+#[test]
+fn log_requires_a_level_and_a_string() {
+    let error = try_parse("::main:\npush \"x\"\nlog loud\n", &RunOptions::default()).unwrap_err();
+    assert_eq!(
+        error.message,
+        "log requires a level (trace, debug, info, warn or error)"
+    );
+
+    assert_eq!(
+        run_with("::main:\npush 1\nlog info\n", RunOptions::default()),
+        Err(RuntimeError::Instruction(
+            "log requires a string on the stack".to_string()
+        ))
+    );
+}
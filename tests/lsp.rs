@@ -0,0 +1,222 @@
+//! Drives the language server through the messages an editor would send
+
+use std::io::Cursor;
+
+use toylang::{json::Json, lsp::serve};
+
+/// Sends `requests` to the server and returns its responses
+fn exchange(requests: &[String]) -> Vec<Json> {
+    messages(requests)
+        .into_iter()
+        .filter(|message| message.get("id").is_some())
+        .collect()
+}
+
+/// Sends `requests` to the server and returns everything it sends back, notifications
+/// included
+fn messages(requests: &[String]) -> Vec<Json> {
+    let input: String = requests
+        .iter()
+        .map(|body| format!("Content-Length: {}\r\n\r\n{body}", body.len()))
+        .collect();
+
+    let mut output = Vec::new();
+    serve(Cursor::new(input), &mut output).unwrap();
+
+    String::from_utf8(output)
+        .unwrap()
+        .split("Content-Length: ")
+        .filter(|message| !message.is_empty())
+        .map(|message| Json::parse(message.split_once("\r\n\r\n").unwrap().1).unwrap())
+        .collect()
+}
+
+fn request(id: usize, method: &str, params: &str) -> String {
+    format!(r#"{{"jsonrpc":"2.0","id":{id},"method":"{method}","params":{params}}}"#)
+}
+
+#[test]
+fn definitions_references_and_outline() {
+    let source = r#"::main:\npush 1\nifjmp done\njump done\n\n::done:\nexit\n"#;
+    let document = r#"{"uri":"file:///virtual/main.tyl"}"#;
+    let at = |line: usize, character: usize| {
+        format!(
+            r#"{{"textDocument":{document},"position":{{"line":{line},"character":{character}}},"context":{{"includeDeclaration":true}}}}"#
+        )
+    };
+
+    let responses = exchange(&[
+        request(1, "initialize", "{}"),
+        format!(
+            r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"file:///virtual/main.tyl","text":"{source}"}}}}}}"#
+        ),
+        request(2, "textDocument/definition", &at(3, 6)),
+        request(3, "textDocument/references", &at(5, 3)),
+        request(
+            4,
+            "textDocument/documentSymbol",
+            &format!(r#"{{"textDocument":{document}}}"#),
+        ),
+        request(5, "textDocument/hover", &at(0, 0)),
+        r#"{"jsonrpc":"2.0","method":"exit"}"#.to_string(),
+    ]);
+
+    assert_eq!(responses.len(), 5);
+
+    let lines = |response: &Json| -> Vec<usize> {
+        response
+            .get("result")
+            .and_then(Json::as_array)
+            .unwrap()
+            .iter()
+            .map(|location| {
+                location
+                    .get("range")
+                    .unwrap()
+                    .get("start")
+                    .unwrap()
+                    .get("line")
+                    .unwrap()
+                    .as_usize()
+                    .unwrap()
+            })
+            .collect()
+    };
+
+    assert_eq!(lines(&responses[1]), [5]);
+    assert_eq!(lines(&responses[2]), [2, 3, 5]);
+
+    let outline: Vec<_> = responses[3]
+        .get("result")
+        .and_then(Json::as_array)
+        .unwrap()
+        .iter()
+        .map(|symbol| symbol.get("name").and_then(Json::as_str).unwrap())
+        .collect();
+    assert_eq!(outline, ["main", "done"]);
+
+    assert!(responses[4].get("error").is_some());
+}
+
+#[test]
+fn semantic_tokens_cover_each_kind_of_word() {
+    let responses = exchange(&[
+        r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///virtual/a.tyl","text":"::main:\n// hi\npush 1\njump main\n"}}}"#.to_string(),
+        request(
+            1,
+            "textDocument/semanticTokens/full",
+            r#"{"textDocument":{"uri":"file:///virtual/a.tyl"}}"#,
+        ),
+    ]);
+
+    let data: Vec<usize> = responses[0]
+        .get("result")
+        .and_then(|result| result.get("data"))
+        .and_then(Json::as_array)
+        .unwrap()
+        .iter()
+        .map(|value| value.as_usize().unwrap())
+        .collect();
+
+    // Label, comment, keyword and number, then keyword and label
+    assert_eq!(
+        data,
+        [
+            0, 2, 4, 1, 0, //
+            1, 0, 5, 6, 0, //
+            1, 0, 4, 0, 0, //
+            0, 5, 1, 4, 0, //
+            1, 0, 4, 0, 0, //
+            0, 5, 4, 1, 0,
+        ]
+    );
+}
+
+#[test]
+fn signature_help_and_line_formatting() {
+    let open = r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///virtual/b.tyl","text":"::main:\n  PUSH   3\nswap \n"}}}"#;
+    let at = |line: usize, character: usize| {
+        format!(
+            r#"{{"textDocument":{{"uri":"file:///virtual/b.tyl"}},"position":{{"line":{line},"character":{character}}},"ch":"\n","options":{{}}}}"#
+        )
+    };
+
+    let responses = exchange(&[
+        open.to_string(),
+        request(1, "textDocument/signatureHelp", &at(2, 5)),
+        request(2, "textDocument/onTypeFormatting", &at(2, 0)),
+    ]);
+
+    let signature = responses[0]
+        .get("result")
+        .unwrap()
+        .get("signatures")
+        .unwrap()
+        .as_array()
+        .unwrap()[0]
+        .get("label")
+        .and_then(Json::as_str)
+        .unwrap();
+    assert_eq!(signature, "swap ( b a -- a b )");
+
+    let edit = &responses[1].get("result").and_then(Json::as_array).unwrap()[0];
+    assert_eq!(edit.get("newText").and_then(Json::as_str), Some("push 3"));
+}
+
+#[test]
+fn stack_analysis_diagnostics() {
+    let open = |text: &str| {
+        format!(
+            r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"file:///virtual/main.tyl","text":"{text}"}}}}}}"#
+        )
+    };
+
+    let sent = messages(&[
+        open(r#"::main:\npush \"a\"\npush 1\nadd\nprint\n"#),
+        open(r#"::main:\nfrobnicate\n"#),
+        open(r#"::main:\npush 1\nprint\nexit\n"#),
+    ]);
+
+    let diagnostics: Vec<&[Json]> = sent
+        .iter()
+        .map(|message| {
+            assert_eq!(
+                message.get("method").and_then(Json::as_str),
+                Some("textDocument/publishDiagnostics")
+            );
+            message
+                .get("params")
+                .and_then(|params| params.get("diagnostics"))
+                .and_then(Json::as_array)
+                .unwrap()
+        })
+        .collect();
+
+    let line = |diagnostic: &Json| {
+        diagnostic
+            .get("range")
+            .and_then(|range| range.get("start"))
+            .and_then(|start| start.get("line"))
+            .and_then(Json::as_usize)
+            .unwrap()
+    };
+
+    // Adding a string to a number is caught before the program runs, pointing back at where
+    // the string came from
+    let mismatch = &diagnostics[0][0];
+    assert_eq!(line(mismatch), 3);
+    assert_eq!(mismatch.get("severity").and_then(Json::as_usize), Some(1));
+    let related = mismatch
+        .get("relatedInformation")
+        .and_then(Json::as_array)
+        .unwrap();
+    assert!(related.iter().any(|info| info
+        .get("location")
+        .is_some_and(|location| line(location) == 1)));
+
+    // Parse errors are reported on the line that caused them
+    assert_eq!(diagnostics[1].len(), 1);
+    assert_eq!(line(&diagnostics[1][0]), 1);
+
+    assert!(diagnostics[2].is_empty());
+}
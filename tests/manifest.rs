@@ -0,0 +1,107 @@
+//! Checks the `[[bin]]` entry points of a `toy.toml` and running a program from one
+
+use std::path::PathBuf;
+
+use toylang::{
+    manifest::{Bin, Manifest},
+    RunOptions, RuntimeError,
+};
+
+mod common;
+
+use common::{interpreter, SharedBuffer};
+
+#[test]
+fn bins_map_names_to_a_file_and_section() {
+    let manifest = Manifest::parse(
+        "[lint]\nallow = [\"magic-number\"]\n\n[[bin]]\nname = \"fizzbuzz\"\npath = \"exercises/basics.toy\"\nsection = \"fizzbuzz\"\n\n[[bin]]\nname = \"hello\" # the first exercise\npath = \"hello.toy\"\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        manifest.bins,
+        [
+            Bin {
+                name: "fizzbuzz".to_string(),
+                path: PathBuf::from("exercises/basics.toy"),
+                section: "fizzbuzz".to_string(),
+            },
+            Bin {
+                name: "hello".to_string(),
+                path: PathBuf::from("hello.toy"),
+                section: "main".to_string(),
+            },
+        ]
+    );
+    assert_eq!(manifest.bin("hello"), Some(&manifest.bins[1]));
+    assert_eq!(manifest.bin("missing"), None);
+}
+
+#[test]
+fn malformed_bins_are_rejected() {
+    let parse = |contents: &str| Manifest::parse(contents).unwrap_err();
+
+    assert_eq!(
+        parse("[[bin]]\npath = \"a.toy\"\n"),
+        "line 1: [[bin]] requires a name"
+    );
+    assert_eq!(
+        parse("\n[[bin]]\nname = \"a\"\n"),
+        "line 2: [[bin]] a requires a path"
+    );
+    assert_eq!(
+        parse("[[bin]]\nname = \"a\"\npath = \"a.toy\"\n[[bin]]\nname = \"a\"\npath = \"b.toy\"\n"),
+        "line 4: [[bin]] a is defined more than once"
+    );
+    assert_eq!(
+        parse("[[bin]]\nname = a\n"),
+        "line 2: name must be a quoted string"
+    );
+    assert_eq!(
+        parse("[[bin]]\nentry = \"a\"\n"),
+        "line 2: unknown setting: entry"
+    );
+}
+
+#[test]
+fn paths_are_relative_to_the_manifest() {
+    let dir = std::env::temp_dir().join(format!("toylang-manifest-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("toy.toml"),
+        "[[bin]]\nname = \"a\"\npath = \"ex/a.toy\"\n",
+    )
+    .unwrap();
+
+    let manifest = Manifest::load(&dir.join("toy.toml"));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(manifest.unwrap().bins[0].path, dir.join("ex/a.toy"));
+}
+
+#[test]
+fn runs_start_from_the_entry_section() {
+    let source = "::main:\npush \"main\"\nprint\nexit\n\n::other:\npush \"other\"\nprint\nexit\n";
+    let run = |entry: &str| {
+        let output = SharedBuffer::default();
+        let options = RunOptions {
+            entry: entry.to_string(),
+            ..RunOptions::default()
+        };
+        let mut interpreter = interpreter(source, options);
+        interpreter.set_output(output.clone());
+        let result = interpreter.run();
+
+        let printed = String::from_utf8(output.0.borrow().clone()).unwrap();
+        result.map(|_| printed)
+    };
+
+    assert_eq!(run("main"), Ok("main".to_string()));
+    assert_eq!(run("other"), Ok("other".to_string()));
+    assert_eq!(
+        run("missing"),
+        Err(RuntimeError::Instruction(
+            "No section named missing to start from".to_string()
+        ))
+    );
+}
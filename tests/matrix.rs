@@ -0,0 +1,61 @@
+//! Checks vector and matrix values and the arithmetic on them
+
+use toylang::Value;
+
+mod common;
+
+use common::run_main;
+
+fn run(source: &str) -> Result<Vec<String>, String> {
+    let stack = run_main(source)?;
+    Ok(stack.iter().map(Value::to_string).collect())
+}
+
+#[test]
+fn arithmetic_works_element_by_element() {
+    let vectors = "push 1\npush 2\npush 3\nvector 3\npush 10\npush 20\npush 30\nvector 3\n";
+
+    assert_eq!(
+        run(&format!("{vectors}add")),
+        Ok(vec!["[11, 22, 33]".to_string()])
+    );
+    assert_eq!(
+        run(&format!("{vectors}sub")),
+        Ok(vec!["[9, 18, 27]".to_string()])
+    );
+    assert_eq!(
+        run("push 1\npush 2.5\nvector 2\npush 2\nmul"),
+        Ok(vec!["[2, 5]".to_string()])
+    );
+
+    let error = run(&format!("{vectors}push 1\nvector 1\nadd")).unwrap_err();
+    assert!(
+        error.contains("Cannot add matrices of different sizes, 1x1 and 1x3"),
+        "{error}"
+    );
+}
+
+#[test]
+fn matmul_multiplies_the_top_matrix_by_the_second() {
+    // The vector (1, 1) times the rotation [[0, 1], [1, 0]]
+    let source = "push 0\npush 1\npush 1\npush 0\nmatrix 2 2\npush 1\npush 1\nvector 2\nmatmul\n";
+    assert_eq!(run(source), Ok(vec!["[1, 1]".to_string()]));
+
+    let source =
+        "push 1\npush 2\npush 3\npush 4\npush 5\npush 6\nmatrix 2 3\ndup\ntranspose\nmatmul\n";
+    assert_eq!(
+        run(source),
+        Ok(vec![
+            "[[17, 22, 27], [22, 29, 36], [27, 36, 45]]".to_string()
+        ])
+    );
+
+    let source = "push 1\npush 2\npush 3\npush 4\npush 5\npush 6\nmatrix 2 3\npush 1\npush 2\nmget";
+    assert_eq!(run(source), Ok(vec!["6".to_string()]));
+
+    let error = run("push 1\npush 2\nvector 2\ndup\nmatmul").unwrap_err();
+    assert!(
+        error.contains("Cannot multiply a 1x2 matrix by a 1x2 matrix"),
+        "{error}"
+    );
+}
@@ -0,0 +1,16 @@
+//! Checks that `meminfo` counts what the program is holding on to
+
+use toylang::Value;
+
+mod common;
+
+use common::run_main;
+
+#[test]
+fn heap_cells_are_counted_across_the_stack_and_variables() {
+    let stack = run_main("push 1\npush \"a\"\nlist\npush \"b\"\nappend\nmeminfo").unwrap();
+    assert_eq!(stack[3..], [Value::Int(3), Value::Int(3), Value::Int(0)]);
+
+    let stack = run_main("push \"kept\"\nstore text\npush 2\nmeminfo").unwrap();
+    assert_eq!(stack[1..], [Value::Int(1), Value::Int(1), Value::Int(1)]);
+}
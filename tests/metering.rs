@@ -0,0 +1,54 @@
+//! Checks that metered programs stop once their budget is spent
+
+use toylang::{Metering, RunOptions, RuntimeError};
+
+mod common;
+
+use common::interpreter;
+
+fn run(source: &str, metering: Metering) -> (Result<(), RuntimeError>, u64) {
+    let options = RunOptions {
+        metering: Some(metering),
+        ..RunOptions::default()
+    };
+    let mut interpreter = interpreter(source, options);
+    let result = interpreter.run();
+
+    (result, interpreter.spent())
+}
+
+#[test]
+fn instructions_are_charged_by_mnemonic() {
+    let source = "::main:\npush 1\npush 2\nadd\ndrop\nexit\n";
+
+    let mut metering = Metering::new(100);
+    metering.costs.insert("add".to_string(), 10);
+    assert_eq!(run(source, metering), (Ok(()), 14));
+
+    // Runs are charged the same way every time
+    assert_eq!(run(source, Metering::new(5)), (Ok(()), 5));
+}
+
+#[test]
+fn running_out_stops_before_the_instruction() {
+    let source = "::main:\npush 1\npush 2\nadd\nexit\n";
+
+    let mut metering = Metering::new(12);
+    metering.costs.insert("add".to_string(), 11);
+    let (result, spent) = run(source, metering);
+
+    assert_eq!(spent, 2);
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "Budget exhausted: spent 2 of 12, not enough to run `add`"
+    );
+
+    // An instruction that costs more than the budget can never run
+    let mut metering = Metering::new(1000);
+    metering.costs.insert("push".to_string(), u64::MAX);
+    metering.default_cost = 0;
+    assert!(matches!(
+        run(source, metering).0,
+        Err(RuntimeError::BudgetExhausted { spent: 0, .. })
+    ));
+}
@@ -0,0 +1,73 @@
+//! Checks that failing programs shrink to the lines that make them fail
+
+use toylang::{
+    conformance::Limits,
+    minimize::{minimize, Check},
+};
+
+const FAILING: &str = "\
+# Adds a String to an Int somewhere along the way
+::main:
+push 1
+push 2
+add
+print
+call helper
+push \"done\"
+print
+exit
+
+::helper:
+push 3
+push \"three\"
+add
+ret
+
+::unused:
+push 4
+print
+ret
+";
+
+#[test]
+fn programs_shrink_while_they_still_fail() {
+    let check: Check = "error contains Cannot add".parse().unwrap();
+    let minimized = minimize(FAILING, &check, Limits::default()).unwrap();
+
+    assert_eq!(
+        minimized.source,
+        "call helper\n::helper:\npush 3\npush \"three\"\nadd\n"
+    );
+    assert!(minimized.runs > 1);
+}
+
+#[test]
+fn other_checks_keep_the_original_error() {
+    let minimized = minimize(FAILING, &Check::ExitsNonzero, Limits::default()).unwrap();
+
+    // An empty program exits nonzero too, but with a different error
+    assert_eq!(
+        minimized.source,
+        "call helper\n::helper:\npush 3\npush \"three\"\nadd\n"
+    );
+}
+
+#[test]
+fn programs_that_pass_are_rejected() {
+    let error = minimize("push 1\nprint\n", &Check::ExitsNonzero, Limits::default());
+    assert_eq!(
+        error,
+        Err("The program does not fail the check `exits nonzero`".to_string())
+    );
+}
+
+#[test]
+fn checks_are_parsed() {
+    assert_eq!("exits nonzero".parse(), Ok(Check::ExitsNonzero));
+    assert_eq!("exits 2".parse(), Ok(Check::Exits(2)));
+    assert_eq!(
+        "stdout contains \" a \"".parse(),
+        Ok(Check::StdoutContains(" a ".to_string()))
+    );
+    assert!("crashes".parse::<Check>().is_err());
+}
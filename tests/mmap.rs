@@ -0,0 +1,24 @@
+//! Checks that mapped programs read the same as programs read from disk
+
+use std::path::Path;
+
+use toylang::mmap::Mmap;
+
+#[test]
+fn mapped_source_matches_the_file() {
+    let path = Path::new(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/samples/fizz_buzz.tyl"
+    ));
+
+    // SAFETY: nothing writes to the samples while the tests run
+    let Ok(mapped) = (unsafe { Mmap::open(path) }) else {
+        // Memory maps are not available on every platform
+        return;
+    };
+
+    assert_eq!(
+        mapped.as_str().unwrap(),
+        std::fs::read_to_string(path).unwrap()
+    );
+}
@@ -0,0 +1,52 @@
+//! Checks that programs are stopped once they print more than their output limit
+
+use toylang::{RunOptions, RuntimeError};
+
+mod common;
+
+use common::{interpreter, SharedBuffer};
+
+fn run(source: &str, limit: usize) -> (String, Result<(), RuntimeError>) {
+    let options = RunOptions {
+        max_output_bytes: Some(limit),
+        ..RunOptions::default()
+    };
+    let output = SharedBuffer::default();
+
+    let mut interpreter = interpreter(source, options);
+    interpreter.set_output(output.clone());
+    let result = interpreter.run();
+
+    let printed = String::from_utf8(output.0.take()).unwrap();
+    (printed, result)
+}
+
+#[test]
+fn printing_in_a_loop_is_stopped() {
+    let (printed, result) = run("::main:\npush \"hello \"\nprint\njump main\n", 16);
+
+    assert_eq!(printed, "hello hello hell");
+    assert_eq!(
+        result,
+        Err(RuntimeError::OutputLimit {
+            limit: 16,
+            truncated: 2
+        })
+    );
+}
+
+#[test]
+fn output_within_the_limit_is_untouched() {
+    let (printed, result) = run("::main:\npush \"héllo\"\nprint\nexit\n", 6);
+
+    assert_eq!(printed, "héllo");
+    assert_eq!(result, Ok(()));
+
+    // Characters are never split, even if that leaves part of the limit unused
+    let (printed, result) = run("::main:\npush \"héllo\"\nprint\nexit\n", 2);
+    assert_eq!(printed, "h");
+    assert!(matches!(
+        result,
+        Err(RuntimeError::OutputLimit { truncated: 5, .. })
+    ));
+}
@@ -0,0 +1,98 @@
+//! Checks packed programs hide their names and comments but still run the same
+
+use std::fs;
+
+use toylang::{
+    pack::{pack, to_source},
+    try_parse, try_validate, RunOptions, Value,
+};
+
+mod common;
+
+const PROGRAM: &str = r#"// Counts down through the traffic lights
+#states Light RED GREEN
+const answers = [42, 7]
+
+::main:
+getconst answers 0
+store secret
+push GREEN
+switch Light
+
+::RED:
+load secret
+pushdata greeting
+exit
+
+::GREEN:
+call decrement
+push RED
+switch Light
+
+::decrement:
+#pre depth>=0
+load secret
+push 1
+sub
+store secret
+ret
+
+::data greeting:
+Hello
+"#;
+
+fn run(source: &str) -> Vec<Value> {
+    common::run(source).unwrap()
+}
+
+#[test]
+fn names_and_comments_are_replaced() {
+    let packed = to_source(&pack(PROGRAM, &RunOptions::default()).unwrap());
+
+    for hidden in [
+        "Counts down",
+        "Light",
+        "answers",
+        "secret",
+        "decrement",
+        "greeting",
+        "RED",
+    ] {
+        assert!(!packed.contains(hidden), "{hidden} is left in:\n{packed}");
+    }
+
+    assert!(packed.contains("::main:\n"));
+    assert!(packed.contains("#states g0 s0 s1\n"));
+    assert!(packed.contains("const c0 = [42, 7]\n"));
+}
+
+#[test]
+fn packed_programs_run_the_same() {
+    let packed = to_source(&pack(PROGRAM, &RunOptions::default()).unwrap());
+
+    assert_eq!(run(&packed), run(PROGRAM));
+    assert_eq!(
+        run(&packed),
+        vec![Value::Int(-41), Value::String("Hello\n".to_string())]
+    );
+}
+
+#[test]
+fn packed_samples_are_valid() {
+    let options = RunOptions::default();
+
+    for entry in fs::read_dir("samples").unwrap() {
+        let source = fs::read_to_string(entry.unwrap().path()).unwrap();
+        let Ok(program) = pack(&source, &options) else {
+            continue;
+        };
+
+        let packed = try_parse(&to_source(&program), &options).unwrap();
+        assert_eq!(try_validate(&packed), Ok(()));
+    }
+}
+
+#[test]
+fn invalid_programs_are_not_packed() {
+    assert!(pack("::main:\njump nowhere\n", &RunOptions::default()).is_err());
+}
@@ -0,0 +1,113 @@
+//! Checks that invalid programs are reported with the line they fail on
+
+use toylang::{fallthrough_warnings, try_parse, try_validate, RunOptions, Value};
+
+mod common;
+
+use common::{interpreter, run};
+
+#[test]
+fn parse_errors_point_at_the_offending_line() {
+    let options = RunOptions::default();
+    let error = try_parse("::main:\npush 1\n  frob 2\nexit\n", &options).unwrap_err();
+
+    assert_eq!(error.line, Some(3));
+    assert_eq!(error.instruction.as_deref(), Some("frob 2"));
+    assert_eq!(error.message, "Unknown instruction:   frob 2");
+
+    let error = error.in_file("program.tyl");
+    assert_eq!(
+        error.to_string(),
+        "program.tyl:3: Unknown instruction:   frob 2\n    3 | frob 2"
+    );
+
+    // Duplicate sections are reported where they start, and unclosed conditions where
+    // they open, rather than wherever the parser noticed
+    let error = try_parse("::main:\nexit\n::main:\nexit\n::other:\n", &options).unwrap_err();
+    assert_eq!(error.line, Some(3));
+
+    let error = try_parse("::main:\n#if DEBUG\nexit\n", &options).unwrap_err();
+    assert_eq!(error.line, Some(2));
+    assert_eq!(error.message, "#if without matching #endif");
+
+    let error = try_parse("::main:\npush 99999999999999999999\n", &options).unwrap_err();
+    assert_eq!(error.line, Some(2));
+}
+
+#[test]
+fn sections_are_only_redefined_with_override() {
+    let program = "::main:\ncall helper\nexit\n\n::helper:\npush 1\nret\n\n";
+    assert_eq!(
+        run(&format!("{program}override ::helper:\npush 2\nret\n")),
+        Ok(vec![Value::Int(2)])
+    );
+
+    let options = RunOptions::default();
+    let error = try_parse(&format!("{program}::helper:\npush 2\nret\n"), &options).unwrap_err();
+    assert_eq!(error.line, Some(9));
+    assert_eq!(
+        error.message,
+        "Duplicate section: helper. Use `override ::helper:` to redefine it"
+    );
+
+    let error = try_parse("::main:\nexit\noverride push 1\n", &options).unwrap_err();
+    assert_eq!(
+        error.message,
+        "override must be followed by a section: override push 1"
+    );
+}
+
+#[test]
+fn malformed_byte_literals_are_errors() {
+    let options = RunOptions::default();
+
+    let error = try_parse("::main:\npush x\"\n", &options).unwrap_err();
+    assert_eq!(error.message, "Invalid value: x\"");
+
+    let error = try_parse("::main:\npush x\"aé1\"\n", &options).unwrap_err();
+    assert_eq!(error.message, "Invalid hex digits in byte literal: aé1");
+
+    let error = try_parse("::main:\npush x\"0g\"\n", &options).unwrap_err();
+    assert_eq!(error.message, "Invalid hex digits in byte literal: 0g");
+}
+
+#[test]
+fn validation_errors_name_the_program() {
+    let options = RunOptions::default();
+    let program = try_parse("::main:\njump nowhere\n", &options).unwrap();
+    let error = try_validate(&program).unwrap_err().in_file("program.tyl");
+
+    assert_eq!(error.line, None);
+    assert_eq!(
+        error.to_string(),
+        "program.tyl: jump found to unknown label: nowhere (in section main)"
+    );
+}
+
+#[test]
+fn sections_that_look_like_they_fall_through_are_warned_about() {
+    let options = RunOptions::default();
+    let program = try_parse(
+        "::main:\npush 1\n\n::next:\nret\n\n::end:\nexit\n",
+        &options,
+    )
+    .unwrap();
+
+    try_validate(&program).unwrap();
+    assert_eq!(
+        fallthrough_warnings(&program),
+        ["section main does not end with exit, jump or ret and will not fall through into next"]
+    );
+}
+
+#[test]
+fn runtime_errors_are_located_in_the_source() {
+    let source = "::main:\npush 1\ncall fail\nexit\n\n::fail:\npush \"a\"\nadd\nret\n";
+    let mut interpreter = interpreter(source, RunOptions::default());
+
+    assert!(interpreter.run().is_err());
+
+    let location = interpreter.error_location(source).unwrap();
+    assert_eq!(location.line, 8);
+    assert_eq!(location.instruction, "add");
+}
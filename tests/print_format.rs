@@ -0,0 +1,54 @@
+//! Checks how `setfmt` and `print <format>` lay out printed values
+
+use toylang::{PrintFormat, RunOptions, Value};
+
+mod common;
+
+use common::{interpreter, SharedBuffer};
+
+fn format(spec: &str, value: Value) -> String {
+    PrintFormat::parse(spec).unwrap().apply(&value)
+}
+
+#[test]
+fn formats() {
+    assert_eq!(format("", Value::Float(-0.5)), "-0.5");
+    assert_eq!(format(",", Value::Int(1234567)), "1,234,567");
+    assert_eq!(format(">8,.2", Value::Float(1234.5)), "1,234.50");
+    assert_eq!(format("10,.2", Value::Int(1234)), "  1,234.00");
+    assert_eq!(format("06", Value::Float(-1.5)), "-001.5");
+    assert_eq!(format("*^7", Value::String("ab".to_string())), "**ab***");
+    assert_eq!(format("5", Value::String("ab".to_string())), "ab   ");
+
+    for spec in ["", "<3", "*^10", "08,.2", ">12,"] {
+        assert_eq!(PrintFormat::parse(spec).unwrap().to_string(), spec);
+    }
+
+    assert!(PrintFormat::parse("8x").is_err());
+    assert!(PrintFormat::parse(".").is_err());
+}
+
+#[test]
+fn setfmt_applies_until_changed_and_print_overrides_it() {
+    let source = "::main:
+push \">6,\"
+setfmt
+push 1000
+print
+push 5
+print <3
+push \"\"
+setfmt
+push 7
+print
+exit
+";
+    let options = RunOptions::default();
+    let output = SharedBuffer::default();
+
+    let mut interpreter = interpreter(source, options);
+    interpreter.set_output(output.clone());
+    interpreter.run().unwrap();
+
+    assert_eq!(String::from_utf8(output.0.take()).unwrap(), " 1,0005  7");
+}
@@ -0,0 +1,52 @@
+//! Checks the tables `printtable` renders
+
+use toylang::RunOptions;
+
+mod common;
+
+use common::{interpreter, SharedBuffer};
+
+fn run(source: &str) -> Result<String, String> {
+    let options = RunOptions::default();
+    let output = SharedBuffer::default();
+
+    let mut interpreter = interpreter(source, options);
+    interpreter.set_output(output.clone());
+    interpreter.run().map_err(|error| error.to_string())?;
+
+    Ok(String::from_utf8(output.0.take()).unwrap())
+}
+
+#[test]
+fn rows_are_aligned_under_a_header() {
+    let table = run("::main:
+push \"name,qty\\nwidget,3\\nsprocket\\n\"
+csvparse
+printtable
+exit
+")
+    .unwrap();
+
+    assert_eq!(
+        table,
+        "\
++----------+-----+
+| name     | qty |
++----------+-----+
+| widget   | 3   |
+| sprocket |     |
++----------+-----+
+"
+    );
+}
+
+#[test]
+fn rows_must_be_lists() {
+    let error = run("::main:\npush \"a,b\\n\"\ncsvparse\nprinttable\npush 1\nprinttable\nexit\n")
+        .unwrap_err();
+
+    assert!(
+        error.contains("printtable requires a List of rows"),
+        "{error}"
+    );
+}
@@ -0,0 +1,56 @@
+//! Checks reading lines of stdin as Strings, Ints and Floats
+
+use std::io::Cursor;
+
+use toylang::{RunOptions, RuntimeError, Value};
+
+mod common;
+
+use common::interpreter;
+
+fn run(source: &str, input: &str) -> Result<Vec<Value>, RuntimeError> {
+    let mut interpreter = interpreter(source, RunOptions::default());
+    interpreter.set_input(Cursor::new(input.as_bytes().to_vec()));
+    interpreter.call_section("main", Vec::new())
+}
+
+#[test]
+fn lines_are_read_as_values() {
+    let source = "::main:\nread\nreadint\nreadfloat\nreadfloat\n";
+
+    assert_eq!(
+        run(source, "hello world\r\n -42 \n2.5\n3"),
+        Ok(vec![
+            Value::String("hello world".to_string()),
+            Value::Int(-42),
+            Value::Float(2.5),
+            Value::Float(3.0),
+        ])
+    );
+}
+
+#[test]
+fn malformed_input_is_an_error() {
+    assert_eq!(
+        run("::main:\nreadint\n", "4.5\n"),
+        Err(RuntimeError::Instruction(
+            "readint: cannot read \"4.5\" as an Int".to_string()
+        ))
+    );
+    assert_eq!(
+        run("::main:\nreadfloat\n", "four\n"),
+        Err(RuntimeError::Instruction(
+            "readfloat: cannot read \"four\" as a Float".to_string()
+        ))
+    );
+}
+
+#[test]
+fn reading_past_the_end_is_an_error() {
+    assert_eq!(
+        run("::main:\nread\nread\n", "only\n"),
+        Err(RuntimeError::Instruction(
+            "read: no more input to read".to_string()
+        ))
+    );
+}
@@ -0,0 +1,42 @@
+//! Checks the rewrites made by `toylang refactor`
+
+use toylang::refactor::{extract_section, rename_section};
+
+#[test]
+fn rename_section_updates_every_file() {
+    let sources = [
+        "::main:\n// jump old\njump old\n".to_string(),
+        "::old:\nifjmp old\nexit\n\noverride ::old:\nexit\n".to_string(),
+    ];
+
+    let (renamed, changed) = rename_section(&sources, "old", "new").unwrap();
+
+    assert_eq!(renamed[0], "::main:\n// jump old\njump new\n");
+    assert_eq!(
+        renamed[1],
+        "::new:\nifjmp new\nexit\n\noverride ::new:\nexit\n"
+    );
+    assert_eq!(changed, 4);
+}
+
+#[test]
+fn rename_section_refuses_collisions() {
+    let sources = ["::main:\njump other\n::other:\nexit\n".to_string()];
+
+    assert!(rename_section(&sources, "other", "main").is_err());
+    assert!(rename_section(&sources, "missing", "new").is_err());
+}
+
+#[test]
+fn extract_section_moves_a_straight_run_of_instructions() {
+    let source = "::main:\npush 2\npush 3\n// multiply\nmul\nprint\nexit\n";
+
+    let (extracted, effect) = extract_section(source, 3..=5, "times_three").unwrap();
+
+    assert_eq!(
+        extracted,
+        "::main:\npush 2\ncall times_three\nprint\nexit\n\n::times_three:\n// ( 1 -- 1 )\npush 3\n// multiply\nmul\n"
+    );
+    assert_eq!(effect, (1, 1));
+    assert!(extract_section(source, 2..=7, "rest").is_err());
+}
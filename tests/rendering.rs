@@ -0,0 +1,41 @@
+//! Pins down how values and stacks are rendered, which embedders rely on staying the same
+
+use toylang::{Complex, Matrix, Stack, Value};
+
+#[test]
+fn values_render_distinctly_by_type() {
+    let cases = [
+        (Value::Int(-5), "-5"),
+        (Value::Float(2.0), "2.0"),
+        (Value::Float(1e-7), "1e-7"),
+        (Value::Bool(true), "true"),
+        (Value::Complex(Complex::new(1.5, -2.0)), "1.5-2i"),
+        (Value::String("a \"b\"\n".to_string()), r#""a \"b\"\n""#),
+        (Value::Bytes(vec![0x0a, 0xff]), r#"x"0aff""#),
+        (Value::Handle(3), "<file 3>"),
+        (
+            Value::List(vec![Value::Int(1), Value::String("a".to_string())]),
+            r#"[1, "a"]"#,
+        ),
+        (
+            Value::Matrix(Matrix::new(2, 2, vec![1.0, 2.0, 3.0, 4.0]).unwrap()),
+            "matrix[[1.0, 2.0], [3.0, 4.0]]",
+        ),
+    ];
+
+    for (value, rendered) in cases {
+        assert_eq!(value.display(), rendered);
+    }
+}
+
+#[test]
+fn stacks_render_bottom_first() {
+    let stack = Stack::from(vec![
+        Value::Int(1),
+        Value::String("a".to_string()),
+        Value::Float(2.5),
+    ]);
+
+    assert_eq!(stack.render(), r#"[1, "a", 2.5]"#);
+    assert_eq!(Stack::new().render(), "[]");
+}
@@ -0,0 +1,63 @@
+//! Checks that replaying a recorded run feeds the program the same input and clock readings
+
+use std::io::Cursor;
+
+use toylang::{DataType, Replay, ReplayEvent, RunOptions};
+
+mod common;
+
+use common::interpreter;
+
+const PROGRAM: &str = "::main:\ntick\nreadline\ndrop\nsetglobal line\ntock\nsetglobal elapsed";
+
+#[test]
+fn replay_reproduces_a_recorded_run() {
+    let path = std::env::temp_dir().join(format!("toylang-replay-{}.json", std::process::id()));
+
+    let options = RunOptions {
+        record: Some(path.clone()),
+        ..RunOptions::default()
+    };
+    let mut recorded = interpreter(PROGRAM, options);
+    recorded.set_input(Cursor::new(b"hello\nworld\n".to_vec()));
+    recorded.run().unwrap();
+
+    let replay = Replay::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let Some(&DataType::Int(elapsed)) = recorded.global("elapsed") else {
+        panic!("tock did not push an Int");
+    };
+    assert_eq!(
+        replay.events,
+        vec![
+            ReplayEvent::Input("hello\n".to_string()),
+            ReplayEvent::Elapsed(elapsed as usize)
+        ]
+    );
+
+    let options = RunOptions {
+        replay: Some(replay),
+        ..RunOptions::default()
+    };
+    let mut replayed = interpreter(PROGRAM, options);
+    replayed.set_input(Cursor::new(b"something else\n".to_vec()));
+    replayed.run().unwrap();
+
+    assert_eq!(replayed.global("line"), recorded.global("line"));
+    assert_eq!(replayed.global("elapsed"), recorded.global("elapsed"));
+}
+
+#[test]
+fn replay_fails_once_the_run_diverges() {
+    let options = RunOptions {
+        replay: Some(Replay {
+            events: vec![ReplayEvent::Elapsed(5)],
+        }),
+        ..RunOptions::default()
+    };
+    let mut interpreter = interpreter("readall", options);
+
+    let error = interpreter.run().unwrap_err();
+    assert!(error.to_string().contains("no longer matches the replay"));
+}
@@ -0,0 +1,89 @@
+//! Running untrusted programs within limits, as a playground would
+
+use toylang::{run_sandboxed, run_source, Limits};
+
+#[test]
+fn runs_within_limits() {
+    let result = run_sandboxed("::main:\npush \"hello\"\nprint\nexit\n", Limits::default());
+
+    assert_eq!(result.output, "hello");
+    assert_eq!(result.truncated, 0);
+    assert_eq!(result.error, None);
+}
+
+#[test]
+fn stops_runaway_programs() {
+    let limits = Limits {
+        steps: 1000,
+        memory: 1024,
+        output_bytes: 10,
+    };
+
+    // Prints forever, so the run ends once the output limit is reached
+    let result = run_sandboxed("::main:\npush \"abc\"\nprint\njump main\n", limits);
+    assert_eq!(result.output, "abcabcabca");
+    assert_eq!(result.truncated, 2);
+    assert!(result.error.unwrap().contains("Output limit"));
+
+    let result = run_sandboxed("::main:\njump main\n", limits);
+    assert!(result.error.unwrap().contains("Step limit"));
+
+    let result = run_sandboxed("::main:\npush 1\ndup\njump main\n", limits);
+    assert!(result.error.unwrap().contains("Out of memory"));
+}
+
+#[test]
+fn reports_errors_instead_of_panicking() {
+    let result = run_sandboxed("::main:\nfrobnicate\n", Limits::default());
+    assert!(result.error.is_some());
+
+    let result = run_sandboxed(
+        "::main:\npush \"/etc/passwd\"\npush \"r\"\nfopen\nexit\n",
+        Limits::default(),
+    );
+    assert!(result.error.unwrap().contains("file access is disabled"));
+}
+
+#[test]
+fn frames_cannot_hold_up_a_sandboxed_run() {
+    let result = run_sandboxed("::main:\nframe 1\nframe 1\nexit\n", Limits::default());
+    assert_eq!(
+        result.error.as_deref(),
+        Some("frame is disabled in this run")
+    );
+}
+
+#[test]
+fn run_source_returns_the_output_and_then_the_error() {
+    assert_eq!(
+        run_source("::main:\npush \"hello\"\nprint\nexit\n"),
+        "hello"
+    );
+    assert_eq!(
+        run_source("::main:\npush \"hello\"\nprint\nreadint\n"),
+        "hello\nerror: readint: no more input to read"
+    );
+    assert!(run_source("::main:\nfrobnicate\n").starts_with("error: line 2: Unknown instruction"));
+}
+
+// wasm32 builds abort on a panic instead of unwinding, so the playground relies on bad
+// programs being reported as errors rather than caught. These used to panic
+#[test]
+fn malformed_programs_are_errors_without_unwinding() {
+    assert_eq!(
+        run_source("::main:\npush x\"\n"),
+        "error: line 2: Invalid value: x\"\n    2 | push x\""
+    );
+    assert_eq!(
+        run_source("::main:\npush x\"aé1\"\n"),
+        "error: line 2: Invalid hex digits in byte literal: aé1\n    2 | push x\"aé1\""
+    );
+    assert_eq!(
+        run_source("::main:\npush \"a,\"bc\"\ncsvparse\nexit\n"),
+        "error: csvparse: Unterminated quoted field in CSV"
+    );
+    assert_eq!(
+        run_source("::main:\nlist\npush 1\nappend\ncsvemit\nexit\n"),
+        "error: csvemit rows must be Lists, found Int"
+    );
+}
@@ -0,0 +1,29 @@
+//! Checks that Ints can be negative and that arithmetic on them reports overflow
+
+use toylang::Value;
+
+mod common;
+
+use common::run_main;
+
+#[test]
+fn arithmetic_can_go_below_zero() {
+    assert_eq!(run_main("push -5"), Ok(vec![Value::Int(-5)]));
+    assert_eq!(run_main("push 5\npush 3\nsub"), Ok(vec![Value::Int(-2)]));
+    assert_eq!(run_main("push -3\npush 4\nmul"), Ok(vec![Value::Int(-12)]));
+    assert_eq!(run_main("push 2\npush -7\ndiv"), Ok(vec![Value::Int(-3)]));
+    assert_eq!(run_main("push 2\npush -7\nmod"), Ok(vec![Value::Int(-1)]));
+    assert_eq!(run_main("push 0\npush -1\nlt"), Ok(vec![Value::Bool(true)]));
+}
+
+#[test]
+fn overflow_and_division_by_zero_fail() {
+    let error = run_main("push 1\npush 9223372036854775807\nadd").unwrap_err();
+    assert!(error.contains("Integer overflow"), "{error}");
+
+    let error = run_main("push 0\npush 1\nmod").unwrap_err();
+    assert!(error.contains("Cannot divide by zero"), "{error}");
+
+    let error = run_main("push x\"00ff\"\npush -1\nbyteat").unwrap_err();
+    assert!(error.contains("Byte index -1 out of bounds"), "{error}");
+}
@@ -0,0 +1,91 @@
+//! Runs every program in `samples/` through the library and compares what it prints with
+//! the snapshot of the same name in `tests/snapshots/`. Run with `UPDATE_SNAPSHOTS=1` to
+//! write the current output as the new snapshots after an intentional change.
+
+use std::{
+    panic::AssertUnwindSafe,
+    path::{Path, PathBuf},
+};
+
+use toylang::{parse, validate, Interpreter, RunOptions};
+
+mod common;
+
+use common::SharedBuffer;
+
+/// Runs a program and returns its output, followed by the error it stopped with if any
+fn run(source: &str) -> String {
+    let output = SharedBuffer::default();
+
+    // Parse errors are still reported by panicking
+    let program = std::panic::catch_unwind(AssertUnwindSafe(|| {
+        let program = parse(source, &RunOptions::default());
+        validate(&program);
+        program
+    }));
+
+    let program = match program {
+        Ok(program) => program,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<String>()
+                .cloned()
+                .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_default();
+
+            return format!("error: {message}\n");
+        }
+    };
+
+    let mut interpreter = Interpreter::new(program, RunOptions::default());
+    interpreter.set_output(output.clone());
+
+    let result = interpreter.run();
+    let mut printed = String::from_utf8(output.0.borrow().clone()).unwrap();
+
+    if let Err(error) = result {
+        printed.push_str(&format!("error: {error}\n"));
+    }
+
+    printed
+}
+
+fn samples() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("samples");
+    let mut samples: Vec<PathBuf> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "tyl"))
+        .collect();
+
+    samples.sort();
+    samples
+}
+
+#[test]
+fn samples_match_snapshots() {
+    let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+    let snapshots = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots");
+    let mut failures = Vec::new();
+
+    for sample in samples() {
+        let name = sample.file_stem().unwrap().to_string_lossy().to_string();
+        let snapshot = snapshots.join(format!("{name}.out"));
+        let actual = run(&std::fs::read_to_string(&sample).unwrap());
+
+        if update {
+            std::fs::write(&snapshot, &actual).unwrap();
+            continue;
+        }
+
+        match std::fs::read_to_string(&snapshot) {
+            Ok(expected) if expected == actual => {}
+            Ok(expected) => failures.push(format!(
+                "{name}: output changed\n--- expected\n{expected}\n--- actual\n{actual}"
+            )),
+            Err(_) => failures.push(format!("{name}: no snapshot at {}", snapshot.display())),
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}
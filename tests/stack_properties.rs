@@ -0,0 +1,176 @@
+//! Property tests for the pure stack instructions. Random programs are run through the
+//! interpreter and checked against a simple model of what each instruction should do.
+
+use toylang::{DataType, Instructions, Interpreter, RunOptions, SectionName};
+
+const CASES: usize = 256;
+
+/// Small deterministic generator so failures can be reproduced from the printed seed
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        // xorshift64
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    fn stack(&mut self, max_depth: usize) -> Vec<DataType> {
+        (0..self.below(max_depth + 1))
+            .map(|_| DataType::Int(self.below(1000) as i64))
+            .collect()
+    }
+}
+
+/// Runs `instructions` as a section on top of `stack` and returns the resulting stack
+fn run(
+    instructions: Vec<Instructions>,
+    stack: Vec<DataType>,
+) -> Result<Vec<DataType>, toylang::RuntimeError> {
+    let mut interpreter = Interpreter::new(Vec::new(), RunOptions::default());
+    interpreter.define_section(SectionName("ops".to_string()), instructions);
+    interpreter.call_section("ops", stack)
+}
+
+/// What each instruction should do to the stack, or `None` if it should underflow
+fn model(instruction: &Instructions, stack: &mut Vec<DataType>) -> Option<()> {
+    match instruction {
+        Instructions::Push(value) => stack.push(value.clone()),
+        Instructions::Dup => stack.push(stack.last()?.clone()),
+        // Dropping from an empty stack is allowed and does nothing
+        Instructions::Drop => {
+            stack.pop();
+        }
+        Instructions::Swap => {
+            let len = stack.len();
+            if len < 2 {
+                return None;
+            }
+            stack.swap(len - 1, len - 2);
+        }
+        Instructions::Over => stack.push(stack.get(stack.len().checked_sub(2)?)?.clone()),
+        Instructions::Rot => {
+            let len = stack.len();
+            if len < 3 {
+                return None;
+            }
+            stack[len - 3..].rotate_left(1);
+        }
+        _ => unreachable!("not a stack instruction: {instruction}"),
+    }
+
+    Some(())
+}
+
+fn random_op(rng: &mut Rng) -> Instructions {
+    match rng.below(6) {
+        0 => Instructions::Push(DataType::Int(rng.below(1000) as i64)),
+        1 => Instructions::Dup,
+        2 => Instructions::Drop,
+        3 => Instructions::Swap,
+        4 => Instructions::Over,
+        _ => Instructions::Rot,
+    }
+}
+
+/// Runs `property` against `CASES` generators with distinct seeds
+fn check(property: impl Fn(&mut Rng)) {
+    for case in 0..CASES as u64 {
+        let seed = 0x9e37_79b9_7f4a_7c15 ^ case;
+        let mut rng = Rng(seed);
+
+        if std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| property(&mut rng))).is_err() {
+            panic!("property failed for seed {seed:#x}");
+        }
+    }
+}
+
+#[test]
+fn dup_increases_depth_by_one() {
+    check(|rng| {
+        let mut stack = rng.stack(8);
+        stack.push(DataType::Int(rng.below(1000) as i64));
+
+        let result = run(vec![Instructions::Dup], stack.clone()).unwrap();
+
+        assert_eq!(result.len(), stack.len() + 1);
+        assert_eq!(result[result.len() - 1], result[result.len() - 2]);
+        assert_eq!(result[..stack.len()], stack[..]);
+    });
+}
+
+#[test]
+fn drop_decreases_depth_by_one() {
+    check(|rng| {
+        let mut stack = rng.stack(8);
+        stack.push(DataType::Int(rng.below(1000) as i64));
+
+        let result = run(vec![Instructions::Drop], stack.clone()).unwrap();
+
+        assert_eq!(result[..], stack[..stack.len() - 1]);
+    });
+}
+
+#[test]
+fn swap_twice_is_identity() {
+    check(|rng| {
+        let mut stack = rng.stack(8);
+        stack.extend([
+            DataType::Int(rng.below(1000) as i64),
+            DataType::Int(rng.below(1000) as i64),
+        ]);
+
+        let result = run(vec![Instructions::Swap, Instructions::Swap], stack.clone()).unwrap();
+
+        assert_eq!(result, stack);
+    });
+}
+
+#[test]
+fn rot_three_times_is_identity() {
+    check(|rng| {
+        let mut stack = rng.stack(8);
+        stack.extend((0..3).map(|_| DataType::Int(rng.below(1000) as i64)));
+
+        let result = run(vec![Instructions::Rot; 3], stack.clone()).unwrap();
+
+        assert_eq!(result, stack);
+    });
+}
+
+#[test]
+fn dup_drop_is_identity() {
+    check(|rng| {
+        let mut stack = rng.stack(8);
+        stack.push(DataType::Int(rng.below(1000) as i64));
+
+        let result = run(vec![Instructions::Dup, Instructions::Drop], stack.clone()).unwrap();
+
+        assert_eq!(result, stack);
+    });
+}
+
+#[test]
+fn random_sequences_match_model() {
+    check(|rng| {
+        let stack = rng.stack(4);
+        let ops: Vec<Instructions> = (0..rng.below(32)).map(|_| random_op(rng)).collect();
+
+        let mut expected = stack.clone();
+        let underflows = ops.iter().any(|op| model(op, &mut expected).is_none());
+
+        let result = run(ops.clone(), stack.clone());
+
+        if underflows {
+            assert!(result.is_err(), "expected underflow running {ops:?} on {stack:?}");
+        } else {
+            assert_eq!(result.unwrap(), expected, "running {ops:?} on {stack:?}");
+        }
+    });
+}
@@ -0,0 +1,21 @@
+//! Checks the figures reported by `toylang stats`
+
+use toylang::{parse, stats::Stats, RunOptions};
+
+#[test]
+fn counts_opcodes_depth_and_branches() {
+    let program = parse(
+        "#states light Red Green Amber\n::main:\npush 1\npush 2\nadd\nifjmp main\nswitch light\n",
+        &RunOptions::default(),
+    );
+
+    let stats = Stats::new(&program);
+    let main = &stats.sections[0];
+
+    assert_eq!(stats.opcodes["push"], 2);
+    assert_eq!(main.peak_depth, 2);
+    assert_eq!(main.needs, 1);
+    // One for the ifjmp and two for the extra states of the switch
+    assert_eq!(main.branches, 3);
+    assert_eq!(stats.complexity(), 4);
+}
@@ -0,0 +1,26 @@
+//! Checks that `tock` pushes the nanoseconds since its matching `tick`
+
+use toylang::Value;
+
+mod common;
+
+use common::run_main;
+
+#[test]
+fn tocks_match_the_latest_tick() {
+    let stack = run_main("tick\ntick\ntock\ntock\n").unwrap();
+
+    let [Value::Int(inner), Value::Int(outer)] = stack[..] else {
+        panic!("tock did not push two Ints: {stack:?}");
+    };
+    // The outer stopwatch was started first, so it has been running at least as long
+    assert!(0 <= inner && inner <= outer, "{inner} > {outer}");
+}
+
+#[test]
+fn tock_requires_a_tick() {
+    assert_eq!(
+        run_main("tick\ntock\ntock\n"),
+        Err("tock without a matching tick".to_string())
+    );
+}
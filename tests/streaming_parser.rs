@@ -0,0 +1,43 @@
+//! Checks that programs can be parsed a line at a time
+
+use std::io::Cursor;
+
+use toylang::{parse, parse_reader, Parser, Program, RunOptions};
+
+#[test]
+fn sections_are_handed_out_as_soon_as_they_end() {
+    let options = RunOptions::default();
+    let mut parser = Parser::new(&options);
+
+    assert!(parser.feed("::main:").unwrap().is_none());
+    assert!(parser.feed("push 1").unwrap().is_none());
+
+    let Some(Program::Section(name, instructions)) = parser.feed("::next:").unwrap() else {
+        panic!("the main section was not completed by the next header");
+    };
+    assert_eq!(name.0, "main");
+    assert_eq!(instructions.len(), 1);
+
+    assert!(parser.feed("exit").unwrap().is_none());
+    assert!(
+        matches!(parser.finish().unwrap(), Some(Program::Section(name, _)) if name.0 == "next")
+    );
+}
+
+#[test]
+fn reader_parses_like_a_string() {
+    let source = std::fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/samples/fizz_buzz.tyl"
+    ))
+    .unwrap();
+    let source = format!("{source}\noverride ::exit:\nexit\n");
+    let options = RunOptions::default();
+
+    let streamed = parse_reader(Cursor::new(source.as_bytes()), &options);
+
+    assert_eq!(
+        format!("{streamed:?}"),
+        format!("{:?}", parse(&source, &options))
+    );
+}
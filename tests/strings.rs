@@ -0,0 +1,52 @@
+//! Checks joining Strings with `add` and the `len`, `substr` and `strindex` instructions
+
+use toylang::{RuntimeError, Value};
+
+mod common;
+
+use common::run;
+
+fn string(value: &str) -> Value {
+    Value::String(value.to_string())
+}
+
+#[test]
+fn add_joins_strings() {
+    assert_eq!(
+        run("::main:\npush \"foo\"\npush \"bar\"\nadd\n"),
+        Ok(vec![string("foobar")])
+    );
+    assert!(run("::main:\npush \"foo\"\npush 1\nadd\n").is_err());
+}
+
+#[test]
+fn strings_are_measured_and_searched_by_character() {
+    assert_eq!(
+        run("::main:\npush \"héllo\"\nlen\n"),
+        Ok(vec![Value::Int(5)])
+    );
+    assert_eq!(
+        run("::main:\npush \"héllo\"\npush 1\npush 4\nsubstr\n"),
+        Ok(vec![string("éll")])
+    );
+    assert_eq!(
+        run("::main:\npush \"héllo\"\npush \"llo\"\nstrindex\n"),
+        Ok(vec![Value::Int(2)])
+    );
+    assert_eq!(
+        run("::main:\npush \"héllo\"\npush \"x\"\nstrindex\n"),
+        Ok(vec![Value::Int(-1)])
+    );
+}
+
+#[test]
+fn substrings_out_of_bounds_are_errors() {
+    let error = run("::main:\npush \"abc\"\npush 1\npush 4\nsubstr\n").unwrap_err();
+    assert_eq!(
+        error,
+        RuntimeError::Instruction("Substring 1..4 out of bounds for length 3".to_string())
+    );
+
+    assert!(run("::main:\npush \"abc\"\npush 2\npush 1\nsubstr\n").is_err());
+    assert!(run("::main:\npush \"abc\"\npush -1\npush 1\nsubstr\n").is_err());
+}
@@ -0,0 +1,23 @@
+//! Checks the figures `run --summary` reports once a program ends
+
+use toylang::{parse, Interpreter, RunOptions};
+
+#[test]
+fn steps_and_peak_depth_of_a_run() {
+    let options = RunOptions::default();
+    let program = parse(
+        "::main:\npush 1\npush 2\npush 3\nadd\nadd\ndrop\nexit\n",
+        &options,
+    );
+
+    let mut interpreter = Interpreter::new(program, options);
+    interpreter.run().unwrap();
+
+    assert_eq!(interpreter.steps(), 7);
+    assert_eq!(interpreter.peak_depth(), 3);
+
+    // Figures start over with each run
+    interpreter.run().unwrap();
+    assert_eq!(interpreter.steps(), 7);
+    assert_eq!(interpreter.peak_depth(), 3);
+}
@@ -0,0 +1,59 @@
+//! Checks the machine readable reports of `toylang test`
+
+use std::time::Duration;
+
+use toylang::test_report::{diff_lines, to_json, to_junit, Status, TestResult};
+
+fn results() -> Vec<TestResult> {
+    vec![
+        TestResult {
+            name: "passes".to_string(),
+            status: Status::Passed,
+            duration: Duration::from_millis(2),
+            diff: None,
+        },
+        TestResult {
+            name: "<fails>".to_string(),
+            status: Status::Failed("expected output \"a\\nb\" but got \"a\\nc\"".to_string()),
+            duration: Duration::from_millis(1),
+            diff: Some(diff_lines("a\nb", "a\nc")),
+        },
+    ]
+}
+
+#[test]
+fn diff_marks_removed_and_added_lines() {
+    assert_eq!(diff_lines("a\nb\nc", "a\nc\nd"), "  a\n- b\n  c\n+ d\n");
+}
+
+#[test]
+fn junit_report_lists_failures_with_their_diff() {
+    let xml = to_junit(&results());
+
+    assert!(xml
+        .contains(r#"<testsuite name="toylang" tests="2" failures="1" errors="0" time="0.003">"#));
+    assert!(xml.contains(r#"<testcase name="passes" time="0.002"/>"#));
+    assert!(xml.contains(r#"<testcase name="&lt;fails&gt;" time="0.001">"#));
+    assert!(xml.contains("  a\n- b\n+ c\n</failure>"));
+}
+
+#[test]
+fn json_report_summarises_results() {
+    let json = to_json(&results());
+
+    assert_eq!(json.get("passed").and_then(|n| n.as_usize()), Some(1));
+    assert_eq!(json.get("failed").and_then(|n| n.as_usize()), Some(1));
+
+    let tests = json
+        .get("tests")
+        .and_then(|tests| tests.as_array())
+        .unwrap();
+    assert_eq!(
+        tests[1].get("status").and_then(|s| s.as_str()),
+        Some("failed")
+    );
+    assert_eq!(
+        tests[1].get("diff").and_then(|s| s.as_str()),
+        Some("  a\n- b\n+ c\n")
+    );
+}
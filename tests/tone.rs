@@ -0,0 +1,41 @@
+//! Checks that `tone` only makes sound when the host provides a speaker
+
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use toylang::{RunOptions, Speaker};
+
+mod common;
+
+use common::interpreter;
+
+struct Recorder(Rc<RefCell<Vec<(usize, Duration)>>>);
+
+impl Speaker for Recorder {
+    fn tone(&self, frequency: usize, duration: Duration) {
+        self.0.borrow_mut().push((frequency, duration));
+    }
+}
+
+const PROGRAM: &str = "::main:\npush 440\npush 250\ntone\nexit\n";
+
+#[test]
+fn tones_are_played_by_the_speaker() {
+    let played = Rc::default();
+    let options = RunOptions {
+        speaker: Some(Box::new(Recorder(Rc::clone(&played)))),
+        ..RunOptions::default()
+    };
+
+    interpreter(PROGRAM, options).run().unwrap();
+
+    assert_eq!(*played.borrow(), [(440, Duration::from_millis(250))]);
+}
+
+#[test]
+fn tones_fail_without_a_speaker() {
+    let error = interpreter(PROGRAM, RunOptions::default())
+        .run()
+        .unwrap_err();
+
+    assert!(error.to_string().contains("sound is disabled"), "{error}");
+}
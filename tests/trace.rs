@@ -0,0 +1,74 @@
+//! Checks that `--trace` writes a step for every instruction and that `tracediff` finds
+//! where two runs part ways
+
+use toylang::{
+    json::Json,
+    trace::{diff, TraceStep},
+    RunOptions,
+};
+
+mod common;
+
+use common::{interpreter, SharedBuffer};
+
+fn trace(source: &str) -> Vec<TraceStep> {
+    let buffer = SharedBuffer::default();
+    let options = RunOptions {
+        trace_to: Some(Box::new(buffer.clone())),
+        ..RunOptions::default()
+    };
+
+    let mut interpreter = interpreter(source, options);
+    let _ = interpreter.run();
+    drop(interpreter);
+
+    let output = String::from_utf8(buffer.0.take()).unwrap();
+    output
+        .lines()
+        .map(|line| TraceStep::from_json(&Json::parse(line).unwrap()).unwrap())
+        .collect()
+}
+
+#[test]
+fn every_instruction_is_traced() {
+    let steps = trace("::main:\npush 2\npush 3\nadd\npush \"a\"\nadd\n");
+
+    let instructions: Vec<&str> = steps.iter().map(|step| step.instruction.as_str()).collect();
+    assert_eq!(
+        instructions,
+        ["push 2", "push 3", "add", "push \"a\"", "add"]
+    );
+
+    assert_eq!(steps[2].stack, ["Int(2)", "Int(3)"]);
+    assert_eq!(
+        (steps[2].popped, steps[2].pushed.clone()),
+        (2, vec!["Int(5)".to_string()])
+    );
+    assert!(steps[4].error.is_some());
+
+    for step in &steps {
+        assert_eq!(TraceStep::from_json(&step.to_json()).as_ref(), Ok(step));
+    }
+}
+
+#[test]
+fn diff_finds_the_first_divergence() {
+    let a = trace("::main:\npush 2\npush 3\nadd\npush 1\nexit\n");
+    let b = trace("::main:\npush 2\npush 3\nsub\npush 1\nexit\n");
+
+    assert_eq!(diff(&a, &a), None);
+
+    let divergence = diff(&a, &b).unwrap();
+    assert_eq!(divergence.step, 2);
+    assert_eq!(
+        divergence.to_string(),
+        "traces diverge at step 2\n  \
+         a: 2 | add popped 2, pushed [Int(5)]\n  \
+         b: 2 | sub popped 2, pushed [Int(1)]\n"
+    );
+
+    let shorter = &a[..3];
+    let divergence = diff(shorter, &a).unwrap();
+    assert_eq!(divergence.step, 3);
+    assert!(divergence.to_string().contains("a: ended after 3 steps"));
+}
@@ -0,0 +1,61 @@
+//! Checks that the turtle draws on the canvas as it moves
+
+use toylang::RunOptions;
+
+mod common;
+
+use common::interpreter;
+
+/// Runs a program on a 9x9 canvas and returns which pixels are lit, row by row
+fn draw(moves: &str) -> Vec<String> {
+    let path = std::env::temp_dir().join(format!("toylang-turtle-{}.png", std::process::id()));
+    let source = format!(
+        "::main:\npush 9\npush 9\ncnew\n{moves}\npush \"{}\"\ntsave\nexit\n",
+        path.display()
+    );
+
+    interpreter(&source, RunOptions::default()).run().unwrap();
+
+    let png = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    // The image data is stored uncompressed after the zlib and block headers, each row
+    // starting with a filter byte
+    let data = png.windows(4).position(|window| window == b"IDAT").unwrap() + 4 + 7;
+    png[data..data + 9 * 28]
+        .chunks(28)
+        .map(|row| {
+            row[1..]
+                .chunks(3)
+                .map(|pixel| if pixel == [255, 255, 255] { '#' } else { '.' })
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn turtle_draws_while_its_pen_is_down() {
+    let moves = "push 3\nforward\npush 90\nturn\npenup\npush 2\nforward\npendown\npush 1\nforward";
+
+    assert_eq!(
+        draw(moves),
+        vec![
+            ".........",
+            "....#.##.",
+            "....#....",
+            "....#....",
+            "....#....",
+            ".........",
+            ".........",
+            ".........",
+            ".........",
+        ]
+    );
+}
+
+#[test]
+fn turtle_may_leave_the_canvas() {
+    let moves = "push 270\nturn\npush 1000000\nforward\npush 180\nturn\npush 2\nforward";
+
+    assert_eq!(draw(moves)[4], "#####....");
+}
@@ -0,0 +1,21 @@
+//! Checks that debug runs explain where the operands of a failing instruction came from
+
+use toylang::{parse, Interpreter, RunOptions};
+
+#[test]
+fn type_error_reports_where_operands_were_pushed() {
+    let options = RunOptions {
+        debug: true,
+        ..RunOptions::default()
+    };
+    let program = parse("push 1\npush \"a\"\nswap\ndup\ndrop\nadd", &options);
+
+    let error = Interpreter::new(program, options).run().unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "Cannot add non-numeric values Int(1) and String(\"a\")\n  \
+         operand Int pushed by instruction 0 (push 1)\n  \
+         operand String pushed by instruction 1 (push \"a\")"
+    );
+}
@@ -0,0 +1,27 @@
+//! Checks that `store` and `load` keep values by name for the rest of a run
+
+use toylang::{RunOptions, Value};
+
+mod common;
+
+use common::{interpreter, run_main};
+
+#[test]
+fn stored_values_are_loaded_by_name() {
+    let source = "push 2\nstore width\npush 3\nstore height\npush 4\nstore width\nload width\nload height\nmul";
+    assert_eq!(run_main(source), Ok(vec![Value::Int(12)]));
+
+    let error = run_main("load missing").unwrap_err();
+    assert!(error.contains("Unknown variable: missing"), "{error}");
+}
+
+#[test]
+fn variables_count_towards_memory() {
+    let mut interpreter = interpreter(
+        "::main:\npush \"a long string to hold on to\"\nstore text\n",
+        RunOptions::default(),
+    );
+
+    interpreter.run().unwrap();
+    assert!(interpreter.memory_used() >= "a long string to hold on to".len());
+}
@@ -0,0 +1,153 @@
+//! Procedural macros for embedding toylang
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, FnArg, ItemFn, LitStr, Pat, ReturnType, Type};
+
+/// Turns a function into an instruction programs can use once it is registered with
+/// `RunOptions::host_functions`. The function is kept as it is, next to a `HostFunction`
+/// constant named after it in upper case:
+///
+/// ```ignore
+/// #[toy_fn]
+/// fn clamp(value: i64, low: i64, high: i64) -> i64 {
+///     value.max(low).min(high)
+/// }
+///
+/// let options = RunOptions {
+///     host_functions: vec![CLAMP],
+///     ..RunOptions::default()
+/// };
+/// ```
+///
+/// Each argument is popped off the stack with `FromStack`, the last one from the top, and
+/// the result is pushed with `ToStack`. A function returning a `Result` fails the
+/// instruction with its error. The instruction is named after the function unless another
+/// name is given with `#[toy_fn(name = "...")]`
+#[proc_macro_attribute]
+pub fn toy_fn(attribute: TokenStream, item: TokenStream) -> TokenStream {
+    let mut name = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("name") {
+            name = Some(meta.value()?.parse::<LitStr>()?);
+            Ok(())
+        } else {
+            Err(meta.error("expected `name = \"...\"`"))
+        }
+    });
+    parse_macro_input!(attribute with parser);
+
+    let function = parse_macro_input!(item as ItemFn);
+
+    match expand(&function, name) {
+        Ok(expanded) => expanded.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn expand(function: &ItemFn, name: Option<LitStr>) -> syn::Result<proc_macro2::TokenStream> {
+    let signature = &function.sig;
+
+    if !signature.generics.params.is_empty() || signature.asyncness.is_some() {
+        return Err(syn::Error::new_spanned(
+            signature,
+            "host functions cannot be generic or async",
+        ));
+    }
+
+    let mut arguments = Vec::new();
+    for input in &signature.inputs {
+        let FnArg::Typed(argument) = input else {
+            return Err(syn::Error::new_spanned(
+                input,
+                "host functions cannot take self",
+            ));
+        };
+
+        let Pat::Ident(pattern) = argument.pat.as_ref() else {
+            return Err(syn::Error::new_spanned(
+                &argument.pat,
+                "host function arguments must be plain names",
+            ));
+        };
+
+        arguments.push((&pattern.ident, argument.ty.as_ref()));
+    }
+
+    let ident = &signature.ident;
+    let visibility = &function.vis;
+    let constant = format_ident!("{}", ident.to_string().to_uppercase());
+    let name = name.unwrap_or_else(|| LitStr::new(&ident.to_string(), Span::call_site()));
+
+    let (output, fallible) = match &signature.output {
+        ReturnType::Default => (None, false),
+        ReturnType::Type(_, output) => match result_value(output) {
+            Some(value) => (Some(value), true),
+            None => (Some(output.as_ref()), false),
+        },
+    };
+
+    let pops: usize = arguments.iter().map(|(_, ty)| values(ty)).sum();
+    let pushes = output.map_or(0, values);
+
+    // The last argument is on top of the stack, so it is popped first
+    let pop = arguments.iter().rev().map(|(argument, ty)| {
+        quote! { let #argument = <#ty as ::toylang::FromStack>::from_stack(stack)?; }
+    });
+    let names = arguments.iter().map(|(argument, _)| argument);
+    let question = fallible.then(|| quote!(?));
+
+    Ok(quote! {
+        #function
+
+        #[doc = concat!("Registers [`", stringify!(#ident), "`] as the `", #name, "` instruction")]
+        #visibility const #constant: ::toylang::HostFunction = ::toylang::HostFunction {
+            name: #name,
+            pops: #pops,
+            pushes: #pushes,
+            call: {
+                fn call(
+                    stack: &mut ::toylang::Stack,
+                ) -> ::std::result::Result<(), ::toylang::RuntimeError> {
+                    #(#pop)*
+                    let result = #ident(#(#names),*)#question;
+                    ::toylang::ToStack::to_stack(result, stack);
+                    ::std::result::Result::Ok(())
+                }
+
+                call
+            },
+        };
+    })
+}
+
+/// The type of the value a `Result` holds, if the type is one
+fn result_value(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Result" {
+        return None;
+    }
+
+    let syn::PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+        return None;
+    };
+
+    match arguments.args.first()? {
+        syn::GenericArgument::Type(value) => Some(value),
+        _ => None,
+    }
+}
+
+/// How many stack values a type takes up, one per element of a tuple
+fn values(ty: &Type) -> usize {
+    match ty {
+        Type::Tuple(tuple) => tuple.elems.len(),
+        Type::Paren(inner) => values(&inner.elem),
+        _ => 1,
+    }
+}